@@ -0,0 +1,339 @@
+// SPDX-License-Identifier: MIT
+//! Threshold alerts evaluated against each sampled frame.
+
+use std::fmt;
+use std::process::Command;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::sampler::accumulator::ComputedFrame;
+
+/// Minimum time between two firings of the same rule, so a value hovering
+/// around the threshold does not fire every frame.
+const ALERT_COOLDOWN: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// `fex_load_percent`
+    Load,
+    Sigbus,
+    Smc,
+    Softfloat,
+    CacheMiss,
+    /// Resident anonymous memory, in MiB
+    MemAnon,
+    /// Resident JIT code memory, in MiB
+    MemJit,
+}
+
+impl Metric {
+    const NAMES: &[(&str, Self)] = &[
+        ("load", Self::Load),
+        ("sigbus", Self::Sigbus),
+        ("smc", Self::Smc),
+        ("softfloat", Self::Softfloat),
+        ("cache_miss", Self::CacheMiss),
+        ("mem_anon", Self::MemAnon),
+        ("mem_jit", Self::MemJit),
+    ];
+
+    fn name(self) -> &'static str {
+        Self::NAMES
+            .iter()
+            .find(|(_, m)| *m == self)
+            .map_or("?", |(n, _)| n)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn value(self, frame: &ComputedFrame) -> f64 {
+        const MIB: f64 = 1024.0 * 1024.0;
+        match self {
+            Self::Load => frame.fex_load_percent,
+            Self::Sigbus => frame.total_sigbus_count as f64,
+            Self::Smc => frame.total_smc_count as f64,
+            Self::Softfloat => frame.total_float_fallback_count as f64,
+            Self::CacheMiss => frame.total_cache_miss_count as f64,
+            Self::MemAnon => frame.mem.total_anon as f64 / MIB,
+            Self::MemJit => frame.mem.jit_code as f64 / MIB,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+}
+
+impl Comparison {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::Greater => ">",
+            Self::GreaterEq => ">=",
+            Self::Less => "<",
+            Self::LessEq => "<=",
+        }
+    }
+
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Self::Greater => value > threshold,
+            Self::GreaterEq => value >= threshold,
+            Self::Less => value < threshold,
+            Self::LessEq => value <= threshold,
+        }
+    }
+}
+
+/// A single `metric<op>threshold` rule such as `load>90`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AlertRule {
+    pub metric: Metric,
+    pub comparison: Comparison,
+    pub threshold: f64,
+}
+
+impl fmt::Display for AlertRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.metric.name(),
+            self.comparison.symbol(),
+            self.threshold
+        )
+    }
+}
+
+impl FromStr for AlertRule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let pos = s
+            .find(['>', '<'])
+            .ok_or_else(|| anyhow!("expected <metric><op><threshold>, e.g. load>90"))?;
+        let (name, rest) = s.split_at(pos);
+        let (comparison, threshold) = if let Some(t) = rest.strip_prefix(">=") {
+            (Comparison::GreaterEq, t)
+        } else if let Some(t) = rest.strip_prefix("<=") {
+            (Comparison::LessEq, t)
+        } else if let Some(t) = rest.strip_prefix('>') {
+            (Comparison::Greater, t)
+        } else {
+            (Comparison::Less, &rest[1..])
+        };
+
+        let name = name.trim();
+        let Some(&(_, metric)) = Metric::NAMES.iter().find(|(n, _)| *n == name) else {
+            let known: Vec<&str> = Metric::NAMES.iter().map(|(n, _)| *n).collect();
+            bail!(
+                "unknown metric \"{name}\" (expected one of {})",
+                known.join(", ")
+            );
+        };
+        let threshold = threshold
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid threshold \"{}\"", threshold.trim()))?;
+
+        Ok(Self {
+            metric,
+            comparison,
+            threshold,
+        })
+    }
+}
+
+/// An alert that fired on the latest frame.
+pub struct Alert {
+    pub rule: String,
+    pub value: f64,
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "alert {} (value {:.2})", self.rule, self.value)
+    }
+}
+
+/// Evaluates rules frame by frame. A rule fires when its condition becomes
+/// true, then stays quiet until the condition clears and the cooldown has
+/// passed.
+pub struct AlertMonitor {
+    rules: Vec<AlertRule>,
+    command: Option<String>,
+    /// Set when a rule fires, cleared once its condition stops holding.
+    active: Vec<bool>,
+    last_fired: Vec<Option<Instant>>,
+}
+
+impl AlertMonitor {
+    #[must_use]
+    pub fn new(rules: Vec<AlertRule>, command: Option<String>) -> Self {
+        let n = rules.len();
+        Self {
+            rules,
+            command,
+            active: vec![false; n],
+            last_fired: vec![None; n],
+        }
+    }
+
+    /// Checks `frame` against every rule and returns the alerts that fired.
+    /// If an alert command is configured it is started for each one, with
+    /// `FELIX_ALERT`, `FELIX_VALUE`, and `FELIX_PID` set in its environment.
+    pub fn evaluate(&mut self, frame: &ComputedFrame, pid: i32) -> Vec<Alert> {
+        let fired = self.check(frame, Instant::now());
+        if let Some(command) = &self.command {
+            for alert in &fired {
+                spawn_command(command, alert, pid);
+            }
+        }
+        fired
+    }
+
+    fn check(&mut self, frame: &ComputedFrame, now: Instant) -> Vec<Alert> {
+        let mut fired = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            let value = rule.metric.value(frame);
+            let holds = rule.comparison.holds(value, rule.threshold);
+            if !holds {
+                self.active[i] = false;
+                continue;
+            }
+
+            // A rise during the cooldown is held back rather than dropped:
+            // it fires once the cooldown is over if the condition still
+            // holds then.
+            let cooled = self.last_fired[i].is_none_or(|t| now.duration_since(t) >= ALERT_COOLDOWN);
+            if !self.active[i] && cooled {
+                self.active[i] = true;
+                self.last_fired[i] = Some(now);
+                fired.push(Alert {
+                    rule: rule.to_string(),
+                    value,
+                });
+            }
+        }
+        fired
+    }
+}
+
+/// Runs `command` through `sh -c` on a background thread so a slow hook
+/// does not stall sampling. The child is reaped when it exits.
+fn spawn_command(command: &str, alert: &Alert, pid: i32) {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .env("FELIX_ALERT", &alert.rule)
+        .env("FELIX_VALUE", format!("{}", alert.value))
+        .env("FELIX_PID", pid.to_string());
+    std::thread::spawn(move || {
+        let _ = cmd.status();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(load: f64, sigbus: u64) -> ComputedFrame {
+        ComputedFrame {
+            fex_load_percent: load,
+            total_sigbus_count: sigbus,
+            ..ComputedFrame::default()
+        }
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rule: AlertRule = "load>90".parse().unwrap();
+        assert_eq!(rule.metric, Metric::Load);
+        assert_eq!(rule.comparison, Comparison::Greater);
+        assert!((rule.threshold - 90.0).abs() < f64::EPSILON);
+
+        let rule: AlertRule = "sigbus >= 5000".parse().unwrap();
+        assert_eq!(rule.metric, Metric::Sigbus);
+        assert_eq!(rule.comparison, Comparison::GreaterEq);
+        assert_eq!(rule.to_string(), "sigbus>=5000");
+
+        let rule: AlertRule = "mem_jit<=1.5".parse().unwrap();
+        assert_eq!(rule.comparison, Comparison::LessEq);
+
+        assert!("load=90".parse::<AlertRule>().is_err());
+        assert!("cpu>90".parse::<AlertRule>().is_err());
+        assert!("load>lots".parse::<AlertRule>().is_err());
+    }
+
+    #[test]
+    fn sustained_spike_fires_once() {
+        let mut monitor = AlertMonitor::new(vec!["load>90".parse().unwrap()], None);
+        let start = Instant::now();
+
+        assert_eq!(monitor.check(&frame(95.0, 0), start).len(), 1);
+        for i in 1..5 {
+            let t = start + Duration::from_secs(i * 20);
+            assert!(monitor.check(&frame(99.0, 0), t).is_empty());
+        }
+    }
+
+    #[test]
+    fn refires_after_clearing_and_cooldown() {
+        let mut monitor = AlertMonitor::new(vec!["sigbus>5000".parse().unwrap()], None);
+        let start = Instant::now();
+
+        assert_eq!(monitor.check(&frame(0.0, 6000), start).len(), 1);
+        assert!(
+            monitor
+                .check(&frame(0.0, 0), start + Duration::from_secs(1))
+                .is_empty()
+        );
+        // Cleared, but still within the cooldown.
+        assert!(
+            monitor
+                .check(&frame(0.0, 6000), start + Duration::from_secs(2))
+                .is_empty()
+        );
+        assert!(
+            monitor
+                .check(&frame(0.0, 0), start + Duration::from_secs(3))
+                .is_empty()
+        );
+
+        let alerts = monitor.check(&frame(0.0, 7000), start + ALERT_COOLDOWN * 2);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].rule, "sigbus>5000");
+    }
+
+    #[test]
+    fn rise_during_cooldown_fires_once_it_passes() {
+        let mut monitor = AlertMonitor::new(vec!["load>90".parse().unwrap()], None);
+        let start = Instant::now();
+
+        assert_eq!(monitor.check(&frame(95.0, 0), start).len(), 1);
+        assert!(
+            monitor
+                .check(&frame(0.0, 0), start + Duration::from_secs(1))
+                .is_empty()
+        );
+        // Rises again within the cooldown and stays up past it.
+        assert!(
+            monitor
+                .check(&frame(95.0, 0), start + Duration::from_secs(2))
+                .is_empty()
+        );
+        assert_eq!(
+            monitor.check(&frame(95.0, 0), start + ALERT_COOLDOWN).len(),
+            1
+        );
+        assert!(
+            monitor
+                .check(&frame(95.0, 0), start + ALERT_COOLDOWN * 3)
+                .is_empty()
+        );
+    }
+}
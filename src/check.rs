@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MIT
+//! Threshold assertions over a whole recording, for gating CI runs.
+
+use std::io::Write;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+
+use crate::summary::Summary;
+
+/// Limits checked by `felix check`. At least one must be given.
+#[derive(Args, Clone, Debug, Default)]
+#[group(required = true, multiple = true)]
+#[allow(clippy::struct_field_names)]
+pub struct Thresholds {
+    /// Fail if the mean FEX load over the recording exceeds this percentage
+    #[arg(long, value_name = "PERCENT")]
+    pub max_load: Option<f64>,
+    /// Fail if SIGBUS events per second, averaged over the recording, exceed
+    /// this rate
+    #[arg(long, value_name = "PER_SECOND")]
+    pub max_sigbus_rate: Option<f64>,
+    /// Fail if resident JIT code memory ever exceeds this size (e.g. 512MiB)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_jit_mem: Option<u64>,
+}
+
+/// Outcome of one threshold.
+pub struct CheckResult {
+    pub name: &'static str,
+    pub limit: String,
+    pub value: String,
+    pub passed: bool,
+}
+
+/// Checks every configured threshold against `summary`, in a fixed order.
+#[must_use]
+pub fn evaluate(thresholds: &Thresholds, summary: &Summary) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    if let Some(limit) = thresholds.max_load {
+        let value = summary.mean_load();
+        results.push(CheckResult {
+            name: "max-load",
+            limit: format!("{limit:.2}%"),
+            value: format!("{value:.2}%"),
+            passed: value <= limit,
+        });
+    }
+    if let Some(limit) = thresholds.max_sigbus_rate {
+        let value = summary.sigbus_rate();
+        results.push(CheckResult {
+            name: "max-sigbus-rate",
+            limit: format!("{limit:.2}/s"),
+            value: format!("{value:.2}/s"),
+            passed: value <= limit,
+        });
+    }
+    if let Some(limit) = thresholds.max_jit_mem {
+        let value = summary.peak_jit_code;
+        results.push(CheckResult {
+            name: "max-jit-mem",
+            limit: format!("{limit} bytes"),
+            value: format!("{value} bytes"),
+            passed: value <= limit,
+        });
+    }
+    results
+}
+
+/// Parses a byte count with an optional binary (`KiB`, `MiB`, `GiB`) or
+/// short (`K`, `M`, `G`) suffix. Both kinds are powers of 1024.
+///
+/// # Errors
+///
+/// Returns an error if the number or suffix is not recognized.
+pub fn parse_size(s: &str) -> Result<u64> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KiB" => 1 << 10,
+        "M" | "MiB" => 1 << 20,
+        "G" | "GiB" => 1 << 30,
+        other => bail!("unknown size suffix \"{other}\" (expected KiB, MiB, or GiB)"),
+    };
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("invalid size \"{s}\""))?;
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    let bytes = (number * multiplier as f64) as u64;
+    Ok(bytes)
+}
+
+/// Writes `results` as a `JUnit` XML report with one test case per threshold.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_junit(mut out: impl Write, suite: &str, results: &[CheckResult]) -> Result<()> {
+    let failures = results.iter().filter(|r| !r.passed).count();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuite name="{}" tests="{}" failures="{failures}">"#,
+        xml_escape(suite),
+        results.len(),
+    )?;
+    for result in results {
+        let name = xml_escape(result.name);
+        if result.passed {
+            writeln!(
+                out,
+                r#"  <testcase classname="felix.check" name="{name}"/>"#
+            )?;
+        } else {
+            writeln!(out, r#"  <testcase classname="felix.check" name="{name}">"#)?;
+            writeln!(
+                out,
+                r#"    <failure message="{} exceeds limit {}"/>"#,
+                xml_escape(&result.value),
+                xml_escape(&result.limit),
+            )?;
+            writeln!(out, "  </testcase>")?;
+        }
+    }
+    writeln!(out, "</testsuite>")?;
+    out.flush().context("failed to flush JUnit report")
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::accumulator::ComputedFrame;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("512MiB").unwrap(), 512 << 20);
+        assert_eq!(parse_size("1.5G").unwrap(), 3 << 29);
+        assert_eq!(parse_size("64 KiB").unwrap(), 64 << 10);
+        assert!(parse_size("12MB").is_err());
+        assert!(parse_size("MiB").is_err());
+    }
+
+    #[test]
+    fn evaluates_configured_thresholds_only() {
+        let mut summary = Summary::default();
+        let mut frame = ComputedFrame {
+            fex_load_percent: 90.0,
+            sample_period_ns: 1_000_000_000,
+            total_sigbus_count: 50,
+            ..ComputedFrame::default()
+        };
+        frame.mem.jit_code = 1 << 20;
+        summary.add(&frame);
+
+        let thresholds = Thresholds {
+            max_load: Some(85.0),
+            max_sigbus_rate: Some(100.0),
+            max_jit_mem: None,
+        };
+        let results = evaluate(&thresholds, &summary);
+        assert_eq!(results.len(), 2);
+        assert!(!results[0].passed);
+        assert_eq!(results[0].value, "90.00%");
+        assert!(results[1].passed);
+    }
+
+    #[test]
+    fn junit_report_lists_failures() {
+        let results = [
+            CheckResult {
+                name: "max-load",
+                limit: "85.00%".to_string(),
+                value: "90.00%".to_string(),
+                passed: false,
+            },
+            CheckResult {
+                name: "max-jit-mem",
+                limit: "1 bytes".to_string(),
+                value: "0 bytes".to_string(),
+                passed: true,
+            },
+        ];
+        let mut buf = Vec::new();
+        write_junit(&mut buf, "run<1>.felixr", &results).unwrap();
+        let xml = String::from_utf8(buf).unwrap();
+
+        assert!(xml.contains(r#"<testsuite name="run&lt;1&gt;.felixr" tests="2" failures="1">"#));
+        assert!(xml.contains(r#"<failure message="90.00% exceeds limit 85.00%"/>"#));
+        assert!(xml.contains(r#"<testcase classname="felix.check" name="max-jit-mem"/>"#));
+    }
+}
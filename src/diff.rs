@@ -0,0 +1,282 @@
+// SPDX-License-Identifier: MIT
+//! Frame-by-frame comparison of a baseline and a candidate recording.
+
+use std::fmt;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::fex::smaps::MemSnapshot;
+use crate::sampler::accumulator::ComputedFrame;
+
+/// Metrics compared ahead of the per-region memory breakdown.
+const SCALAR_METRICS: [&str; 5] = [
+    "fex_load_percent",
+    "total_jit_time",
+    "sigbus",
+    "smc",
+    "softfloat",
+];
+
+#[allow(clippy::cast_precision_loss)]
+fn metric_values(frame: &ComputedFrame) -> impl Iterator<Item = f64> {
+    let scalars = [
+        frame.fex_load_percent,
+        frame.total_jit_time as f64,
+        frame.total_sigbus_count as f64,
+        frame.total_smc_count as f64,
+        frame.total_float_fallback_count as f64,
+    ];
+    let regions = frame.mem.regions().map(|(_, bytes)| bytes as f64);
+    scalars.into_iter().chain(regions)
+}
+
+fn metric_names() -> Vec<String> {
+    let regions = MemSnapshot::default()
+        .regions()
+        .map(|(name, _)| format!("mem.{name}"));
+    SCALAR_METRICS
+        .iter()
+        .map(|s| (*s).to_string())
+        .chain(regions)
+        .collect()
+}
+
+/// Running mean and maximum of one metric.
+#[derive(Clone, Copy, Default)]
+struct SeriesStats {
+    sum: f64,
+    max: f64,
+    count: u64,
+}
+
+impl SeriesStats {
+    fn add(&mut self, value: f64) {
+        self.max = if self.count == 0 {
+            value
+        } else {
+            self.max.max(value)
+        };
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn mean(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let mean = self.sum / self.count as f64;
+        mean
+    }
+}
+
+/// Mean and maximum of one metric in each recording. Deltas are candidate
+/// minus baseline.
+#[derive(Debug, Serialize)]
+pub struct MetricDiff {
+    pub name: String,
+    pub baseline_mean: f64,
+    pub candidate_mean: f64,
+    pub mean_delta: f64,
+    pub baseline_max: f64,
+    pub candidate_max: f64,
+    pub max_delta: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffReport {
+    pub baseline_frames: u64,
+    pub candidate_frames: u64,
+    /// Frames present in both recordings; only these are compared.
+    pub compared_frames: u64,
+    pub baseline_period_ns: u64,
+    pub candidate_period_ns: u64,
+    pub metrics: Vec<MetricDiff>,
+    pub warnings: Vec<String>,
+}
+
+/// Aligns two frame sequences by index and compares every metric over the
+/// frames both have. Mismatched lengths or sample periods are reported in
+/// `warnings` rather than treated as errors.
+///
+/// # Errors
+///
+/// Returns the first error yielded by either sequence.
+pub fn diff_frames(
+    baseline: impl IntoIterator<Item = Result<ComputedFrame>>,
+    candidate: impl IntoIterator<Item = Result<ComputedFrame>>,
+) -> Result<DiffReport> {
+    let names = metric_names();
+    let mut base_stats = vec![SeriesStats::default(); names.len()];
+    let mut cand_stats = vec![SeriesStats::default(); names.len()];
+    let mut baseline = baseline.into_iter();
+    let mut candidate = candidate.into_iter();
+
+    let mut compared = 0u64;
+    let mut base_period = None;
+    let mut cand_period = None;
+    let (mut base_frames, mut cand_frames) = loop {
+        match (baseline.next(), candidate.next()) {
+            (Some(base), Some(cand)) => {
+                let (base, cand) = (base?, cand?);
+                base_period.get_or_insert(base.sample_period_ns);
+                cand_period.get_or_insert(cand.sample_period_ns);
+                for (stats, value) in base_stats.iter_mut().zip(metric_values(&base)) {
+                    stats.add(value);
+                }
+                for (stats, value) in cand_stats.iter_mut().zip(metric_values(&cand)) {
+                    stats.add(value);
+                }
+                compared += 1;
+            }
+            (Some(base), None) => {
+                base_period.get_or_insert(base?.sample_period_ns);
+                break (compared + 1, compared);
+            }
+            (None, Some(cand)) => {
+                cand_period.get_or_insert(cand?.sample_period_ns);
+                break (compared, compared + 1);
+            }
+            (None, None) => break (compared, compared),
+        }
+    };
+
+    // Only one sequence can have frames left; count them.
+    for frame in baseline {
+        base_period.get_or_insert(frame?.sample_period_ns);
+        base_frames += 1;
+    }
+    for frame in candidate {
+        cand_period.get_or_insert(frame?.sample_period_ns);
+        cand_frames += 1;
+    }
+
+    let baseline_period_ns = base_period.unwrap_or(0);
+    let candidate_period_ns = cand_period.unwrap_or(0);
+    let mut warnings = Vec::new();
+    if base_frames != cand_frames {
+        warnings.push(format!(
+            "frame counts differ ({base_frames} vs {cand_frames}); comparing the first {compared}"
+        ));
+    }
+    if baseline_period_ns != candidate_period_ns {
+        warnings.push(format!(
+            "sample periods differ ({} ms vs {} ms); frames are aligned by index, not time",
+            baseline_period_ns / 1_000_000,
+            candidate_period_ns / 1_000_000,
+        ));
+    }
+
+    let metrics = names
+        .into_iter()
+        .zip(base_stats.iter().zip(&cand_stats))
+        .map(|(name, (base, cand))| MetricDiff {
+            name,
+            baseline_mean: base.mean(),
+            candidate_mean: cand.mean(),
+            mean_delta: cand.mean() - base.mean(),
+            baseline_max: base.max,
+            candidate_max: cand.max,
+            max_delta: cand.max - base.max,
+        })
+        .collect();
+
+    Ok(DiffReport {
+        baseline_frames: base_frames,
+        candidate_frames: cand_frames,
+        compared_frames: compared,
+        baseline_period_ns,
+        candidate_period_ns,
+        metrics,
+        warnings,
+    })
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Frames compared: {} (baseline {}, candidate {})",
+            self.compared_frames, self.baseline_frames, self.candidate_frames
+        )?;
+        for warning in &self.warnings {
+            writeln!(f, "warning: {warning}")?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<20} {:>14} {:>14} {:>14} {:>14} {:>14} {:>14}",
+            "metric", "base mean", "cand mean", "mean delta", "base max", "cand max", "max delta"
+        )?;
+        for m in &self.metrics {
+            writeln!(
+                f,
+                "{:<20} {:>14.2} {:>14.2} {:>+14.2} {:>14.2} {:>14.2} {:>+14.2}",
+                m.name,
+                m.baseline_mean,
+                m.candidate_mean,
+                m.mean_delta,
+                m.baseline_max,
+                m.candidate_max,
+                m.max_delta
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn frame(load: f64, sigbus: u64, period_ns: u64) -> Result<ComputedFrame> {
+        Ok(ComputedFrame {
+            fex_load_percent: load,
+            total_sigbus_count: sigbus,
+            sample_period_ns: period_ns,
+            ..ComputedFrame::default()
+        })
+    }
+
+    fn metric<'a>(report: &'a DiffReport, name: &str) -> &'a MetricDiff {
+        report.metrics.iter().find(|m| m.name == name).unwrap()
+    }
+
+    #[test]
+    fn reports_mean_and_max_deltas() {
+        let base = vec![frame(10.0, 1, 1000), frame(30.0, 3, 1000)];
+        let cand = vec![frame(20.0, 0, 1000), frame(60.0, 2, 1000)];
+        let report = diff_frames(base, cand).unwrap();
+
+        assert_eq!(report.compared_frames, 2);
+        assert!(report.warnings.is_empty());
+        let load = metric(&report, "fex_load_percent");
+        assert!((load.mean_delta - 20.0).abs() < f64::EPSILON);
+        assert!((load.max_delta - 30.0).abs() < f64::EPSILON);
+        let sigbus = metric(&report, "sigbus");
+        assert!((sigbus.mean_delta + 1.0).abs() < f64::EPSILON);
+        assert!(metric(&report, "mem.jit_code").max_delta.abs() < f64::EPSILON);
+        assert_eq!(report.metrics.len(), SCALAR_METRICS.len() + 12);
+    }
+
+    #[test]
+    fn flags_mismatched_counts_and_periods() {
+        let base = vec![
+            frame(10.0, 0, 1000),
+            frame(10.0, 0, 1000),
+            frame(90.0, 0, 1000),
+        ];
+        let cand = vec![frame(10.0, 0, 2_000_000_000)];
+        let report = diff_frames(base, cand).unwrap();
+
+        assert_eq!(report.baseline_frames, 3);
+        assert_eq!(report.candidate_frames, 1);
+        assert_eq!(report.compared_frames, 1);
+        assert_eq!(report.warnings.len(), 2);
+        assert!(report.warnings[0].contains("3 vs 1"));
+        // Unmatched baseline frames are not compared.
+        assert!(metric(&report, "fex_load_percent").max_delta.abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,492 @@
+// SPDX-License-Identifier: MIT
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+
+use super::FrameSink;
+use crate::datasource::SessionMetadata;
+use crate::recording::format::Frame;
+use crate::sampler::accumulator::{ComputedFrame, CumulativeCountStats};
+
+pub struct CsvSink<W: Write> {
+    out: W,
+    recording_start: SystemTime,
+    /// Running totals of the per-sample counts since the first row.
+    session: CumulativeCountStats,
+}
+
+impl<W: Write> CsvSink<W> {
+    /// Creates a CSV sink and writes the header row.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header cannot be written.
+    pub fn new(mut out: W, recording_start: SystemTime) -> Result<Self> {
+        write_csv_header(&mut out)?;
+        Ok(Self {
+            out,
+            recording_start,
+            session: CumulativeCountStats::default(),
+        })
+    }
+}
+
+impl<W: Write> FrameSink for CsvSink<W> {
+    fn write_frame(&mut self, index: usize, frame: &ComputedFrame) -> Result<()> {
+        self.session.add_sample(frame);
+        write_csv_row(
+            &mut self.out,
+            index,
+            frame,
+            &self.session,
+            self.recording_start,
+        )
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush().context("failed to flush CSV output")
+    }
+}
+
+/// Frame-by-thread load matrix: one row per frame and one column per tid
+/// seen anywhere in the export. The columns are only known once every frame
+/// has been seen, so rows are kept until `finish`. Only the threads a frame
+/// kept in `thread_loads` appear; the rest of a row is blank.
+pub struct ThreadMatrixSink<W: Write> {
+    out: W,
+    recording_start: SystemTime,
+    tids: BTreeSet<u32>,
+    rows: Vec<MatrixRow>,
+}
+
+struct MatrixRow {
+    index: usize,
+    timestamp_ns: u64,
+    /// The frame's thread loads, sorted by tid.
+    loads: Vec<(u32, f32)>,
+}
+
+impl<W: Write> ThreadMatrixSink<W> {
+    #[must_use]
+    pub fn new(out: W, recording_start: SystemTime) -> Self {
+        Self {
+            out,
+            recording_start,
+            tids: BTreeSet::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    fn write_row(&mut self, row: &MatrixRow) -> std::io::Result<()> {
+        let frame = ComputedFrame {
+            timestamp_ns: row.timestamp_ns,
+            ..ComputedFrame::default()
+        };
+        write!(
+            self.out,
+            "{},{},{}",
+            row.index,
+            row.timestamp_ns,
+            super::wallclock(self.recording_start, &frame)
+        )?;
+        let mut loads = row.loads.iter().peekable();
+        for &tid in &self.tids {
+            match loads.next_if(|&&(t, _)| t == tid) {
+                Some((_, load)) => write!(self.out, ",{load:.4}")?,
+                None => write!(self.out, ",")?,
+            }
+        }
+        writeln!(self.out)
+    }
+}
+
+impl<W: Write> FrameSink for ThreadMatrixSink<W> {
+    fn write_frame(&mut self, index: usize, frame: &ComputedFrame) -> Result<()> {
+        let mut loads: Vec<(u32, f32)> = frame
+            .thread_loads
+            .iter()
+            .map(|t| (t.tid, t.load_percent))
+            .collect();
+        loads.sort_unstable_by_key(|&(tid, _)| tid);
+        self.tids.extend(loads.iter().map(|&(tid, _)| tid));
+        self.rows.push(MatrixRow {
+            index,
+            timestamp_ns: frame.timestamp_ns,
+            loads,
+        });
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        write!(self.out, "frame,timestamp_ns,wallclock").context("failed to write CSV header")?;
+        for tid in &self.tids {
+            write!(self.out, ",tid_{tid}").context("failed to write CSV header")?;
+        }
+        writeln!(self.out).context("failed to write CSV header")?;
+
+        for row in std::mem::take(&mut self.rows) {
+            self.write_row(&row).context("failed to write CSV row")?;
+        }
+        self.out.flush().context("failed to flush CSV output")
+    }
+}
+
+/// Thread activity summed over every thread a frame recorded, next to the
+/// same sums over only the threads kept in `thread_loads`. Recordings store
+/// all per-thread deltas whatever `--top` was, so these totals do not depend
+/// on the display cap. Loads are in percent of one core, so four fully busy
+/// threads give 400.
+pub struct AllThreadsSink<W: Write> {
+    out: W,
+    recording_start: SystemTime,
+    cycle_freq: f64,
+}
+
+impl<W: Write> AllThreadsSink<W> {
+    /// Creates an all-threads sink and writes the header row.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header cannot be written.
+    pub fn new(mut out: W, metadata: &SessionMetadata) -> Result<Self> {
+        writeln!(
+            out,
+            "frame,timestamp_ns,wallclock,threads_reported,threads_active,threads_kept,\
+             all_threads_cycles,kept_threads_cycles,all_threads_load_percent,\
+             kept_threads_load_percent,dropped_threads_load_percent"
+        )
+        .context("failed to write CSV header")?;
+        #[allow(clippy::cast_precision_loss)]
+        let cycle_freq = metadata.cycle_counter_frequency as f64;
+        Ok(Self {
+            out,
+            recording_start: metadata.recording_start,
+            cycle_freq,
+        })
+    }
+
+    /// `cycles` as a percentage of one core's cycles over the frame.
+    fn load_percent(&self, cycles: u64, f: &ComputedFrame) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let max_cycles = self.cycle_freq * (f.elapsed_ns as f64 / 1_000_000_000.0);
+        if max_cycles > 0.0 {
+            #[allow(clippy::cast_precision_loss)]
+            let cycles = cycles as f64;
+            cycles / max_cycles * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+impl<W: Write> FrameSink for AllThreadsSink<W> {
+    fn write_frame(&mut self, index: usize, frame: &ComputedFrame) -> Result<()> {
+        self.write_recorded(
+            index,
+            &Frame {
+                computed: frame.clone(),
+                per_thread_deltas: Vec::new(),
+            },
+        )
+    }
+
+    fn write_recorded(&mut self, index: usize, frame: &Frame) -> Result<()> {
+        let f = &frame.computed;
+        let busy: Vec<u64> = frame
+            .per_thread_deltas
+            .iter()
+            .map(|d| d.jit_time + d.signal_time)
+            .collect();
+        let all_cycles: u64 = busy.iter().sum();
+        let kept_cycles: u64 = f.thread_loads.iter().map(|t| t.total_cycles).sum();
+        let all_load = self.load_percent(all_cycles, f);
+        let kept_load = self.load_percent(kept_cycles, f);
+        writeln!(
+            self.out,
+            "{index},{},{},{},{},{},{all_cycles},{kept_cycles},{all_load:.4},{kept_load:.4},{:.4}",
+            f.timestamp_ns,
+            super::wallclock(self.recording_start, f),
+            busy.len(),
+            busy.iter().filter(|&&cycles| cycles > 0).count(),
+            f.thread_loads.len(),
+            (all_load - kept_load).max(0.0),
+        )
+        .context("failed to write CSV row")
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush().context("failed to flush CSV output")
+    }
+}
+
+/// Writes the CSV column header line.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_csv_header(out: &mut impl Write) -> Result<()> {
+    writeln!(
+        out,
+        "frame,timestamp_ns,sample_period_ns,threads_sampled,\
+         total_jit_time,total_signal_time,total_sigbus_count,\
+         total_smc_count,total_float_fallback_count,\
+         total_cache_miss_count,total_cache_read_lock_time,\
+         total_cache_write_lock_time,total_jit_count,\
+         total_jit_invocations,fex_load_percent,\
+         mem_total_anon,mem_jit_code,mem_op_dispatcher,\
+         mem_frontend,mem_cpu_backend,mem_lookup,mem_lookup_l1,\
+         mem_thread_states,mem_block_links,mem_misc,\
+         mem_jemalloc,mem_unaccounted,\
+         cum_sigbus_count,cum_smc_count,cum_float_fallback_count,\
+         cum_cache_miss_count,cum_jit_count,\
+         sigbus_per_second,cache_miss_per_second,jit_count_per_second,\
+         resets_detected,\
+         mem_largest_begin,mem_largest_end,mem_largest_size,\
+         high_jit_load,high_invalidation_or_smc,high_sigbus,high_softfloat,\
+         wallclock,\
+         session_sigbus_count,session_smc_count,\
+         session_float_fallback_count,session_cache_miss_count,\
+         session_jit_count"
+    )
+    .context("failed to write CSV header")
+}
+
+/// Writes one CSV row for `f`, whose wall-clock time is `recording_start`
+/// plus its timestamp. `session` holds the per-sample counts summed up to and
+/// including `f`.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_csv_row(
+    out: &mut impl Write,
+    index: usize,
+    f: &ComputedFrame,
+    session: &CumulativeCountStats,
+    recording_start: SystemTime,
+) -> Result<()> {
+    writeln!(
+        out,
+        "{index},{},{},{},{},{},{},{},{},{},{},{},{},{},{:.4},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{:.2},{:.2},{:.2},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+        f.timestamp_ns,
+        f.sample_period_ns,
+        f.threads_sampled,
+        f.total_jit_time,
+        f.total_signal_time,
+        f.total_sigbus_count,
+        f.total_smc_count,
+        f.total_float_fallback_count,
+        f.total_cache_miss_count,
+        f.total_cache_read_lock_time,
+        f.total_cache_write_lock_time,
+        f.total_jit_count,
+        f.total_jit_invocations,
+        f.fex_load_percent,
+        f.mem.total_anon,
+        f.mem.jit_code,
+        f.mem.op_dispatcher,
+        f.mem.frontend,
+        f.mem.cpu_backend,
+        f.mem.lookup,
+        f.mem.lookup_l1,
+        f.mem.thread_states,
+        f.mem.block_links,
+        f.mem.misc,
+        f.mem.jemalloc,
+        f.mem.unaccounted,
+        f.cumulative.sigbus,
+        f.cumulative.smc,
+        f.cumulative.float_fallback,
+        f.cumulative.cache_miss,
+        f.cumulative.jit,
+        f.rates.sigbus,
+        f.rates.cache_miss,
+        f.rates.jit_count,
+        f.resets_detected,
+        f.mem.largest_anon.begin,
+        f.mem.largest_anon.end,
+        f.mem.largest_anon.size,
+        u8::from(f.histogram_entry.high_jit_load),
+        u8::from(f.histogram_entry.high_invalidation_or_smc),
+        u8::from(f.histogram_entry.high_sigbus),
+        u8::from(f.histogram_entry.high_softfloat),
+        super::wallclock(recording_start, f),
+        session.sigbus,
+        session.smc,
+        session.float_fallback,
+        session.cache_miss,
+        session.jit,
+    )
+    .context("failed to write CSV row")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::accumulator::ThreadLoad;
+    use crate::sampler::thread_stats::ThreadDelta;
+    use crate::testing::make_metadata;
+
+    #[test]
+    fn rows_match_header_columns() {
+        let mut frame = ComputedFrame::default();
+        frame.mem.largest_anon.size = 4096;
+        frame.histogram_entry.high_sigbus = true;
+
+        let mut out = Vec::new();
+        write_csv_header(&mut out).unwrap();
+        frame.timestamp_ns = 1_500_000_000;
+        let session = CumulativeCountStats {
+            sigbus: 9,
+            ..CumulativeCountStats::default()
+        };
+        write_csv_row(&mut out, 0, &frame, &session, SystemTime::UNIX_EPOCH).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<Vec<&str>> = text.lines().map(|l| l.split(',').collect()).collect();
+
+        assert_eq!(lines[0].len(), lines[1].len());
+        let column = |name: &str| lines[1][lines[0].iter().position(|c| *c == name).unwrap()];
+        assert_eq!(column("mem_largest_size"), "4096");
+        assert_eq!(column("high_sigbus"), "1");
+        assert_eq!(column("session_sigbus_count"), "9");
+        assert_eq!(column("high_softfloat"), "0");
+        assert_eq!(column("wallclock"), "1970-01-01T00:00:01.500Z");
+    }
+
+    #[test]
+    fn columns_are_only_ever_appended() {
+        // Parsers index columns by position, so new ones go at the end.
+        let mut out = Vec::new();
+        write_csv_header(&mut out).unwrap();
+        let header = String::from_utf8(out).unwrap();
+        let columns: Vec<&str> = header.trim_end().split(',').collect();
+        assert_eq!(
+            columns,
+            [
+                "frame",
+                "timestamp_ns",
+                "sample_period_ns",
+                "threads_sampled",
+                "total_jit_time",
+                "total_signal_time",
+                "total_sigbus_count",
+                "total_smc_count",
+                "total_float_fallback_count",
+                "total_cache_miss_count",
+                "total_cache_read_lock_time",
+                "total_cache_write_lock_time",
+                "total_jit_count",
+                "total_jit_invocations",
+                "fex_load_percent",
+                "mem_total_anon",
+                "mem_jit_code",
+                "mem_op_dispatcher",
+                "mem_frontend",
+                "mem_cpu_backend",
+                "mem_lookup",
+                "mem_lookup_l1",
+                "mem_thread_states",
+                "mem_block_links",
+                "mem_misc",
+                "mem_jemalloc",
+                "mem_unaccounted",
+                "cum_sigbus_count",
+                "cum_smc_count",
+                "cum_float_fallback_count",
+                "cum_cache_miss_count",
+                "cum_jit_count",
+                "sigbus_per_second",
+                "cache_miss_per_second",
+                "jit_count_per_second",
+                "resets_detected",
+                "mem_largest_begin",
+                "mem_largest_end",
+                "mem_largest_size",
+                "high_jit_load",
+                "high_invalidation_or_smc",
+                "high_sigbus",
+                "high_softfloat",
+                "wallclock",
+                "session_sigbus_count",
+                "session_smc_count",
+                "session_float_fallback_count",
+                "session_cache_miss_count",
+                "session_jit_count",
+            ]
+        );
+    }
+
+    #[test]
+    fn thread_matrix_has_a_column_per_tid() {
+        let frame = |loads: &[(u32, f32)]| ComputedFrame {
+            thread_loads: loads
+                .iter()
+                .map(|&(tid, load_percent)| ThreadLoad {
+                    tid,
+                    load_percent,
+                    total_cycles: 0,
+                })
+                .collect(),
+            ..ComputedFrame::default()
+        };
+        let mut out = Vec::new();
+        let mut sink = ThreadMatrixSink::new(&mut out, SystemTime::UNIX_EPOCH);
+        sink.write_frame(0, &frame(&[(20, 50.0), (7, 12.5)]))
+            .unwrap();
+        sink.write_frame(1, &frame(&[(9, 3.0)])).unwrap();
+        sink.finish().unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "frame,timestamp_ns,wallclock,tid_7,tid_9,tid_20");
+        assert!(lines[1].ends_with(",12.5000,,50.0000"));
+        assert!(lines[2].ends_with(",,3.0000,"));
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn all_threads_columns_cover_threads_beyond_the_kept_ones() {
+        let metadata = SessionMetadata {
+            cycle_counter_frequency: 1_000,
+            hardware_concurrency: 1,
+            ..make_metadata()
+        };
+        let delta = |tid, jit_time| ThreadDelta {
+            tid,
+            jit_time,
+            ..ThreadDelta::default()
+        };
+        // One second at 1000 cycles per second: 500 cycles is half a core.
+        let frame = Frame {
+            computed: ComputedFrame {
+                elapsed_ns: 1_000_000_000,
+                thread_loads: vec![ThreadLoad {
+                    tid: 1,
+                    load_percent: 50.0,
+                    total_cycles: 500,
+                }],
+                ..ComputedFrame::default()
+            },
+            per_thread_deltas: vec![delta(1, 500), delta(2, 250), delta(3, 0)],
+        };
+
+        let mut out = Vec::new();
+        let mut sink = AllThreadsSink::new(&mut out, &metadata).unwrap();
+        sink.write_recorded(0, &frame).unwrap();
+        sink.finish().unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<Vec<&str>> = text.lines().map(|l| l.split(',').collect()).collect();
+        assert_eq!(lines[0].len(), lines[1].len());
+        let column = |name: &str| lines[1][lines[0].iter().position(|c| *c == name).unwrap()];
+        assert_eq!(column("threads_reported"), "3");
+        assert_eq!(column("threads_active"), "2");
+        assert_eq!(column("threads_kept"), "1");
+        assert_eq!(column("all_threads_cycles"), "750");
+        assert_eq!(column("all_threads_load_percent"), "75.0000");
+        assert_eq!(column("kept_threads_load_percent"), "50.0000");
+        assert_eq!(column("dropped_threads_load_percent"), "25.0000");
+    }
+}
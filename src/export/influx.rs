@@ -0,0 +1,178 @@
+// SPDX-License-Identifier: MIT
+//! `InfluxDB` line protocol: one point per frame, tagged with the session's pid
+//! and FEX version, timestamped in nanoseconds since the Unix epoch.
+
+use std::fmt::Write as _;
+use std::io::Write;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result};
+
+use super::FrameSink;
+use crate::datasource::SessionMetadata;
+use crate::sampler::accumulator::ComputedFrame;
+
+/// Measurement name used unless `--measurement` overrides it.
+pub const DEFAULT_MEASUREMENT: &str = "felix";
+
+pub struct InfluxSink<W: Write> {
+    out: W,
+    /// Escaped measurement and tag set, identical on every line.
+    series: String,
+    /// Nanoseconds from the Unix epoch to the start of the recording.
+    start_ns: u128,
+}
+
+impl<W: Write> InfluxSink<W> {
+    /// Creates a sink writing points named `measurement`, tagged with the
+    /// session's pid and FEX version.
+    #[must_use]
+    pub fn new(out: W, metadata: &SessionMetadata, measurement: &str) -> Self {
+        let mut series = escape(measurement, &[',', ' ']);
+        let _ = write!(series, ",pid={}", metadata.pid);
+        // Line protocol has no empty tag values.
+        if !metadata.fex_version.is_empty() {
+            series.push_str(",fex_version=");
+            series.push_str(&escape(&metadata.fex_version, &[',', '=', ' ']));
+        }
+        Self {
+            out,
+            series,
+            start_ns: metadata
+                .recording_start
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+        }
+    }
+}
+
+/// Backslash-escapes `special` characters for a measurement, tag or field
+/// key.
+fn escape(text: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Integer fields carry an `i` suffix and must fit in an i64.
+fn int(value: u64) -> String {
+    format!("{}i", value.min(i64::MAX as u64))
+}
+
+fn point_fields(frame: &ComputedFrame) -> Vec<(String, String)> {
+    let mut fields = vec![
+        (
+            "fex_load_percent".to_string(),
+            format!("{}", frame.fex_load_percent),
+        ),
+        (
+            "threads_sampled".to_string(),
+            int(frame.threads_sampled as u64),
+        ),
+        ("jit_time".to_string(), int(frame.total_jit_time)),
+        ("signal_time".to_string(), int(frame.total_signal_time)),
+        ("sigbus_count".to_string(), int(frame.total_sigbus_count)),
+        ("smc_count".to_string(), int(frame.total_smc_count)),
+        (
+            "float_fallback_count".to_string(),
+            int(frame.total_float_fallback_count),
+        ),
+        (
+            "cache_miss_count".to_string(),
+            int(frame.total_cache_miss_count),
+        ),
+        ("jit_count".to_string(), int(frame.total_jit_count)),
+    ];
+    fields.extend(
+        frame
+            .mem
+            .regions()
+            .into_iter()
+            .map(|(region, bytes)| (format!("mem_{region}"), int(bytes))),
+    );
+    fields.push(("mem_pss".to_string(), int(frame.mem.total_pss)));
+    fields.push(("mem_swap".to_string(), int(frame.mem.total_swap)));
+    fields
+}
+
+impl<W: Write> FrameSink for InfluxSink<W> {
+    fn write_frame(&mut self, _index: usize, frame: &ComputedFrame) -> Result<()> {
+        let fields: Vec<String> = point_fields(frame)
+            .into_iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        let timestamp = self.start_ns + u128::from(frame.timestamp_ns);
+        writeln!(self.out, "{} {} {timestamp}", self.series, fields.join(","))
+            .context("failed to write line protocol point")
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out
+            .flush()
+            .context("failed to flush line protocol output")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::make_metadata;
+
+    fn metadata(fex_version: &str) -> SessionMetadata {
+        SessionMetadata {
+            pid: 4321,
+            fex_version: fex_version.into(),
+            recording_start: UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000),
+            ..make_metadata()
+        }
+    }
+
+    fn export(sink: InfluxSink<&mut Vec<u8>>, frame: &ComputedFrame) {
+        let mut sink = sink;
+        sink.write_frame(0, frame).unwrap();
+        sink.finish().unwrap();
+    }
+
+    #[test]
+    fn point_has_tags_fields_and_epoch_timestamp() {
+        let mut frame = ComputedFrame {
+            timestamp_ns: 1_500,
+            fex_load_percent: 12.5,
+            total_sigbus_count: 3,
+            ..ComputedFrame::default()
+        };
+        frame.mem.jit_code = 4096;
+
+        let mut out = Vec::new();
+        export(
+            InfluxSink::new(&mut out, &metadata("FEX 2501"), DEFAULT_MEASUREMENT),
+            &frame,
+        );
+        let line = String::from_utf8(out).unwrap();
+        let (point, timestamp) = line.trim_end().rsplit_once(' ').unwrap();
+        assert_eq!(timestamp, "1700000000000001500");
+        let fields = point
+            .strip_prefix("felix,pid=4321,fex_version=FEX\\ 2501 ")
+            .unwrap();
+        assert!(fields.starts_with("fex_load_percent=12.5,"));
+        assert!(fields.contains(",sigbus_count=3i,"));
+        assert!(fields.contains(",mem_jit_code=4096i,"));
+    }
+
+    #[test]
+    fn measurement_is_configurable_and_empty_tags_are_left_out() {
+        let metadata = metadata("");
+        let mut out = Vec::new();
+        export(
+            InfluxSink::new(&mut out, &metadata, "fex stats"),
+            &ComputedFrame::default(),
+        );
+        let line = String::from_utf8(out).unwrap();
+        assert!(line.starts_with("fex\\ stats,pid=4321 fex_load_percent=0,"));
+    }
+}
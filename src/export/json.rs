@@ -0,0 +1,163 @@
+// SPDX-License-Identifier: MIT
+use std::io::Write;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use super::FrameSink;
+use crate::sampler::accumulator::ComputedFrame;
+
+/// A frame as exported: its wall-clock time followed by every stored field.
+#[derive(Serialize)]
+struct JsonFrame<'a> {
+    wallclock: String,
+    #[serde(flatten)]
+    frame: &'a ComputedFrame,
+}
+
+impl<'a> JsonFrame<'a> {
+    fn new(recording_start: SystemTime, frame: &'a ComputedFrame) -> Self {
+        Self {
+            wallclock: super::wallclock(recording_start, frame),
+            frame,
+        }
+    }
+}
+
+/// Writes frames as one JSON array. Frames are streamed element by element,
+/// so the array is never built in memory.
+pub struct JsonArraySink<W: Write> {
+    out: W,
+    recording_start: SystemTime,
+    first: bool,
+}
+
+impl<W: Write> JsonArraySink<W> {
+    /// Creates a JSON array sink and writes the opening bracket.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn new(mut out: W, recording_start: SystemTime) -> Result<Self> {
+        out.write_all(b"[")
+            .context("failed to write JSON array start")?;
+        Ok(Self {
+            out,
+            recording_start,
+            first: true,
+        })
+    }
+}
+
+impl<W: Write> FrameSink for JsonArraySink<W> {
+    fn write_frame(&mut self, _index: usize, frame: &ComputedFrame) -> Result<()> {
+        let separator: &[u8] = if self.first { b"\n" } else { b",\n" };
+        self.first = false;
+        self.out
+            .write_all(separator)
+            .context("failed to write JSON separator")?;
+        serde_json::to_writer(&mut self.out, &JsonFrame::new(self.recording_start, frame))
+            .context("failed to write JSON frame")
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out
+            .write_all(b"\n]\n")
+            .context("failed to write JSON array end")?;
+        self.out.flush().context("failed to flush JSON output")
+    }
+}
+
+/// Writes frames as newline-delimited JSON, one object per line.
+pub struct JsonLinesSink<W: Write> {
+    out: W,
+    recording_start: SystemTime,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    #[must_use]
+    pub fn new(out: W, recording_start: SystemTime) -> Self {
+        Self {
+            out,
+            recording_start,
+        }
+    }
+}
+
+impl<W: Write> FrameSink for JsonLinesSink<W> {
+    fn write_frame(&mut self, _index: usize, frame: &ComputedFrame) -> Result<()> {
+        serde_json::to_writer(&mut self.out, &JsonFrame::new(self.recording_start, frame))
+            .context("failed to write JSON frame")?;
+        self.out
+            .write_all(b"\n")
+            .context("failed to write JSON line terminator")
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush().context("failed to flush JSON output")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_frame(load: f64) -> ComputedFrame {
+        ComputedFrame {
+            fex_load_percent: load,
+            threads_sampled: 1,
+            ..ComputedFrame::default()
+        }
+    }
+
+    #[test]
+    fn json_array_round_trips() {
+        let mut buf = Vec::new();
+        {
+            let mut sink = JsonArraySink::new(&mut buf, SystemTime::UNIX_EPOCH).unwrap();
+            sink.write_frame(0, &make_frame(10.0)).unwrap();
+            sink.write_frame(1, &make_frame(20.0)).unwrap();
+            sink.finish().unwrap();
+        }
+
+        let parsed: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        let frames = parsed.as_array().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1]["fex_load_percent"], 20.0);
+        assert!(frames[0]["thread_loads"].is_array());
+        assert!(frames[0]["mem"]["largest_anon"].is_object());
+    }
+
+    #[test]
+    fn empty_json_array_is_valid() {
+        let mut buf = Vec::new();
+        {
+            let mut sink = JsonArraySink::new(&mut buf, SystemTime::UNIX_EPOCH).unwrap();
+            sink.finish().unwrap();
+        }
+
+        let parsed: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert!(parsed.as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn json_lines_one_object_per_line() {
+        let mut buf = Vec::new();
+        {
+            let mut sink = JsonLinesSink::new(&mut buf, SystemTime::UNIX_EPOCH);
+            sink.write_frame(0, &make_frame(1.0)).unwrap();
+            sink.write_frame(1, &make_frame(2.0)).unwrap();
+            sink.finish().unwrap();
+        }
+
+        let text = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(value["histogram_entry"].is_object());
+            assert_eq!(value["wallclock"], "1970-01-01T00:00:00.000Z");
+        }
+    }
+}
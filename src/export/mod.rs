@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: MIT
+pub mod csv;
+pub mod influx;
+pub mod json;
+pub mod prometheus;
+pub mod trace;
+
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use clap::ValueEnum;
+
+use crate::datasource::SessionMetadata;
+use crate::recording::format::Frame;
+use crate::sampler::accumulator::ComputedFrame;
+use crate::wallclock;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// Comma-separated values, one row per frame
+    #[default]
+    Csv,
+    /// A single JSON array of frames
+    Json,
+    /// Newline-delimited JSON, one frame object per line
+    Jsonl,
+    /// Chrome Trace Event JSON with counter tracks, for ui.perfetto.dev
+    Trace,
+    /// `InfluxDB` line protocol, one point per frame
+    Influx,
+    /// Prometheus text exposition of the recording's totals, peaks and
+    /// means, for a pushgateway
+    Prometheus,
+}
+
+/// Destination for exported frames. Implementations write any preamble when
+/// created and any trailer in `finish`.
+pub trait FrameSink {
+    /// Writes a single frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing fails.
+    fn write_frame(&mut self, index: usize, frame: &ComputedFrame) -> Result<()>;
+
+    /// Writes a recorded frame. Sinks that need the per-thread deltas, not
+    /// just the computed frame, override this.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if serialization or writing fails.
+    fn write_recorded(&mut self, index: usize, frame: &Frame) -> Result<()> {
+        self.write_frame(index, &frame.computed)
+    }
+
+    /// Writes any trailing data and flushes the output.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing or flushing fails.
+    fn finish(&mut self) -> Result<()>;
+}
+
+/// An inclusive `start..=end` frame selection. A missing `end` means "to the
+/// last frame". Bounds past the end of the recording are clamped by the
+/// caller, which only learns the frame count once the stream is exhausted.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameWindow {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+impl FrameWindow {
+    #[must_use]
+    pub fn contains(&self, index: usize) -> bool {
+        index >= self.start && self.end.is_none_or(|end| index <= end)
+    }
+
+    /// Returns true once `index` is beyond the window, so iteration can stop.
+    #[must_use]
+    pub fn is_past(&self, index: usize) -> bool {
+        self.end.is_some_and(|end| index > end.max(self.start))
+    }
+}
+
+/// Converts a time offset in seconds to the index of the frame covering it.
+#[must_use]
+pub fn seconds_to_frame(seconds: f64, sample_period_ns: u64) -> usize {
+    if sample_period_ns == 0 || seconds <= 0.0 {
+        return 0;
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let period_secs = sample_period_ns as f64 / 1_000_000_000.0;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let index = (seconds / period_secs).floor() as usize;
+    index
+}
+
+/// The RFC 3339 wall-clock time of `frame`, given the session's
+/// `recording_start`.
+#[must_use]
+pub fn wallclock(recording_start: SystemTime, frame: &ComputedFrame) -> String {
+    wallclock::format_rfc3339(recording_start + Duration::from_nanos(frame.timestamp_ns))
+}
+
+/// Creates a sink for `format` that writes to `out`. The session's
+/// `recording_start` anchors the wall-clock timestamps of CSV, JSON and
+/// line protocol frames, and `measurement` names the line protocol points.
+///
+/// # Errors
+///
+/// Returns an error if the format preamble cannot be written.
+pub fn create_sink<W: Write + 'static>(
+    format: ExportFormat,
+    out: W,
+    metadata: &SessionMetadata,
+    measurement: &str,
+) -> Result<Box<dyn FrameSink>> {
+    let recording_start = metadata.recording_start;
+    Ok(match format {
+        ExportFormat::Csv => Box::new(csv::CsvSink::new(out, recording_start)?),
+        ExportFormat::Json => Box::new(json::JsonArraySink::new(out, recording_start)?),
+        ExportFormat::Jsonl => Box::new(json::JsonLinesSink::new(out, recording_start)),
+        ExportFormat::Trace => Box::new(trace::TraceSink::new(out)?),
+        ExportFormat::Influx => Box::new(influx::InfluxSink::new(out, metadata, measurement)),
+        ExportFormat::Prometheus => Box::new(prometheus::PrometheusSink::new(out, metadata)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_window_contains_everything() {
+        let window = FrameWindow::default();
+        assert!(window.contains(0));
+        assert!(window.contains(1_000_000));
+        assert!(!window.is_past(1_000_000));
+    }
+
+    #[test]
+    fn window_is_inclusive() {
+        let window = FrameWindow {
+            start: 2,
+            end: Some(4),
+        };
+        assert!(!window.contains(1));
+        assert!(window.contains(2));
+        assert!(window.contains(4));
+        assert!(!window.contains(5));
+        assert!(!window.is_past(4));
+        assert!(window.is_past(5));
+    }
+
+    #[test]
+    fn inverted_window_is_empty() {
+        let window = FrameWindow {
+            start: 6,
+            end: Some(3),
+        };
+        assert!((0..10).all(|i| !window.contains(i)));
+        assert!(window.is_past(7));
+    }
+
+    #[test]
+    fn seconds_translate_to_frames() {
+        assert_eq!(seconds_to_frame(0.0, 500_000_000), 0);
+        assert_eq!(seconds_to_frame(2.0, 500_000_000), 4);
+        assert_eq!(seconds_to_frame(2.7, 1_000_000_000), 2);
+        assert_eq!(seconds_to_frame(5.0, 0), 0);
+    }
+}
@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: MIT
+//! Prometheus text exposition of a whole recording: its totals, peaks and
+//! means as one snapshot, for pushing to a pushgateway after a batch run.
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+use anyhow::{Context, Result};
+
+use super::FrameSink;
+use crate::datasource::SessionMetadata;
+use crate::metrics::metric_header;
+use crate::sampler::accumulator::ComputedFrame;
+use crate::summary::Summary;
+
+/// Sums the exported frames and writes the metrics in `finish`.
+pub struct PrometheusSink<W: Write> {
+    out: W,
+    pid: i32,
+    fex_version: String,
+    summary: Summary,
+}
+
+impl<W: Write> PrometheusSink<W> {
+    #[must_use]
+    pub fn new(out: W, metadata: &SessionMetadata) -> Self {
+        Self {
+            out,
+            pid: metadata.pid,
+            fex_version: metadata.fex_version.clone(),
+            summary: Summary::default(),
+        }
+    }
+}
+
+/// Escapes a label value: backslash, double quote and newline.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// The exposition for `summary`, labelled with the session's pid and FEX
+/// version through an info metric.
+fn render(summary: &Summary, pid: i32, fex_version: &str) -> String {
+    let mut out = String::new();
+    metric_header(
+        &mut out,
+        "felix_recording_info",
+        "gauge",
+        "The recorded process, always 1",
+    );
+    let _ = writeln!(
+        out,
+        "felix_recording_info{{pid=\"{pid}\",fex_version=\"{}\"}} 1",
+        escape_label(fex_version)
+    );
+
+    #[allow(clippy::cast_precision_loss)]
+    let gauges = [
+        (
+            "felix_recording_frames",
+            "Frames in the recording",
+            summary.frames as f64,
+        ),
+        (
+            "felix_recording_duration_seconds",
+            "Recorded time, the sum of the sample periods",
+            summary.duration_ns as f64 / 1e9,
+        ),
+        (
+            "felix_recording_fex_load_min_percent",
+            "Lowest FEX JIT load of any frame",
+            summary.min_load,
+        ),
+        (
+            "felix_recording_fex_load_mean_percent",
+            "Mean FEX JIT load over the frames",
+            summary.mean_load(),
+        ),
+        (
+            "felix_recording_fex_load_peak_percent",
+            "Highest FEX JIT load of any frame",
+            summary.max_load,
+        ),
+        (
+            "felix_recording_sigbus_per_second",
+            "SIGBUS events per second over the recording",
+            summary.sigbus_rate(),
+        ),
+        (
+            "felix_recording_peak_memory_bytes",
+            "Largest total anonymous memory of any frame",
+            summary.peak_mem as f64,
+        ),
+        (
+            "felix_recording_peak_jit_code_bytes",
+            "Largest JIT code size of any frame",
+            summary.peak_jit_code as f64,
+        ),
+    ];
+    for (name, help, value) in gauges {
+        metric_header(&mut out, name, "gauge", help);
+        let _ = writeln!(out, "{name} {value}");
+    }
+
+    for (name, help, value) in [
+        (
+            "felix_recording_sigbus_total",
+            "SIGBUS events",
+            summary.total_sigbus,
+        ),
+        (
+            "felix_recording_smc_total",
+            "Self-modifying code events",
+            summary.total_smc,
+        ),
+        (
+            "felix_recording_softfloat_total",
+            "Softfloat fallback operations",
+            summary.total_float_fallback,
+        ),
+        (
+            "felix_recording_cache_miss_total",
+            "Block lookup cache misses",
+            summary.total_cache_miss,
+        ),
+        (
+            "felix_recording_jit_total",
+            "Blocks compiled",
+            summary.total_jit,
+        ),
+    ] {
+        metric_header(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{name} {value}");
+    }
+    out
+}
+
+impl<W: Write> FrameSink for PrometheusSink<W> {
+    fn write_frame(&mut self, _index: usize, frame: &ComputedFrame) -> Result<()> {
+        self.summary.add(frame);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let text = render(&self.summary, self.pid, &self.fex_version);
+        self.out
+            .write_all(text.as_bytes())
+            .and_then(|()| self.out.flush())
+            .context("failed to write Prometheus metrics")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_has_a_header_per_metric_and_recording_totals() {
+        let mut summary = Summary::default();
+        for (load, sigbus) in [(20.0, 3), (60.0, 1)] {
+            summary.add(&ComputedFrame {
+                fex_load_percent: load,
+                sample_period_ns: 500_000_000,
+                total_sigbus_count: sigbus,
+                ..ComputedFrame::default()
+            });
+        }
+        let text = render(&summary, 42, "FEX \"2501\"");
+        assert!(text.starts_with("# HELP felix_recording_info "));
+        assert!(
+            text.contains("felix_recording_info{pid=\"42\",fex_version=\"FEX \\\"2501\\\"\"} 1\n")
+        );
+        assert!(text.contains("felix_recording_duration_seconds 1\n"));
+        assert!(text.contains("felix_recording_fex_load_mean_percent 40\n"));
+        assert!(text.contains("felix_recording_fex_load_peak_percent 60\n"));
+        assert!(text.contains(
+            "# TYPE felix_recording_sigbus_total counter\nfelix_recording_sigbus_total 4\n"
+        ));
+        assert!(text.contains("felix_recording_sigbus_per_second 4\n"));
+
+        let mut samples = 0;
+        for line in text.lines() {
+            if let Some(name) = line.strip_prefix("# TYPE ") {
+                let name = name.split(' ').next().unwrap();
+                assert!(text.contains(&format!("# HELP {name} ")));
+            } else if !line.starts_with('#') {
+                samples += 1;
+            }
+        }
+        assert_eq!(samples, 14);
+    }
+}
@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: MIT
+//! Chrome Trace Event format, loadable in ui.perfetto.dev and
+//! `chrome://tracing`. Every metric becomes a counter track (`"ph": "C"`).
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+
+use super::FrameSink;
+use crate::sampler::accumulator::ComputedFrame;
+
+/// Trace viewers group tracks by pid; all felix tracks share one process.
+const TRACE_PID: u32 = 1;
+
+/// Streams frames as a `{"traceEvents": [...]}` object with one counter
+/// event per series per frame.
+pub struct TraceSink<W: Write> {
+    out: W,
+    first: bool,
+}
+
+impl<W: Write> TraceSink<W> {
+    /// Creates a trace sink and writes the object preamble plus a metadata
+    /// event naming the process track.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn new(mut out: W) -> Result<Self> {
+        out.write_all(b"{\"displayTimeUnit\":\"ms\",\"traceEvents\":[")
+            .context("failed to write trace preamble")?;
+        let mut sink = Self { out, first: true };
+        sink.write_event(&json!({
+            "name": "process_name",
+            "ph": "M",
+            "pid": TRACE_PID,
+            "args": { "name": "felix" },
+        }))?;
+        Ok(sink)
+    }
+
+    fn write_event(&mut self, event: &Value) -> Result<()> {
+        let separator: &[u8] = if self.first { b"\n" } else { b",\n" };
+        self.first = false;
+        self.out
+            .write_all(separator)
+            .context("failed to write trace separator")?;
+        serde_json::to_writer(&mut self.out, event).context("failed to write trace event")
+    }
+
+    fn write_counter(&mut self, name: &str, ts_us: f64, args: &Value) -> Result<()> {
+        self.write_event(&json!({
+            "name": name,
+            "ph": "C",
+            "ts": ts_us,
+            "pid": TRACE_PID,
+            "args": args,
+        }))
+    }
+}
+
+impl<W: Write> FrameSink for TraceSink<W> {
+    fn write_frame(&mut self, _index: usize, frame: &ComputedFrame) -> Result<()> {
+        #[allow(clippy::cast_precision_loss)]
+        let ts_us = frame.timestamp_ns as f64 / 1000.0;
+
+        self.write_counter(
+            "fex_load_percent",
+            ts_us,
+            &json!({ "value": frame.fex_load_percent }),
+        )?;
+        for (region, bytes) in frame.mem.regions() {
+            self.write_counter(&format!("mem.{region}"), ts_us, &json!({ "bytes": bytes }))?;
+        }
+        for tl in &frame.thread_loads {
+            self.write_counter(
+                &format!("thread {} load_percent", tl.tid),
+                ts_us,
+                &json!({ "value": tl.load_percent }),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out
+            .write_all(b"\n]}\n")
+            .context("failed to write trace trailer")?;
+        self.out.flush().context("failed to flush trace output")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::accumulator::ThreadLoad;
+
+    #[test]
+    fn counters_per_metric_and_thread() {
+        let mut frame = ComputedFrame {
+            timestamp_ns: 2_500_000,
+            fex_load_percent: 12.5,
+            thread_loads: vec![ThreadLoad {
+                tid: 42,
+                load_percent: 7.0,
+                total_cycles: 10,
+            }],
+            ..ComputedFrame::default()
+        };
+        frame.mem.jit_code = 8192;
+
+        let mut buf = Vec::new();
+        {
+            let mut sink = TraceSink::new(&mut buf).unwrap();
+            sink.write_frame(0, &frame).unwrap();
+            sink.finish().unwrap();
+        }
+
+        let parsed: Value = serde_json::from_slice(&buf).unwrap();
+        let events = parsed["traceEvents"].as_array().unwrap();
+        assert_eq!(events[0]["ph"], "M");
+
+        let find = |name: &str| events.iter().find(|e| e["name"] == name).unwrap();
+        let load = find("fex_load_percent");
+        assert_eq!(load["ph"], "C");
+        assert_eq!(load["ts"], 2500.0);
+        assert_eq!(load["args"]["value"], 12.5);
+        assert_eq!(find("mem.jit_code")["args"]["bytes"], 8192);
+        assert_eq!(find("thread 42 load_percent")["args"]["value"], 7.0);
+        // metadata + load + 12 memory regions + 1 thread
+        assert_eq!(events.len(), 15);
+    }
+
+    #[test]
+    fn empty_trace_is_valid() {
+        let mut buf = Vec::new();
+        {
+            let mut sink = TraceSink::new(&mut buf).unwrap();
+            sink.finish().unwrap();
+        }
+        let parsed: Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(parsed["traceEvents"].as_array().unwrap().len(), 1);
+    }
+}
@@ -1,9 +1,21 @@
 // SPDX-License-Identifier: MIT
 
+#[cfg(target_arch = "x86_64")]
+use std::sync::OnceLock;
+#[cfg(target_arch = "x86_64")]
+use std::time::{Duration, Instant};
+
+/// How long the TSC is timed against the monotonic clock when CPUID does not
+/// report its frequency.
+#[cfg(target_arch = "x86_64")]
+const TSC_CALIBRATION_INTERVAL: Duration = Duration::from_millis(50);
+
 /// Returns the frequency of the hardware cycle counter.
 ///
-/// On aarch64, reads `CNTFRQ_EL0`. On `x86_64`, returns 1 (cycle counter
-/// frequency is not directly readable).
+/// On aarch64, reads `CNTFRQ_EL0`. On `x86_64`, returns the TSC frequency,
+/// taken from CPUID when the CPU reports an invariant TSC and its crystal
+/// ratio, and otherwise measured once per run by timing `rdtsc` over a short
+/// interval.
 #[must_use]
 pub fn cycle_counter_frequency() -> u64 {
     #[cfg(target_arch = "aarch64")]
@@ -17,10 +29,65 @@ pub fn cycle_counter_frequency() -> u64 {
     }
     #[cfg(target_arch = "x86_64")]
     {
-        1
+        static FREQUENCY: OnceLock<u64> = OnceLock::new();
+        *FREQUENCY.get_or_init(|| cpuid_tsc_frequency().unwrap_or_else(measure_tsc_frequency))
     }
 }
 
+#[cfg(target_arch = "x86_64")]
+fn cpuid(leaf: u32) -> std::arch::x86_64::CpuidResult {
+    // SAFETY: CPUID is available on every x86_64 CPU, and leaves beyond the
+    // maximum supported one return zeros or repeat the last leaf.
+    unsafe { std::arch::x86_64::__cpuid(leaf) }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn rdtsc() -> u64 {
+    // SAFETY: RDTSC is available on every x86_64 CPU and has no side effects.
+    unsafe { std::arch::x86_64::_rdtsc() }
+}
+
+/// True if CPUID reports an invariant TSC, which ticks at a constant rate
+/// regardless of P-, C-, and T-states.
+#[cfg(target_arch = "x86_64")]
+fn invariant_tsc() -> bool {
+    let max_extended = cpuid(0x8000_0000).eax;
+    max_extended >= 0x8000_0007 && cpuid(0x8000_0007).edx & (1 << 8) != 0
+}
+
+/// The TSC frequency from CPUID leaf 0x15 (TSC to crystal clock ratio), if
+/// the TSC is invariant and the CPU fills in every field of the leaf.
+#[cfg(target_arch = "x86_64")]
+fn cpuid_tsc_frequency() -> Option<u64> {
+    if !invariant_tsc() || cpuid(0).eax < 0x15 {
+        return None;
+    }
+    let leaf = cpuid(0x15);
+    if leaf.eax == 0 || leaf.ebx == 0 || leaf.ecx == 0 {
+        return None;
+    }
+    Some(u64::from(leaf.ecx) * u64::from(leaf.ebx) / u64::from(leaf.eax))
+}
+
+/// Estimates the TSC frequency by counting ticks over
+/// `TSC_CALIBRATION_INTERVAL` of monotonic time.
+#[cfg(target_arch = "x86_64")]
+fn measure_tsc_frequency() -> u64 {
+    let start = Instant::now();
+    let start_ticks = rdtsc();
+    std::thread::sleep(TSC_CALIBRATION_INTERVAL);
+    let ticks = rdtsc().wrapping_sub(start_ticks);
+    let elapsed = start.elapsed().as_secs_f64();
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    let frequency = (ticks as f64 / elapsed) as u64;
+    frequency.max(1)
+}
+
 /// Issues a store memory barrier visible to the inner-shareable domain.
 ///
 /// On aarch64, executes `dmb ishst`. On `x86_64`, this is a no-op because
@@ -37,3 +104,19 @@ pub fn store_memory_barrier() {
     #[cfg(target_arch = "x86_64")]
     {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_counter_frequency_is_plausible() {
+        // Every supported counter runs between 1 MHz and 10 GHz.
+        let frequency = cycle_counter_frequency();
+        assert!(
+            (1_000_000..10_000_000_000).contains(&frequency),
+            "{frequency}"
+        );
+        assert_eq!(cycle_counter_frequency(), frequency);
+    }
+}
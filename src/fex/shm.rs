@@ -1,22 +1,29 @@
 // SPDX-License-Identifier: MIT
+use std::collections::HashSet;
+use std::fmt;
 use std::num::NonZeroUsize;
 use std::os::fd::{AsRawFd, OwnedFd};
 use std::ptr::{self, NonNull};
 
 use anyhow::{Context, bail};
+use nix::errno::Errno;
 use nix::fcntl::OFlag;
+#[cfg(target_os = "linux")]
+use nix::sys::mman::MRemapFlags;
 use nix::sys::mman::{self, MapFlags, ProtFlags};
 use nix::sys::stat::Mode;
 
 use super::types::{AppType, ThreadStats, ThreadStatsHeader};
 
-#[derive(Debug, Clone)]
+/// The header fields as of the latest read. `version`, `app_type` and
+/// `thread_stats_size` are fixed when FEX creates the segment and are only
+/// read at `open`; `head` and `size` are re-read every sample.
+#[derive(Debug, Clone, Copy)]
 pub struct HeaderSnapshot {
     pub version: u8,
     pub app_type: AppType,
     #[allow(dead_code)]
     pub thread_stats_size: u16,
-    pub fex_version: String,
     pub head: u32,
     pub size: u32,
 }
@@ -25,6 +32,17 @@ pub struct ShmReader {
     base: NonNull<u8>,
     fd: OwnedFd,
     size: usize,
+    header: HeaderSnapshot,
+    /// Decoded once at `open`, as FEX writes it before publishing the segment.
+    fex_version: String,
+    /// Set while a malformed thread list has been reported and not yet seen
+    /// intact again, so a persistently corrupt segment does not warn on
+    /// every sample.
+    warned: bool,
+    /// Scratch for `read_thread_stats`, cleared and refilled each sample so
+    /// steady-state reads do not allocate.
+    stats: Vec<ThreadStats>,
+    visited: HashSet<u32>,
 }
 
 // SAFETY: The mapped memory is read-only and only accessed through volatile reads.
@@ -72,105 +90,163 @@ impl ShmReader {
 
         let base = mapped.cast::<u8>();
 
+        // SAFETY: The mapping is at least as large as ThreadStatsHeader, and
+        // page-aligned because it comes from mmap. The read is volatile
+        // because the other process may update the header concurrently.
+        #[allow(clippy::cast_ptr_alignment)] // mmap guarantees page alignment
+        let raw = unsafe { ptr::read_volatile(base.as_ptr().cast::<ThreadStatsHeader>()) };
+        let version_len = raw
+            .fex_version
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(raw.fex_version.len());
+
         Ok(Self {
             base,
             fd,
             size: file_size,
+            header: HeaderSnapshot {
+                version: raw.version,
+                app_type: AppType::from_u8(raw.app_type).unwrap_or(AppType::Linux64),
+                thread_stats_size: raw.thread_stats_size,
+                head: raw.head,
+                size: raw.size,
+            },
+            fex_version: String::from_utf8_lossy(&raw.fex_version[..version_len]).into_owned(),
+            warned: false,
+            stats: Vec::new(),
+            visited: HashSet::new(),
         })
     }
 
-    /// Reads the shared memory header using volatile reads.
+    /// The header as of `open` or the latest `check_resize`.
+    #[must_use]
+    pub fn header(&self) -> HeaderSnapshot {
+        self.header
+    }
+
+    #[must_use]
+    pub fn fex_version(&self) -> &str {
+        &self.fex_version
+    }
+
+    /// Re-reads the header's `head` and `size` fields using volatile reads.
     ///
     /// # Panics
     ///
     /// Panics if the mapped region is smaller than `ThreadStatsHeader`. This
-    /// cannot happen because `open` validates the minimum size.
-    #[must_use]
-    pub fn read_header(&self) -> HeaderSnapshot {
+    /// cannot happen because `check_resize` bails out first.
+    fn refresh_header(&mut self) -> HeaderSnapshot {
         assert!(self.size >= std::mem::size_of::<ThreadStatsHeader>());
-
-        // SAFETY: We validated that the mapping is at least as large as
-        // ThreadStatsHeader. The pointer is aligned because mmap returns
-        // page-aligned addresses. We use read_volatile because the other
-        // process may update these fields concurrently.
-        #[allow(clippy::cast_ptr_alignment)] // mmap guarantees page alignment
-        let raw = unsafe { ptr::read_volatile(self.base.as_ptr().cast::<ThreadStatsHeader>()) };
-
-        let version_len = raw
-            .fex_version
-            .iter()
-            .position(|&b| b == 0)
-            .unwrap_or(raw.fex_version.len());
-        let fex_version = String::from_utf8_lossy(&raw.fex_version[..version_len]).into_owned();
-
-        let app_type = AppType::from_u8(raw.app_type).unwrap_or(AppType::Linux64);
-
-        HeaderSnapshot {
-            version: raw.version,
-            app_type,
-            thread_stats_size: raw.thread_stats_size,
-            fex_version,
-            head: raw.head,
-            size: raw.size,
-        }
+        let field = |offset: usize| {
+            // SAFETY: The mapping covers the header, whose u32 fields sit at
+            // 4-byte aligned offsets from a page-aligned base. We use
+            // read_volatile because the other process may update them
+            // concurrently.
+            #[allow(clippy::cast_ptr_alignment)]
+            unsafe {
+                ptr::read_volatile(self.base.as_ptr().add(offset).cast::<u32>())
+            }
+        };
+        self.header.head = field(std::mem::offset_of!(ThreadStatsHeader, head));
+        self.header.size = field(std::mem::offset_of!(ThreadStatsHeader, size));
+        self.header
     }
 
-    /// Walks the linked list of thread stats from the header and returns
-    /// a snapshot of all thread stats entries.
-    #[must_use]
-    pub fn read_thread_stats(&self) -> Vec<ThreadStats> {
-        let header = self.read_header();
-        let mut result = Vec::new();
-        let mut offset = header.head;
-
-        while offset != 0 {
-            let offset_usize = offset as usize;
-            if offset_usize + std::mem::size_of::<ThreadStats>() > self.size {
-                break;
+    /// Walks the linked list of thread stats starting at `head`, taken from
+    /// a header snapshot, and returns a snapshot of all thread stats entries.
+    /// The snapshot lives in a buffer reused by the next call.
+    /// Reads are bounded by the current mapping, which `check_resize` keeps
+    /// in step with the header's size. A misaligned or repeated offset ends
+    /// the walk early with a warning.
+    pub fn read_thread_stats(&mut self, head: u32) -> &[ThreadStats] {
+        // SAFETY: base points to a live mapping of self.size bytes.
+        let anomaly = unsafe {
+            walk_thread_list(
+                self.base.as_ptr(),
+                self.size,
+                head,
+                &mut self.stats,
+                &mut self.visited,
+            )
+        };
+        // Logged when an anomaly first shows up, not for every sample it
+        // persists; a clean walk re-arms the warning.
+        match anomaly {
+            Some(anomaly) if !std::mem::replace(&mut self.warned, true) => {
+                tracing::warn!(%anomaly, "ignoring the rest of the thread list");
             }
-
-            // SAFETY: We just bounds-checked that offset + sizeof(ThreadStats)
-            // fits within the mapped region. ThreadStats is repr(C, align(16))
-            // and shm offsets from FEX are always 16-byte aligned.
-            let stats = unsafe {
-                let src = self.base.as_ptr().add(offset_usize);
-                volatile_copy_thread_stats(src)
-            };
-
-            offset = stats.next;
-            result.push(stats);
+            Some(_) => {}
+            None => self.warned = false,
         }
-
-        result
+        &self.stats
     }
 
-    /// Re-checks the shared memory size and remaps if it has grown.
+    /// Re-reads the header's list head and size, remaps if the shared memory
+    /// size has changed, and returns the header so the caller can walk the
+    /// thread list without reading it again.
+    ///
+    /// Mapped pages past the end of the file fault when touched, so the
+    /// segment's current size is checked first and bounds the mapping.
     ///
     /// # Errors
     ///
-    /// Returns an error if the remap fails.
-    pub fn check_resize(&mut self) -> anyhow::Result<()> {
-        let header = self.read_header();
-
-        let new_size = header.size as usize;
-        if new_size == self.size || new_size == 0 {
-            return Ok(());
+    /// Returns `SegmentGone` if the segment has been truncated below its
+    /// header, as when the FEX process exits mid-sample, or another error if
+    /// the remap fails.
+    pub fn check_resize(&mut self) -> anyhow::Result<HeaderSnapshot> {
+        let min_size = std::mem::size_of::<ThreadStatsHeader>();
+        let file_size = self.file_size()?;
+        if file_size < min_size {
+            return Err(SegmentGone.into());
+        }
+        if file_size < self.size {
+            self.remap_to(file_size)?;
         }
 
-        // SAFETY: self.base was obtained from mmap and self.size is the
-        // correct mapped length.
-        unsafe {
-            mman::munmap(self.base.cast::<std::ffi::c_void>(), self.size)
-                .context("failed to munmap during resize")?;
+        let header = self.refresh_header();
+
+        let new_size = (header.size as usize).min(file_size);
+        if new_size != self.size && new_size >= min_size {
+            self.remap_to(new_size)?;
         }
+        Ok(header)
+    }
 
+    fn file_size(&self) -> anyhow::Result<usize> {
+        let stat =
+            nix::sys::stat::fstat(self.fd.as_raw_fd()).context("failed to fstat shared memory")?;
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        // st_size is non-negative for valid fds, and we target 64-bit only
+        Ok(stat.st_size as usize)
+    }
+
+    /// Resizes the mapping to `new_size` bytes, updating `base` and `size`
+    /// only once the new mapping is in place.
+    fn remap_to(&mut self, new_size: usize) -> anyhow::Result<()> {
         let map_len = NonZeroUsize::new(new_size).context("new size is zero")?;
+        // SAFETY: self.base was obtained from mmap with self.size length. On
+        // failure mremap leaves the old mapping in place.
+        let mapped = match unsafe { remap(self.base.cast(), self.size, new_size) } {
+            Ok(mapped) => mapped,
+            Err(Errno::ENOSYS) => self.map_then_unmap(map_len)?,
+            Err(err) => return Err(err).context("failed to remap shared memory"),
+        };
 
+        self.base = mapped.cast::<u8>();
+        self.size = new_size;
+        Ok(())
+    }
+
+    /// Resize fallback for kernels without mremap: maps `len` bytes afresh
+    /// before unmapping the old region, so `self.base` stays valid if the new
+    /// mapping fails.
+    fn map_then_unmap(&self, len: NonZeroUsize) -> anyhow::Result<NonNull<std::ffi::c_void>> {
         // SAFETY: Valid fd, read-only mapping, shared.
-        let mapped: NonNull<std::ffi::c_void> = unsafe {
+        let mapped = unsafe {
             mman::mmap(
                 None,
-                map_len,
+                len,
                 ProtFlags::PROT_READ,
                 MapFlags::MAP_SHARED,
                 &self.fd,
@@ -178,12 +254,40 @@ impl ShmReader {
             )
             .context("failed to remap shared memory")?
         };
+        // SAFETY: self.base was obtained from mmap and self.size is the
+        // correct mapped length. Nothing refers to it past this point.
+        if let Err(err) = unsafe { mman::munmap(self.base.cast(), self.size) } {
+            // SAFETY: mapped was just obtained from mmap with len bytes.
+            let _ = unsafe { mman::munmap(mapped, len.get()) };
+            return Err(err).context("failed to munmap during resize");
+        }
+        Ok(mapped)
+    }
+}
 
-        self.base = mapped.cast::<u8>();
-        self.size = new_size;
+/// Grows or shrinks a read-only shared mapping in one step, moving it if
+/// needed.
+///
+/// # Safety
+///
+/// `base` must be a live mapping of `old_size` bytes.
+#[cfg(target_os = "linux")]
+unsafe fn remap(
+    base: NonNull<std::ffi::c_void>,
+    old_size: usize,
+    new_size: usize,
+) -> nix::Result<NonNull<std::ffi::c_void>> {
+    // SAFETY: Upheld by the caller.
+    unsafe { mman::mremap(base, old_size, new_size, MRemapFlags::MREMAP_MAYMOVE, None) }
+}
 
-        Ok(())
-    }
+#[cfg(not(target_os = "linux"))]
+unsafe fn remap(
+    _base: NonNull<std::ffi::c_void>,
+    _old_size: usize,
+    _new_size: usize,
+) -> nix::Result<NonNull<std::ffi::c_void>> {
+    Err(Errno::ENOSYS)
 }
 
 impl Drop for ShmReader {
@@ -196,6 +300,83 @@ impl Drop for ShmReader {
     }
 }
 
+/// The shared memory segment shrank below its header, which happens when
+/// the FEX process exits between samples. Callers treat it as the process
+/// having gone rather than as a failure.
+#[derive(Debug)]
+pub struct SegmentGone;
+
+impl fmt::Display for SegmentGone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FEX shared memory segment has gone away")
+    }
+}
+
+impl std::error::Error for SegmentGone {}
+
+/// Why a walk of the thread list stopped before reaching a zero `next`.
+#[derive(Debug, PartialEq, Eq)]
+enum ListAnomaly {
+    /// The offset is not aligned to `ThreadStats`, so it points mid-struct.
+    Misaligned(u32),
+    /// The offset was already visited; following it would loop forever.
+    Cycle(u32),
+}
+
+impl fmt::Display for ListAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Misaligned(offset) => {
+                write!(f, "FEX thread list offset {offset:#x} is misaligned")
+            }
+            Self::Cycle(offset) => write!(f, "FEX thread list loops back to {offset:#x}"),
+        }
+    }
+}
+
+/// Follows `next` offsets from `head` through the `size`-byte region at
+/// `base`, replacing the contents of `result`; `visited` is scratch space.
+/// Offsets that would read past the end stop the walk quietly, since FEX may
+/// have grown the segment since the last remap.
+///
+/// # Safety
+///
+/// `base` must point to a readable, 16-byte aligned region of `size` bytes.
+unsafe fn walk_thread_list(
+    base: *const u8,
+    size: usize,
+    head: u32,
+    result: &mut Vec<ThreadStats>,
+    visited: &mut HashSet<u32>,
+) -> Option<ListAnomaly> {
+    result.clear();
+    visited.clear();
+    let mut offset = head;
+
+    while offset != 0 {
+        let offset_usize = offset as usize;
+        if offset_usize + std::mem::size_of::<ThreadStats>() > size {
+            break;
+        }
+        if !offset_usize.is_multiple_of(std::mem::align_of::<ThreadStats>()) {
+            return Some(ListAnomaly::Misaligned(offset));
+        }
+        if !visited.insert(offset) {
+            return Some(ListAnomaly::Cycle(offset));
+        }
+
+        // SAFETY: We just bounds-checked that offset + sizeof(ThreadStats)
+        // fits within the region, and checked the offset is 16-byte aligned
+        // from a base the caller guarantees is aligned too.
+        let stats = unsafe { volatile_copy_thread_stats(base.add(offset_usize)) };
+
+        offset = stats.next;
+        result.push(stats);
+    }
+
+    None
+}
+
 /// Performs a volatile copy of a `ThreadStats` struct using naturally-aligned
 /// chunk reads to take advantage of single-copy atomicity guarantees.
 ///
@@ -239,3 +420,129 @@ unsafe fn volatile_copy_thread_stats(src: *const u8) -> ThreadStats {
 
     dest
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::cast_possible_truncation)]
+    const STRIDE: u32 = std::mem::size_of::<ThreadStats>() as u32;
+
+    /// Walks a list laid out in `nodes`, whose first slot stands in for the
+    /// header (offset zero terminates the list).
+    fn walk(nodes: &[ThreadStats], head: u32) -> (Vec<ThreadStats>, Option<ListAnomaly>) {
+        let bytes = std::mem::size_of_val(nodes);
+        let mut stats = Vec::new();
+        // SAFETY: nodes is a live, 16-byte aligned slice of `bytes` bytes.
+        let anomaly = unsafe {
+            walk_thread_list(
+                nodes.as_ptr().cast::<u8>(),
+                bytes,
+                head,
+                &mut stats,
+                &mut HashSet::new(),
+            )
+        };
+        (stats, anomaly)
+    }
+
+    fn node(tid: u32, next: u32) -> ThreadStats {
+        ThreadStats {
+            next,
+            tid,
+            ..ThreadStats::default()
+        }
+    }
+
+    #[test]
+    fn walks_list_until_zero() {
+        let nodes = [node(0, 0), node(1, STRIDE * 2), node(2, 0)];
+        let (stats, anomaly) = walk(&nodes, STRIDE);
+        assert_eq!(stats.iter().map(|s| s.tid).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(anomaly, None);
+    }
+
+    #[test]
+    fn stops_on_cycles_and_misaligned_offsets() {
+        let nodes = [node(0, 0), node(1, STRIDE * 2), node(2, STRIDE)];
+        let (stats, anomaly) = walk(&nodes, STRIDE);
+        assert_eq!(stats.len(), 2);
+        assert_eq!(anomaly, Some(ListAnomaly::Cycle(STRIDE)));
+
+        let nodes = [node(0, 0), node(1, STRIDE + 8), node(2, 0)];
+        let (stats, anomaly) = walk(&nodes, STRIDE);
+        assert_eq!(stats.len(), 1);
+        assert_eq!(anomaly, Some(ListAnomaly::Misaligned(STRIDE + 8)));
+    }
+
+    #[test]
+    fn resize_follows_the_header_and_file_size() {
+        use std::os::unix::fs::FileExt;
+
+        // No real process has a negative pid, so this cannot clash with FEX.
+        let pid = -i32::try_from(std::process::id()).unwrap();
+        let name = format!("/fex-{pid}-stats");
+        let fd = mman::shm_open(
+            name.as_str(),
+            OFlag::O_CREAT | OFlag::O_RDWR,
+            Mode::S_IRUSR | Mode::S_IWUSR,
+        )
+        .unwrap();
+        let file = std::fs::File::from(fd);
+        let set_size = |size: u32| {
+            file.set_len(size.into()).unwrap();
+            let offset = std::mem::offset_of!(ThreadStatsHeader, size) as u64;
+            file.write_all_at(&size.to_ne_bytes(), offset).unwrap();
+        };
+
+        let version_offset = std::mem::offset_of!(ThreadStatsHeader, fex_version) as u64;
+        set_size(4096);
+        file.write_all_at(b"FEX-2501\0", version_offset).unwrap();
+        let reader = ShmReader::open(pid);
+        let _ = mman::shm_unlink(name.as_str());
+        let mut reader = reader.unwrap();
+        // The version is taken once at open, not on every sample.
+        file.write_all_at(b"FEX-9999\0", version_offset).unwrap();
+
+        set_size(64 * 1024);
+        file.write_all_at(&[0xab], 64 * 1024 - 1).unwrap();
+        assert_eq!(reader.check_resize().unwrap().size, 64 * 1024);
+        assert_eq!(reader.size, 64 * 1024);
+        // SAFETY: the mapping now covers 64 KiB.
+        let last = unsafe { reader.base.as_ptr().add(64 * 1024 - 1).read_volatile() };
+        assert_eq!(last, 0xab);
+
+        set_size(8192);
+        assert_eq!(reader.check_resize().unwrap().size, 8192);
+        assert_eq!(reader.size, 8192);
+
+        // Once the mapping is sized, sampling does not allocate.
+        let head = reader.check_resize().unwrap().head;
+        reader.read_thread_stats(head);
+        let (_, allocations) = crate::testing::count_allocations(|| {
+            let head = reader.check_resize().unwrap().head;
+            reader.read_thread_stats(head).len()
+        });
+        assert_eq!(allocations, 0);
+
+        // A corrupt list warns once per run of bad samples.
+        let next_offset = std::mem::offset_of!(ThreadStats, next) as u64;
+        file.write_all_at(&4096u32.to_ne_bytes(), 4096 + next_offset)
+            .unwrap();
+        reader.read_thread_stats(4096);
+        assert!(reader.warned);
+        reader.read_thread_stats(4096);
+        assert!(reader.warned);
+        reader.read_thread_stats(0);
+        assert!(!reader.warned);
+
+        // A file cut short of the header's size bounds the mapping, and one
+        // cut below the header means the process is gone.
+        file.set_len(4096).unwrap();
+        assert_eq!(reader.check_resize().unwrap().size, 8192);
+        assert_eq!(reader.size, 4096);
+        file.set_len(0).unwrap();
+        assert!(reader.check_resize().unwrap_err().is::<SegmentGone>());
+        assert_eq!(reader.fex_version(), "FEX-2501");
+    }
+}
@@ -1,8 +1,12 @@
 // SPDX-License-Identifier: MIT
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
 
-use anyhow::Context;
+use anyhow::{Context, bail};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -15,6 +19,11 @@ pub struct LargestAnon {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MemSnapshot {
     pub total_anon: u64,
+    /// Proportional set size of the FEX regions, sharing pages fairly
+    /// between the processes that map them.
+    pub total_pss: u64,
+    /// Bytes of the FEX regions that are swapped out.
+    pub total_swap: u64,
     pub jit_code: u64,
     pub op_dispatcher: u64,
     pub frontend: u64,
@@ -29,13 +38,60 @@ pub struct MemSnapshot {
     pub largest_anon: LargestAnon,
 }
 
+impl MemSnapshot {
+    /// Every resident-size field paired with a stable lowercase name, for
+    /// exporters that emit one series per region.
+    #[must_use]
+    pub fn regions(&self) -> [(&'static str, u64); 12] {
+        [
+            ("total_anon", self.total_anon),
+            ("jit_code", self.jit_code),
+            ("op_dispatcher", self.op_dispatcher),
+            ("frontend", self.frontend),
+            ("cpu_backend", self.cpu_backend),
+            ("lookup", self.lookup),
+            ("lookup_l1", self.lookup_l1),
+            ("thread_states", self.thread_states),
+            ("block_links", self.block_links),
+            ("misc", self.misc),
+            ("jemalloc", self.jemalloc),
+            ("unaccounted", self.unaccounted),
+        ]
+    }
+}
+
+/// Which `/proc/<pid>` file a `MemSampler` reads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum MemMode {
+    /// Parse `smaps` for the per-region FEX breakdown
+    #[default]
+    Full,
+    /// Read process-wide totals from `smaps_rollup`. Much cheaper, but
+    /// loses the FEX region breakdown
+    Rollup,
+}
+
+/// How a `MemSampler` reads and classifies memory.
+#[derive(Clone, Default)]
+pub struct MemOptions {
+    pub mode: MemMode,
+    /// Collect the names of unrecognized `FEXMem` regions; see
+    /// `MemSampler::unknown_regions`.
+    pub track_unknown: bool,
+    pub patterns: Arc<RegionPatterns>,
+}
+
 pub struct MemSampler {
     file: File,
     buf: String,
+    options: MemOptions,
+    /// Peak resident bytes of each `FEXMem` region name the parser does not
+    /// recognize, when tracking was asked for.
+    unknown_regions: Option<BTreeMap<String, u64>>,
 }
 
 /// Identifies which sub-region accumulator an smaps region maps to.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum ActiveRegion {
     JitCode,
     OpDispatcher,
@@ -50,22 +106,165 @@ enum ActiveRegion {
     Unaccounted,
 }
 
+impl ActiveRegion {
+    /// Every region with the name of the `MemSnapshot` field it adds to.
+    const ALL: [(&'static str, Self); 11] = [
+        ("jit_code", Self::JitCode),
+        ("op_dispatcher", Self::OpDispatcher),
+        ("frontend", Self::Frontend),
+        ("cpu_backend", Self::CpuBackend),
+        ("lookup", Self::Lookup),
+        ("lookup_l1", Self::LookupL1),
+        ("thread_states", Self::ThreadStates),
+        ("block_links", Self::BlockLinks),
+        ("misc", Self::Misc),
+        ("jemalloc", Self::JeMalloc),
+        ("unaccounted", Self::Unaccounted),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|&(_, region)| region)
+    }
+}
+
+/// Maps smaps region names to memory categories: the first pattern a
+/// mapping line contains decides its category. A `FEXMem` region no pattern
+/// matches is unaccounted.
+#[derive(Clone)]
+pub struct RegionPatterns {
+    rules: Vec<(String, ActiveRegion)>,
+}
+
+impl Default for RegionPatterns {
+    /// The labels upstream FEX uses. Order matters: more specific names come
+    /// before the names they contain.
+    fn default() -> Self {
+        let rules = [
+            ("FEXMemJIT", ActiveRegion::JitCode),
+            ("FEXMem_OpDispatcher", ActiveRegion::OpDispatcher),
+            ("FEXMem_Frontend", ActiveRegion::Frontend),
+            ("FEXMem_CPUBackend", ActiveRegion::CpuBackend),
+            ("FEXMem_Lookup_L1", ActiveRegion::LookupL1),
+            ("FEXMem_Lookup", ActiveRegion::Lookup),
+            ("FEXMem_ThreadState", ActiveRegion::ThreadStates),
+            ("FEXMem_BlockLinks", ActiveRegion::BlockLinks),
+            ("FEXMem_Misc", ActiveRegion::Misc),
+            ("JEMalloc", ActiveRegion::JeMalloc),
+            ("FEXAllocator", ActiveRegion::JeMalloc),
+        ];
+        Self {
+            rules: rules
+                .into_iter()
+                .map(|(pattern, region)| (pattern.to_string(), region))
+                .collect(),
+        }
+    }
+}
+
+/// On-disk pattern list: `[[region]]` tables, tried in file order.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatternsConfig {
+    region: Vec<PatternConfig>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatternConfig {
+    /// Substring of the smaps mapping line, e.g. `FEXMemJIT`.
+    pattern: String,
+    /// Name of the `MemSnapshot` field the region adds to, e.g. `jit_code`.
+    category: String,
+}
+
+impl RegionPatterns {
+    /// Loads patterns from a TOML file, or returns the built-in ones if no
+    /// path is given. The file replaces the built-in list entirely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, a pattern is
+    /// empty, or a category is not a known memory field.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read region patterns: {}", path.display()))?;
+        Self::parse(&text)
+            .with_context(|| format!("invalid region patterns file: {}", path.display()))
+    }
+
+    fn parse(text: &str) -> anyhow::Result<Self> {
+        let config: PatternsConfig = toml::from_str(text)?;
+        let rules = config
+            .region
+            .into_iter()
+            .map(|rule| {
+                if rule.pattern.is_empty() {
+                    bail!("empty pattern for category \"{}\"", rule.category);
+                }
+                let Some(region) = ActiveRegion::from_name(&rule.category) else {
+                    let known: Vec<_> = ActiveRegion::ALL.iter().map(|(name, _)| *name).collect();
+                    bail!(
+                        "unknown category \"{}\" for pattern \"{}\" (expected one of: {})",
+                        rule.category,
+                        rule.pattern,
+                        known.join(", ")
+                    );
+                };
+                Ok((rule.pattern, region))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    fn classify(&self, line: &str) -> Option<ActiveRegion> {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| line.contains(pattern.as_str()))
+            .map(|&(_, region)| region)
+    }
+}
+
 impl MemSampler {
-    /// Opens `/proc/{pid}/smaps` and keeps the fd open for repeated sampling.
+    /// Opens `/proc/{pid}/smaps`, or `smaps_rollup` in rollup mode, and
+    /// keeps the fd open for repeated sampling. Rollup snapshots fill only
+    /// `total_anon`, `total_pss`, and `total_swap`, covering the whole
+    /// process rather than FEX regions, since the kernel sums over every
+    /// mapping.
     ///
     /// # Errors
     ///
-    /// Returns an error if the smaps file cannot be opened.
-    pub fn new(pid: i32) -> anyhow::Result<Self> {
-        let path = format!("/proc/{pid}/smaps");
+    /// Returns an error if the file cannot be opened.
+    pub fn open(pid: i32, options: &MemOptions) -> anyhow::Result<Self> {
+        let (name, capacity) = match options.mode {
+            MemMode::Full => ("smaps", 256 * 1024),
+            MemMode::Rollup => ("smaps_rollup", 4 * 1024),
+        };
+        let path = format!("/proc/{pid}/{name}");
         let file = File::open(&path).with_context(|| format!("failed to open {path}"))?;
         Ok(Self {
             file,
-            buf: String::with_capacity(256 * 1024),
+            buf: String::with_capacity(capacity),
+            options: options.clone(),
+            unknown_regions: options.track_unknown.then(BTreeMap::new),
         })
     }
 
-    /// Reads and parses the full smaps file, returning a memory snapshot.
+    /// Each `FEXMem` region name that fell through to `unaccounted`, with the
+    /// largest total resident size it reached in one sample, or `None` if
+    /// tracking is off. Always empty in rollup mode, which sees no region
+    /// names.
+    #[must_use]
+    pub fn unknown_regions(&self) -> Option<&BTreeMap<String, u64>> {
+        self.unknown_regions.as_ref()
+    }
+
+    /// Re-reads and parses the file, returning a memory snapshot.
     ///
     /// # Errors
     ///
@@ -79,64 +278,87 @@ impl MemSampler {
             .read_to_string(&mut self.buf)
             .context("failed to read smaps")?;
 
-        Ok(parse_smaps(&self.buf))
+        let patterns = &self.options.patterns;
+        Ok(match self.options.mode {
+            MemMode::Full => match &mut self.unknown_regions {
+                Some(peaks) => {
+                    let mut sample = BTreeMap::new();
+                    let snap = parse_smaps(&self.buf, patterns, Some(&mut sample));
+                    for (name, rss) in sample {
+                        let peak = peaks.entry(name).or_default();
+                        *peak = (*peak).max(rss);
+                    }
+                    snap
+                }
+                None => parse_smaps(&self.buf, patterns, None),
+            },
+            MemMode::Rollup => parse_smaps_rollup(&self.buf),
+        })
+    }
+}
+
+/// Reads the process totals from `smaps_rollup`. `Anonymous:` stands in for
+/// the FEX anonymous total, since the rollup has no per-region names.
+fn parse_smaps_rollup(content: &str) -> MemSnapshot {
+    let mut snap = MemSnapshot::default();
+    for line in content.lines() {
+        if let Some(bytes) = parse_kb_line(line, "Anonymous:") {
+            snap.total_anon = bytes;
+        } else if let Some(bytes) = parse_pss_line(line) {
+            snap.total_pss = bytes;
+        } else if let Some(bytes) = parse_swap_line(line) {
+            snap.total_swap = bytes;
+        }
     }
+    snap
 }
 
-fn parse_smaps(content: &str) -> MemSnapshot {
+/// Sums the Rss of the FEX regions in `smaps` by kind, as `patterns`
+/// classifies them; any other `FEXMem` region is unaccounted. If `unknown` is
+/// given, the Rss of each of those is also added up under its name.
+fn parse_smaps(
+    content: &str,
+    patterns: &RegionPatterns,
+    mut unknown: Option<&mut BTreeMap<String, u64>>,
+) -> MemSnapshot {
     let mut snap = MemSnapshot::default();
     let mut active: Option<ActiveRegion> = None;
+    let mut unknown_name: Option<&str> = None;
     let mut current_begin: u64 = 0;
     let mut current_end: u64 = 0;
 
     for line in content.lines() {
         // Region header lines look like:
         // 359519000-359918000 ---p 00000000 00:00 0    [anon:FEXMem]
-        if line.contains("FEXMem") {
-            if let Some((begin, end)) = parse_address_range(line) {
-                current_begin = begin;
-                current_end = end;
-            }
-
-            // Order matters: check more specific names before less specific ones.
-            if line.contains("FEXMemJIT") {
-                active = Some(ActiveRegion::JitCode);
-            } else if line.contains("FEXMem_OpDispatcher") {
-                active = Some(ActiveRegion::OpDispatcher);
-            } else if line.contains("FEXMem_Frontend") {
-                active = Some(ActiveRegion::Frontend);
-            } else if line.contains("FEXMem_CPUBackend") {
-                active = Some(ActiveRegion::CpuBackend);
-            } else if line.contains("FEXMem_Lookup_L1") {
-                active = Some(ActiveRegion::LookupL1);
-            } else if line.contains("FEXMem_Lookup") {
-                active = Some(ActiveRegion::Lookup);
-            } else if line.contains("FEXMem_ThreadState") {
-                active = Some(ActiveRegion::ThreadStates);
-            } else if line.contains("FEXMem_BlockLinks") {
-                active = Some(ActiveRegion::BlockLinks);
-            } else if line.contains("FEXMem_Misc") {
-                active = Some(ActiveRegion::Misc);
-            } else {
+        if let Some((begin, end)) = parse_address_range(line) {
+            current_begin = begin;
+            current_end = end;
+            active = patterns.classify(line);
+            unknown_name = None;
+            if active.is_none() && line.contains("FEXMem") {
                 active = Some(ActiveRegion::Unaccounted);
-            }
-            continue;
-        }
-
-        if line.contains("JEMalloc") || line.contains("FEXAllocator") {
-            active = Some(ActiveRegion::JeMalloc);
-            if let Some((begin, end)) = parse_address_range(line) {
-                current_begin = begin;
-                current_end = end;
+                unknown_name = Some(region_name(line));
             }
             continue;
         }
 
         if line.contains("VmFlags") {
             active = None;
+            unknown_name = None;
             continue;
         }
 
+        if active.is_some() {
+            if let Some(pss_bytes) = parse_pss_line(line) {
+                snap.total_pss += pss_bytes;
+                continue;
+            }
+            if let Some(swap_bytes) = parse_swap_line(line) {
+                snap.total_swap += swap_bytes;
+                continue;
+            }
+        }
+
         if let Some(region) = active
             && let Some(rss_bytes) = parse_rss_line(line)
         {
@@ -155,8 +377,11 @@ fn parse_smaps(content: &str) -> MemSnapshot {
                 ActiveRegion::Unaccounted => &mut snap.unaccounted,
             };
             *target += rss_bytes;
+            if let (Some(name), Some(unknown)) = (unknown_name, unknown.as_deref_mut()) {
+                *unknown.entry(name.to_string()).or_default() += rss_bytes;
+            }
 
-            if matches!(region, ActiveRegion::JeMalloc) && rss_bytes > snap.largest_anon.size {
+            if rss_bytes > snap.largest_anon.size {
                 snap.largest_anon = LargestAnon {
                     begin: current_begin,
                     end: current_end,
@@ -169,6 +394,18 @@ fn parse_smaps(content: &str) -> MemSnapshot {
     snap
 }
 
+/// The name a mapping line gives its region, e.g. `[anon:FEXMem_New]`, falling
+/// back to the line's last field.
+fn region_name(line: &str) -> &str {
+    line.rfind("[anon:")
+        .and_then(|start| {
+            let name = &line[start..];
+            name.find(']').map(|end| &name[..=end])
+        })
+        .or_else(|| line.split_whitespace().last())
+        .unwrap_or_default()
+}
+
 /// Parses an address range from the start of a mapping line.
 /// Example: `359519000-359918000 ---p ...` -> Some((0x359519000, 0x359918000))
 fn parse_address_range(line: &str) -> Option<(u64, u64)> {
@@ -182,12 +419,23 @@ fn parse_address_range(line: &str) -> Option<(u64, u64)> {
 /// Parses an `Rss:` line and returns the value in bytes.
 /// Example: `Rss:                 560 kB` -> Some(573440)
 fn parse_rss_line(line: &str) -> Option<u64> {
-    let trimmed = line.trim_start();
-    if !trimmed.starts_with("Rss:") {
-        return None;
-    }
+    parse_kb_line(line, "Rss:")
+}
+
+/// Parses a `Pss:` line and returns the value in bytes.
+fn parse_pss_line(line: &str) -> Option<u64> {
+    parse_kb_line(line, "Pss:")
+}
+
+/// Parses a `Swap:` line and returns the value in bytes.
+fn parse_swap_line(line: &str) -> Option<u64> {
+    parse_kb_line(line, "Swap:")
+}
 
-    let value_part = trimmed.strip_prefix("Rss:")?;
+/// Parses a `<key> <n> kB` line. `key` includes the colon, so `Pss:` does not
+/// match `Pss_Anon:` and `Swap:` does not match `SwapPss:`.
+fn parse_kb_line(line: &str, key: &str) -> Option<u64> {
+    let value_part = line.trim_start().strip_prefix(key)?;
     let mut parts = value_part.split_whitespace();
     let size_str = parts.next()?;
     let granule = parts.next()?;
@@ -220,6 +468,14 @@ mod tests {
         assert_eq!(parse_rss_line("Pss:                 560 kB"), None);
     }
 
+    #[test]
+    fn parse_pss_and_swap_lines() {
+        assert_eq!(parse_pss_line("Pss:                 280 kB"), Some(286_720));
+        assert_eq!(parse_pss_line("Pss_Anon:            280 kB"), None);
+        assert_eq!(parse_swap_line("Swap:                 16 kB"), Some(16_384));
+        assert_eq!(parse_swap_line("SwapPss:              16 kB"), None);
+    }
+
     #[test]
     fn parse_address_range_valid() {
         let line = "359519000-359918000 ---p 00000000 00:00 0                                [anon:FEXMem]";
@@ -240,13 +496,132 @@ VmFlags: rd
 400000000-400100000 ---p 00000000 00:00 0                                [anon:JEMalloc]
 Size:               1024 kB
 Rss:                 128 kB
-Pss:                 128 kB
+Pss:                  64 kB
+Swap:                 32 kB
+SwapPss:              16 kB
 VmFlags: rd wr
 ";
-        let snap = parse_smaps(content);
+        let snap = parse_smaps(content, &RegionPatterns::default(), None);
         assert_eq!(snap.jit_code, 560 * 1024);
         assert_eq!(snap.jemalloc, 128 * 1024);
         assert_eq!(snap.total_anon, (560 + 128) * 1024);
-        assert_eq!(snap.largest_anon.size, 128 * 1024);
+        assert_eq!(snap.total_pss, (560 + 64) * 1024);
+        assert_eq!(snap.total_swap, 32 * 1024);
+        assert_eq!(snap.largest_anon.size, 560 * 1024);
+    }
+
+    #[test]
+    fn parse_smaps_rollup_totals() {
+        let content = "\
+00400000-7ffc6a5f2000 ---p 00000000 00:00 0                              [rollup]
+Rss:               20480 kB
+Pss:               10240 kB
+Pss_Anon:           8192 kB
+Anonymous:          9216 kB
+Swap:                512 kB
+SwapPss:             256 kB
+";
+        let snap = parse_smaps_rollup(content);
+        assert_eq!(snap.total_anon, 9216 * 1024);
+        assert_eq!(snap.total_pss, 10240 * 1024);
+        assert_eq!(snap.total_swap, 512 * 1024);
+        assert_eq!(snap.jit_code, 0);
+    }
+
+    #[test]
+    fn largest_anon_spans_all_regions() {
+        let content = "\
+400000000-400100000 ---p 00000000 00:00 0                                [anon:JEMalloc]
+Rss:                 128 kB
+VmFlags: rd wr
+359519000-359918000 ---p 00000000 00:00 0                                [anon:FEXMemJIT]
+Rss:                2048 kB
+VmFlags: rd
+500000000-500200000 ---p 00000000 00:00 0                                [anon:FEXMem_Lookup]
+Rss:                 512 kB
+VmFlags: rd wr
+";
+        let snap = parse_smaps(content, &RegionPatterns::default(), None);
+        assert_eq!(snap.largest_anon.size, 2048 * 1024);
+        assert_eq!(snap.largest_anon.begin, 0x3_5951_9000);
+        assert_eq!(snap.largest_anon.end, 0x3_5991_8000);
+    }
+
+    #[test]
+    fn unknown_regions_collected_by_name() {
+        let content = "\
+359519000-359918000 ---p 00000000 00:00 0                                [anon:FEXMemJIT]
+Rss:                 560 kB
+VmFlags: rd
+500000000-500200000 ---p 00000000 00:00 0                                [anon:FEXMem_Shiny]
+Rss:                 256 kB
+VmFlags: rd wr
+600000000-600200000 ---p 00000000 00:00 0                                [anon:FEXMem_Shiny]
+Rss:                 128 kB
+VmFlags: rd wr
+700000000-700200000 ---p 00000000 00:00 0                                [anon:FEXMem]
+Rss:                  64 kB
+VmFlags: rd wr
+";
+        let mut unknown = BTreeMap::new();
+        let snap = parse_smaps(content, &RegionPatterns::default(), Some(&mut unknown));
+        assert_eq!(snap.unaccounted, (256 + 128 + 64) * 1024);
+        assert_eq!(unknown.len(), 2);
+        assert_eq!(unknown["[anon:FEXMem_Shiny]"], (256 + 128) * 1024);
+        assert_eq!(unknown["[anon:FEXMem]"], 64 * 1024);
+    }
+
+    #[test]
+    fn custom_patterns_replace_builtin() {
+        let patterns = RegionPatterns::parse(
+            r#"
+[[region]]
+pattern = "ForkJIT"
+category = "jit_code"
+
+[[region]]
+pattern = "FEXMem_Shiny"
+category = "misc"
+"#,
+        )
+        .unwrap();
+        let content = "\
+359519000-359918000 ---p 00000000 00:00 0                                [anon:ForkJIT]
+Rss:                 560 kB
+VmFlags: rd
+500000000-500200000 ---p 00000000 00:00 0                                [anon:FEXMem_Shiny]
+Rss:                 256 kB
+VmFlags: rd wr
+600000000-600200000 ---p 00000000 00:00 0                                [anon:FEXMemJIT]
+Rss:                 128 kB
+VmFlags: rd wr
+";
+        let mut unknown = BTreeMap::new();
+        let snap = parse_smaps(content, &patterns, Some(&mut unknown));
+        assert_eq!(snap.jit_code, 560 * 1024);
+        assert_eq!(snap.misc, 256 * 1024);
+        // The built-in FEXMemJIT pattern is gone, so that region falls
+        // through to unaccounted.
+        assert_eq!(snap.unaccounted, 128 * 1024);
+        assert_eq!(unknown.keys().collect::<Vec<_>>(), ["[anon:FEXMemJIT]"]);
+    }
+
+    #[test]
+    fn patterns_reject_unknown_category() {
+        let err = RegionPatterns::parse(
+            r#"
+[[region]]
+pattern = "FEXMemJIT"
+category = "jit"
+"#,
+        )
+        .err()
+        .unwrap();
+        let message = format!("{err:#}");
+        assert!(message.contains("unknown category \"jit\""), "{message}");
+        assert!(message.contains("jit_code"), "{message}");
+
+        let empty = "[[region]]\npattern = \"\"\ncategory = \"misc\"\n";
+        assert!(RegionPatterns::parse(empty).is_err());
     }
 }
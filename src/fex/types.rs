@@ -1,10 +1,52 @@
 // SPDX-License-Identifier: MIT
 use std::fmt;
 
+use anyhow::bail;
 use serde::{Deserialize, Serialize};
 
+/// The stats layout felix is written against.
 pub const STATS_VERSION: u8 = 2;
 
+/// Newest stats version felix still attaches to, with a warning, on the
+/// assumption that FEX only appended fields to the layout felix reads.
+/// Raise it once a new version is known to be compatible; versions past it
+/// are refused.
+pub const MAX_COMPATIBLE_STATS_VERSION: u8 = 3;
+
+/// Checks that felix can read a stats region of `version` whose entries are
+/// `thread_stats_size` bytes each.
+///
+/// # Errors
+///
+/// Returns an error for versions older than `STATS_VERSION` or newer than
+/// `MAX_COMPATIBLE_STATS_VERSION`, and for newer versions whose entries are
+/// smaller than the `ThreadStats` felix reads.
+pub fn check_stats_version(version: u8, thread_stats_size: u16) -> anyhow::Result<()> {
+    if !(STATS_VERSION..=MAX_COMPATIBLE_STATS_VERSION).contains(&version) {
+        bail!(
+            "unsupported stats version {version} (expected {STATS_VERSION}, or up to {MAX_COMPATIBLE_STATS_VERSION} with a warning)"
+        );
+    }
+    if version > STATS_VERSION
+        && usize::from(thread_stats_size) < std::mem::size_of::<ThreadStats>()
+    {
+        bail!(
+            "stats version {version} has {thread_stats_size}-byte thread entries, smaller than the {} bytes felix reads",
+            std::mem::size_of::<ThreadStats>()
+        );
+    }
+    Ok(())
+}
+
+/// The warning to show for a stats version newer than `STATS_VERSION` that
+/// `check_stats_version` accepted.
+#[must_use]
+pub fn stats_version_warning(version: u8) -> Option<String> {
+    (version > STATS_VERSION).then(|| {
+        format!("stats v{version} > expected v{STATS_VERSION}, fields may be misinterpreted")
+    })
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum AppType {
@@ -76,3 +118,25 @@ const _: () = assert!(
     std::mem::align_of::<ThreadStats>() == 16,
     "ThreadStats must be 16-byte aligned"
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_compatible_stats_version_warns() {
+        let entry = u16::try_from(std::mem::size_of::<ThreadStats>()).unwrap();
+        assert!(check_stats_version(STATS_VERSION, entry).is_ok());
+        assert!(stats_version_warning(STATS_VERSION).is_none());
+
+        assert!(check_stats_version(MAX_COMPATIBLE_STATS_VERSION, entry + 16).is_ok());
+        assert_eq!(
+            stats_version_warning(3).as_deref(),
+            Some("stats v3 > expected v2, fields may be misinterpreted")
+        );
+
+        assert!(check_stats_version(STATS_VERSION - 1, entry).is_err());
+        assert!(check_stats_version(MAX_COMPATIBLE_STATS_VERSION + 1, entry).is_err());
+        assert!(check_stats_version(MAX_COMPATIBLE_STATS_VERSION, entry - 16).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+// SPDX-License-Identifier: MIT
+//! Unix domain socket server streaming every frame as newline-delimited JSON.
+
+use std::io::{ErrorKind, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+
+use crate::sampler::accumulator::ComputedFrame;
+
+/// How long the server waits for a new frame before checking for new
+/// clients again.
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+
+/// Latest frame waiting to be sent, numbered so the server sends each one
+/// once.
+#[derive(Default)]
+struct Slot {
+    sequence: u64,
+    frame: Option<ComputedFrame>,
+    stop: bool,
+}
+
+/// What the server should do after waiting on the feed.
+enum Next {
+    /// Send this frame, serialized as one line.
+    Frame {
+        sequence: u64,
+        line: String,
+    },
+    Timeout,
+    Stop,
+}
+
+/// Handle the sampling loop publishes frames through. Cheap to clone.
+#[derive(Clone, Default)]
+pub struct FrameFeed {
+    shared: Arc<(Mutex<Slot>, Condvar)>,
+}
+
+impl FrameFeed {
+    /// Replaces the latest frame and wakes the server. Never blocks on
+    /// clients; if the server has not sent the previous frame yet, that
+    /// frame is skipped.
+    pub fn publish(&self, frame: &ComputedFrame) {
+        let (slot, ready) = &*self.shared;
+        let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        slot.sequence += 1;
+        slot.frame = Some(frame.clone());
+        ready.notify_one();
+    }
+
+    fn stop(&self) {
+        let (slot, ready) = &*self.shared;
+        slot.lock().unwrap_or_else(PoisonError::into_inner).stop = true;
+        ready.notify_one();
+    }
+
+    /// Waits up to `timeout` for a frame newer than `sent`.
+    fn next(&self, sent: u64, timeout: Duration) -> Next {
+        let (slot, ready) = &*self.shared;
+        let slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+        let (slot, _) = ready
+            .wait_timeout_while(slot, timeout, |s| !s.stop && s.sequence == sent)
+            .unwrap_or_else(PoisonError::into_inner);
+        if slot.stop {
+            return Next::Stop;
+        }
+        let (sequence, frame) = match &slot.frame {
+            Some(frame) if slot.sequence != sent => (slot.sequence, frame.clone()),
+            _ => return Next::Timeout,
+        };
+        // Serialize after releasing the lock so publishing never waits on it.
+        drop(slot);
+        match serde_json::to_string(&frame) {
+            Ok(line) => Next::Frame {
+                sequence,
+                line: line + "\n",
+            },
+            Err(_) => Next::Timeout,
+        }
+    }
+}
+
+/// Listens on a Unix socket and sends every published frame to each
+/// connected client. The socket file is removed when the server is dropped.
+pub struct IpcServer {
+    path: PathBuf,
+    feed: FrameFeed,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl IpcServer {
+    /// Binds `path` and starts serving from a background thread. A stale
+    /// socket file left by an earlier run is replaced; one that still has a
+    /// listener is an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the socket cannot be bound or the thread cannot
+    /// be spawned.
+    pub fn spawn(path: &Path) -> Result<Self> {
+        if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+            if UnixStream::connect(path).is_ok() {
+                bail!("{} is already in use by another server", path.display());
+            }
+            std::fs::remove_file(path)
+                .with_context(|| format!("failed to remove stale socket {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("failed to bind IPC socket {}", path.display()))?;
+        listener
+            .set_nonblocking(true)
+            .context("failed to make IPC socket non-blocking")?;
+
+        let feed = FrameFeed::default();
+        let handle = {
+            let feed = feed.clone();
+            thread::Builder::new()
+                .name("felix-ipc".into())
+                .spawn(move || serve(&listener, &feed))
+                .context("failed to spawn IPC thread")?
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            feed,
+            handle: Some(handle),
+        })
+    }
+
+    /// A handle for publishing frames to this server's clients.
+    #[must_use]
+    pub fn feed(&self) -> FrameFeed {
+        self.feed.clone()
+    }
+
+    pub fn publish(&self, frame: &ComputedFrame) {
+        self.feed.publish(frame);
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        self.feed.stop();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn serve(listener: &UnixListener, feed: &FrameFeed) {
+    let mut clients: Vec<UnixStream> = Vec::new();
+    let mut sent = 0;
+    loop {
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    // Writes must not block the server, so a client that
+                    // stops reading is dropped once its buffer fills.
+                    if stream.set_nonblocking(true).is_ok() {
+                        clients.push(stream);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => break,
+            }
+        }
+
+        match feed.next(sent, ACCEPT_POLL) {
+            Next::Frame { sequence, line } => {
+                sent = sequence;
+                clients.retain_mut(|client| client.write_all(line.as_bytes()).is_ok());
+            }
+            Next::Timeout => {}
+            Next::Stop => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+
+    use super::*;
+
+    #[test]
+    fn streams_frames_and_removes_socket() {
+        let path = std::env::temp_dir().join(format!("felix_ipc_test_{}.sock", std::process::id()));
+        let server = IpcServer::spawn(&path).unwrap();
+        assert!(IpcServer::spawn(&path).is_err());
+
+        let client = UnixStream::connect(&path).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        // Give the server a poll interval to accept the client.
+        thread::sleep(ACCEPT_POLL * 3);
+        server.publish(&ComputedFrame {
+            fex_load_percent: 42.5,
+            ..ComputedFrame::default()
+        });
+
+        let mut line = String::new();
+        BufReader::new(&client).read_line(&mut line).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert!((value["fex_load_percent"].as_f64().unwrap() - 42.5).abs() < f64::EPSILON);
+
+        drop(server);
+        assert!(!path.exists());
+    }
+}
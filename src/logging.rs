@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: MIT
+//! Leveled diagnostic logging for `--log-level`: `tracing` events formatted
+//! one line per event with their key-value fields, written to stderr or a
+//! `--log-file`.
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use tracing::Subscriber;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+
+use crate::wallclock;
+
+/// Most verbose level logged. `Off`, the default, logs nothing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    #[default]
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Off => Self::OFF,
+            LogLevel::Error => Self::ERROR,
+            LogLevel::Warn => Self::WARN,
+            LogLevel::Info => Self::INFO,
+            LogLevel::Debug => Self::DEBUG,
+            LogLevel::Trace => Self::TRACE,
+        }
+    }
+}
+
+/// Stamps events with the same RFC 3339 times as exports.
+struct Rfc3339;
+
+impl FormatTime for Rfc3339 {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        w.write_str(&wallclock::format_rfc3339(SystemTime::now()))
+    }
+}
+
+/// Formats events as `<time> <LEVEL> <message> key=value...`, without
+/// colors or module targets.
+fn subscriber<W>(level: LevelFilter, out: W) -> impl Subscriber + Send + Sync
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_timer(Rfc3339)
+        .with_target(false)
+        .with_ansi(false)
+        .with_writer(out)
+        .finish()
+}
+
+/// Installs the logger. Events go to `file` (appended to) or else stderr;
+/// a full-screen TUI needs a file, since stderr lines would be drawn over
+/// the alternate screen.
+///
+/// # Errors
+///
+/// Returns an error if logging is on for a TUI without a file, or the file
+/// cannot be opened.
+pub fn init(level: LogLevel, file: Option<&Path>, tui: bool) -> Result<()> {
+    if level == LogLevel::Off {
+        return Ok(());
+    }
+    let level = LevelFilter::from(level);
+    let installed = match file {
+        Some(path) => {
+            let file = File::options()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("failed to open log file {}", path.display()))?;
+            tracing::subscriber::set_global_default(subscriber(level, Mutex::new(file)))
+        }
+        None if tui => bail!("--log-level needs --log-file here, as the TUI owns the terminal"),
+        None => tracing::subscriber::set_global_default(subscriber(level, io::stderr)),
+    };
+    installed.context("logger already installed")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Collects everything the subscriber writes.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn event_lists_fields_after_message() {
+        let buffer = Buffer::default();
+        let out = buffer.clone();
+        tracing::subscriber::with_default(
+            subscriber(LevelFilter::INFO, move || out.clone()),
+            || {
+                tracing::info!(pid = 42, frames = 7u64, "recording stopped");
+                tracing::debug!("filtered out");
+            },
+        );
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let (time, event) = text.split_once(' ').unwrap();
+        assert!(time.ends_with('Z'));
+        assert_eq!(event, " INFO recording stopped pid=42 frames=7\n");
+    }
+
+    #[test]
+    fn tui_without_file_is_refused() {
+        assert!(init(LogLevel::Info, None, true).is_err());
+        assert!(init(LogLevel::Off, None, true).is_ok());
+    }
+}
@@ -3,85 +3,344 @@
 #![deny(clippy::all)]
 #![deny(clippy::pedantic)]
 
+mod alert;
+mod check;
 mod datasource;
+mod diff;
+mod export;
 mod fex;
+mod ipc;
+mod logging;
+mod metrics;
+mod plain;
 mod recording;
 mod sampler;
+mod stats;
+mod summary;
+#[cfg(test)]
+mod testing;
 mod tui;
+mod wallclock;
 
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::io::{self, BufRead, IsTerminal, Stdout, Write};
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, Result, bail};
-use clap::{Parser, Subcommand};
-use crossterm::event::{self, Event, KeyEventKind};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
 use ratatui::Terminal;
 use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout};
+use tracing::{debug, info, warn};
 
+use crate::alert::{AlertMonitor, AlertRule};
+use crate::check::Thresholds;
 use crate::datasource::{DataSource, SessionMetadata};
+use crate::export::csv::{AllThreadsSink, ThreadMatrixSink};
+use crate::export::{ExportFormat, FrameSink};
 use crate::fex::platform::{cycle_counter_frequency, store_memory_barrier};
-use crate::fex::shm::ShmReader;
-use crate::fex::types::STATS_VERSION;
+use crate::fex::shm::{SegmentGone, ShmReader};
+use crate::fex::smaps::{MemMode, MemOptions, RegionPatterns};
+use crate::fex::types::{check_stats_version, stats_version_warning};
+use crate::ipc::{FrameFeed, IpcServer};
+use crate::logging::LogLevel;
 use crate::recording::format::Frame;
 use crate::recording::reader::{RecordingReader, ReplaySource};
-use crate::recording::writer::RecordingWriter;
-use crate::sampler::accumulator::{Accumulator, CumulativeCountStats};
+use crate::recording::writer::{Compression, DEFAULT_ZSTD_LEVEL, RecordingWriter, tagged_path};
+use crate::sampler::accumulator::{Accumulator, ComputedFrame, CumulativeCountStats};
 use crate::sampler::mem_stats::MemStatsWorker;
 use crate::sampler::thread_stats::ThreadSampler;
-use crate::tui::app::App;
-use crate::tui::input::{Action, handle_key};
+use crate::summary::{RecordReport, RecordTally, Summary};
+use crate::tui::app::{App, DEFAULT_HISTORY};
+use crate::tui::input::{Action, Keymap};
+use crate::tui::panels::mem_stats::format_bytes;
+use crate::tui::process_tabs::{self, ProcessTab};
+use crate::tui::theme::Theme;
+use crate::tui::ui_state::UiState;
 
 const EVENT_POLL_TIMEOUT: Duration = Duration::from_millis(10);
 const WATCH_POLL_INTERVAL: Duration = Duration::from_secs(1);
 const HEADLESS_STATUS_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the single-line progress bar of a `--duration` capture redraws.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+const PROGRESS_BAR_WIDTH: usize = 20;
+const MIN_SAMPLE_PERIOD: Duration = Duration::from_millis(50);
+const MAX_SAMPLE_PERIOD: Duration = Duration::from_secs(10);
+/// Default cap on live TUI redraws per second.
+const DEFAULT_FPS: u32 = 30;
+/// Longest the live loop blocks waiting for input between liveness checks.
+const LIVE_POLL_CAP: Duration = Duration::from_millis(100);
 
 #[derive(Parser)]
 #[command(name = "felix", about = "felix: FEX-Emu profiler and recorder")]
+#[allow(clippy::struct_excessive_bools)] // independent flags
 struct Cli {
+    /// Color theme: a preset name ("dark", "light") or a path to a TOML file
+    #[arg(long, global = true)]
+    theme: Option<String>,
+    /// Number of samples kept for the histogram and memory history panels
+    #[arg(long, global = true, default_value_t = DEFAULT_HISTORY, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    history: usize,
+    /// Show only the N busiest threads (default: one per hardware core)
+    #[arg(long, global = true, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    top: Option<usize>,
+    /// Smooth the displayed FEX and thread loads with a moving average that
+    /// gives the newest sample this weight (0 to 1; 0 disables). Recordings
+    /// keep the raw values
+    #[arg(long, global = true, value_name = "ALPHA", default_value_t = 0.0, value_parser = parse_smoothing)]
+    smooth: f64,
+    /// Hide threads below this load percentage from the JIT panel; `f`
+    /// toggles the filter
+    #[arg(long, global = true, value_name = "PERCENT", default_value_t = 0.0)]
+    min_thread_load: f32,
+    /// Warn in the memory panel when JIT code or total anonymous memory
+    /// grows faster than this many MiB per minute over recent samples
+    #[arg(long, global = true, value_name = "MIB_PER_MIN", value_parser = parse_growth_rate)]
+    mem_growth_warn: Option<f64>,
+    /// Show only the selected panel at full height; Up/Down switch panels.
+    /// Also used automatically when the terminal is too short for all of them
+    #[arg(long, global = true)]
+    compact: bool,
+    /// On terminals at least 160 columns wide, show the JIT stats panel in a
+    /// left column beside the other panels instead of above them
+    #[arg(long, global = true)]
+    wide: bool,
+    /// How to sample memory usage
+    #[arg(long, global = true, value_enum, default_value_t = MemMode::Full)]
+    mem_mode: MemMode,
+    /// Seconds a thread may be missing from FEX's stats before it is
+    /// forgotten; it then returns with no delta for its first sample
+    #[arg(long, global = true, value_name = "SECONDS", default_value = "10", value_parser = parse_seconds)]
+    stale_timeout: Duration,
+    /// TOML file mapping smaps region names to memory categories, replacing
+    /// the built-in FEX patterns
+    #[arg(long, global = true, value_name = "FILE")]
+    region_patterns: Option<PathBuf>,
+    /// On exit, list the `[anon:FEXMem*]` regions felix does not recognize
+    /// (counted as unaccounted) with their peak resident size
+    #[arg(long, global = true)]
+    report_unknown_regions: bool,
+    /// File the panel layout, thread sort order and theme are restored from
+    /// and saved to (default: `$XDG_CONFIG_HOME/felix/state.toml`)
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Restore the saved UI state but do not overwrite it on exit
+    #[arg(long, global = true)]
+    no_save_state: bool,
+    /// TOML file rebinding keys: a `[keys]` table mapping action names to
+    /// a key or list of keys
+    #[arg(long, global = true, value_name = "FILE")]
+    keymap: Option<PathBuf>,
+    /// Log diagnostic events at this level and above (default: off)
+    #[arg(long, global = true, value_enum, default_value_t = LogLevel::Off)]
+    log_level: LogLevel,
+    /// Append log events to this file instead of stderr; required for the
+    /// TUI commands
+    #[arg(long, global = true, value_name = "FILE")]
+    log_file: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
 
+/// Threshold alerts shared by `live` and `record`.
+#[derive(Args, Clone, Default)]
+struct AlertArgs {
+    /// Alert when a metric crosses a threshold, e.g. load>90 or
+    /// sigbus>5000. May be given more than once
+    #[arg(long = "alert", value_name = "RULE")]
+    alerts: Vec<AlertRule>,
+    /// Shell command to run when an alert fires. The rule, value, and pid
+    /// are passed in environment variables
+    #[arg(long, requires = "alerts")]
+    alert_cmd: Option<String>,
+}
+
+impl AlertArgs {
+    fn monitor(&self) -> AlertMonitor {
+        AlertMonitor::new(self.alerts.clone(), self.alert_cmd.clone())
+    }
+}
+
+/// Size-based rotation for `record`.
+#[derive(Args, Clone, Copy, Default)]
+struct RotationArgs {
+    /// Rotate to a new numbered file (out-001.felixr, ...) once the current
+    /// one reaches this size (e.g. 512MiB)
+    #[arg(long, value_name = "SIZE", value_parser = check::parse_size)]
+    max_size: Option<u64>,
+    /// Keep at most this many recording files, deleting the oldest after
+    /// each rotation
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_files: Option<u64>,
+}
+
+/// The FEX process `live` and `record` attach to: a PID, or a substring of
+/// its command line. An explicit PID takes precedence over `--name`.
+#[derive(Args, Clone)]
+struct ProcessTarget {
+    #[arg(required_unless_present = "name")]
+    pid: Option<i32>,
+    /// Attach to the FEX process whose command line contains this text
+    #[arg(long, value_name = "SUBSTR")]
+    name: Option<String>,
+    /// With --name, take the most recently started match instead of failing
+    /// when several processes match
+    #[arg(long, requires = "name")]
+    newest: bool,
+}
+
+impl ProcessTarget {
+    fn resolve(&self) -> Result<i32> {
+        match (self.pid, &self.name) {
+            (Some(pid), _) => Ok(pid),
+            (None, Some(name)) => find_fex_process_by_name(name, self.newest),
+            (None, None) => bail!("a PID or --name is required"),
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Monitor a running FEX process
     Live {
-        pid: i32,
-        #[arg(short, long, default_value = "1000")]
-        sample_period: u64,
-        #[arg(short, long)]
-        record: Option<PathBuf>,
+        #[command(flatten)]
+        target: ProcessTarget,
+        #[command(flatten)]
+        options: LiveOptions,
     },
     /// Replay a recorded session
-    Replay { path: PathBuf },
+    Replay {
+        path: PathBuf,
+        /// Refuse recordings that were cut short instead of replaying their
+        /// complete frames
+        #[arg(long)]
+        strict: bool,
+    },
     /// Record without TUI (headless)
     Record {
-        pid: i32,
+        #[command(flatten)]
+        target: ProcessTarget,
         #[arg(short, long)]
         output: PathBuf,
+        /// Output format. Defaults to the one matching the output file's
+        /// extension (.csv, .jsonl), or a .felixr recording otherwise
+        #[arg(short, long, value_enum)]
+        format: Option<RecordFormat>,
         #[arg(short, long, default_value = "1000")]
         sample_period: u64,
         #[arg(long, default_value = "0")]
         duration: u64,
+        /// Stop after writing exactly N frames, however long that takes
+        #[arg(long, value_name = "N", conflicts_with = "duration", value_parser = clap::value_parser!(u64).range(1..))]
+        frames: Option<u64>,
+        /// Append to the output file if it already exists
+        #[arg(long)]
+        append: bool,
+        /// Serve the latest frame as Prometheus metrics on this address
+        /// (e.g. 127.0.0.1:9100)
+        #[arg(long)]
+        metrics_addr: Option<SocketAddr>,
+        /// Stream every frame as a line of JSON to clients of a Unix socket
+        /// created at this path
+        #[arg(long, value_name = "PATH")]
+        ipc_socket: Option<PathBuf>,
+        /// zstd compression level (1-19). Higher levels shrink recordings
+        /// at the cost of more CPU per frame; 1 suits high sample rates.
+        #[arg(long, default_value_t = DEFAULT_ZSTD_LEVEL, value_parser = clap::value_parser!(i32).range(1..=19))]
+        zstd_level: i32,
+        /// Write the .felixr recording without zstd, for the least CPU per
+        /// frame. Replay and export read it either way
+        #[arg(long, conflicts_with = "zstd_level")]
+        no_compress: bool,
+        /// Also write the end-of-record summary as JSON to
+        /// <output>.summary.json
+        #[arg(long)]
+        summary_json: bool,
+        #[command(flatten)]
+        alert: AlertArgs,
+        #[command(flatten)]
+        rotation: RotationArgs,
     },
-    /// Watch for FEX processes and auto-attach
-    Watch {
+    /// Record every FEX process that appears, unattended, until stopped
+    Daemon {
+        /// Directory for the recordings, named fex-<pid>-<timestamp>.felixr
+        out_dir: PathBuf,
         #[arg(short, long, default_value = "1000")]
         sample_period: u64,
-        #[arg(short, long)]
-        record: Option<PathBuf>,
     },
-    /// Export a recording to CSV
+    /// Watch for FEX processes and auto-attach
+    Watch {
+        #[command(flatten)]
+        args: WatchArgs,
+    },
+    /// Export a recording to CSV, JSON, or a Chrome/Perfetto trace
     Export {
         input: PathBuf,
         #[arg(short, long)]
         output: PathBuf,
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        #[command(flatten)]
+        window: ExportWindow,
+        #[command(flatten)]
+        layout: ExportLayout,
+        /// Measurement name for `--format influx` points
+        #[arg(long, default_value = export::influx::DEFAULT_MEASUREMENT)]
+        measurement: String,
+    },
+    /// Merge several recordings into one continuous timeline
+    Merge {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Merge even if the inputs record different PIDs or FEX versions,
+        /// or were cut short
+        #[arg(long)]
+        force: bool,
+    },
+    /// Reduce a recording's frame rate by combining consecutive frames
+    Downsample {
+        input: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Number of consecutive frames combined into each output frame
+        #[arg(short, long)]
+        factor: usize,
+    },
+    /// Print a recording's metadata and summary statistics
+    Info { path: PathBuf },
+    /// Print p50/p95/p99 load and event rates plus memory high-water marks
+    Stats {
+        path: PathBuf,
+        /// Print the report as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compare two recordings frame by frame and report metric deltas
+    Diff {
+        baseline: PathBuf,
+        candidate: PathBuf,
+        /// Print the report as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Assert thresholds over a recording, exiting non-zero if any is exceeded
+    Check {
+        input: PathBuf,
+        #[command(flatten)]
+        thresholds: Thresholds,
+        /// Also write the results as an XML test report (junit format) for CI
+        #[arg(long, value_name = "PATH")]
+        junit: Option<PathBuf>,
     },
     /// Pick a running FEX process interactively
     Pick {
@@ -89,34 +348,105 @@ enum Commands {
         sample_period: u64,
         #[arg(short, long)]
         record: Option<PathBuf>,
+        #[command(flatten)]
+        select: PickSelection,
     },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    let tui = cli.command.uses_tui();
+    logging::init(cli.log_level, cli.log_file.as_deref(), tui)?;
+    let display = DisplayOptions::from_cli(&cli)?;
 
     match cli.command {
-        Commands::Live {
-            pid,
-            sample_period,
-            record,
-        } => cmd_live(pid, sample_period, record.as_deref()),
-        Commands::Replay { path } => cmd_replay(&path),
+        Commands::Live { target, options } => cmd_live(target.resolve()?, &options, display),
+        Commands::Replay { path, strict } => cmd_replay(&path, strict, display),
         Commands::Record {
-            pid,
+            target,
             output,
+            format,
             sample_period,
             duration,
-        } => cmd_record(pid, &output, sample_period, duration),
-        Commands::Watch {
+            frames,
+            append,
+            metrics_addr,
+            ipc_socket,
+            zstd_level,
+            no_compress,
+            summary_json,
+            alert,
+            rotation,
+        } => cmd_record(
+            target.resolve()?,
+            &output,
+            &RecordOptions {
+                format,
+                sample_period_ms: sample_period,
+                duration_secs: duration,
+                max_frames: frames,
+                append,
+                compression: compression(zstd_level, no_compress),
+                metrics_addr,
+                ipc_socket,
+                summary_json,
+                sampler: display.sampler,
+                alert,
+                rotation,
+            },
+        ),
+        Commands::Daemon {
+            out_dir,
             sample_period,
-            record,
-        } => cmd_watch(sample_period, record.as_deref()),
-        Commands::Export { input, output } => cmd_export(&input, &output),
+        } => cmd_daemon(&out_dir, sample_period, &display),
+        Commands::Watch { args } if args.all => cmd_watch_all(&args, &display),
+        Commands::Watch { args } => cmd_watch(&args, display),
+        Commands::Export {
+            input,
+            output,
+            format,
+            window,
+            layout,
+            measurement,
+        } => cmd_export(&input, &output, format, window, layout, &measurement),
+        Commands::Merge {
+            inputs,
+            output,
+            force,
+        } => cmd_merge(&inputs, &output, force),
+        Commands::Downsample {
+            input,
+            output,
+            factor,
+        } => cmd_downsample(&input, &output, factor),
+        Commands::Info { path } => cmd_info(&path),
+        Commands::Stats { path, json } => cmd_stats(&path, json),
+        Commands::Diff {
+            baseline,
+            candidate,
+            json,
+        } => cmd_diff(&baseline, &candidate, json),
+        Commands::Check {
+            input,
+            thresholds,
+            junit,
+        } => cmd_check(&input, &thresholds, junit.as_deref()),
         Commands::Pick {
             sample_period,
             record,
-        } => cmd_pick(sample_period, record.as_deref()),
+            select,
+        } => cmd_pick(sample_period, record.as_deref(), select, display),
+    }
+}
+
+impl Commands {
+    /// Whether the command draws the full-screen TUI.
+    fn uses_tui(&self) -> bool {
+        match self {
+            Self::Live { options, .. } => !options.plain && !options.json_stream,
+            Self::Replay { .. } | Self::Watch { .. } | Self::Pick { .. } => true,
+            _ => false,
+        }
     }
 }
 
@@ -133,6 +463,15 @@ fn install_signal_handler() -> Result<Arc<AtomicBool>> {
     Ok(shutdown)
 }
 
+/// Registers SIGHUP to set the returned flag, asking for the current
+/// recording file to be rotated.
+fn install_rotate_handler() -> Result<Arc<AtomicBool>> {
+    let rotate = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&rotate))
+        .context("failed to register SIGHUP handler")?;
+    Ok(rotate)
+}
+
 // ---------------------------------------------------------------------------
 // Process liveness check
 // ---------------------------------------------------------------------------
@@ -168,18 +507,12 @@ fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result
 // ---------------------------------------------------------------------------
 
 fn build_metadata(shm: &ShmReader, pid: i32) -> Result<SessionMetadata> {
-    let header = shm.read_header();
-
-    if header.version != STATS_VERSION {
-        bail!(
-            "unsupported stats version {} (expected {STATS_VERSION})",
-            header.version
-        );
-    }
+    let header = shm.header();
+    check_stats_version(header.version, header.thread_stats_size)?;
 
     Ok(SessionMetadata {
         pid,
-        fex_version: header.fex_version,
+        fex_version: shm.fex_version().to_string(),
         app_type: header.app_type,
         stats_version: header.version,
         cycle_counter_frequency: cycle_counter_frequency(),
@@ -190,24 +523,252 @@ fn build_metadata(shm: &ShmReader, pid: i32) -> Result<SessionMetadata> {
     })
 }
 
+/// Prints the stats version warning for sessions without a TUI header to
+/// show it in.
+fn warn_stats_version(metadata: &SessionMetadata) {
+    if let Some(warning) = stats_version_warning(metadata.stats_version) {
+        eprintln!("warning: {warning}");
+    }
+}
+
 fn hardware_concurrency() -> usize {
     std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
 }
 
-// ---------------------------------------------------------------------------
-// Live subcommand
-// ---------------------------------------------------------------------------
+/// How each sample is taken, shared by every subcommand that attaches.
+#[derive(Clone)]
+struct SamplerOptions {
+    top: Option<usize>,
+    mem: MemOptions,
+    stale_timeout: Duration,
+}
 
-fn cmd_live(pid: i32, sample_period_ms: u64, record_path: Option<&Path>) -> Result<()> {
-    let shutdown = install_signal_handler()?;
-    let mut shm = ShmReader::open(pid)?;
-    let metadata = build_metadata(&shm, pid)?;
-    let sample_period = Duration::from_millis(sample_period_ms);
-    #[allow(clippy::cast_possible_truncation)]
-    let period_nanos = sample_period.as_nanos() as u64;
+/// TUI settings shared by every interactive subcommand.
+struct DisplayOptions {
+    theme: Theme,
+    history: usize,
+    smoothing: f64,
+    min_thread_load: f32,
+    mem_growth_warn: Option<f64>,
+    compact: bool,
+    wide: bool,
+    keymap: Keymap,
+    saved: SavedState,
+    sampler: SamplerOptions,
+}
+
+impl DisplayOptions {
+    fn from_cli(cli: &Cli) -> Result<Self> {
+        let (saved, theme) =
+            SavedState::load(cli.config.clone(), cli.no_save_state, cli.theme.clone())?;
+        Ok(Self {
+            theme,
+            history: cli.history,
+            smoothing: cli.smooth,
+            min_thread_load: cli.min_thread_load,
+            mem_growth_warn: cli.mem_growth_warn,
+            compact: cli.compact,
+            wide: cli.wide,
+            keymap: Keymap::load(cli.keymap.as_deref())?,
+            saved,
+            sampler: SamplerOptions {
+                top: cli.top,
+                mem: MemOptions {
+                    mode: cli.mem_mode,
+                    track_unknown: cli.report_unknown_regions,
+                    patterns: Arc::new(RegionPatterns::load(cli.region_patterns.as_deref())?),
+                },
+                stale_timeout: cli.stale_timeout,
+            },
+        })
+    }
+}
+
+/// UI state restored at startup and written back when the TUI exits.
+struct SavedState {
+    state: UiState,
+    /// Where the state is written on exit; `None` with `--no-save-state` or
+    /// when there is no config directory.
+    save_path: Option<PathBuf>,
+    /// The theme in effect, saved so the next run picks it up without
+    /// `--theme`.
+    theme: Option<String>,
+}
+
+impl SavedState {
+    /// Reads the state file and resolves the theme: `--theme` wins over the
+    /// saved one. A state file or saved theme that cannot be loaded is
+    /// reported and ignored, since it is not something the user passed.
+    fn load(
+        config: Option<PathBuf>,
+        no_save: bool,
+        theme_arg: Option<String>,
+    ) -> Result<(Self, Theme)> {
+        let path = config.or_else(UiState::default_path);
+        let state = path.as_deref().map_or_else(UiState::default, |path| {
+            UiState::load(path).unwrap_or_else(|e| {
+                eprintln!("warning: {e:#}; using the default layout");
+                UiState::default()
+            })
+        });
+        let (theme_name, theme) = match theme_arg {
+            Some(name) => {
+                let theme = Theme::load(Some(&name))?;
+                (Some(name), theme)
+            }
+            None => match state.theme.as_deref().map(|name| Theme::load(Some(name))) {
+                Some(Ok(theme)) => (state.theme.clone(), theme),
+                Some(Err(e)) => {
+                    eprintln!("warning: saved theme: {e:#}; using the default theme");
+                    (None, Theme::default())
+                }
+                None => (None, Theme::default()),
+            },
+        };
+        let saved = Self {
+            state,
+            save_path: path.filter(|_| !no_save),
+            theme: theme_name,
+        };
+        Ok((saved, theme))
+    }
+
+    /// Saves `app`'s layout for the next run. Failing to save only warns,
+    /// since the session itself went fine.
+    fn save(&self, app: &App) {
+        let Some(path) = &self.save_path else {
+            return;
+        };
+        if let Err(e) = UiState::capture(app, self.theme.clone()).save(path) {
+            eprintln!("warning: failed to save UI state: {e:#}");
+        }
+    }
+}
+
+fn parse_smoothing(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|alpha| (0.0..=1.0).contains(alpha))
+        .ok_or_else(|| format!("expected a weight between 0 and 1, got \"{s}\""))
+}
+
+fn parse_growth_rate(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|rate| rate.is_finite() && *rate > 0.0)
+        .ok_or_else(|| format!("expected a positive rate in MiB per minute, got \"{s}\""))
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    s.parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .filter(|d| !d.is_zero())
+        .ok_or_else(|| format!("expected a positive number of seconds, got \"{s}\""))
+}
+
+/// Settings for the interactive live view.
+#[derive(Args)]
+#[allow(clippy::struct_excessive_bools)] // independent command-line flags
+struct LiveOptions {
+    #[arg(
+        short,
+        long = "sample-period",
+        value_name = "SAMPLE_PERIOD",
+        default_value = "1000"
+    )]
+    sample_period_ms: u64,
+    #[arg(short, long = "record", value_name = "RECORD")]
+    record_path: Option<PathBuf>,
+    /// zstd compression level (1-19). Higher levels shrink recordings
+    /// at the cost of more CPU per frame; 1 suits high sample rates.
+    #[arg(long, default_value_t = DEFAULT_ZSTD_LEVEL, value_parser = clap::value_parser!(i32).range(1..=19))]
+    zstd_level: i32,
+    /// Write the recording without zstd, for the least CPU per frame
+    #[arg(long, conflicts_with = "zstd_level")]
+    no_compress: bool,
+    /// When the process exits, keep the TUI up and attach to the next
+    /// FEX process that appears. Recordings continue in <name>-<pid>.<ext>
+    #[arg(long)]
+    reattach: bool,
+    /// Keep the histogram and memory history across re-attaches
+    #[arg(long, requires = "reattach")]
+    keep_history: bool,
+    /// Text the command line of a process must contain to be re-attached
+    /// to, set by `watch --match`
+    #[arg(skip)]
+    reattach_match: Option<String>,
+    /// Redraw the TUI at most this many times per second. Redraws only
+    /// happen when a sample arrives or a key is pressed
+    #[arg(long, default_value_t = DEFAULT_FPS, value_parser = clap::value_parser!(u32).range(1..=240))]
+    fps: u32,
+    /// Print one line of text per sample instead of starting the TUI, for
+    /// logs and SSH sessions. Color is used only when stdout is a terminal
+    #[arg(long, conflicts_with = "reattach")]
+    plain: bool,
+    /// Write each frame to stdout as one line of JSON instead of starting
+    /// the TUI, for piping into jq and similar tools
+    #[arg(long, conflicts_with_all = ["reattach", "plain"])]
+    json_stream: bool,
+    /// Stream every frame as a line of JSON to clients of a Unix socket
+    /// created at this path
+    #[arg(long, value_name = "PATH")]
+    ipc_socket: Option<PathBuf>,
+    #[command(flatten)]
+    alert: AlertArgs,
+}
+
+impl LiveOptions {
+    /// Options for attaching from `watch` or `pick`, which only choose the
+    /// sample period and recording path.
+    fn new(sample_period_ms: u64, record_path: Option<&Path>) -> Self {
+        Self {
+            sample_period_ms,
+            record_path: record_path.map(Path::to_path_buf),
+            zstd_level: DEFAULT_ZSTD_LEVEL,
+            no_compress: false,
+            reattach: false,
+            keep_history: false,
+            reattach_match: None,
+            fps: DEFAULT_FPS,
+            plain: false,
+            json_stream: false,
+            ipc_socket: None,
+            alert: AlertArgs::default(),
+        }
+    }
+
+    fn compression(&self) -> Compression {
+        compression(self.zstd_level, self.no_compress)
+    }
+}
+
+/// The recording compression picked by `--zstd-level` and `--no-compress`.
+fn compression(zstd_level: i32, no_compress: bool) -> Compression {
+    if no_compress {
+        Compression::None
+    } else {
+        Compression::Zstd(zstd_level)
+    }
+}
+
+/// Settings for headless recording.
+struct RecordOptions {
+    format: Option<RecordFormat>,
+    sample_period_ms: u64,
+    duration_secs: u64,
+    max_frames: Option<u64>,
+    append: bool,
+    compression: Compression,
+    metrics_addr: Option<SocketAddr>,
+    ipc_socket: Option<PathBuf>,
+    summary_json: bool,
+    sampler: SamplerOptions,
+    alert: AlertArgs,
+    rotation: RotationArgs,
+}
 
-    let mut mem_worker = MemStatsWorker::spawn(pid, sample_period)?;
-    let mut thread_sampler = ThreadSampler::new();
+fn new_accumulator(metadata: &SessionMetadata, top: Option<usize>) -> Accumulator {
     let accumulator = Accumulator::new(
         #[allow(clippy::cast_precision_loss)]
         {
@@ -215,122 +776,491 @@ fn cmd_live(pid: i32, sample_period_ms: u64, record_path: Option<&Path>) -> Resu
         },
         metadata.hardware_concurrency,
     );
+    match top {
+        Some(n) => accumulator.with_max_threads(n),
+        None => accumulator,
+    }
+}
 
-    let mut writer = match record_path {
-        Some(p) => Some(RecordingWriter::create(p, &metadata)?),
-        None => None,
-    };
+// ---------------------------------------------------------------------------
+// Live subcommand
+// ---------------------------------------------------------------------------
+
+fn cmd_live(pid: i32, options: &LiveOptions, display: DisplayOptions) -> Result<()> {
+    if options.plain || options.json_stream {
+        return cmd_live_plain(pid, options, &display);
+    }
+    let shutdown = install_signal_handler()?;
+    let mut interval = Duration::from_millis(options.sample_period_ms);
+    let (mut session, metadata) = LiveSession::attach(
+        pid,
+        interval,
+        options.record_path.as_deref(),
+        options.compression(),
+        &display.sampler,
+    )?;
+    let ipc = spawn_ipc(options.ipc_socket.as_deref())?;
+    session.feed = ipc.as_ref().map(IpcServer::feed);
 
     let mut terminal = setup_terminal()?;
-    let mut app = App::new(metadata, false);
-    let mut total_jit_invocations: u64 = 0;
-    let mut last_sample = Instant::now();
+    let mut app = App::new(metadata, false, display.history);
+    app.set_recording(session.record_path.clone());
+    app.theme = display.theme;
+    app.smoothing = display.smoothing;
+    app.min_thread_load = display.min_thread_load;
+    app.mem_growth_warn = display.mem_growth_warn;
+    app.compact = display.compact;
+    app.wide = display.wide;
+    app.keymap = display.keymap.clone();
+    display.saved.state.apply(&mut app);
+    let mut alerts = options.alert.monitor();
+
+    let result = loop {
+        let exit = run_live_loop(
+            &shutdown,
+            &mut session,
+            &mut app,
+            &mut terminal,
+            &mut interval,
+            &mut alerts,
+            Duration::from_secs(1) / options.fps,
+        );
+        if !matches!(exit, Ok(LiveExit::ProcessExited)) || !options.reattach {
+            break exit.map(|_| ());
+        }
 
-    let result = run_live_loop(
-        &shutdown,
-        pid,
-        &mut shm,
-        &mut thread_sampler,
-        &accumulator,
-        &mut mem_worker,
-        &mut app,
-        &mut writer,
-        &mut terminal,
-        &mut total_jit_invocations,
-        &mut last_sample,
-        sample_period,
-        period_nanos,
-    );
+        let old_pid = session.pid;
+        session.detach();
+        app.set_waiting_for_process(true);
+        let reattached = wait_for_new_process(
+            &shutdown,
+            &mut app,
+            &mut terminal,
+            old_pid,
+            options.reattach_match.as_deref(),
+            |new_pid| {
+                // Each attached process gets its own recording, since the
+                // file header names a single pid.
+                let record_path = options
+                    .record_path
+                    .as_deref()
+                    .map(|p| tagged_path(p, new_pid));
+                LiveSession::attach(
+                    new_pid,
+                    interval,
+                    record_path.as_deref(),
+                    options.compression(),
+                    &display.sampler,
+                )
+            },
+        );
+        match reattached {
+            Ok(Some((new_session, metadata))) => {
+                session = new_session;
+                session.feed = ipc.as_ref().map(IpcServer::feed);
+                app.reattach(metadata, options.keep_history);
+                app.set_recording(session.record_path.clone());
+            }
+            Ok(None) => {
+                restore_terminal(&mut terminal)?;
+                display.saved.save(&app);
+                return Ok(());
+            }
+            Err(e) => {
+                restore_terminal(&mut terminal)?;
+                return Err(e);
+            }
+        }
+    };
 
-    mem_worker.shutdown();
-    if let Some(w) = writer {
-        let _ = w.finish();
-    }
+    let (last_pid, unknown_regions) = (session.pid, session.unknown_regions());
+    session.detach();
     restore_terminal(&mut terminal)?;
+    display.saved.save(&app);
+    report_unknown_regions(last_pid, unknown_regions.as_ref());
 
     result
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Starts the `--ipc-socket` server, if one was asked for.
+fn spawn_ipc(path: Option<&Path>) -> Result<Option<IpcServer>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let server = IpcServer::spawn(path)?;
+    eprintln!("Streaming frames on {}", path.display());
+    Ok(Some(server))
+}
+
+/// `live --plain` and `live --json-stream`: samples like the TUI but prints
+/// one line per frame to stdout, without raw mode or the alternate screen.
+fn cmd_live_plain(pid: i32, options: &LiveOptions, display: &DisplayOptions) -> Result<()> {
+    let shutdown = install_signal_handler()?;
+    let interval = Duration::from_millis(options.sample_period_ms);
+    let (mut session, metadata) = LiveSession::attach(
+        pid,
+        interval,
+        options.record_path.as_deref(),
+        options.compression(),
+        &display.sampler,
+    )?;
+    warn_stats_version(&metadata);
+    let ipc = spawn_ipc(options.ipc_socket.as_deref())?;
+    session.feed = ipc.as_ref().map(IpcServer::feed);
+    let mut alerts = options.alert.monitor();
+    let color = io::stdout().is_terminal();
+    let mut stdout = io::stdout().lock();
+
+    let result = loop {
+        if shutdown.load(Ordering::Relaxed) {
+            break Ok(());
+        }
+        if !process_alive(pid) {
+            eprintln!("Process {pid} exited.");
+            break Ok(());
+        }
+
+        std::thread::sleep(interval);
+        let frame = match session.sample(duration_to_nanos(interval)) {
+            Ok(frame) => frame,
+            Err(e) if e.is::<SegmentGone>() => {
+                eprintln!("Process {pid} exited.");
+                break Ok(());
+            }
+            Err(e) => break Err(e),
+        };
+        for alert in alerts.evaluate(&frame, pid) {
+            eprintln!("  {alert}");
+        }
+        let line = if options.json_stream {
+            match serde_json::to_string(&frame) {
+                Ok(json) => json,
+                Err(e) => break Err(anyhow::Error::new(e).context("failed to serialize frame")),
+            }
+        } else {
+            plain::format_line(&frame, color)
+        };
+        match writeln!(stdout, "{line}") {
+            Ok(()) => {}
+            // The reader went away (e.g. piped into `head`); stop quietly.
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break Ok(()),
+            Err(e) => break Err(anyhow::Error::new(e).context("failed to write sample")),
+        }
+    };
+
+    let _ = stdout.flush();
+    report_unknown_regions(pid, session.unknown_regions().as_ref());
+    session.detach();
+    result
+}
+
+/// Prints what `--report-unknown-regions` collected, if it was given.
+fn report_unknown_regions(pid: i32, regions: Option<&BTreeMap<String, u64>>) {
+    let Some(regions) = regions else {
+        return;
+    };
+    if regions.is_empty() {
+        eprintln!("PID {pid}: no unrecognized FEX memory regions");
+        return;
+    }
+    eprintln!("PID {pid}: unrecognized FEX memory regions (peak Rss):");
+    for (name, bytes) in regions {
+        eprintln!("  {name:<40} {}", format_bytes(*bytes));
+    }
+}
+
+/// Why `run_live_loop` returned.
+enum LiveExit {
+    Quit,
+    ProcessExited,
+}
+
 fn run_live_loop(
     shutdown: &Arc<AtomicBool>,
-    pid: i32,
-    shm: &mut ShmReader,
-    thread_sampler: &mut ThreadSampler,
-    accumulator: &Accumulator,
-    mem_worker: &mut MemStatsWorker,
+    session: &mut LiveSession,
     app: &mut App,
-    writer: &mut Option<RecordingWriter>,
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
-    total_jit_invocations: &mut u64,
-    last_sample: &mut Instant,
-    interval: Duration,
-    period_nanos: u64,
-) -> Result<()> {
+    interval: &mut Duration,
+    alerts: &mut AlertMonitor,
+    frame_interval: Duration,
+) -> Result<LiveExit> {
+    let mut last_sample = Instant::now();
+    let mut last_draw: Option<Instant> = None;
+
     loop {
         if shutdown.load(Ordering::Relaxed) || app.should_quit {
-            break;
+            return Ok(LiveExit::Quit);
         }
 
-        if !process_alive(pid) {
-            break;
+        if !process_alive(session.pid) {
+            return Ok(LiveExit::ProcessExited);
         }
-
-        let elapsed = last_sample.elapsed();
-        let poll_timeout = if elapsed >= interval {
-            Duration::ZERO
-        } else {
-            EVENT_POLL_TIMEOUT.min(interval.checked_sub(elapsed).unwrap())
+        app.tick_clock();
+
+        // Sleep in poll until the next sample is due, a pending redraw is
+        // allowed, or a key arrives. Polling is capped so a process exit or
+        // shutdown request is still noticed promptly.
+        let until_sample = interval.saturating_sub(last_sample.elapsed());
+        let until_draw = match last_draw {
+            Some(t) if app.is_dirty() => frame_interval.saturating_sub(t.elapsed()),
+            None if app.is_dirty() => Duration::ZERO,
+            _ => until_sample,
         };
+        let poll_timeout = until_sample.min(until_draw).min(LIVE_POLL_CAP);
+
+        if event::poll(poll_timeout).context("failed to poll events")? {
+            match event::read().context("failed to read event")? {
+                Event::Key(key) if key.kind == KeyEventKind::Press && app.is_prompting() => {
+                    app.handle_prompt_key(key.code);
+                }
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    let action = app.keymap.action(key.code, false);
+                    handle_sample_period_action(&action, interval, [&session.mem_worker]);
+                    if matches!(action, Action::AddMarker) {
+                        session.add_marker();
+                    }
+                    app.handle_action(&action);
+                }
+                Event::Resize(..) => {
+                    clear_after_resize(terminal)?;
+                    app.mark_dirty();
+                    last_draw = None;
+                }
+                _ => {}
+            }
+        }
 
-        if event::poll(poll_timeout).context("failed to poll events")?
-            && let Event::Key(key) = event::read().context("failed to read event")?
-            && key.kind == KeyEventKind::Press
-        {
-            let action = handle_key(key, false);
-            handle_sample_period_action(&action, app);
-            app.handle_action(&action);
+        if last_sample.elapsed() >= *interval {
+            let skipped = skipped_periods(last_sample.elapsed(), *interval);
+            if skipped > 0 {
+                debug!(pid = session.pid, skipped, "sampling fell behind");
+            }
+            app.add_dropped_samples(skipped);
+            let frame = match session.sample(duration_to_nanos(*interval)) {
+                Ok(frame) => frame,
+                Err(e) if e.is::<SegmentGone>() => return Ok(LiveExit::ProcessExited),
+                Err(e) => return Err(e),
+            };
+            if !alerts.evaluate(&frame, session.pid).is_empty() {
+                ring_bell(terminal)?;
+            }
+            app.update_frame(frame);
+            app.set_mem_error(session.mem_error());
+            if let Some(bytes) = session.recorded_bytes() {
+                app.set_recorded_bytes(bytes);
+            }
+            last_sample = Instant::now();
         }
 
-        if last_sample.elapsed() >= interval {
-            take_live_sample(
-                shm,
-                thread_sampler,
-                accumulator,
-                mem_worker,
-                app,
-                writer,
-                total_jit_invocations,
-                period_nanos,
-            )?;
-            *last_sample = Instant::now();
+        if app.is_dirty() && last_draw.is_none_or(|t| t.elapsed() >= frame_interval) {
+            app.take_dirty();
+            terminal
+                .draw(|f| app.render(f))
+                .context("failed to draw frame")?;
+            last_draw = Some(Instant::now());
+        }
+    }
+}
+
+/// Whole sample periods that passed without a sample, given the time since
+/// the last one.
+fn skipped_periods(since_last: Duration, interval: Duration) -> u64 {
+    let periods = since_last.as_nanos() / interval.as_nanos().max(1);
+    u64::try_from(periods.saturating_sub(1)).unwrap_or(u64::MAX)
+}
+
+/// Blanks the screen and ratatui's buffers after a resize, so the next draw
+/// repaints every cell at the new size rather than diffing against a frame
+/// drawn at the old one.
+fn clear_after_resize(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+    terminal
+        .autoresize()
+        .and_then(|()| terminal.clear())
+        .context("failed to clear the terminal after a resize")
+}
+
+fn ring_bell(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+    let backend = terminal.backend_mut();
+    backend
+        .write_all(b"\x07")
+        .and_then(|()| backend.flush())
+        .context("failed to ring terminal bell")
+}
+
+/// Keeps the TUI responsive while polling for a FEX process other than
+/// `old_pid` whose command line contains `pattern`, if given, and attaching
+/// to it with `attach`. A process whose shared memory is not ready yet is
+/// retried on the next scan. Returns `None` if the user quits first.
+fn wait_for_new_process<T>(
+    shutdown: &Arc<AtomicBool>,
+    app: &mut App,
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    old_pid: i32,
+    pattern: Option<&str>,
+    mut attach: impl FnMut(i32) -> Result<T>,
+) -> Result<Option<T>> {
+    let mut last_scan: Option<Instant> = None;
+
+    loop {
+        if shutdown.load(Ordering::Relaxed) || app.should_quit {
+            return Ok(None);
+        }
+
+        if last_scan.is_none_or(|t| t.elapsed() >= WATCH_POLL_INTERVAL) {
+            if let Some(pid) = find_fex_process(pattern).filter(|&pid| pid != old_pid) {
+                match attach(pid) {
+                    Ok(attached) => return Ok(Some(attached)),
+                    Err(e) => {
+                        debug!(pid, error = %format_args!("{e:#}"), "not ready to attach yet");
+                    }
+                }
+            }
+            last_scan = Some(Instant::now());
+        }
+
+        if event::poll(EVENT_POLL_TIMEOUT).context("failed to poll events")? {
+            match event::read().context("failed to read event")? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    if app.is_prompting() {
+                        app.handle_prompt_key(key.code);
+                    } else {
+                        let action = app.keymap.action(key.code, false);
+                        app.handle_action(&action);
+                    }
+                }
+                Event::Resize(..) => clear_after_resize(terminal)?,
+                _ => {}
+            }
         }
 
         terminal
             .draw(|f| app.render(f))
             .context("failed to draw frame")?;
     }
+}
 
-    Ok(())
+/// Sampling state for one attached FEX process.
+struct LiveSession {
+    pid: i32,
+    shm: ShmReader,
+    thread_sampler: ThreadSampler,
+    accumulator: Accumulator,
+    mem_worker: MemStatsWorker,
+    writer: Option<RecordingWriter>,
+    /// The file `writer` writes to.
+    record_path: Option<PathBuf>,
+    /// Where frames are streamed for `--ipc-socket` clients.
+    feed: Option<FrameFeed>,
+    total_jit_invocations: u64,
 }
 
-#[allow(clippy::too_many_arguments)]
-fn take_live_sample(
+impl LiveSession {
+    fn attach(
+        pid: i32,
+        sample_period: Duration,
+        record_path: Option<&Path>,
+        compression: Compression,
+        sampler: &SamplerOptions,
+    ) -> Result<(Self, SessionMetadata)> {
+        let shm = ShmReader::open(pid)?;
+        let metadata = build_metadata(&shm, pid)?;
+        let writer = match record_path {
+            Some(p) => Some(RecordingWriter::create(p, &metadata, compression)?),
+            None => None,
+        };
+        let session = Self {
+            pid,
+            shm,
+            thread_sampler: ThreadSampler::with_stale_timeout(sampler.stale_timeout),
+            accumulator: new_accumulator(&metadata, sampler.top),
+            mem_worker: MemStatsWorker::spawn(pid, sample_period, &sampler.mem)?,
+            writer,
+            record_path: record_path.map(Path::to_path_buf),
+            feed: None,
+            total_jit_invocations: 0,
+        };
+        info!(pid, fex_version = %metadata.fex_version, recording = ?record_path, "attached");
+        Ok((session, metadata))
+    }
+
+    /// Takes one sample, appends it to the recording if there is one, and
+    /// returns the computed frame for display.
+    fn sample(&mut self, period_nanos: u64) -> Result<ComputedFrame> {
+        let frame = sample_frame(
+            &mut self.shm,
+            &mut self.thread_sampler,
+            &mut self.accumulator,
+            &mut self.mem_worker,
+            &mut self.total_jit_invocations,
+            period_nanos,
+        )?;
+        if let Some(ref mut w) = self.writer {
+            w.write_frame(&frame)?;
+        }
+        if let Some(feed) = &self.feed {
+            feed.publish(&frame.computed);
+        }
+        self.thread_sampler.recycle(frame.per_thread_deltas);
+        Ok(frame.computed)
+    }
+
+    /// Bookmarks the latest frame in the recording, if there is one.
+    fn add_marker(&mut self) {
+        if let Some(ref mut w) = self.writer {
+            w.add_marker(None);
+        }
+    }
+
+    /// Compressed bytes written to the recording so far, if recording.
+    fn recorded_bytes(&self) -> Option<u64> {
+        self.writer.as_ref().map(RecordingWriter::compressed_size)
+    }
+
+    /// Why the latest memory sample failed, if it did.
+    fn mem_error(&self) -> Option<String> {
+        self.mem_worker.latest_with_status().1
+    }
+
+    fn unknown_regions(&self) -> Option<BTreeMap<String, u64>> {
+        self.mem_worker.unknown_regions()
+    }
+
+    fn detach(self) {
+        let pid = self.pid;
+        match self.finish() {
+            Ok(()) => info!(pid, "detached"),
+            Err(e) => {
+                warn!(pid, error = %format_args!("{e:#}"), "failed to finish recording on detach");
+            }
+        }
+    }
+
+    /// Like `detach`, but reports a failure to finalize the recording.
+    fn finish(mut self) -> Result<()> {
+        self.mem_worker.shutdown();
+        match self.writer {
+            Some(w) => w.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Reads the shared-memory stats and latest memory snapshot and turns them
+/// into one recording frame. Shared by live and headless sampling.
+fn sample_frame(
     shm: &mut ShmReader,
     thread_sampler: &mut ThreadSampler,
-    accumulator: &Accumulator,
+    accumulator: &mut Accumulator,
     mem_worker: &mut MemStatsWorker,
-    app: &mut App,
-    writer: &mut Option<RecordingWriter>,
     total_jit_invocations: &mut u64,
     period_nanos: u64,
-) -> Result<()> {
+) -> Result<Frame> {
     store_memory_barrier();
-    shm.check_resize()?;
+    let header = shm.check_resize()?;
 
-    let raw_stats = shm.read_thread_stats();
+    let raw_stats = shm.read_thread_stats(header.head);
     let now = Instant::now();
-    let sample = thread_sampler.sample(&raw_stats, now);
+    let sample = thread_sampler.sample(raw_stats, now);
     let mem = mem_worker.latest();
 
     *total_jit_invocations = total_jit_invocations
@@ -347,7 +1277,7 @@ fn take_live_sample(
         jit: raw_stats.iter().map(|s| s.accumulated_jit_count).sum(),
     };
 
-    let frame = accumulator.compute_frame(
+    let computed = accumulator.compute_frame(
         &sample,
         &mem,
         period_nanos,
@@ -355,36 +1285,77 @@ fn take_live_sample(
         cumulative,
     );
 
-    if let Some(ref mut w) = *writer {
-        let rec_frame = Frame {
-            computed: frame.clone(),
-            per_thread_deltas: sample.per_thread,
-        };
-        w.write_frame(&rec_frame)?;
-    }
+    Ok(Frame {
+        computed,
+        per_thread_deltas: thread_sampler.take_deltas(),
+    })
+}
 
-    app.update_frame(frame);
-    Ok(())
+/// Adjusts the live sample period: `+` halves it, `-` doubles it, clamped to
+/// `MIN_SAMPLE_PERIOD..=MAX_SAMPLE_PERIOD`. The memory workers are told about
+/// the new cadence so smaps sampling keeps pace with thread sampling.
+fn handle_sample_period_action<'a>(
+    action: &Action,
+    interval: &mut Duration,
+    mem_workers: impl IntoIterator<Item = &'a MemStatsWorker>,
+) {
+    let new_interval = match *action {
+        Action::IncreaseSampleRate => *interval / 2,
+        Action::DecreaseSampleRate => interval.saturating_mul(2),
+        _ => return,
+    };
+    let new_interval = new_interval.clamp(MIN_SAMPLE_PERIOD, MAX_SAMPLE_PERIOD);
+    if new_interval != *interval {
+        *interval = new_interval;
+        for worker in mem_workers {
+            worker.set_sample_period(new_interval);
+        }
+    }
 }
 
-fn handle_sample_period_action(_action: &Action, _app: &mut App) {
-    // Sample period adjustment is a no-op for now; the C++ version
-    // supported +/- keys to change sample period at runtime, but the
-    // current architecture uses a fixed period per session.
+fn duration_to_nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
 }
 
 // ---------------------------------------------------------------------------
 // Replay subcommand
 // ---------------------------------------------------------------------------
 
-fn cmd_replay(path: &Path) -> Result<()> {
+fn cmd_replay(path: &Path, strict: bool, display: DisplayOptions) -> Result<()> {
     let shutdown = install_signal_handler()?;
-    let reader = RecordingReader::open(path)?;
+    let reader = if strict {
+        RecordingReader::open_strict(path)?
+    } else {
+        RecordingReader::open(path)?
+    };
+    if reader.truncated() {
+        eprintln!(
+            "warning: {} was cut short; replaying its {} complete frames",
+            path.display(),
+            reader.frame_count()
+        );
+    }
     let total = reader.frame_count();
     let metadata = reader.metadata().clone();
 
-    let mut app = App::new(metadata, true);
+    let mut app = App::new(metadata, true, display.history);
+    app.theme = display.theme;
+    app.smoothing = display.smoothing;
+    app.min_thread_load = display.min_thread_load;
+    app.mem_growth_warn = display.mem_growth_warn;
+    app.compact = display.compact;
+    app.wide = display.wide;
+    app.keymap = display.keymap.clone();
+    display.saved.state.apply(&mut app);
     app.set_replay_total_frames(total);
+    #[allow(clippy::cast_possible_truncation)]
+    app.set_replay_markers(
+        reader
+            .markers()
+            .iter()
+            .map(|m| m.frame_index as usize)
+            .collect(),
+    );
 
     let mut source = ReplaySource::new(reader);
     let mut terminal = setup_terminal()?;
@@ -392,6 +1363,7 @@ fn cmd_replay(path: &Path) -> Result<()> {
     let result = run_replay_loop(&shutdown, &mut app, &mut source, &mut terminal);
 
     restore_terminal(&mut terminal)?;
+    display.saved.save(&app);
     result
 }
 
@@ -406,181 +1378,954 @@ fn run_replay_loop(
             break;
         }
 
-        if event::poll(EVENT_POLL_TIMEOUT).context("failed to poll events")?
-            && let Event::Key(key) = event::read().context("failed to read event")?
-            && key.kind == KeyEventKind::Press
-        {
-            let action = handle_key(key, true);
-            app.handle_action(&action);
+        if event::poll(EVENT_POLL_TIMEOUT).context("failed to poll events")? {
+            match event::read().context("failed to read event")? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    if app.is_prompting() {
+                        app.handle_prompt_key(key.code);
+                    } else {
+                        let action = app.keymap.action(key.code, true);
+                        app.handle_action(&action);
+                    }
+                }
+                Event::Resize(..) => clear_after_resize(terminal)?,
+                _ => {}
+            }
         }
 
         sync_replay_state(app, source);
 
         if let Some(frame) = source.next_frame() {
-            app.update_frame(frame);
-            if let Some(controls) = app.replay_controls_mut() {
-                controls.update_position(source.current_index());
+            // The source's index has moved past the frame it returned.
+            app.update_replay_frame(frame, source.current_index() - 1);
+        }
+        // The source may pause itself, e.g. on reaching the start in reverse.
+        if let Some(controls) = app.replay_controls_mut() {
+            controls.update_position(source.current_index());
+            controls.paused = source.is_paused();
+        }
+
+        terminal
+            .draw(|f| app.render(f))
+            .context("failed to draw frame")?;
+    }
+    Ok(())
+}
+
+fn sync_replay_state(app: &App, source: &mut ReplaySource) {
+    if let Some(controls) = app.replay_controls() {
+        source.set_speed(controls.speed);
+        source.set_reversed(controls.reversed);
+        source.set_loop_playback(controls.looping);
+        if controls.paused != source.is_paused() {
+            source.toggle_pause();
+        }
+        if controls.current_frame != source.current_index() {
+            source.seek_to(controls.current_frame);
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Record (headless) subcommand
+// ---------------------------------------------------------------------------
+
+/// Output format for `record`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum RecordFormat {
+    /// A compressed felix recording, for replay and later export
+    Felixr,
+    /// CSV rows, as written by `export`
+    Csv,
+    /// Newline-delimited JSON, one frame object per line
+    Jsonl,
+}
+
+impl RecordFormat {
+    /// The format implied by `path`'s extension, defaulting to `Felixr`.
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("csv") => Self::Csv,
+            Some("jsonl") => Self::Jsonl,
+            _ => Self::Felixr,
+        }
+    }
+}
+
+/// Where `record` writes frames: a `.felixr` recording, or an export sink
+/// fed as frames are computed.
+enum RecordOutput {
+    Recording(RecordingWriter),
+    Export {
+        sink: Box<dyn FrameSink>,
+        frames: usize,
+    },
+}
+
+impl RecordOutput {
+    fn create(
+        path: &Path,
+        format: RecordFormat,
+        append: bool,
+        metadata: &SessionMetadata,
+        compression: Compression,
+    ) -> Result<Self> {
+        let export_format = match format {
+            RecordFormat::Felixr if append => {
+                return Ok(Self::Recording(RecordingWriter::append(
+                    path,
+                    metadata,
+                    compression,
+                )?));
+            }
+            RecordFormat::Felixr => {
+                return Ok(Self::Recording(RecordingWriter::create(
+                    path,
+                    metadata,
+                    compression,
+                )?));
+            }
+            RecordFormat::Csv => ExportFormat::Csv,
+            RecordFormat::Jsonl => ExportFormat::Jsonl,
+        };
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("failed to create {}", path.display()))?;
+        // Every row ends in a newline, so a line writer keeps the file
+        // current for anyone tailing it.
+        let sink = export::create_sink(
+            export_format,
+            io::LineWriter::new(file),
+            metadata,
+            export::influx::DEFAULT_MEASUREMENT,
+        )?;
+        Ok(Self::Export { sink, frames: 0 })
+    }
+
+    fn frame_count(&self) -> u64 {
+        match self {
+            Self::Recording(writer) => writer.frame_count(),
+            Self::Export { frames, .. } => *frames as u64,
+        }
+    }
+
+    /// Bytes written so far to the file at `path`.
+    fn size(&self, path: &Path) -> u64 {
+        match self {
+            // The file lags behind the encoder's output by its buffer.
+            Self::Recording(writer) => writer.compressed_size(),
+            Self::Export { .. } => std::fs::metadata(path).map_or(0, |m| m.len()),
+        }
+    }
+
+    fn write_frame(&mut self, frame: &Frame) -> Result<()> {
+        match self {
+            Self::Recording(writer) => writer.write_frame(frame),
+            Self::Export { sink, frames } => {
+                sink.write_frame(*frames, &frame.computed)?;
+                *frames += 1;
+                Ok(())
             }
         }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Recording(writer) => writer.finish(),
+            Self::Export { mut sink, .. } => sink.finish(),
+        }
+    }
+}
+
+/// A `RecordOutput` that can switch to a fresh file partway through a
+/// recording, for logrotate-style management of long captures.
+struct RotatingOutput {
+    /// The path given on the command line; rotated files are named after it.
+    output: PathBuf,
+    format: RecordFormat,
+    metadata: SessionMetadata,
+    compression: Compression,
+    rotation: RotationArgs,
+    writer: RecordOutput,
+    /// The file currently being written.
+    path: PathBuf,
+    /// Frames written to the current file by this run.
+    file_frames: u64,
+    /// Files written by this run that are still on disk, oldest first.
+    files: VecDeque<PathBuf>,
+    /// Number of the last size-rotated file.
+    sequence: u32,
+}
+
+impl RotatingOutput {
+    fn create(
+        output: &Path,
+        append: bool,
+        metadata: &SessionMetadata,
+        options: &RecordOptions,
+    ) -> Result<Self> {
+        let format = options
+            .format
+            .unwrap_or_else(|| RecordFormat::from_path(output));
+        Ok(Self {
+            output: output.to_path_buf(),
+            format,
+            metadata: metadata.clone(),
+            compression: options.compression,
+            rotation: options.rotation,
+            writer: RecordOutput::create(output, format, append, metadata, options.compression)?,
+            path: output.to_path_buf(),
+            file_frames: 0,
+            files: VecDeque::from([output.to_path_buf()]),
+            sequence: 0,
+        })
+    }
+
+    fn frame_count(&self) -> u64 {
+        self.writer.frame_count()
+    }
+
+    /// Writes `frame`, then rotates to a new numbered file if the current
+    /// one has reached `--max-size`.
+    fn write_frame(&mut self, frame: &Frame) -> Result<()> {
+        self.writer.write_frame(frame)?;
+        self.file_frames += 1;
+        if let Some(max) = self.rotation.max_size
+            && self.writer.size(&self.path) >= max
+        {
+            let next = loop {
+                self.sequence += 1;
+                let candidate = tagged_path(&self.output, format!("{:03}", self.sequence));
+                if !candidate.exists() {
+                    break candidate;
+                }
+            };
+            self.rotate_to(next)?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the current file and continues in a new timestamped one.
+    fn rotate(&mut self) -> Result<()> {
+        self.rotate_to(rotated_path(&self.output, SystemTime::now()))
+    }
+
+    fn rotate_to(&mut self, next: PathBuf) -> Result<()> {
+        // Open the new file first so a failure leaves the current one
+        // recording.
+        let next_writer =
+            RecordOutput::create(&next, self.format, false, &self.metadata, self.compression)?;
+        std::mem::replace(&mut self.writer, next_writer).finish()?;
+        eprintln!(
+            "Rotated: {} frames written to {}; now recording to {}",
+            self.file_frames,
+            self.path.display(),
+            next.display()
+        );
+        info!(
+            frames = self.file_frames,
+            finished = %self.path.display(),
+            next = %next.display(),
+            "rotated recording"
+        );
+        self.files.push_back(next.clone());
+        self.path = next;
+        self.file_frames = 0;
+        self.prune();
+        Ok(())
+    }
+
+    /// Deletes the oldest files once there are more than `--max-files`.
+    fn prune(&mut self) {
+        let Some(max) = self.rotation.max_files else {
+            return;
+        };
+        while self.files.len() as u64 > max {
+            let Some(oldest) = self.files.pop_front() else {
+                break;
+            };
+            match std::fs::remove_file(&oldest) {
+                Ok(()) => info!(path = %oldest.display(), "removed old recording"),
+                Err(e) => eprintln!("warning: failed to remove {}: {e}", oldest.display()),
+            }
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        self.writer.finish()
+    }
+}
+
+/// Tells the user a recording has started, noting the frames already in the
+/// file when appending.
+fn announce_recording(
+    pid: i32,
+    output: &Path,
+    existing_frames: Option<u64>,
+    sample_period_ms: u64,
+) {
+    match existing_frames {
+        Some(frames) => eprintln!(
+            "Appending PID {pid} to {} ({frames} existing frames) ...",
+            output.display()
+        ),
+        None => eprintln!("Recording PID {pid} to {} ...", output.display()),
+    }
+    info!(
+        pid,
+        output = %output.display(),
+        sample_period_ms,
+        existing_frames = existing_frames.unwrap_or(0),
+        "recording started"
+    );
+}
+
+fn cmd_record(pid: i32, output: &Path, options: &RecordOptions) -> Result<()> {
+    let format = options
+        .format
+        .unwrap_or_else(|| RecordFormat::from_path(output));
+    let appending = options.append && output.exists();
+    if appending && format != RecordFormat::Felixr {
+        bail!("--append is only supported for .felixr recordings");
+    }
+    let shutdown = install_signal_handler()?;
+    let rotate = install_rotate_handler()?;
+    let mut shm = ShmReader::open(pid)?;
+    let metadata = build_metadata(&shm, pid)?;
+    warn_stats_version(&metadata);
+    let sample_period = Duration::from_millis(options.sample_period_ms);
+    #[allow(clippy::cast_possible_truncation)]
+    let period_nanos = sample_period.as_nanos() as u64;
+
+    let mut mem_worker = MemStatsWorker::spawn(pid, sample_period, &options.sampler.mem)?;
+    let mut thread_sampler = ThreadSampler::with_stale_timeout(options.sampler.stale_timeout);
+    let mut accumulator = new_accumulator(&metadata, options.sampler.top);
+
+    let mut writer = RotatingOutput::create(output, appending, &metadata, options)?;
+    let mut total_jit_invocations: u64 = 0;
+    let mut alerts = options.alert.monitor();
+
+    let metrics_frame = spawn_metrics(options.metrics_addr)?;
+    let ipc = spawn_ipc(options.ipc_socket.as_deref())?;
+
+    let max_duration =
+        (options.duration_secs > 0).then(|| Duration::from_secs(options.duration_secs));
+    // A bar that redraws in place only makes sense on a terminal.
+    let progress_total = max_duration.filter(|_| io::stderr().is_terminal());
+    let status_interval = progress_total.map_or(HEADLESS_STATUS_INTERVAL, |_| PROGRESS_INTERVAL);
+
+    let start = Instant::now();
+    let mut last_status = Instant::now();
+    let mut frames_recorded: u64 = 0;
+    let mut tally = RecordTally::default();
+
+    announce_recording(
+        pid,
+        output,
+        appending.then(|| writer.frame_count()),
+        options.sample_period_ms,
+    );
+
+    let reason = loop {
+        if let Some(reason) = record_stop_reason(
+            &shutdown,
+            pid,
+            start.elapsed(),
+            max_duration,
+            frames_recorded,
+            options.max_frames,
+        ) {
+            break reason;
+        }
+
+        std::thread::sleep(sample_period);
+
+        let rec_frame = match sample_frame(
+            &mut shm,
+            &mut thread_sampler,
+            &mut accumulator,
+            &mut mem_worker,
+            &mut total_jit_invocations,
+            period_nanos,
+        ) {
+            Ok(frame) => frame,
+            Err(e) if e.is::<SegmentGone>() => {
+                eprintln!("\nProcess {pid} exited.");
+                break "process_exited";
+            }
+            Err(e) => return Err(e),
+        };
+
+        if let Some(latest) = &metrics_frame {
+            *latest
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = rec_frame.computed.clone();
+        }
+        if let Some(ipc) = &ipc {
+            ipc.publish(&rec_frame.computed);
+        }
+        for alert in alerts.evaluate(&rec_frame.computed, pid) {
+            eprintln!("  {alert}");
+        }
+
+        writer.write_frame(&rec_frame)?;
+        frames_recorded += 1;
+        tally.add(&rec_frame);
+        thread_sampler.recycle(rec_frame.per_thread_deltas);
+
+        if rotate.swap(false, Ordering::Relaxed) {
+            writer.rotate()?;
+        }
+
+        if last_status.elapsed() >= status_interval {
+            match progress_total {
+                Some(total) => print_recording_progress(start.elapsed(), total, frames_recorded),
+                None => print_recording_status(start.elapsed(), frames_recorded, &writer.path),
+            }
+            last_status = Instant::now();
+        }
+    };
+    info!(pid, frames = frames_recorded, reason = %reason, "recording stopped");
+
+    mem_worker.shutdown();
+    report_unknown_regions(pid, mem_worker.unknown_regions().as_ref());
+    let (file_frames, path) = (writer.file_frames, writer.path.clone());
+    writer.finish()?;
+
+    eprintln!(
+        "Finished: {file_frames} frames written to {}",
+        path.display()
+    );
+    print_record_report(output, &tally.report(start.elapsed()), options.summary_json)
+}
+
+/// Prints the end-of-record summary and, with `--summary-json`, writes it
+/// to `<output>.summary.json` next to the recording.
+fn print_record_report(output: &Path, report: &RecordReport, json: bool) -> Result<()> {
+    eprint!("{report}");
+    if !json {
+        return Ok(());
+    }
+    let path = output.with_extension("summary.json");
+    let json = serde_json::to_string_pretty(report).context("failed to serialize summary")?;
+    std::fs::write(&path, json + "\n")
+        .with_context(|| format!("failed to write summary: {}", path.display()))?;
+    eprintln!("Summary written to {}", path.display());
+    Ok(())
+}
+
+/// Why a headless recording should stop before its next sample, if it
+/// should, after telling the user.
+fn record_stop_reason(
+    shutdown: &AtomicBool,
+    pid: i32,
+    elapsed: Duration,
+    max_duration: Option<Duration>,
+    frames_recorded: u64,
+    max_frames: Option<u64>,
+) -> Option<&'static str> {
+    let (message, reason) = if shutdown.load(Ordering::Relaxed) {
+        ("Interrupted.".to_string(), "interrupted")
+    } else if !process_alive(pid) {
+        (format!("Process {pid} exited."), "process_exited")
+    } else if max_duration.is_some_and(|max| elapsed >= max) {
+        ("Duration limit reached.".to_string(), "duration_limit")
+    } else if max_frames.is_some_and(|max| frames_recorded >= max) {
+        ("Frame limit reached.".to_string(), "frame_limit")
+    } else {
+        return None;
+    };
+    eprintln!("\n{message}");
+    Some(reason)
+}
+
+/// Starts the `--metrics-addr` server, if one was asked for, returning the
+/// frame it serves.
+fn spawn_metrics(addr: Option<SocketAddr>) -> Result<Option<Arc<Mutex<ComputedFrame>>>> {
+    let Some(addr) = addr else {
+        return Ok(None);
+    };
+    let latest = Arc::new(Mutex::new(ComputedFrame::default()));
+    let bound = metrics::spawn(addr, Arc::clone(&latest))?;
+    eprintln!("Serving metrics on http://{bound}/metrics");
+    Ok(Some(latest))
+}
+
+/// Names the file a SIGHUP rotation switches to: `output` with a UTC
+/// timestamp before the extension, plus a counter if that name is taken.
+fn rotated_path(output: &Path, time: SystemTime) -> PathBuf {
+    let timestamp = wallclock::format_compact(time);
+    let mut candidate = tagged_path(output, &timestamp);
+    let mut n = 1;
+    while candidate.exists() {
+        candidate = tagged_path(output, format!("{timestamp}-{n}"));
+        n += 1;
+    }
+    candidate
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn print_recording_status(elapsed: Duration, frames: u64, path: &Path) {
+    let secs = elapsed.as_secs();
+    let size = std::fs::metadata(path).map_or(0, |m| m.len());
+    eprintln!(
+        "  [{secs}s] {frames} frames, {:.1} KB",
+        size as f64 / 1024.0
+    );
+}
+
+/// Redraws `  [####----] 45% ~12s left, N frames` over the previous status
+/// line. The stop message that ends the capture starts on a new line.
+fn print_recording_progress(elapsed: Duration, total: Duration, frames: u64) {
+    let fraction = (elapsed.as_secs_f64() / total.as_secs_f64()).min(1.0);
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    let filled = (fraction * PROGRESS_BAR_WIDTH as f64).round() as usize;
+    let left = total.saturating_sub(elapsed).as_secs_f64().ceil();
+    eprint!(
+        "\r  [{}{}] {:.0}% ~{left:.0}s left, {frames} frames\x1b[K",
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        fraction * 100.0
+    );
+}
+
+// ---------------------------------------------------------------------------
+// Daemon subcommand
+// ---------------------------------------------------------------------------
+
+/// A background recording of one process started by the daemon.
+struct DaemonRecorder {
+    path: PathBuf,
+    handle: std::thread::JoinHandle<Result<u64>>,
+}
+
+fn cmd_daemon(out_dir: &Path, sample_period_ms: u64, display: &DisplayOptions) -> Result<()> {
+    std::fs::create_dir_all(out_dir)
+        .with_context(|| format!("failed to create {}", out_dir.display()))?;
+    let shutdown = install_signal_handler()?;
+    let sample_period = Duration::from_millis(sample_period_ms);
+    let mut recorders: BTreeMap<i32, DaemonRecorder> = BTreeMap::new();
+    // Pids already attached to, so one whose recording failed is not
+    // restarted every scan. Pids whose shared memory is not ready yet stay
+    // out and are retried. A pid leaves the set once its process is gone,
+    // in case the number is reused.
+    let mut seen: BTreeSet<i32> = BTreeSet::new();
+
+    eprintln!(
+        "Recording every FEX process to {} (Ctrl-C to stop) ...",
+        out_dir.display()
+    );
+
+    while !shutdown.load(Ordering::Relaxed) {
+        let running = find_all_fex_processes();
+        seen.retain(|pid| running.contains(pid));
+        for &pid in &running {
+            if seen.contains(&pid) {
+                continue;
+            }
+            let path = out_dir.join(format!(
+                "fex-{pid}-{}.felixr",
+                wallclock::format_compact(SystemTime::now())
+            ));
+            let (session, metadata) = match LiveSession::attach(
+                pid,
+                sample_period,
+                Some(&path),
+                Compression::DEFAULT,
+                &display.sampler,
+            ) {
+                Ok(attached) => attached,
+                Err(e) => {
+                    debug!(pid, error = %format_args!("{e:#}"), "not ready to attach yet");
+                    continue;
+                }
+            };
+            seen.insert(pid);
+            eprintln!("PID {pid}: recording to {}", path.display());
+            warn_stats_version(&metadata);
+            let handle = {
+                let shutdown = Arc::clone(&shutdown);
+                std::thread::spawn(move || daemon_record(session, sample_period, &shutdown))
+            };
+            recorders.insert(pid, DaemonRecorder { path, handle });
+        }
+
+        let finished: Vec<i32> = recorders
+            .iter()
+            .filter(|(_, r)| r.handle.is_finished())
+            .map(|(&pid, _)| pid)
+            .collect();
+        for pid in finished {
+            if let Some(recorder) = recorders.remove(&pid) {
+                report_daemon_recorder(pid, recorder);
+            }
+        }
+
+        std::thread::sleep(WATCH_POLL_INTERVAL);
+    }
 
-        terminal
-            .draw(|f| app.render(f))
-            .context("failed to draw frame")?;
+    eprintln!("\nStopping; finishing {} open recordings.", recorders.len());
+    for (pid, recorder) in recorders {
+        report_daemon_recorder(pid, recorder);
     }
     Ok(())
 }
 
-fn sync_replay_state(app: &App, source: &mut ReplaySource) {
-    if let Some(controls) = app.replay_controls() {
-        source.set_speed(controls.speed);
-        if controls.paused != source.is_paused() {
-            source.toggle_pause();
+/// Samples an attached session into its recording until the process exits
+/// or `shutdown` is set. Returns the number of frames written.
+fn daemon_record(
+    mut session: LiveSession,
+    sample_period: Duration,
+    shutdown: &AtomicBool,
+) -> Result<u64> {
+    let pid = session.pid;
+    let mut frames = 0;
+    let result = loop {
+        if shutdown.load(Ordering::Relaxed) || !process_alive(pid) {
+            break Ok(frames);
         }
-        if controls.current_frame != source.current_index() {
-            source.seek_to(controls.current_frame);
+        std::thread::sleep(sample_period);
+        match session.sample(duration_to_nanos(sample_period)) {
+            Ok(_) => {}
+            Err(e) if e.is::<SegmentGone>() => break Ok(frames),
+            Err(e) => break Err(e),
         }
+        frames += 1;
+    };
+    report_unknown_regions(pid, session.unknown_regions().as_ref());
+    let finished = session.finish();
+    let frames = result?;
+    finished?;
+    Ok(frames)
+}
+
+/// Waits for a recorder thread and reports how its recording ended.
+fn report_daemon_recorder(pid: i32, recorder: DaemonRecorder) {
+    match recorder.handle.join() {
+        Ok(Ok(frames)) => eprintln!(
+            "PID {pid}: {frames} frames written to {}",
+            recorder.path.display()
+        ),
+        Ok(Err(e)) => eprintln!("PID {pid}: recording failed: {e:#}"),
+        Err(_) => eprintln!("PID {pid}: recorder thread panicked"),
     }
 }
 
 // ---------------------------------------------------------------------------
-// Record (headless) subcommand
+// Watch subcommand
 // ---------------------------------------------------------------------------
 
-fn cmd_record(pid: i32, output: &Path, sample_period_ms: u64, duration_secs: u64) -> Result<()> {
-    let shutdown = install_signal_handler()?;
-    let mut shm = ShmReader::open(pid)?;
-    let metadata = build_metadata(&shm, pid)?;
-    let sample_period = Duration::from_millis(sample_period_ms);
-    #[allow(clippy::cast_possible_truncation)]
-    let period_nanos = sample_period.as_nanos() as u64;
-
-    let mut mem_worker = MemStatsWorker::spawn(pid, sample_period)?;
-    let mut thread_sampler = ThreadSampler::new();
-    let accumulator = Accumulator::new(
-        #[allow(clippy::cast_precision_loss)]
-        {
-            metadata.cycle_counter_frequency as f64
-        },
-        metadata.hardware_concurrency,
-    );
-
-    let mut writer = RecordingWriter::create(output, &metadata)?;
-    let mut total_jit_invocations: u64 = 0;
-
-    let max_duration = if duration_secs > 0 {
-        Some(Duration::from_secs(duration_secs))
-    } else {
-        None
-    };
+#[derive(Args)]
+struct WatchArgs {
+    #[arg(short, long, default_value = "1000")]
+    sample_period: u64,
+    #[arg(short, long)]
+    record: Option<PathBuf>,
+    /// Attach to every FEX process at once, one tab per process. With
+    /// --record, each process is written to <name>-<pid>.<ext>
+    #[arg(long)]
+    all: bool,
+    /// Only attach to FEX processes whose command line contains this
+    /// text; with several, the newest (highest PID)
+    #[arg(long = "match", visible_alias = "watch-name", value_name = "SUBSTR")]
+    pattern: Option<String>,
+    /// Exit when the attached process does, instead of waiting for the next
+    /// matching FEX process and attaching to it
+    #[arg(long, conflicts_with = "all")]
+    once: bool,
+}
 
-    let start = Instant::now();
-    let mut last_status = Instant::now();
-    let mut frames_recorded: u64 = 0;
+fn cmd_watch(args: &WatchArgs, display: DisplayOptions) -> Result<()> {
+    let shutdown = install_signal_handler()?;
+    let pattern = args.pattern.as_deref();
 
-    eprintln!("Recording PID {pid} to {} ...", output.display());
+    match pattern {
+        Some(pattern) => eprintln!("Watching for FEX processes matching \"{pattern}\"..."),
+        None => eprintln!("Watching for FEX processes..."),
+    }
 
     loop {
         if shutdown.load(Ordering::Relaxed) {
-            eprintln!("\nInterrupted.");
-            break;
-        }
-        if !process_alive(pid) {
-            eprintln!("\nProcess {pid} exited.");
-            break;
+            bail!("interrupted while watching for FEX processes");
         }
-        if let Some(max) = max_duration
-            && start.elapsed() >= max
-        {
-            eprintln!("\nDuration limit reached.");
-            break;
+
+        if let Some(pid) = find_fex_process(pattern) {
+            info!(pid, "found FEX process");
+            // Relaunching the program shows up as a new pid, which the
+            // live view re-attaches to rather than exiting.
+            let options = LiveOptions {
+                reattach: !args.once,
+                reattach_match: args.pattern.clone(),
+                ..LiveOptions::new(args.sample_period, args.record.as_deref())
+            };
+            return cmd_live(pid, &options, display);
         }
 
-        std::thread::sleep(sample_period);
+        std::thread::sleep(WATCH_POLL_INTERVAL);
+    }
+}
 
-        store_memory_barrier();
-        shm.check_resize()?;
-
-        let raw_stats = shm.read_thread_stats();
-        let now = Instant::now();
-        let sample = thread_sampler.sample(&raw_stats, now);
-        let mem = mem_worker.latest();
-
-        total_jit_invocations = total_jit_invocations
-            .wrapping_add(sample.per_thread.iter().map(|d| d.jit_count).sum::<u64>());
-
-        let cumulative = CumulativeCountStats {
-            sigbus: raw_stats.iter().map(|s| s.sigbus_count).sum(),
-            smc: raw_stats.iter().map(|s| s.smc_count).sum(),
-            float_fallback: raw_stats.iter().map(|s| s.float_fallback_count).sum(),
-            cache_miss: raw_stats
-                .iter()
-                .map(|s| s.accumulated_cache_miss_count)
-                .sum(),
-            jit: raw_stats.iter().map(|s| s.accumulated_jit_count).sum(),
-        };
+/// One process attached by `watch --all`, with its own UI state.
+struct WatchedProcess {
+    session: LiveSession,
+    name: String,
+    app: App,
+}
 
-        let frame = accumulator.compute_frame(
-            &sample,
-            &mem,
-            period_nanos,
-            total_jit_invocations,
-            cumulative,
-        );
+impl WatchedProcess {
+    fn attach(
+        pid: i32,
+        sample_period: Duration,
+        record_path: Option<&Path>,
+        display: &DisplayOptions,
+    ) -> Result<Self> {
+        let record_path = record_path.map(|base| tagged_path(base, pid));
+        let (session, metadata) = LiveSession::attach(
+            pid,
+            sample_period,
+            record_path.as_deref(),
+            Compression::DEFAULT,
+            &display.sampler,
+        )?;
+        let mut app = App::new(metadata, false, display.history);
+        app.set_recording(session.record_path.clone());
+        app.theme = display.theme.clone();
+        app.smoothing = display.smoothing;
+        app.min_thread_load = display.min_thread_load;
+        app.mem_growth_warn = display.mem_growth_warn;
+        app.compact = display.compact;
+        app.wide = display.wide;
+        app.keymap = display.keymap.clone();
+        display.saved.state.apply(&mut app);
+        let name = read_process_cmdline(pid)
+            .split_whitespace()
+            .next()
+            .and_then(|exe| exe.rsplit(['/', '\\']).next())
+            .unwrap_or_default()
+            .to_string();
+
+        Ok(Self { session, name, app })
+    }
 
-        let rec_frame = Frame {
-            computed: frame,
-            per_thread_deltas: sample.per_thread,
-        };
-        writer.write_frame(&rec_frame)?;
-        frames_recorded += 1;
+    fn pid(&self) -> i32 {
+        self.session.pid
+    }
 
-        if last_status.elapsed() >= HEADLESS_STATUS_INTERVAL {
-            print_recording_status(start.elapsed(), frames_recorded, output);
-            last_status = Instant::now();
+    fn sample(&mut self, period_nanos: u64) -> Result<()> {
+        let frame = self.session.sample(period_nanos)?;
+        self.app.update_frame(frame);
+        self.app.set_mem_error(self.session.mem_error());
+        if let Some(bytes) = self.session.recorded_bytes() {
+            self.app.set_recorded_bytes(bytes);
         }
+        Ok(())
     }
 
-    mem_worker.shutdown();
-    writer.finish()?;
-
-    eprintln!(
-        "Finished: {frames_recorded} frames written to {}",
-        output.display()
-    );
-    Ok(())
+    fn detach(self) {
+        self.session.detach();
+    }
 }
 
-#[allow(clippy::cast_precision_loss)]
-fn print_recording_status(elapsed: Duration, frames: u64, path: &Path) {
-    let secs = elapsed.as_secs();
-    let size = std::fs::metadata(path).map_or(0, |m| m.len());
-    eprintln!(
-        "  [{secs}s] {frames} frames, {:.1} KB",
-        size as f64 / 1024.0
+fn cmd_watch_all(args: &WatchArgs, display: &DisplayOptions) -> Result<()> {
+    let shutdown = install_signal_handler()?;
+    let sample_period = Duration::from_millis(args.sample_period);
+    let mut processes: Vec<WatchedProcess> = Vec::new();
+
+    let mut terminal = setup_terminal()?;
+    let result = run_watch_all_loop(
+        &shutdown,
+        &mut processes,
+        &mut terminal,
+        sample_period,
+        args,
+        display,
     );
+
+    // The selected process's layout is saved once the terminal is back, so
+    // a failure to save is seen.
+    let selected_pid = result.as_ref().ok().copied().flatten();
+    let mut selected_app = None;
+    for process in processes {
+        let WatchedProcess { session, app, .. } = process;
+        if Some(session.pid) == selected_pid {
+            selected_app = Some(app);
+        }
+        session.detach();
+    }
+    restore_terminal(&mut terminal)?;
+    if let Some(app) = selected_app {
+        display.saved.save(&app);
+    }
+
+    result.map(drop)
 }
 
-// ---------------------------------------------------------------------------
-// Watch subcommand
-// ---------------------------------------------------------------------------
+/// Adds newly-started FEX processes and drops exited ones, keeping
+/// `processes` sorted by pid. Processes whose shared memory is not ready yet
+/// are retried on the next scan.
+fn refresh_watched_processes(
+    processes: &mut Vec<WatchedProcess>,
+    sample_period: Duration,
+    args: &WatchArgs,
+    display: &DisplayOptions,
+) {
+    let (alive, exited): (Vec<_>, Vec<_>) = std::mem::take(processes)
+        .into_iter()
+        .partition(|p| process_alive(p.pid()));
+    for process in exited {
+        process.detach();
+    }
+    *processes = alive;
 
-fn cmd_watch(sample_period_ms: u64, record_path: Option<&Path>) -> Result<()> {
-    let shutdown = install_signal_handler()?;
+    for pid in find_matching_fex_processes(args.pattern.as_deref()) {
+        if processes.iter().any(|p| p.pid() == pid) {
+            continue;
+        }
+        match WatchedProcess::attach(pid, sample_period, args.record.as_deref(), display) {
+            Ok(process) => processes.push(process),
+            Err(e) => debug!(pid, error = %format_args!("{e:#}"), "not ready to attach yet"),
+        }
+    }
+    processes.sort_by_key(WatchedProcess::pid);
+}
 
-    eprintln!("Watching for FEX processes...");
+/// Runs the tabbed view until quit or a signal, returning the pid of the
+/// process selected at the end.
+fn run_watch_all_loop(
+    shutdown: &Arc<AtomicBool>,
+    processes: &mut Vec<WatchedProcess>,
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    mut interval: Duration,
+    args: &WatchArgs,
+    display: &DisplayOptions,
+) -> Result<Option<i32>> {
+    let mut selected_pid: Option<i32> = None;
+    let mut last_sample = Instant::now();
+    let mut last_scan: Option<Instant> = None;
 
     loop {
         if shutdown.load(Ordering::Relaxed) {
-            bail!("interrupted while watching for FEX processes");
+            break;
         }
 
-        if let Some(pid) = find_fex_process() {
-            eprintln!("Found FEX process with PID {pid}");
-            return cmd_live(pid, sample_period_ms, record_path);
+        if last_scan.is_none_or(|t| t.elapsed() >= WATCH_POLL_INTERVAL) {
+            refresh_watched_processes(processes, interval, args, display);
+            last_scan = Some(Instant::now());
         }
+        let mut selected = selected_pid
+            .and_then(|pid| processes.iter().position(|p| p.pid() == pid))
+            .unwrap_or(0);
 
-        std::thread::sleep(WATCH_POLL_INTERVAL);
+        let elapsed = last_sample.elapsed();
+        let poll_timeout = if elapsed >= interval {
+            Duration::ZERO
+        } else {
+            EVENT_POLL_TIMEOUT.min(interval.checked_sub(elapsed).unwrap())
+        };
+
+        if event::poll(poll_timeout).context("failed to poll events")? {
+            match event::read().context("failed to read event")? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    let quit = handle_watch_all_key(
+                        key.code,
+                        processes,
+                        &mut selected,
+                        &mut interval,
+                        display,
+                    );
+                    if quit {
+                        selected_pid = processes.get(selected).map(WatchedProcess::pid);
+                        break;
+                    }
+                }
+                Event::Resize(..) => clear_after_resize(terminal)?,
+                _ => {}
+            }
+        }
+        selected_pid = processes.get(selected).map(WatchedProcess::pid);
+
+        if last_sample.elapsed() >= interval {
+            let period_nanos = duration_to_nanos(interval);
+            // A process that fails to sample (e.g. its shared memory went
+            // away) is detached rather than ending the whole session.
+            let mut i = 0;
+            while i < processes.len() {
+                if processes[i].sample(period_nanos).is_ok() {
+                    i += 1;
+                } else {
+                    processes.remove(i).detach();
+                }
+            }
+            last_sample = Instant::now();
+        }
+
+        let tabs: Vec<ProcessTab> = processes
+            .iter()
+            .map(|p| ProcessTab {
+                pid: p.pid(),
+                name: p.name.clone(),
+            })
+            .collect();
+        terminal
+            .draw(|f| {
+                let [tab_area, body_area] =
+                    Layout::vertical([Constraint::Length(1), Constraint::Min(1)]).areas(f.area());
+                process_tabs::render(f, tab_area, &tabs, selected, &display.theme);
+                if let Some(process) = processes.get(selected) {
+                    process.app.render_in(f, body_area);
+                }
+            })
+            .context("failed to draw frame")?;
+    }
+
+    Ok(selected_pid)
+}
+
+/// Applies a key press to the tabbed view: switching tabs and the sample
+/// rate are handled here, anything else goes to the selected process's
+/// view. Returns true on quit.
+fn handle_watch_all_key(
+    key: KeyCode,
+    processes: &mut [WatchedProcess],
+    selected: &mut usize,
+    interval: &mut Duration,
+    display: &DisplayOptions,
+) -> bool {
+    // An open prompt in the selected process's view takes the key.
+    let action = match processes.get_mut(*selected) {
+        Some(process) if process.app.is_prompting() => {
+            process.app.handle_prompt_key(key);
+            Action::None
+        }
+        _ => display.keymap.action(key, false),
+    };
+    let count = processes.len().max(1);
+    match action {
+        Action::Quit => return true,
+        Action::NextProcess => *selected = (*selected + 1) % count,
+        Action::PrevProcess => *selected = (*selected + count - 1) % count,
+        Action::AddMarker => {
+            if let Some(process) = processes.get_mut(*selected) {
+                process.session.add_marker();
+            }
+        }
+        Action::IncreaseSampleRate | Action::DecreaseSampleRate => {
+            handle_sample_period_action(
+                &action,
+                interval,
+                processes.iter().map(|p| &p.session.mem_worker),
+            );
+        }
+        _ => {
+            if let Some(process) = processes.get_mut(*selected) {
+                process.app.handle_action(&action);
+            }
+        }
     }
+    false
 }
 
 fn find_all_fex_processes() -> Vec<i32> {
@@ -605,8 +2350,19 @@ fn find_all_fex_processes() -> Vec<i32> {
     candidates
 }
 
-fn find_fex_process() -> Option<i32> {
-    find_all_fex_processes().last().copied()
+/// The running FEX processes whose command line contains `pattern`, or
+/// all of them without one, sorted by pid.
+fn find_matching_fex_processes(pattern: Option<&str>) -> Vec<i32> {
+    let mut pids = find_all_fex_processes();
+    if let Some(pattern) = pattern {
+        pids.retain(|&pid| read_process_cmdline(pid).contains(pattern));
+    }
+    pids
+}
+
+/// The newest (highest pid) FEX process matching `pattern`.
+fn find_fex_process(pattern: Option<&str>) -> Option<i32> {
+    find_matching_fex_processes(pattern).last().copied()
 }
 
 fn read_process_cmdline(pid: i32) -> String {
@@ -624,6 +2380,29 @@ fn read_process_cmdline(pid: i32) -> String {
     )
 }
 
+/// Finds the FEX process whose command line contains `name`. Several
+/// matches are an error unless `newest` picks the latest started one.
+fn find_fex_process_by_name(name: &str, newest: bool) -> Result<i32> {
+    let matches = find_matching_fex_processes(Some(name));
+    match matches.as_slice() {
+        [] => bail!("no running FEX process has \"{name}\" in its command line"),
+        [pid] => Ok(*pid),
+        _ if newest => Ok(matches
+            .iter()
+            .copied()
+            .max_by_key(|&pid| (read_process_start_time(pid), pid))
+            .unwrap_or(matches[0])),
+        _ => {
+            let pids: Vec<String> = matches.iter().map(ToString::to_string).collect();
+            bail!(
+                "{} FEX processes match \"{name}\" (PIDs {}); pass a PID or --newest",
+                matches.len(),
+                pids.join(", ")
+            )
+        }
+    }
+}
+
 fn read_process_ppid(pid: i32) -> Option<i32> {
     let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
     // Format: pid (comm) state ppid ... — comm can contain ')' so find the last one
@@ -632,11 +2411,35 @@ fn read_process_ppid(pid: i32) -> Option<i32> {
     after_comm.split_whitespace().nth(1)?.parse().ok()
 }
 
+/// Start time of `pid` in clock ticks since boot, from `/proc/<pid>/stat`.
+fn read_process_start_time(pid: i32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let after_comm = &stat[stat.rfind(')')? + 2..];
+    // starttime is field 22 overall, the 20th after comm.
+    after_comm.split_whitespace().nth(19)?.parse().ok()
+}
+
 // ---------------------------------------------------------------------------
 // Pick subcommand
 // ---------------------------------------------------------------------------
 
-fn cmd_pick(sample_period_ms: u64, record_path: Option<&Path>) -> Result<()> {
+/// Chooses the process for `pick` without prompting, for scripts.
+#[derive(Args, Clone, Copy)]
+struct PickSelection {
+    /// Attach to the process shown as [N] in the process tree
+    #[arg(long, value_name = "N", conflicts_with = "pid")]
+    index: Option<usize>,
+    /// Attach to this PID, which must be a running FEX process
+    #[arg(long, value_name = "PID")]
+    pid: Option<i32>,
+}
+
+fn cmd_pick(
+    sample_period_ms: u64,
+    record_path: Option<&Path>,
+    select: PickSelection,
+    display: DisplayOptions,
+) -> Result<()> {
     let pids = find_all_fex_processes();
 
     if pids.is_empty() {
@@ -645,7 +2448,21 @@ fn cmd_pick(sample_period_ms: u64, record_path: Option<&Path>) -> Result<()> {
 
     let color = io::stderr().is_terminal();
 
-    let pid = if pids.len() == 1 {
+    let pid = if let Some(pid) = select.pid {
+        if !pids.contains(&pid) {
+            bail!("PID {pid} is not a running FEX process");
+        }
+        pid
+    } else if let Some(index) = select.index {
+        let ordered = print_process_tree(&pids, color);
+        let Some(&pid) = ordered.get(index) else {
+            bail!(
+                "index {index} is out of range ({} FEX processes found)",
+                ordered.len()
+            );
+        };
+        pid
+    } else if pids.len() == 1 {
         let pid = pids[0];
         let cmdline = read_process_cmdline(pid);
         if color {
@@ -654,12 +2471,22 @@ fn cmd_pick(sample_period_ms: u64, record_path: Option<&Path>) -> Result<()> {
             eprintln!("Only one FEX process found: PID {pid}  {cmdline}");
         }
         pid
-    } else {
+    } else if io::stdin().is_terminal() {
         let ordered = print_process_tree(&pids, color);
         prompt_selection(&ordered)?
+    } else {
+        bail!(
+            "{} FEX processes found and stdin is not a terminal; choose one with --index or --pid",
+            pids.len()
+        );
     };
+    info!(pid, candidates = pids.len(), "picked FEX process");
 
-    cmd_live(pid, sample_period_ms, record_path)
+    cmd_live(
+        pid,
+        &LiveOptions::new(sample_period_ms, record_path),
+        display,
+    )
 }
 
 fn print_process_tree(pids: &[i32], color: bool) -> Vec<i32> {
@@ -789,87 +2616,297 @@ fn prompt_selection(pids: &[i32]) -> Result<i32> {
 // Export subcommand
 // ---------------------------------------------------------------------------
 
-fn cmd_export(input: &Path, output: &Path) -> Result<()> {
-    let reader = RecordingReader::open(input)?;
-    let total = reader.frame_count();
+/// Frame selection for `export`, either by index or by time in seconds.
+#[derive(Args, Clone, Copy)]
+struct ExportWindow {
+    /// First frame to export (inclusive)
+    #[arg(long, conflicts_with = "start_time")]
+    start: Option<usize>,
+    /// Last frame to export (inclusive)
+    #[arg(long, conflicts_with = "end_time")]
+    end: Option<usize>,
+    /// Start of the export window, in seconds from the recording start
+    #[arg(long)]
+    start_time: Option<f64>,
+    /// End of the export window, in seconds from the recording start
+    #[arg(long)]
+    end_time: Option<f64>,
+}
 
-    let mut out = std::fs::File::create(output)
-        .with_context(|| format!("failed to create {}", output.display()))?;
+/// Which columns `export` writes.
+#[derive(Args, Clone, Copy)]
+struct ExportLayout {
+    /// Write a CSV with one load column per thread instead of the
+    /// aggregate columns. Only threads kept in each frame appear; record
+    /// with a larger --top to keep more
+    #[arg(long)]
+    per_thread: bool,
+    /// Write a CSV of thread activity summed over every recorded thread,
+    /// next to the sums over the threads kept in each frame, so busy
+    /// threads beyond --top still count
+    #[arg(long, conflicts_with = "per_thread")]
+    all_threads: bool,
+}
+
+fn cmd_export(
+    input: &Path,
+    output: &Path,
+    format: ExportFormat,
+    window: ExportWindow,
+    layout: ExportLayout,
+    measurement: &str,
+) -> Result<()> {
+    let ExportLayout {
+        per_thread,
+        all_threads,
+    } = layout;
+    if per_thread && format != ExportFormat::Csv {
+        bail!("--per-thread always writes CSV; leave out --format");
+    }
+    if all_threads && format != ExportFormat::Csv {
+        bail!("--all-threads always writes CSV; leave out --format");
+    }
+    if measurement.is_empty() {
+        bail!("--measurement must not be empty");
+    }
+    let mut frames = RecordingReader::frames_streaming(input)?;
+    let metadata = frames.metadata().clone();
+    let mut stream = frames.by_ref().peekable();
+
+    let sample_period_ns = match stream.peek() {
+        Some(Ok(f)) => f.computed.sample_period_ns,
+        _ => 0,
+    };
+    let start = window.start.or_else(|| {
+        window
+            .start_time
+            .map(|t| export::seconds_to_frame(t, sample_period_ns))
+    });
+    let end = window.end.or_else(|| {
+        window
+            .end_time
+            .map(|t| export::seconds_to_frame(t, sample_period_ns))
+    });
+    let window = export::FrameWindow {
+        start: start.unwrap_or(0),
+        end,
+    };
 
-    write_csv_header(&mut out)?;
+    let file = std::fs::File::create(output)
+        .with_context(|| format!("failed to create {}", output.display()))?;
+    let out = io::BufWriter::new(file);
+    let mut sink: Box<dyn FrameSink> = if per_thread {
+        Box::new(ThreadMatrixSink::new(out, metadata.recording_start))
+    } else if all_threads {
+        Box::new(AllThreadsSink::new(out, &metadata)?)
+    } else {
+        export::create_sink(format, out, &metadata, measurement)?
+    };
 
-    for i in 0..total {
-        if let Some(frame) = reader.frame_at(i) {
-            write_csv_row(&mut out, i, &frame.computed)?;
+    let mut total = 0;
+    let mut exported = 0;
+    let mut last = None;
+    for (i, frame) in stream.enumerate() {
+        if window.is_past(i) {
+            break;
+        }
+        let frame = frame?;
+        total = i + 1;
+        if window.contains(i) {
+            sink.write_recorded(i, &frame)?;
+            exported += 1;
+        } else {
+            last = Some(frame);
         }
     }
 
+    // A start beyond the end of the recording clamps to the last frame.
+    if exported == 0
+        && window.start >= total
+        && let Some(frame) = last
+    {
+        sink.write_recorded(total - 1, &frame)?;
+        exported = 1;
+    }
+    sink.finish()?;
+
+    if frames.truncated() {
+        eprintln!(
+            "warning: {} was cut short; its last frames are missing",
+            input.display()
+        );
+    }
+
     eprintln!(
-        "Exported {total} frames from {} to {}",
+        "Exported {exported} frames from {} to {}",
         input.display(),
         output.display()
     );
     Ok(())
 }
 
-fn write_csv_header(out: &mut impl Write) -> Result<()> {
-    writeln!(
-        out,
-        "frame,timestamp_ns,sample_period_ns,threads_sampled,\
-         total_jit_time,total_signal_time,total_sigbus_count,\
-         total_smc_count,total_float_fallback_count,\
-         total_cache_miss_count,total_cache_read_lock_time,\
-         total_cache_write_lock_time,total_jit_count,\
-         total_jit_invocations,fex_load_percent,\
-         mem_total_anon,mem_jit_code,mem_op_dispatcher,\
-         mem_frontend,mem_cpu_backend,mem_lookup,mem_lookup_l1,\
-         mem_thread_states,mem_block_links,mem_misc,\
-         mem_jemalloc,mem_unaccounted,\
-         cum_sigbus_count,cum_smc_count,cum_float_fallback_count,\
-         cum_cache_miss_count,cum_jit_count"
-    )
-    .context("failed to write CSV header")
+// ---------------------------------------------------------------------------
+// Info subcommand
+// ---------------------------------------------------------------------------
+
+#[allow(clippy::cast_precision_loss)]
+fn cmd_info(path: &Path) -> Result<()> {
+    let mut stream = RecordingReader::frames_streaming(path)?;
+    let metadata = stream.metadata().clone();
+    let file_size = std::fs::metadata(path)
+        .with_context(|| format!("failed to stat {}", path.display()))?
+        .len();
+
+    let mut summary = Summary::default();
+    for frame in stream.by_ref() {
+        summary.add(&frame?.computed);
+    }
+
+    println!("Recording:        {}", path.display());
+    println!("  File size:      {:.1} KB", file_size as f64 / 1024.0);
+    println!("  PID:            {}", metadata.pid);
+    println!("  FEX version:    {}", metadata.fex_version);
+    println!("  App type:       {}", metadata.app_type);
+    println!("  Stats version:  {}", metadata.stats_version);
+    println!("  Cycle freq:     {} Hz", metadata.cycle_counter_frequency);
+    println!("  HW concurrency: {}", metadata.hardware_concurrency);
+    println!(
+        "  Started:        {}",
+        wallclock::format_rfc3339(metadata.recording_start)
+    );
+    if stream.truncated() {
+        println!(
+            "  Frames:         {} (cut short: no EOF marker)",
+            summary.frames
+        );
+    } else {
+        println!("  Frames:         {}", summary.frames);
+    }
+    println!(
+        "  Duration:       {:.1} s",
+        summary.duration_ns as f64 / 1_000_000_000.0
+    );
+    if summary.frames > 0 {
+        println!(
+            "  FEX load:       min {:.2}%  max {:.2}%  mean {:.2}%",
+            summary.min_load,
+            summary.max_load,
+            summary.mean_load()
+        );
+    }
+    Ok(())
 }
 
-fn write_csv_row(
-    out: &mut impl Write,
-    index: usize,
-    f: &sampler::accumulator::ComputedFrame,
-) -> Result<()> {
-    writeln!(
-        out,
-        "{index},{},{},{},{},{},{},{},{},{},{},{},{},{},{:.4},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
-        f.timestamp_ns,
-        f.sample_period_ns,
-        f.threads_sampled,
-        f.total_jit_time,
-        f.total_signal_time,
-        f.total_sigbus_count,
-        f.total_smc_count,
-        f.total_float_fallback_count,
-        f.total_cache_miss_count,
-        f.total_cache_read_lock_time,
-        f.total_cache_write_lock_time,
-        f.total_jit_count,
-        f.total_jit_invocations,
-        f.fex_load_percent,
-        f.mem.total_anon,
-        f.mem.jit_code,
-        f.mem.op_dispatcher,
-        f.mem.frontend,
-        f.mem.cpu_backend,
-        f.mem.lookup,
-        f.mem.lookup_l1,
-        f.mem.thread_states,
-        f.mem.block_links,
-        f.mem.misc,
-        f.mem.jemalloc,
-        f.mem.unaccounted,
-        f.cumulative.sigbus,
-        f.cumulative.smc,
-        f.cumulative.float_fallback,
-        f.cumulative.cache_miss,
-        f.cumulative.jit,
-    )
-    .context("failed to write CSV row")
+// ---------------------------------------------------------------------------
+// Merge subcommand
+// ---------------------------------------------------------------------------
+
+fn cmd_merge(inputs: &[PathBuf], output: &Path, force: bool) -> Result<()> {
+    let written = recording::transform::merge(inputs, output, force)?;
+    eprintln!(
+        "Merged {} recordings ({written} frames) into {}",
+        inputs.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Stats subcommand
+// ---------------------------------------------------------------------------
+
+fn cmd_stats(path: &Path, json: bool) -> Result<()> {
+    let frames = RecordingReader::frames_streaming(path)?
+        .map(|frame| frame.map(|f| f.computed))
+        .collect::<Result<Vec<_>>>()?;
+    let report = stats::compute(&frames);
+
+    if json {
+        let out = serde_json::to_string_pretty(&report).context("failed to serialize stats")?;
+        println!("{out}");
+    } else {
+        println!("Recording: {}", path.display());
+        print!("{report}");
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Diff subcommand
+// ---------------------------------------------------------------------------
+
+fn cmd_diff(baseline: &Path, candidate: &Path, json: bool) -> Result<()> {
+    let computed = |path| {
+        RecordingReader::frames_streaming(path)
+            .map(|stream| stream.map(|frame| frame.map(|f| f.computed)))
+    };
+    let report = diff::diff_frames(computed(baseline)?, computed(candidate)?)?;
+
+    if json {
+        let out = serde_json::to_string_pretty(&report).context("failed to serialize diff")?;
+        println!("{out}");
+    } else {
+        println!("Baseline:  {}", baseline.display());
+        println!("Candidate: {}", candidate.display());
+        print!("{report}");
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Check subcommand
+// ---------------------------------------------------------------------------
+
+fn cmd_check(input: &Path, thresholds: &Thresholds, junit: Option<&Path>) -> Result<()> {
+    let mut summary = Summary::default();
+    for frame in RecordingReader::frames_streaming(input)? {
+        summary.add(&frame?.computed);
+    }
+    let results = check::evaluate(thresholds, &summary);
+
+    for result in &results {
+        let verdict = if result.passed { "PASS" } else { "FAIL" };
+        println!(
+            "{verdict}  {:<16} {} (limit {})",
+            result.name, result.value, result.limit
+        );
+    }
+    if let Some(path) = junit {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("failed to create JUnit report: {}", path.display()))?;
+        check::write_junit(
+            io::BufWriter::new(file),
+            &input.display().to_string(),
+            &results,
+        )?;
+    }
+
+    let failed: Vec<&str> = results
+        .iter()
+        .filter(|r| !r.passed)
+        .map(|r| r.name)
+        .collect();
+    if !failed.is_empty() {
+        bail!(
+            "{} of {} checks failed: {}",
+            failed.len(),
+            results.len(),
+            failed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Downsample subcommand
+// ---------------------------------------------------------------------------
+
+fn cmd_downsample(input: &Path, output: &Path, factor: usize) -> Result<()> {
+    let written = recording::transform::downsample(input, output, factor)?;
+    eprintln!(
+        "Downsampled {} by {factor}x to {} ({written} frames)",
+        input.display(),
+        output.display()
+    );
+    Ok(())
 }
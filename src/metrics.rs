@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: MIT
+//! Minimal HTTP endpoint exposing the latest frame in Prometheus text format.
+
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::sampler::accumulator::ComputedFrame;
+
+/// Latest frame shared between the sampling loop and the metrics server.
+pub type SharedFrame = Arc<Mutex<ComputedFrame>>;
+
+/// How long a client may take to send its request or read the response
+/// before its connection is dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Binds `addr` and serves `/metrics` from a background thread. Every request
+/// path returns the same payload. Returns the bound address, which differs
+/// from `addr` when port 0 is requested.
+///
+/// # Errors
+///
+/// Returns an error if the address cannot be bound or the thread cannot be
+/// spawned.
+pub fn spawn(addr: SocketAddr, latest: SharedFrame) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)
+        .with_context(|| format!("failed to bind metrics address {addr}"))?;
+    let bound = listener
+        .local_addr()
+        .context("failed to query metrics address")?;
+
+    thread::Builder::new()
+        .name("felix-metrics".into())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A misbehaving client must not take the server down, nor
+                // hold up the scrapes behind it, so each connection gets its
+                // own thread and a deadline.
+                let latest = Arc::clone(&latest);
+                let _ = thread::Builder::new()
+                    .name("felix-metrics-client".into())
+                    .spawn(move || {
+                        let _ = serve(&stream, &latest);
+                    });
+            }
+        })
+        .context("failed to spawn metrics thread")?;
+
+    Ok(bound)
+}
+
+fn serve(stream: &TcpStream, latest: &SharedFrame) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    // Drain the request head; the response does not depend on it.
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" && line != "\n" {
+        line.clear();
+    }
+
+    let body = {
+        let frame = latest
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        render(&frame)
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Renders `frame` in the Prometheus text exposition format.
+#[must_use]
+pub fn render(frame: &ComputedFrame) -> String {
+    let mut out = String::new();
+
+    metric_header(
+        &mut out,
+        "felix_fex_load_percent",
+        "gauge",
+        "FEX JIT load as a percentage of available cores",
+    );
+    let _ = writeln!(out, "felix_fex_load_percent {}", frame.fex_load_percent);
+
+    metric_header(
+        &mut out,
+        "felix_thread_load_percent",
+        "gauge",
+        "Per-thread JIT load percentage",
+    );
+    for tl in &frame.thread_loads {
+        let _ = writeln!(
+            out,
+            "felix_thread_load_percent{{tid=\"{}\"}} {}",
+            tl.tid, tl.load_percent
+        );
+    }
+
+    metric_header(
+        &mut out,
+        "felix_memory_bytes",
+        "gauge",
+        "Resident memory per FEX region",
+    );
+    for (region, bytes) in frame.mem.regions() {
+        let _ = writeln!(out, "felix_memory_bytes{{region=\"{region}\"}} {bytes}");
+    }
+
+    let c = &frame.cumulative;
+    for (name, help, value) in [
+        ("felix_sigbus_total", "SIGBUS events", c.sigbus),
+        ("felix_smc_total", "Self-modifying code events", c.smc),
+        (
+            "felix_softfloat_total",
+            "Softfloat fallback operations",
+            c.float_fallback,
+        ),
+    ] {
+        metric_header(&mut out, name, "counter", help);
+        let _ = writeln!(out, "{name} {value}");
+    }
+
+    out
+}
+
+/// Writes the `# HELP` and `# TYPE` lines that precede metric `name`.
+pub fn metric_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+    use crate::sampler::accumulator::{CumulativeCountStats, ThreadLoad};
+
+    fn sample_frame() -> ComputedFrame {
+        let mut frame = ComputedFrame {
+            fex_load_percent: 42.5,
+            thread_loads: vec![ThreadLoad {
+                tid: 7,
+                load_percent: 12.0,
+                total_cycles: 100,
+            }],
+            cumulative: CumulativeCountStats {
+                sigbus: 3,
+                smc: 4,
+                float_fallback: 5,
+                ..CumulativeCountStats::default()
+            },
+            ..ComputedFrame::default()
+        };
+        frame.mem.jit_code = 4096;
+        frame
+    }
+
+    #[test]
+    fn render_includes_gauges_and_counters() {
+        let text = render(&sample_frame());
+        assert!(
+            text.contains("# TYPE felix_fex_load_percent gauge\nfelix_fex_load_percent 42.5\n")
+        );
+        assert!(text.contains("felix_thread_load_percent{tid=\"7\"} 12\n"));
+        assert!(text.contains("felix_memory_bytes{region=\"jit_code\"} 4096\n"));
+        assert!(text.contains("# TYPE felix_sigbus_total counter\nfelix_sigbus_total 3\n"));
+        assert!(text.contains("felix_smc_total 4\n"));
+        assert!(text.contains("felix_softfloat_total 5\n"));
+    }
+
+    fn scrape(addr: SocketAddr) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn server_returns_latest_frame() {
+        let latest: SharedFrame = Arc::new(Mutex::new(ComputedFrame::default()));
+        let addr = spawn("127.0.0.1:0".parse().unwrap(), Arc::clone(&latest)).unwrap();
+        *latest.lock().unwrap() = sample_frame();
+
+        let response = scrape(addr);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("felix_fex_load_percent 42.5"));
+    }
+
+    #[test]
+    fn idle_client_does_not_stall_other_scrapes() {
+        let latest: SharedFrame = Arc::new(Mutex::new(sample_frame()));
+        let addr = spawn("127.0.0.1:0".parse().unwrap(), latest).unwrap();
+
+        let _idle = TcpStream::connect(addr).unwrap();
+        let (done, finished) = std::sync::mpsc::channel();
+        thread::spawn(move || done.send(scrape(addr)).unwrap());
+        // Well short of CLIENT_TIMEOUT, so only a concurrent server passes.
+        let response = finished.recv_timeout(Duration::from_secs(2)).unwrap();
+        assert!(response.contains("felix_fex_load_percent 42.5"));
+    }
+}
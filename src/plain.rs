@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: MIT
+//! One-line-per-sample text output for `live --plain`.
+
+use crate::sampler::accumulator::ComputedFrame;
+use crate::tui::panels::mem_stats::format_bytes;
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Formats `frame` as a single line: time since the first sample, FEX load,
+/// the busiest thread, resident anonymous memory, and SIGBUS and SMC counts.
+/// With `color`, the load is colored using the histogram's thresholds.
+#[must_use]
+pub fn format_line(frame: &ComputedFrame, color: bool) -> String {
+    #[allow(clippy::cast_precision_loss)]
+    let secs = frame.timestamp_ns as f64 / 1e9;
+    let load = format!("{:6.2}%", frame.fex_load_percent);
+    let load = if color {
+        let code = if frame.fex_load_percent >= 75.0 {
+            RED
+        } else if frame.fex_load_percent >= 50.0 {
+            YELLOW
+        } else {
+            GREEN
+        };
+        format!("{code}{load}{RESET}")
+    } else {
+        load
+    };
+    let top = frame.thread_loads.first().map_or_else(
+        || "-".to_string(),
+        |t| format!("{} {:.1}%", t.tid, t.load_percent),
+    );
+    format!(
+        "{secs:9.1}s  load {load}  top {top}  anon {}  sigbus {}  smc {}",
+        format_bytes(frame.mem.total_anon),
+        frame.total_sigbus_count,
+        frame.total_smc_count,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::accumulator::ThreadLoad;
+
+    #[test]
+    fn formats_one_line_per_frame() {
+        let mut frame = ComputedFrame {
+            timestamp_ns: 12_500_000_000,
+            fex_load_percent: 80.0,
+            total_sigbus_count: 3,
+            thread_loads: vec![ThreadLoad {
+                tid: 42,
+                load_percent: 61.32,
+                total_cycles: 0,
+            }],
+            ..ComputedFrame::default()
+        };
+        frame.mem.total_anon = 512 << 20;
+
+        assert_eq!(
+            format_line(&frame, false),
+            "     12.5s  load  80.00%  top 42 61.3%  anon 512 MiB  sigbus 3  smc 0"
+        );
+        assert!(format_line(&frame, true).contains("\x1b[31m 80.00%\x1b[0m"));
+
+        frame.thread_loads.clear();
+        assert!(format_line(&frame, false).contains("top -"));
+    }
+}
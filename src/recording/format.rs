@@ -2,21 +2,100 @@
 use serde::{Deserialize, Serialize};
 
 use crate::datasource::SessionMetadata;
-use crate::fex::smaps::MemSnapshot;
+use crate::fex::smaps::{LargestAnon, MemSnapshot};
 use crate::sampler::accumulator::{
-    ComputedFrame, CumulativeCountStats, HistogramEntry, ThreadLoad,
+    ComputedFrame, CumulativeCountStats, HistogramEntry, RatesPerSecond, ThreadLoad,
 };
 use crate::sampler::thread_stats::ThreadDelta;
 
 pub const MAGIC: [u8; 4] = *b"FLXR";
-pub const FORMAT_VERSION: u8 = 2;
+pub const FORMAT_VERSION: u8 = 9;
 pub const EOF_MARKER: [u8; 4] = *b"FEOF";
+/// Trailing magic identifying a frame index after the compressed stream.
+pub const INDEX_MAGIC: [u8; 4] = *b"FIDX";
+/// Number of frames per independently-decodable zstd frame (v3+), or per
+/// seekable run of an uncompressed recording.
+pub const FRAMES_PER_CHUNK: u64 = 256;
+
+/// Leading bytes of every zstd frame, which is how a compressed recording
+/// starts.
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileHeader {
     pub magic: [u8; 4],
     pub format_version: u8,
     pub metadata: SessionMetadata,
+    /// False for `--no-compress` recordings (v9+), whose header and frames
+    /// are stored without zstd.
+    pub compressed: bool,
+}
+
+/// File header layout before v9, when every recording was compressed.
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct LegacyFileHeader {
+    pub magic: [u8; 4],
+    pub format_version: u8,
+    pub metadata: SessionMetadata,
+}
+
+impl From<LegacyFileHeader> for FileHeader {
+    fn from(legacy: LegacyFileHeader) -> Self {
+        Self {
+            magic: legacy.magic,
+            format_version: legacy.format_version,
+            metadata: legacy.metadata,
+            compressed: true,
+        }
+    }
+}
+
+/// Location of one independently-decodable zstd frame in the file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ChunkEntry {
+    /// Byte offset of the zstd frame from the start of the file.
+    pub offset: u64,
+    /// Index of the first recording frame stored in this chunk.
+    pub first_frame: u64,
+}
+
+/// Seek index written uncompressed after the zstd stream (v3+). The file ends
+/// with the postcard-encoded index, its `u32` length, then `INDEX_MAGIC`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FrameIndex {
+    pub frame_count: u64,
+    pub chunks: Vec<ChunkEntry>,
+    /// Bookmarks added during capture (v6+), in the order they were added.
+    pub markers: Vec<Marker>,
+}
+
+/// A bookmark the user dropped on a frame while recording.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    /// Index of the frame that was on screen when the marker was added.
+    pub frame_index: u64,
+    /// Timestamp of that frame, on the recording's timeline.
+    pub timestamp_ns: u64,
+    pub label: Option<String>,
+}
+
+/// Frame index layout before v6, without markers.
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct LegacyFrameIndex {
+    pub frame_count: u64,
+    pub chunks: Vec<ChunkEntry>,
+}
+
+impl From<LegacyFrameIndex> for FrameIndex {
+    fn from(legacy: LegacyFrameIndex) -> Self {
+        Self {
+            frame_count: legacy.frame_count,
+            chunks: legacy.chunks,
+            markers: Vec::new(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -25,8 +104,115 @@ pub struct Frame {
     pub per_thread_deltas: Vec<ThreadDelta>,
 }
 
+/// `MemSnapshot` as stored before v5, without Pss and Swap totals.
 #[derive(Deserialize)]
-pub struct LegacyComputedFrame {
+#[cfg_attr(test, derive(Serialize))]
+pub struct LegacyMemSnapshot {
+    pub total_anon: u64,
+    pub jit_code: u64,
+    pub op_dispatcher: u64,
+    pub frontend: u64,
+    pub cpu_backend: u64,
+    pub lookup: u64,
+    pub lookup_l1: u64,
+    pub thread_states: u64,
+    pub block_links: u64,
+    pub misc: u64,
+    pub jemalloc: u64,
+    pub unaccounted: u64,
+    pub largest_anon: LargestAnon,
+}
+
+impl From<LegacyMemSnapshot> for MemSnapshot {
+    fn from(legacy: LegacyMemSnapshot) -> Self {
+        Self {
+            total_anon: legacy.total_anon,
+            total_pss: 0,
+            total_swap: 0,
+            jit_code: legacy.jit_code,
+            op_dispatcher: legacy.op_dispatcher,
+            frontend: legacy.frontend,
+            cpu_backend: legacy.cpu_backend,
+            lookup: legacy.lookup,
+            lookup_l1: legacy.lookup_l1,
+            thread_states: legacy.thread_states,
+            block_links: legacy.block_links,
+            misc: legacy.misc,
+            jemalloc: legacy.jemalloc,
+            unaccounted: legacy.unaccounted,
+            largest_anon: legacy.largest_anon,
+        }
+    }
+}
+
+/// `ComputedFrame` as stored by v7, before counter reset counts.
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct V7ComputedFrame {
+    pub timestamp_ns: u64,
+    pub sample_period_ns: u64,
+    pub elapsed_ns: u64,
+    pub threads_sampled: usize,
+    pub total_jit_time: u64,
+    pub total_signal_time: u64,
+    pub total_sigbus_count: u64,
+    pub total_smc_count: u64,
+    pub total_float_fallback_count: u64,
+    pub total_cache_miss_count: u64,
+    pub total_cache_read_lock_time: u64,
+    pub total_cache_write_lock_time: u64,
+    pub total_jit_count: u64,
+    pub total_jit_invocations: u64,
+    pub fex_load_percent: f64,
+    pub thread_loads: Vec<ThreadLoad>,
+    pub mem: MemSnapshot,
+    pub histogram_entry: HistogramEntry,
+    pub cumulative: CumulativeCountStats,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct V7Frame {
+    pub computed: V7ComputedFrame,
+    pub per_thread_deltas: Vec<ThreadDelta>,
+}
+
+impl From<V7Frame> for Frame {
+    fn from(v7: V7Frame) -> Self {
+        let c = v7.computed;
+        Self {
+            computed: ComputedFrame {
+                timestamp_ns: c.timestamp_ns,
+                sample_period_ns: c.sample_period_ns,
+                elapsed_ns: c.elapsed_ns,
+                threads_sampled: c.threads_sampled,
+                total_jit_time: c.total_jit_time,
+                total_signal_time: c.total_signal_time,
+                total_sigbus_count: c.total_sigbus_count,
+                total_smc_count: c.total_smc_count,
+                total_float_fallback_count: c.total_float_fallback_count,
+                total_cache_miss_count: c.total_cache_miss_count,
+                total_cache_read_lock_time: c.total_cache_read_lock_time,
+                total_cache_write_lock_time: c.total_cache_write_lock_time,
+                total_jit_count: c.total_jit_count,
+                total_jit_invocations: c.total_jit_invocations,
+                fex_load_percent: c.fex_load_percent,
+                thread_loads: c.thread_loads,
+                mem: c.mem,
+                histogram_entry: c.histogram_entry,
+                cumulative: c.cumulative,
+                resets_detected: 0,
+                rates: RatesPerSecond::default(),
+            },
+            per_thread_deltas: v7.per_thread_deltas,
+        }
+    }
+}
+
+/// `ComputedFrame` as stored by v5 and v6, before the measured elapsed time.
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct V6ComputedFrame {
     pub timestamp_ns: u64,
     pub sample_period_ns: u64,
     pub threads_sampled: usize,
@@ -44,9 +230,135 @@ pub struct LegacyComputedFrame {
     pub thread_loads: Vec<ThreadLoad>,
     pub mem: MemSnapshot,
     pub histogram_entry: HistogramEntry,
+    pub cumulative: CumulativeCountStats,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct V6Frame {
+    pub computed: V6ComputedFrame,
+    pub per_thread_deltas: Vec<ThreadDelta>,
+}
+
+impl From<V6Frame> for Frame {
+    fn from(v6: V6Frame) -> Self {
+        let c = v6.computed;
+        Self {
+            computed: ComputedFrame {
+                timestamp_ns: c.timestamp_ns,
+                sample_period_ns: c.sample_period_ns,
+                elapsed_ns: c.sample_period_ns,
+                threads_sampled: c.threads_sampled,
+                total_jit_time: c.total_jit_time,
+                total_signal_time: c.total_signal_time,
+                total_sigbus_count: c.total_sigbus_count,
+                total_smc_count: c.total_smc_count,
+                total_float_fallback_count: c.total_float_fallback_count,
+                total_cache_miss_count: c.total_cache_miss_count,
+                total_cache_read_lock_time: c.total_cache_read_lock_time,
+                total_cache_write_lock_time: c.total_cache_write_lock_time,
+                total_jit_count: c.total_jit_count,
+                total_jit_invocations: c.total_jit_invocations,
+                fex_load_percent: c.fex_load_percent,
+                thread_loads: c.thread_loads,
+                mem: c.mem,
+                histogram_entry: c.histogram_entry,
+                cumulative: c.cumulative,
+                resets_detected: 0,
+                rates: RatesPerSecond::default(),
+            },
+            per_thread_deltas: v6.per_thread_deltas,
+        }
+    }
+}
+
+/// `ComputedFrame` as stored by v2 through v4.
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct V4ComputedFrame {
+    pub timestamp_ns: u64,
+    pub sample_period_ns: u64,
+    pub threads_sampled: usize,
+    pub total_jit_time: u64,
+    pub total_signal_time: u64,
+    pub total_sigbus_count: u64,
+    pub total_smc_count: u64,
+    pub total_float_fallback_count: u64,
+    pub total_cache_miss_count: u64,
+    pub total_cache_read_lock_time: u64,
+    pub total_cache_write_lock_time: u64,
+    pub total_jit_count: u64,
+    pub total_jit_invocations: u64,
+    pub fex_load_percent: f64,
+    pub thread_loads: Vec<ThreadLoad>,
+    pub mem: LegacyMemSnapshot,
+    pub histogram_entry: HistogramEntry,
+    pub cumulative: CumulativeCountStats,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct V4Frame {
+    pub computed: V4ComputedFrame,
+    pub per_thread_deltas: Vec<ThreadDelta>,
+}
+
+impl From<V4Frame> for Frame {
+    fn from(v4: V4Frame) -> Self {
+        let c = v4.computed;
+        Self {
+            computed: ComputedFrame {
+                timestamp_ns: c.timestamp_ns,
+                sample_period_ns: c.sample_period_ns,
+                elapsed_ns: c.sample_period_ns,
+                threads_sampled: c.threads_sampled,
+                total_jit_time: c.total_jit_time,
+                total_signal_time: c.total_signal_time,
+                total_sigbus_count: c.total_sigbus_count,
+                total_smc_count: c.total_smc_count,
+                total_float_fallback_count: c.total_float_fallback_count,
+                total_cache_miss_count: c.total_cache_miss_count,
+                total_cache_read_lock_time: c.total_cache_read_lock_time,
+                total_cache_write_lock_time: c.total_cache_write_lock_time,
+                total_jit_count: c.total_jit_count,
+                total_jit_invocations: c.total_jit_invocations,
+                fex_load_percent: c.fex_load_percent,
+                thread_loads: c.thread_loads,
+                mem: c.mem.into(),
+                histogram_entry: c.histogram_entry,
+                cumulative: c.cumulative,
+                resets_detected: 0,
+                rates: RatesPerSecond::default(),
+            },
+            per_thread_deltas: v4.per_thread_deltas,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+pub struct LegacyComputedFrame {
+    pub timestamp_ns: u64,
+    pub sample_period_ns: u64,
+    pub threads_sampled: usize,
+    pub total_jit_time: u64,
+    pub total_signal_time: u64,
+    pub total_sigbus_count: u64,
+    pub total_smc_count: u64,
+    pub total_float_fallback_count: u64,
+    pub total_cache_miss_count: u64,
+    pub total_cache_read_lock_time: u64,
+    pub total_cache_write_lock_time: u64,
+    pub total_jit_count: u64,
+    pub total_jit_invocations: u64,
+    pub fex_load_percent: f64,
+    pub thread_loads: Vec<ThreadLoad>,
+    pub mem: LegacyMemSnapshot,
+    pub histogram_entry: HistogramEntry,
 }
 
 #[derive(Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
 pub struct LegacyFrame {
     pub computed: LegacyComputedFrame,
     pub per_thread_deltas: Vec<ThreadDelta>,
@@ -59,6 +371,7 @@ impl From<LegacyFrame> for Frame {
             computed: ComputedFrame {
                 timestamp_ns: lc.timestamp_ns,
                 sample_period_ns: lc.sample_period_ns,
+                elapsed_ns: lc.sample_period_ns,
                 threads_sampled: lc.threads_sampled,
                 total_jit_time: lc.total_jit_time,
                 total_signal_time: lc.total_signal_time,
@@ -72,9 +385,11 @@ impl From<LegacyFrame> for Frame {
                 total_jit_invocations: lc.total_jit_invocations,
                 fex_load_percent: lc.fex_load_percent,
                 thread_loads: lc.thread_loads,
-                mem: lc.mem,
+                mem: lc.mem.into(),
                 histogram_entry: lc.histogram_entry,
                 cumulative: CumulativeCountStats::default(),
+                resets_detected: 0,
+                rates: RatesPerSecond::default(),
             },
             per_thread_deltas: legacy.per_thread_deltas,
         }
@@ -1,42 +1,38 @@
 // SPDX-License-Identifier: MIT
 pub mod format;
 pub mod reader;
+pub mod transform;
 pub mod writer;
 
 #[cfg(test)]
 mod tests {
-    use std::time::SystemTime;
+    use std::path::Path;
+    use std::time::{Duration, Instant, SystemTime};
 
-    use crate::datasource::SessionMetadata;
+    use crate::datasource::DataSource;
+    use crate::fex::smaps::LargestAnon;
     use crate::fex::smaps::MemSnapshot;
-    use crate::fex::types::AppType;
-    use crate::recording::format::Frame;
-    use crate::recording::reader::RecordingReader;
-    use crate::recording::writer::RecordingWriter;
+    use crate::recording::format::{
+        ChunkEntry, EOF_MARKER, FRAMES_PER_CHUNK, Frame, INDEX_MAGIC, LegacyComputedFrame,
+        LegacyFileHeader, LegacyFrame, LegacyFrameIndex, LegacyMemSnapshot, MAGIC, Marker,
+        V4ComputedFrame, V4Frame, V6ComputedFrame, V6Frame, V7ComputedFrame, V7Frame, ZSTD_MAGIC,
+    };
+    use crate::recording::reader::{RecordingReader, ReplaySource};
+    use crate::recording::transform;
+    use crate::recording::writer::{Compression, RecordingWriter, tagged_path};
     use crate::sampler::accumulator::{
-        ComputedFrame, CumulativeCountStats, HistogramEntry, ThreadLoad,
+        Accumulator, ComputedFrame, CumulativeCountStats, HistogramEntry, RatesPerSecond,
+        ThreadLoad,
     };
-    use crate::sampler::thread_stats::ThreadDelta;
-
-    fn make_metadata() -> SessionMetadata {
-        SessionMetadata {
-            pid: 1234,
-            fex_version: "FEX-2501".to_string(),
-            app_type: AppType::Linux64,
-            stats_version: 3,
-            cycle_counter_frequency: 1_000_000_000,
-            hardware_concurrency: 8,
-            recording_start: SystemTime::UNIX_EPOCH,
-            head: 0x40,
-            size: 0x1000,
-        }
-    }
+    use crate::sampler::thread_stats::{SampleResult, ThreadDelta};
+    use crate::testing::make_metadata;
 
     fn make_frame(index: u64) -> Frame {
         Frame {
             computed: ComputedFrame {
                 timestamp_ns: index * 1_000_000_000,
                 sample_period_ns: 500_000_000,
+                elapsed_ns: 500_000_000 + index * 1000,
                 threads_sampled: 2,
                 total_jit_time: 100 + index,
                 total_signal_time: 50 + index,
@@ -76,6 +72,8 @@ mod tests {
                     cache_miss: 4000 + index,
                     jit: 5000 + index,
                 },
+                resets_detected: index % 3,
+                rates: RatesPerSecond::default(),
             },
             per_thread_deltas: vec![
                 ThreadDelta {
@@ -96,6 +94,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::too_many_lines)]
     fn round_trip_write_then_read() {
         let dir = std::env::temp_dir().join("felix_recording_test");
         std::fs::create_dir_all(&dir).unwrap();
@@ -105,14 +104,15 @@ mod tests {
         let frames: Vec<Frame> = (0..5).map(make_frame).collect();
 
         {
-            let mut writer = RecordingWriter::create(&path, &metadata).unwrap();
+            let mut writer =
+                RecordingWriter::create(&path, &metadata, Compression::DEFAULT).unwrap();
             for frame in &frames {
                 writer.write_frame(frame).unwrap();
             }
             writer.finish().unwrap();
         }
 
-        let reader = RecordingReader::open(&path).unwrap();
+        let mut reader = RecordingReader::open(&path).unwrap();
 
         assert_eq!(reader.metadata().pid, metadata.pid);
         assert_eq!(reader.metadata().fex_version, metadata.fex_version);
@@ -164,6 +164,16 @@ mod tests {
                 (actual.computed.fex_load_percent - expected.computed.fex_load_percent).abs()
                     < f64::EPSILON
             );
+            assert_eq!(actual.computed.elapsed_ns, expected.computed.elapsed_ns);
+            assert_eq!(
+                actual.computed.resets_detected,
+                expected.computed.resets_detected
+            );
+            // Rates are not stored; the reader derives them on load.
+            assert_eq!(
+                actual.computed.rates,
+                RatesPerSecond::for_frame(&expected.computed)
+            );
 
             assert_eq!(
                 actual.computed.cumulative.sigbus,
@@ -223,6 +233,63 @@ mod tests {
         std::fs::remove_dir(&dir).ok();
     }
 
+    #[test]
+    fn accumulated_timing_round_trips() {
+        let dir = std::env::temp_dir().join("felix_recording_test_timing");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("timing_recording.felixr");
+
+        // Unevenly spaced samples, as when sampling falls behind.
+        let start = Instant::now();
+        let mut accumulator = Accumulator::new(1_000_000_000.0, 4);
+        let mut writer =
+            RecordingWriter::create(&path, &make_metadata(), Compression::DEFAULT).unwrap();
+        for ms in [0, 250, 600, 1000] {
+            let sample = SampleResult {
+                timestamp: start + Duration::from_millis(ms),
+                per_thread: &[],
+                threads_sampled: 0,
+                resets_detected: 0,
+            };
+            let computed = accumulator.compute_frame(
+                &sample,
+                &MemSnapshot::default(),
+                500_000_000,
+                0,
+                CumulativeCountStats::default(),
+            );
+            writer
+                .write_frame(&Frame {
+                    computed,
+                    per_thread_deltas: Vec::new(),
+                })
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let timing: Vec<(u64, u64)> = RecordingReader::frames_streaming(&path)
+            .unwrap()
+            .map(|frame| {
+                let computed = frame.unwrap().computed;
+                (computed.timestamp_ns, computed.elapsed_ns)
+            })
+            .collect();
+        // The first sample has no predecessor, so its elapsed time is the
+        // nominal period.
+        assert_eq!(
+            timing,
+            [
+                (0, 500_000_000),
+                (250_000_000, 250_000_000),
+                (600_000_000, 350_000_000),
+                (1_000_000_000, 400_000_000)
+            ]
+        );
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
     #[test]
     fn empty_recording_round_trip() {
         let dir = std::env::temp_dir().join("felix_recording_test_empty");
@@ -232,15 +299,753 @@ mod tests {
         let metadata = make_metadata();
 
         {
-            let writer = RecordingWriter::create(&path, &metadata).unwrap();
+            let writer = RecordingWriter::create(&path, &metadata, Compression::DEFAULT).unwrap();
             writer.finish().unwrap();
         }
 
-        let reader = RecordingReader::open(&path).unwrap();
+        let mut reader = RecordingReader::open(&path).unwrap();
         assert_eq!(reader.frame_count(), 0);
         assert!(reader.frame_at(0).is_none());
 
         std::fs::remove_file(&path).ok();
         std::fs::remove_dir(&dir).ok();
     }
+
+    #[test]
+    fn streaming_read_matches_full_read() {
+        let dir = std::env::temp_dir().join("felix_recording_test_stream");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stream_recording.felixr");
+
+        let metadata = make_metadata();
+        {
+            let mut writer =
+                RecordingWriter::create(&path, &metadata, Compression::DEFAULT).unwrap();
+            for i in 0..4 {
+                writer.write_frame(&make_frame(i)).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+
+        let stream = RecordingReader::frames_streaming(&path).unwrap();
+        assert_eq!(stream.metadata().pid, metadata.pid);
+        let streamed: Vec<Frame> = stream.map(Result::unwrap).collect();
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(streamed.len(), reader.frame_count());
+        for (i, frame) in streamed.iter().enumerate() {
+            let expected = reader.frame_at(i).unwrap();
+            assert_eq!(frame.computed.timestamp_ns, expected.computed.timestamp_ns);
+            assert_eq!(
+                frame.computed.total_jit_time,
+                expected.computed.total_jit_time
+            );
+        }
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    /// Re-encodes `frame` in the v5/v6 layout, without `elapsed_ns`.
+    fn v6_frame(frame: Frame) -> V6Frame {
+        let c = frame.computed;
+        V6Frame {
+            computed: V6ComputedFrame {
+                timestamp_ns: c.timestamp_ns,
+                sample_period_ns: c.sample_period_ns,
+                threads_sampled: c.threads_sampled,
+                total_jit_time: c.total_jit_time,
+                total_signal_time: c.total_signal_time,
+                total_sigbus_count: c.total_sigbus_count,
+                total_smc_count: c.total_smc_count,
+                total_float_fallback_count: c.total_float_fallback_count,
+                total_cache_miss_count: c.total_cache_miss_count,
+                total_cache_read_lock_time: c.total_cache_read_lock_time,
+                total_cache_write_lock_time: c.total_cache_write_lock_time,
+                total_jit_count: c.total_jit_count,
+                total_jit_invocations: c.total_jit_invocations,
+                fex_load_percent: c.fex_load_percent,
+                thread_loads: c.thread_loads,
+                mem: c.mem,
+                histogram_entry: c.histogram_entry,
+                cumulative: c.cumulative,
+            },
+            per_thread_deltas: frame.per_thread_deltas,
+        }
+    }
+
+    /// Re-encodes `frame` in the v7 layout, without `resets_detected`.
+    fn v7_frame(frame: Frame) -> V7Frame {
+        let c = frame.computed;
+        V7Frame {
+            computed: V7ComputedFrame {
+                timestamp_ns: c.timestamp_ns,
+                sample_period_ns: c.sample_period_ns,
+                elapsed_ns: c.elapsed_ns,
+                threads_sampled: c.threads_sampled,
+                total_jit_time: c.total_jit_time,
+                total_signal_time: c.total_signal_time,
+                total_sigbus_count: c.total_sigbus_count,
+                total_smc_count: c.total_smc_count,
+                total_float_fallback_count: c.total_float_fallback_count,
+                total_cache_miss_count: c.total_cache_miss_count,
+                total_cache_read_lock_time: c.total_cache_read_lock_time,
+                total_cache_write_lock_time: c.total_cache_write_lock_time,
+                total_jit_count: c.total_jit_count,
+                total_jit_invocations: c.total_jit_invocations,
+                fex_load_percent: c.fex_load_percent,
+                thread_loads: c.thread_loads,
+                mem: c.mem,
+                histogram_entry: c.histogram_entry,
+                cumulative: c.cumulative,
+            },
+            per_thread_deltas: frame.per_thread_deltas,
+        }
+    }
+
+    fn write_recording(path: &std::path::Path, count: u64) {
+        let mut writer =
+            RecordingWriter::create(path, &make_metadata(), Compression::DEFAULT).unwrap();
+        for i in 0..count {
+            writer.write_frame(&make_frame(i)).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    /// Writes an unindexed recording by hand with the given format version
+    /// and already-serialized frame payloads, as an older felix would have.
+    fn write_raw_recording(path: &Path, version: u8, payloads: &[Vec<u8>]) {
+        let header = postcard::to_stdvec(&LegacyFileHeader {
+            magic: MAGIC,
+            format_version: version,
+            metadata: make_metadata(),
+        })
+        .unwrap();
+
+        let mut raw = Vec::new();
+        #[allow(clippy::cast_possible_truncation)]
+        raw.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        raw.extend_from_slice(&header);
+        for payload in payloads {
+            #[allow(clippy::cast_possible_truncation)]
+            raw.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            if version >= 4 {
+                raw.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+            }
+            raw.extend_from_slice(payload);
+        }
+        raw.extend_from_slice(&EOF_MARKER);
+        std::fs::write(path, zstd::encode_all(raw.as_slice(), 3).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn reads_v1_frames_through_legacy_path() {
+        let dir = std::env::temp_dir().join("felix_recording_test_v1");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("v1_recording.felixr");
+
+        let payloads: Vec<Vec<u8>> = (0..2)
+            .map(|i| {
+                let frame = LegacyFrame {
+                    computed: LegacyComputedFrame {
+                        timestamp_ns: i * 1_000_000_000,
+                        sample_period_ns: 1_000_000_000,
+                        threads_sampled: 1,
+                        total_jit_time: 100 + i,
+                        total_signal_time: 0,
+                        total_sigbus_count: 2,
+                        total_smc_count: 0,
+                        total_float_fallback_count: 0,
+                        total_cache_miss_count: 0,
+                        total_cache_read_lock_time: 0,
+                        total_cache_write_lock_time: 0,
+                        total_jit_count: 0,
+                        total_jit_invocations: 0,
+                        fex_load_percent: 3.0,
+                        thread_loads: vec![],
+                        mem: LegacyMemSnapshot {
+                            total_anon: 8192,
+                            jit_code: 0,
+                            op_dispatcher: 0,
+                            frontend: 0,
+                            cpu_backend: 0,
+                            lookup: 0,
+                            lookup_l1: 0,
+                            thread_states: 0,
+                            block_links: 0,
+                            misc: 0,
+                            jemalloc: 0,
+                            unaccounted: 0,
+                            largest_anon: LargestAnon::default(),
+                        },
+                        histogram_entry: HistogramEntry::default(),
+                    },
+                    per_thread_deltas: vec![],
+                };
+                postcard::to_stdvec(&frame).unwrap()
+            })
+            .collect();
+        write_raw_recording(&path, 1, &payloads);
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count(), 2);
+        let frame = reader.frame_at(1).unwrap();
+        assert_eq!(frame.computed.total_jit_time, 101);
+        assert_eq!(frame.computed.mem.total_anon, 8192);
+        // v1 predates cumulative stats; they start out empty.
+        assert_eq!(frame.computed.cumulative.sigbus, 0);
+        assert!((frame.computed.rates.sigbus - 2.0).abs() < f64::EPSILON);
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn reads_v4_frames_without_pss() {
+        let dir = std::env::temp_dir().join("felix_recording_test_v4");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("v4_recording.felixr");
+
+        let frame = V4Frame {
+            computed: V4ComputedFrame {
+                timestamp_ns: 0,
+                sample_period_ns: 1_000_000_000,
+                threads_sampled: 1,
+                total_jit_time: 100,
+                total_signal_time: 0,
+                total_sigbus_count: 4,
+                total_smc_count: 0,
+                total_float_fallback_count: 0,
+                total_cache_miss_count: 0,
+                total_cache_read_lock_time: 0,
+                total_cache_write_lock_time: 0,
+                total_jit_count: 0,
+                total_jit_invocations: 0,
+                fex_load_percent: 1.5,
+                thread_loads: vec![],
+                mem: LegacyMemSnapshot {
+                    total_anon: 4096,
+                    jit_code: 1024,
+                    op_dispatcher: 0,
+                    frontend: 0,
+                    cpu_backend: 0,
+                    lookup: 0,
+                    lookup_l1: 0,
+                    thread_states: 0,
+                    block_links: 0,
+                    misc: 0,
+                    jemalloc: 0,
+                    unaccounted: 0,
+                    largest_anon: LargestAnon::default(),
+                },
+                histogram_entry: HistogramEntry::default(),
+                cumulative: CumulativeCountStats {
+                    sigbus: 40,
+                    ..CumulativeCountStats::default()
+                },
+            },
+            per_thread_deltas: vec![],
+        };
+        write_raw_recording(&path, 4, &[postcard::to_stdvec(&frame).unwrap()]);
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count(), 1);
+        let frame = reader.frame_at(0).unwrap();
+        assert_eq!(frame.computed.total_jit_time, 100);
+        assert_eq!(frame.computed.mem.total_anon, 4096);
+        assert_eq!(frame.computed.mem.jit_code, 1024);
+        assert_eq!(frame.computed.mem.total_pss, 0);
+        assert_eq!(frame.computed.cumulative.sigbus, 40);
+        assert!((frame.computed.rates.sigbus - 4.0).abs() < f64::EPSILON);
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn reads_v7_frames_without_reset_counts() {
+        let dir = std::env::temp_dir().join("felix_recording_test_v7");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("v7_recording.felixr");
+
+        let payloads: Vec<Vec<u8>> = (0..3)
+            .map(|i| postcard::to_stdvec(&v7_frame(make_frame(i))).unwrap())
+            .collect();
+        write_raw_recording(&path, 7, &payloads);
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count(), 3);
+        let frame = reader.frame_at(2).unwrap();
+        assert_eq!(frame.computed.total_jit_time, 102);
+        assert_eq!(frame.computed.elapsed_ns, 500_002_000);
+        assert_eq!(frame.computed.resets_detected, 0);
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn replay_source_plays_in_reverse_and_pauses_at_start() {
+        let dir = std::env::temp_dir().join("felix_recording_test_reverse");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("reverse_recording.felixr");
+        write_recording(&path, 4);
+
+        let mut source = ReplaySource::new(RecordingReader::open(&path).unwrap());
+        // Fast enough that every call is due.
+        source.set_speed(f64::MAX);
+        let mut next = || source.next_frame().map(|f| f.total_jit_time - 100);
+
+        assert_eq!(next(), Some(0));
+        assert_eq!(next(), Some(1));
+        assert_eq!(next(), Some(2));
+
+        source.set_reversed(true);
+        let mut next = || source.next_frame().map(|f| f.total_jit_time - 100);
+        assert_eq!(next(), Some(1));
+        assert_eq!(next(), Some(0));
+        assert_eq!(next(), None);
+        assert!(source.is_paused());
+        assert_eq!(source.current_index(), 1);
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn replay_source_loops_in_both_directions() {
+        let dir = std::env::temp_dir().join("felix_recording_test_loop");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("loop_recording.felixr");
+        write_recording(&path, 3);
+
+        let mut source = ReplaySource::new(RecordingReader::open(&path).unwrap());
+        source.set_speed(f64::MAX);
+        source.set_loop_playback(true);
+        let played: Vec<u64> = (0..5)
+            .map(|_| source.next_frame().unwrap().total_jit_time - 100)
+            .collect();
+        assert_eq!(played, vec![0, 1, 2, 0, 1]);
+        assert_eq!(source.current_index(), 2);
+
+        source.set_reversed(true);
+        let played: Vec<u64> = (0..3)
+            .map(|_| source.next_frame().unwrap().total_jit_time - 100)
+            .collect();
+        assert_eq!(played, vec![0, 2, 1]);
+        assert!(!source.is_paused());
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn indexed_random_access_across_chunks() {
+        let dir = std::env::temp_dir().join("felix_recording_test_index");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("indexed_recording.felixr");
+
+        let count = FRAMES_PER_CHUNK * 3 + 7;
+        write_recording(&path, count);
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count() as u64, count);
+
+        for i in [
+            count - 1,
+            0,
+            FRAMES_PER_CHUNK,
+            FRAMES_PER_CHUNK - 1,
+            2 * FRAMES_PER_CHUNK + 3,
+        ] {
+            let frame = reader.frame_at(usize::try_from(i).unwrap()).unwrap();
+            assert_eq!(frame.computed.total_jit_time, 100 + i);
+        }
+        assert!(reader.frame_at(usize::try_from(count).unwrap()).is_none());
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn compressed_size_grows_per_chunk() {
+        let dir = std::env::temp_dir().join("felix_recording_test_size");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("sized_recording.felixr");
+
+        let mut writer =
+            RecordingWriter::create(&path, &make_metadata(), Compression::DEFAULT).unwrap();
+        for i in 0..FRAMES_PER_CHUNK {
+            writer.write_frame(&make_frame(i)).unwrap();
+        }
+        let first_chunk = writer.compressed_size();
+        writer.write_frame(&make_frame(FRAMES_PER_CHUNK)).unwrap();
+        let after_chunk = writer.compressed_size();
+        assert!(after_chunk > first_chunk);
+        writer.finish().unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > after_chunk);
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn missing_index_falls_back_to_full_load() {
+        let dir = std::env::temp_dir().join("felix_recording_test_no_index");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("no_index_recording.felixr");
+
+        let count = FRAMES_PER_CHUNK + 5;
+        write_recording(&path, count);
+
+        // Chop off the trailer magic so the index is no longer recognised.
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 4);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count() as u64, count);
+        let last = reader
+            .frame_at(usize::try_from(count - 1).unwrap())
+            .unwrap();
+        assert_eq!(last.computed.total_jit_time, 100 + count - 1);
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn corrupted_frame_reports_checksum_mismatch() {
+        let dir = std::env::temp_dir().join("felix_recording_test_crc");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("crc_recording.felixr");
+        write_recording(&path, 3);
+
+        // Decompress the single chunk (dropping the index trailer), flip the
+        // last byte of the final frame's payload, and recompress it.
+        let bytes = std::fs::read(&path).unwrap();
+        let index_len =
+            u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap());
+        let stream_len = bytes.len() - 8 - index_len as usize;
+        let mut raw = zstd::decode_all(&bytes[..stream_len]).unwrap();
+        let last_payload_byte = raw.len() - EOF_MARKER.len() - 1;
+        raw[last_payload_byte] ^= 0xff;
+        std::fs::write(&path, zstd::encode_all(raw.as_slice(), 3).unwrap()).unwrap();
+
+        let err = RecordingReader::open(&path).err().unwrap();
+        let message = format!("{err:#}");
+        assert!(message.contains("frame 2"), "{message}");
+        assert!(message.contains("checksum mismatch"), "{message}");
+
+        let mut stream = RecordingReader::frames_streaming(&path).unwrap();
+        assert!(stream.next().unwrap().is_ok());
+        assert!(stream.next().unwrap().is_ok());
+        let err = stream.next().unwrap().unwrap_err();
+        assert!(format!("{err:#}").contains("frame 2"));
+        assert!(stream.next().is_none());
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn truncated_recording_keeps_complete_frames() {
+        let dir = std::env::temp_dir().join("felix_recording_test_truncated");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("truncated_recording.felixr");
+        write_recording(&path, 3);
+
+        // Recompress the stream without its index, EOF marker, and the tail
+        // of the final frame, as if felix had been killed mid-write.
+        let bytes = std::fs::read(&path).unwrap();
+        let index_len =
+            u32::from_le_bytes(bytes[bytes.len() - 8..bytes.len() - 4].try_into().unwrap());
+        let stream_len = bytes.len() - 8 - index_len as usize;
+        let raw = zstd::decode_all(&bytes[..stream_len]).unwrap();
+        for (cut, complete) in [(EOF_MARKER.len() + 5, 2), (EOF_MARKER.len(), 3)] {
+            let truncated = &raw[..raw.len() - cut];
+            std::fs::write(&path, zstd::encode_all(truncated, 3).unwrap()).unwrap();
+
+            let reader = RecordingReader::open(&path).unwrap();
+            assert!(reader.truncated());
+            assert_eq!(reader.frame_count(), complete);
+
+            let err = RecordingReader::open_strict(&path).err().unwrap();
+            assert!(format!("{err:#}").contains("cut short"), "{err:#}");
+
+            let mut stream = RecordingReader::frames_streaming(&path).unwrap();
+            assert_eq!(stream.by_ref().map(Result::unwrap).count(), complete);
+            assert!(stream.truncated());
+        }
+
+        write_recording(&path, 3);
+        assert!(!RecordingReader::open_strict(&path).unwrap().truncated());
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn uncompressed_recording_reads_and_appends_like_compressed() {
+        let dir = std::env::temp_dir().join("felix_recording_test_uncompressed");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("uncompressed_recording.felixr");
+
+        let count = FRAMES_PER_CHUNK + 7;
+        let mut writer =
+            RecordingWriter::create(&path, &make_metadata(), Compression::None).unwrap();
+        for i in 0..count {
+            writer.write_frame(&make_frame(i)).unwrap();
+        }
+        writer.finish().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(!bytes.starts_with(&ZSTD_MAGIC));
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.metadata().pid, make_metadata().pid);
+        assert_eq!(reader.frame_count() as u64, count);
+        for i in [count - 1, 0, FRAMES_PER_CHUNK] {
+            let frame = reader.frame_at(usize::try_from(i).unwrap()).unwrap();
+            assert_eq!(frame.computed.total_jit_time, 100 + i);
+        }
+        let streamed: Vec<u64> = RecordingReader::frames_streaming(&path)
+            .unwrap()
+            .map(|frame| frame.unwrap().computed.total_jit_time)
+            .collect();
+        assert_eq!(streamed, (100..100 + count).collect::<Vec<_>>());
+
+        // Appending keeps the file uncompressed whatever was asked for.
+        let mut writer =
+            RecordingWriter::append(&path, &make_metadata(), Compression::DEFAULT).unwrap();
+        writer.write_frame(&make_frame(count)).unwrap();
+        writer.finish().unwrap();
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count() as u64, count + 1);
+        let last = reader.frame_at(usize::try_from(count).unwrap()).unwrap();
+        assert_eq!(last.computed.total_jit_time, 100 + count);
+        assert!(!std::fs::read(&path).unwrap().starts_with(&ZSTD_MAGIC));
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn append_continues_existing_recording() {
+        let dir = std::env::temp_dir().join("felix_recording_test_append");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("append_recording.felixr");
+
+        for (first, second) in [(3, 4), (FRAMES_PER_CHUNK + 2, FRAMES_PER_CHUNK)] {
+            write_recording(&path, first);
+            {
+                let mut writer =
+                    RecordingWriter::append(&path, &make_metadata(), Compression::DEFAULT).unwrap();
+                assert_eq!(writer.frame_count(), first);
+                for i in 0..second {
+                    writer.write_frame(&make_frame(first + i)).unwrap();
+                }
+                writer.finish().unwrap();
+            }
+
+            let mut reader = RecordingReader::open(&path).unwrap();
+            let total = first + second;
+            assert_eq!(reader.frame_count() as u64, total);
+            let mut prev_ts = None;
+            for i in 0..total {
+                let frame = reader.frame_at(usize::try_from(i).unwrap()).unwrap();
+                assert_eq!(frame.computed.total_jit_time, 100 + i);
+                let ts = frame.computed.timestamp_ns;
+                assert!(prev_ts.is_none_or(|p| ts > p));
+                prev_ts = Some(ts);
+            }
+        }
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn markers_survive_finish_and_append() {
+        let dir = std::env::temp_dir().join("felix_recording_test_markers");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("markers.felixr");
+
+        let mut writer =
+            RecordingWriter::create(&path, &make_metadata(), Compression::DEFAULT).unwrap();
+        for i in 0..5 {
+            writer.write_frame(&make_frame(i)).unwrap();
+        }
+        writer.add_marker(Some("spike".to_string()));
+        writer.finish().unwrap();
+
+        let mut writer =
+            RecordingWriter::append(&path, &make_metadata(), Compression::DEFAULT).unwrap();
+        writer.write_frame(&make_frame(5)).unwrap();
+        writer.add_marker(None);
+        writer.finish().unwrap();
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        let markers = reader.markers().to_vec();
+        assert_eq!(markers.len(), 2);
+        assert_eq!(
+            markers[0],
+            Marker {
+                frame_index: 4,
+                timestamp_ns: reader.frame_at(4).unwrap().computed.timestamp_ns,
+                label: Some("spike".to_string()),
+            }
+        );
+        assert_eq!(markers[1].frame_index, 5);
+        assert_eq!(
+            markers[1].timestamp_ns,
+            reader.frame_at(5).unwrap().computed.timestamp_ns
+        );
+        assert_eq!(markers[1].label, None);
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn reads_v5_index_without_markers() {
+        let dir = std::env::temp_dir().join("felix_recording_test_v5_index");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("v5.felixr");
+
+        let payloads: Vec<Vec<u8>> = (0..3)
+            .map(|i| postcard::to_stdvec(&v6_frame(make_frame(i))).unwrap())
+            .collect();
+        write_raw_recording(&path, 5, &payloads);
+        let index = postcard::to_stdvec(&LegacyFrameIndex {
+            frame_count: 3,
+            chunks: vec![ChunkEntry {
+                offset: 0,
+                first_frame: 0,
+            }],
+        })
+        .unwrap();
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.extend_from_slice(&index);
+        bytes.extend_from_slice(&u32::try_from(index.len()).unwrap().to_le_bytes());
+        bytes.extend_from_slice(&INDEX_MAGIC);
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut reader = RecordingReader::open(&path).unwrap();
+        assert_eq!(reader.frame_count(), 3);
+        assert!(reader.markers().is_empty());
+        let frame = reader.frame_at(2).unwrap();
+        assert_eq!(frame.computed.total_jit_time, 102);
+        // Before v7 the nominal period stands in for the measured interval.
+        assert_eq!(frame.computed.elapsed_ns, frame.computed.sample_period_ns);
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn append_rejects_different_session() {
+        let dir = std::env::temp_dir().join("felix_recording_test_append_mismatch");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("append_mismatch.felixr");
+
+        write_recording(&path, 2);
+        let mut other = make_metadata();
+        other.pid = 4321;
+        let err = RecordingWriter::append(&path, &other, Compression::DEFAULT)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("cannot append"));
+
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn merge_orders_by_start_and_rebases_timestamps() {
+        let dir = std::env::temp_dir().join("felix_recording_test_merge");
+        std::fs::create_dir_all(&dir).unwrap();
+        let early = dir.join("early.felixr");
+        let late = dir.join("late.felixr");
+        let merged = dir.join("merged.felixr");
+
+        let mut late_metadata = make_metadata();
+        late_metadata.recording_start = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(60);
+        {
+            let mut writer =
+                RecordingWriter::create(&late, &late_metadata, Compression::DEFAULT).unwrap();
+            for i in 0..2 {
+                writer.write_frame(&make_frame(10 + i)).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        write_recording(&early, 3);
+
+        let written = transform::merge(&[late.clone(), early.clone()], &merged, false).unwrap();
+        assert_eq!(written, 5);
+
+        let mut reader = RecordingReader::open(&merged).unwrap();
+        assert_eq!(reader.metadata().recording_start, SystemTime::UNIX_EPOCH);
+        let frames: Vec<Frame> = (0..reader.frame_count())
+            .map(|i| reader.frame_at(i).unwrap().clone())
+            .collect();
+        let jit: Vec<u64> = frames.iter().map(|f| f.computed.total_jit_time).collect();
+        assert_eq!(jit, vec![100, 101, 102, 110, 111]);
+        let ts: Vec<u64> = frames.iter().map(|f| f.computed.timestamp_ns).collect();
+        // Early input spans 0..2s at 1s spacing, then ends one 0.5s period later.
+        assert_eq!(
+            ts,
+            vec![
+                0,
+                1_000_000_000,
+                2_000_000_000,
+                2_500_000_000,
+                3_500_000_000
+            ]
+        );
+
+        for path in [&early, &late, &merged] {
+            std::fs::remove_file(path).ok();
+        }
+        std::fs::remove_dir(&dir).ok();
+    }
+
+    #[test]
+    fn merge_rejects_mismatched_pid_unless_forced() {
+        let dir = std::env::temp_dir().join("felix_recording_test_merge_mismatch");
+        std::fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.felixr");
+        let b = dir.join("b.felixr");
+        let merged = dir.join("merged.felixr");
+
+        write_recording(&a, 1);
+        let mut other = make_metadata();
+        other.pid = 999;
+        {
+            let mut writer = RecordingWriter::create(&b, &other, Compression::DEFAULT).unwrap();
+            writer.write_frame(&make_frame(0)).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let inputs = [a.clone(), b.clone()];
+        let err = transform::merge(&inputs, &merged, false).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        assert_eq!(transform::merge(&inputs, &merged, true).unwrap(), 2);
+
+        for path in [&a, &b, &merged] {
+            std::fs::remove_file(path).ok();
+        }
+        std::fs::remove_dir(&dir).ok();
+    }
+    #[test]
+    fn tagged_path_inserts_pid_before_extension() {
+        assert_eq!(
+            tagged_path(Path::new("/tmp/session.felixr"), 42),
+            Path::new("/tmp/session-42.felixr")
+        );
+        assert_eq!(tagged_path(Path::new("out"), 7), Path::new("out-7"));
+        assert_eq!(
+            tagged_path(Path::new("run.csv"), "002"),
+            Path::new("run-002.csv")
+        );
+    }
 }
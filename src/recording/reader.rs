@@ -1,56 +1,256 @@
 // SPDX-License-Identifier: MIT
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 use std::time::Instant;
 
 use anyhow::{Context, Result, bail};
 
-use super::format::{EOF_MARKER, FORMAT_VERSION, MAGIC};
+use super::format::{
+    EOF_MARKER, FORMAT_VERSION, FrameIndex, INDEX_MAGIC, LegacyFileHeader, LegacyFrameIndex, MAGIC,
+    Marker, ZSTD_MAGIC,
+};
 use crate::datasource::{DataSource, SessionMetadata};
-use crate::recording::format::{FileHeader, Frame, LegacyFrame};
-use crate::sampler::accumulator::ComputedFrame;
+use crate::recording::format::{FileHeader, Frame, LegacyFrame, V4Frame, V6Frame, V7Frame};
+use crate::sampler::accumulator::{ComputedFrame, RatesPerSecond};
+
+/// First format version whose files may carry a trailing `FrameIndex`.
+const INDEXED_FORMAT_VERSION: u8 = 3;
+/// First format version whose frames carry a CRC32 after the length prefix.
+const CHECKSUM_FORMAT_VERSION: u8 = 4;
+/// First format version whose memory snapshots carry Pss and Swap totals.
+const PSS_FORMAT_VERSION: u8 = 5;
+/// First format version whose frame index carries markers.
+const MARKERS_FORMAT_VERSION: u8 = 6;
+/// First format version whose frames carry the measured elapsed time.
+const ELAPSED_FORMAT_VERSION: u8 = 7;
+/// First format version whose frames count counter resets.
+const RESETS_FORMAT_VERSION: u8 = 8;
+/// First format version whose header says whether the file is compressed.
+const COMPRESSION_FORMAT_VERSION: u8 = 9;
 
 pub struct RecordingReader {
     metadata: SessionMetadata,
-    frames: Vec<Frame>,
-    #[allow(dead_code)]
+    frames: FrameStore,
+    format_version: u8,
+    compressed: bool,
+    /// Whether the stream ended before its EOF marker, so only the complete
+    /// frames before that point were loaded.
+    truncated: bool,
+}
+
+/// The stream ended before its EOF marker, possibly partway through a
+/// frame, as happens when felix is killed before the recording is finished.
+#[derive(Debug)]
+pub struct Truncated;
+
+impl std::fmt::Display for Truncated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("recording ends without an EOF marker (was it cut short?)")
+    }
+}
+
+impl std::error::Error for Truncated {}
+
+/// Where frames come from: fully decoded up front, or decoded one chunk at a
+/// time through the seek index.
+enum FrameStore {
+    Loaded(Vec<Frame>),
+    Indexed(IndexedFrames),
+}
+
+struct IndexedFrames {
+    file: File,
+    index: FrameIndex,
     format_version: u8,
+    compressed: bool,
+    cached_chunk: Option<usize>,
+    chunk_frames: Vec<Frame>,
 }
 
 impl RecordingReader {
-    /// Opens a recording file, validates the header, and reads all frames.
+    /// Opens a recording file and validates the header. If the file carries a
+    /// frame index, frames are decoded on demand; otherwise all frames are
+    /// read into memory. A recording that was cut short keeps every complete
+    /// frame; check `truncated` to tell.
     ///
     /// # Errors
     ///
     /// Returns an error if the file cannot be opened, the header is invalid,
     /// or frame data is corrupted.
     pub fn open(path: &Path) -> Result<Self> {
-        let file = File::open(path)
-            .with_context(|| format!("failed to open recording file: {}", path.display()))?;
-        let buf_reader = BufReader::new(file);
-        let mut decoder =
-            zstd::Decoder::new(buf_reader).context("failed to create zstd decoder")?;
+        Self::open_with(path, false)
+    }
 
-        let header = Self::read_header(&mut decoder)?;
+    /// Like `open`, but a recording that was cut short is an error instead of
+    /// being loaded up to its last complete frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, the header is invalid,
+    /// or frame data is corrupted or truncated.
+    pub fn open_strict(path: &Path) -> Result<Self> {
+        Self::open_with(path, true)
+    }
 
-        if header.magic != MAGIC {
-            bail!("invalid magic bytes in recording file");
-        }
+    fn open_with(path: &Path, strict: bool) -> Result<Self> {
+        let (mut decoder, header) = Self::open_decoder(path)?;
         let version = header.format_version;
-        if version != 1 && version != FORMAT_VERSION {
-            bail!("unsupported format version {version} (expected 1 or {FORMAT_VERSION})",);
-        }
 
-        let frames = Self::read_all_frames(&mut decoder, version)?;
+        let index = if version >= INDEXED_FORMAT_VERSION {
+            read_frame_index(path, version)?
+        } else {
+            None
+        };
+
+        let (frames, truncated) = if let Some(index) = index {
+            let file = File::open(path)
+                .with_context(|| format!("failed to open recording file: {}", path.display()))?;
+            let indexed = IndexedFrames {
+                file,
+                index,
+                format_version: version,
+                compressed: header.compressed,
+                cached_chunk: None,
+                chunk_frames: Vec::new(),
+            };
+            (FrameStore::Indexed(indexed), false)
+        } else {
+            let (frames, truncated) = Self::read_all_frames(&mut decoder, version, strict)?;
+            (FrameStore::Loaded(frames), truncated)
+        };
 
         Ok(Self {
             metadata: header.metadata,
             frames,
             format_version: version,
+            compressed: header.compressed,
+            truncated,
         })
     }
 
+    /// Opens a recording for sequential reading. Frames are decoded lazily as
+    /// the returned stream is iterated, so memory use does not grow with the
+    /// length of the recording.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or the header is invalid.
+    /// Errors decoding individual frames are yielded by the iterator. A
+    /// recording that was cut short simply ends early; see
+    /// `FrameStream::truncated`.
+    pub fn frames_streaming(path: &Path) -> Result<FrameStream> {
+        let (decoder, header) = Self::open_decoder(path)?;
+        Ok(FrameStream {
+            decoder,
+            metadata: header.metadata,
+            format_version: header.format_version,
+            next_index: 0,
+            done: false,
+            truncated: false,
+        })
+    }
+
+    /// Decodes the frames of the final chunk and returns them with the index.
+    /// Used by `RecordingWriter::append` to rewrite the tail of the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recording has no frame index or the final
+    /// chunk cannot be decoded.
+    pub(super) fn last_chunk(&mut self) -> Result<(FrameIndex, Vec<Frame>)> {
+        let FrameStore::Indexed(indexed) = &mut self.frames else {
+            bail!(
+                "recording has no frame index (format v{INDEXED_FORMAT_VERSION}+ written by a clean finish is required)"
+            );
+        };
+        let last = indexed.index.chunks.len() - 1;
+        indexed.load_chunk(last)?;
+        Ok((
+            indexed.index.clone(),
+            std::mem::take(&mut indexed.chunk_frames),
+        ))
+    }
+
+    /// Bookmarks recorded during capture. Recordings loaded without a frame
+    /// index have none.
+    #[must_use]
+    pub fn markers(&self) -> &[Marker] {
+        match &self.frames {
+            FrameStore::Indexed(indexed) => &indexed.index.markers,
+            FrameStore::Loaded(_) => &[],
+        }
+    }
+
+    /// Reads just the markers from a recording's frame index, without
+    /// decoding any frames. Recordings without an index have none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened, the header is invalid,
+    /// or the index is corrupted.
+    pub fn read_markers(path: &Path) -> Result<Vec<Marker>> {
+        let (_, header) = Self::open_decoder(path)?;
+        if header.format_version < INDEXED_FORMAT_VERSION {
+            return Ok(Vec::new());
+        }
+        Ok(read_frame_index(path, header.format_version)?
+            .map(|index| index.markers)
+            .unwrap_or_default())
+    }
+
+    /// True if the recording was cut short; any incomplete final frame has
+    /// been dropped.
+    #[must_use]
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub(super) fn format_version(&self) -> u8 {
+        self.format_version
+    }
+
+    /// False for a `--no-compress` recording.
+    pub(super) fn compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Opens the file's frame stream past the header. Compressed recordings
+    /// start with a zstd frame; anything else is read as an uncompressed
+    /// recording, whose header must agree.
+    fn open_decoder(path: &Path) -> Result<(RecordingDecoder, FileHeader)> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open recording file: {}", path.display()))?;
+        let mut buf_reader = BufReader::new(file);
+        let starts_compressed = buf_reader
+            .fill_buf()
+            .context("failed to read recording file")?
+            .starts_with(&ZSTD_MAGIC);
+
+        let (decoder, header): (RecordingDecoder, _) = if starts_compressed {
+            let mut decoder =
+                zstd::Decoder::new(buf_reader).context("failed to create zstd decoder")?;
+            let header = Self::read_header(&mut decoder)?;
+            (Box::new(decoder), header)
+        } else {
+            let header = Self::read_header(&mut buf_reader)?;
+            if header.compressed {
+                bail!("recording header says compressed, but the file is not zstd");
+            }
+            (Box::new(buf_reader), header)
+        };
+
+        if header.magic != MAGIC {
+            bail!("invalid magic bytes in recording file");
+        }
+        let version = header.format_version;
+        if version == 0 || version > FORMAT_VERSION {
+            bail!("unsupported format version {version} (expected 1 to {FORMAT_VERSION})");
+        }
+
+        Ok((decoder, header))
+    }
+
     #[must_use]
     pub fn metadata(&self) -> &SessionMetadata {
         &self.metadata
@@ -58,12 +258,22 @@ impl RecordingReader {
 
     #[must_use]
     pub fn frame_count(&self) -> usize {
-        self.frames.len()
+        match &self.frames {
+            FrameStore::Loaded(frames) => frames.len(),
+            FrameStore::Indexed(indexed) => {
+                usize::try_from(indexed.index.frame_count).unwrap_or(usize::MAX)
+            }
+        }
     }
 
-    #[must_use]
-    pub fn frame_at(&self, index: usize) -> Option<&Frame> {
-        self.frames.get(index)
+    /// Returns the frame at `index`, decoding its chunk first if the
+    /// recording is indexed. Returns `None` past the end or if the chunk
+    /// cannot be decoded.
+    pub fn frame_at(&mut self, index: usize) -> Option<&Frame> {
+        match &mut self.frames {
+            FrameStore::Loaded(frames) => frames.get(index),
+            FrameStore::Indexed(indexed) => indexed.frame_at(index),
+        }
     }
 
     fn read_header(reader: &mut impl Read) -> Result<FileHeader> {
@@ -78,50 +288,290 @@ impl RecordingReader {
             .read_exact(&mut data)
             .context("failed to read header data")?;
 
+        let legacy: LegacyFileHeader =
+            postcard::from_bytes(&data).context("failed to deserialize file header")?;
+        if legacy.format_version < COMPRESSION_FORMAT_VERSION {
+            return Ok(FileHeader::from(legacy));
+        }
         postcard::from_bytes(&data).context("failed to deserialize file header")
     }
 
-    fn read_all_frames(reader: &mut impl Read, version: u8) -> Result<Vec<Frame>> {
+    /// Reads frames up to the EOF marker. Unless `strict` is set, a stream
+    /// that was cut short ends the read and is reported by returning `true`
+    /// alongside the complete frames before it.
+    fn read_all_frames(
+        reader: &mut impl Read,
+        version: u8,
+        strict: bool,
+    ) -> Result<(Vec<Frame>, bool)> {
         let mut frames = Vec::new();
-        let mut len_buf = [0u8; 4];
-
         loop {
-            match reader.read_exact(&mut len_buf) {
-                Ok(()) => {}
-                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(e).context("failed to read frame length"),
+            match read_next_frame(reader, version) {
+                Ok(Some(frame)) => frames.push(frame),
+                Ok(None) => return Ok((frames, false)),
+                Err(e) if !strict && e.is::<Truncated>() => return Ok((frames, true)),
+                Err(e) => return Err(e.context(format!("failed to read frame {}", frames.len()))),
             }
+        }
+    }
+}
 
-            if len_buf == EOF_MARKER {
-                break;
-            }
+/// Reads and decodes one length-prefixed frame, returning `None` at the EOF
+/// marker.
+fn read_next_frame(reader: &mut impl Read, version: u8) -> Result<Option<Frame>> {
+    read_frame_blob(reader, version)?
+        .map(|data| decode_frame(&data, version))
+        .transpose()
+}
+
+/// Reads one length-prefixed frame's bytes without deserializing them,
+/// returning `None` at the EOF marker. If the input runs out first the error
+/// is `Truncated`. From v4 the length is followed by a CRC32 of the frame
+/// bytes, which must match.
+fn read_frame_blob(reader: &mut impl Read, version: u8) -> Result<Option<Vec<u8>>> {
+    let mut len_buf = [0u8; 4];
+    match reader.read_exact(&mut len_buf) {
+        Ok(()) => {}
+        Err(e) => return Err(truncation_or(e, "failed to read frame length")),
+    }
 
-            let len = u32::from_le_bytes(len_buf) as usize;
-            let mut data = vec![0u8; len];
-            reader
-                .read_exact(&mut data)
-                .context("failed to read frame data")?;
+    if len_buf == EOF_MARKER {
+        return Ok(None);
+    }
 
-            let frame = if version == 1 {
-                let legacy: LegacyFrame =
-                    postcard::from_bytes(&data).context("failed to deserialize v1 frame")?;
-                Frame::from(legacy)
-            } else {
-                postcard::from_bytes(&data).context("failed to deserialize frame")?
-            };
-            frames.push(frame);
+    let len = u32::from_le_bytes(len_buf) as usize;
+    let expected_crc = if version >= CHECKSUM_FORMAT_VERSION {
+        let mut crc_buf = [0u8; 4];
+        reader
+            .read_exact(&mut crc_buf)
+            .map_err(|e| truncation_or(e, "failed to read frame checksum"))?;
+        Some(u32::from_le_bytes(crc_buf))
+    } else {
+        None
+    };
+
+    let mut data = vec![0u8; len];
+    reader
+        .read_exact(&mut data)
+        .map_err(|e| truncation_or(e, "failed to read frame data"))?;
+
+    if let Some(expected) = expected_crc {
+        let actual = crc32fast::hash(&data);
+        if actual != expected {
+            bail!("frame checksum mismatch (expected {expected:08x}, found {actual:08x})");
+        }
+    }
+    Ok(Some(data))
+}
+
+/// Deserializes a frame written with format `version`, deriving its rates.
+fn decode_frame(data: &[u8], version: u8) -> Result<Frame> {
+    let mut frame = if version == 1 {
+        let legacy: LegacyFrame =
+            postcard::from_bytes(data).context("failed to deserialize v1 frame")?;
+        Frame::from(legacy)
+    } else if version < PSS_FORMAT_VERSION {
+        let v4: V4Frame = postcard::from_bytes(data).context("failed to deserialize v4 frame")?;
+        Frame::from(v4)
+    } else if version < ELAPSED_FORMAT_VERSION {
+        let v6: V6Frame = postcard::from_bytes(data).context("failed to deserialize v6 frame")?;
+        Frame::from(v6)
+    } else if version < RESETS_FORMAT_VERSION {
+        let v7: V7Frame = postcard::from_bytes(data).context("failed to deserialize v7 frame")?;
+        Frame::from(v7)
+    } else {
+        postcard::from_bytes::<Frame>(data).context("failed to deserialize frame")?
+    };
+    frame.computed.rates = RatesPerSecond::for_frame(&frame.computed);
+    Ok(frame)
+}
+
+/// Maps running out of input to `Truncated`, and any other read error to
+/// `context`.
+fn truncation_or(e: std::io::Error, context: &'static str) -> anyhow::Error {
+    if e.kind() == std::io::ErrorKind::UnexpectedEof {
+        anyhow::Error::new(Truncated)
+    } else {
+        anyhow::Error::new(e).context(context)
+    }
+}
+
+/// Reads the `FrameIndex` trailer from the end of the file, if present.
+fn read_frame_index(path: &Path, version: u8) -> Result<Option<FrameIndex>> {
+    let mut file = File::open(path)
+        .with_context(|| format!("failed to open recording file: {}", path.display()))?;
+    let file_len = file
+        .metadata()
+        .context("failed to stat recording file")?
+        .len();
+    if file_len < 8 {
+        return Ok(None);
+    }
+
+    let mut trailer = [0u8; 8];
+    file.seek(SeekFrom::End(-8))
+        .context("failed to seek to frame index trailer")?;
+    file.read_exact(&mut trailer)
+        .context("failed to read frame index trailer")?;
+    if trailer[4..] != INDEX_MAGIC {
+        return Ok(None);
+    }
+
+    let len = u64::from(u32::from_le_bytes([
+        trailer[0], trailer[1], trailer[2], trailer[3],
+    ]));
+    if len + 8 > file_len {
+        return Ok(None);
+    }
+
+    let mut data = vec![0u8; usize::try_from(len).context("frame index too large")?];
+    file.seek(SeekFrom::End(
+        -8 - i64::try_from(len).context("frame index too large")?,
+    ))
+    .context("failed to seek to frame index")?;
+    file.read_exact(&mut data)
+        .context("failed to read frame index")?;
+    let index = if version < MARKERS_FORMAT_VERSION {
+        let legacy: LegacyFrameIndex =
+            postcard::from_bytes(&data).context("failed to deserialize frame index")?;
+        FrameIndex::from(legacy)
+    } else {
+        postcard::from_bytes(&data).context("failed to deserialize frame index")?
+    };
+    Ok(Some(index))
+}
+
+impl IndexedFrames {
+    fn frame_at(&mut self, index: usize) -> Option<&Frame> {
+        let index = index as u64;
+        if index >= self.index.frame_count {
+            return None;
+        }
+
+        // Binary search for the last chunk starting at or before `index`.
+        let chunk = self
+            .index
+            .chunks
+            .partition_point(|c| c.first_frame <= index)
+            .checked_sub(1)?;
+        if self.cached_chunk != Some(chunk) {
+            self.load_chunk(chunk).ok()?;
+        }
+
+        let first = self.index.chunks[chunk].first_frame;
+        self.chunk_frames.get(usize::try_from(index - first).ok()?)
+    }
+
+    fn load_chunk(&mut self, chunk: usize) -> Result<()> {
+        self.cached_chunk = None;
+        self.chunk_frames.clear();
+
+        let entry = self.index.chunks[chunk];
+        let end = self
+            .index
+            .chunks
+            .get(chunk + 1)
+            .map_or(self.index.frame_count, |next| next.first_frame);
+
+        self.file
+            .seek(SeekFrom::Start(entry.offset))
+            .context("failed to seek to chunk")?;
+        let mut decoder: Box<dyn Read + '_> = if self.compressed {
+            Box::new(
+                zstd::Decoder::new(&mut self.file)
+                    .context("failed to create zstd decoder")?
+                    .single_frame(),
+            )
+        } else {
+            Box::new(BufReader::new(&mut self.file))
+        };
+
+        if chunk == 0 {
+            RecordingReader::read_header(&mut decoder)?;
         }
+        for index in entry.first_frame..end {
+            let frame = read_next_frame(&mut decoder, self.format_version)
+                .with_context(|| format!("failed to read frame {index}"))?
+                .context("chunk ended before its last indexed frame")?;
+            self.chunk_frames.push(frame);
+        }
+
+        self.cached_chunk = Some(chunk);
+        Ok(())
+    }
+}
+
+/// A recording's frame stream: zstd-decoded, or read as is for an
+/// uncompressed recording.
+type RecordingDecoder = Box<dyn Read + Send>;
+
+/// Sequential, lazily-decoded view of a recording's frames.
+pub struct FrameStream {
+    decoder: RecordingDecoder,
+    metadata: SessionMetadata,
+    format_version: u8,
+    next_index: usize,
+    done: bool,
+    truncated: bool,
+}
 
-        Ok(frames)
+impl FrameStream {
+    #[must_use]
+    pub fn metadata(&self) -> &SessionMetadata {
+        &self.metadata
+    }
+
+    /// True once the stream has ended early because the recording was cut
+    /// short.
+    #[must_use]
+    pub fn truncated(&self) -> bool {
+        self.truncated
     }
 }
 
+impl Iterator for FrameStream {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match read_next_frame(&mut self.decoder, self.format_version) {
+            Ok(Some(frame)) => {
+                self.next_index += 1;
+                Some(Ok(frame))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) if e.is::<Truncated>() => {
+                self.done = true;
+                self.truncated = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e.context(format!(
+                    "failed to read frame {}",
+                    self.next_index
+                ))))
+            }
+        }
+    }
+}
+
+/// Emits recorded frames at their original pace, scaled by the playback
+/// speed. `current_index` is the next frame to emit when playing forward, so
+/// the frame on screen is always the one before it, in either direction.
 pub struct ReplaySource {
     reader: RecordingReader,
     current_index: usize,
     playback_speed: f64,
     last_emitted: Instant,
     paused: bool,
+    reversed: bool,
+    loop_playback: bool,
 }
 
 impl ReplaySource {
@@ -133,9 +583,23 @@ impl ReplaySource {
             playback_speed: 1.0,
             last_emitted: Instant::now(),
             paused: false,
+            reversed: false,
+            loop_playback: false,
         }
     }
 
+    /// Wraps around to the other end instead of stopping when playback runs
+    /// off either end of the recording.
+    pub fn set_loop_playback(&mut self, loop_playback: bool) {
+        self.loop_playback = loop_playback;
+    }
+
+    /// Plays backward when `reversed` is set. Reaching the first frame while
+    /// reversed pauses playback.
+    pub fn set_reversed(&mut self, reversed: bool) {
+        self.reversed = reversed;
+    }
+
     pub fn set_speed(&mut self, speed: f64) {
         self.playback_speed = speed;
     }
@@ -181,7 +645,24 @@ impl DataSource for ReplaySource {
             return None;
         }
 
-        let frame = self.reader.frame_at(self.current_index)?;
+        let frame_count = self.reader.frame_count();
+        let index = if self.reversed {
+            // The frame on screen is `current_index - 1`; step to the one
+            // before it, or stop at the start.
+            if self.current_index >= 2 {
+                self.current_index - 2
+            } else if self.loop_playback && frame_count > 0 {
+                frame_count - 1
+            } else {
+                self.paused = true;
+                return None;
+            }
+        } else if self.current_index >= frame_count && self.loop_playback {
+            0
+        } else {
+            self.current_index
+        };
+        let frame = self.reader.frame_at(index)?;
 
         let sample_period_ns = frame.computed.sample_period_ns;
         #[allow(clippy::cast_precision_loss)]
@@ -194,7 +675,7 @@ impl DataSource for ReplaySource {
         }
 
         let computed = frame.computed.clone();
-        self.current_index += 1;
+        self.current_index = index + 1;
         self.last_emitted = Instant::now();
         Some(computed)
     }
@@ -0,0 +1,469 @@
+// SPDX-License-Identifier: MIT
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::datasource::SessionMetadata;
+use crate::recording::format::{Frame, Marker};
+use crate::recording::reader::RecordingReader;
+use crate::recording::writer::{Compression, RecordingWriter};
+use crate::sampler::accumulator::{ComputedFrame, HistogramEntry, RatesPerSecond, ThreadLoad};
+use crate::sampler::thread_stats::ThreadDelta;
+
+/// Concatenates `inputs` into a single recording at `output`, ordered by each
+/// input's `recording_start`. Timestamps are rebased so the merged timeline is
+/// continuous: each input starts one sample period after the previous ends.
+/// Markers move with their frames.
+///
+/// All inputs must share a pid and FEX version, and none may have been cut
+/// short, unless `force` is set. The output header takes the metadata of the
+/// earliest input.
+///
+/// Returns the number of frames written.
+///
+/// # Errors
+///
+/// Returns an error if an input cannot be read, the inputs are incompatible
+/// and `force` is not set, or the output cannot be written.
+pub fn merge(inputs: &[PathBuf], output: &Path, force: bool) -> Result<u64> {
+    if inputs.is_empty() {
+        bail!("no input recordings given");
+    }
+
+    let mut ordered: Vec<(SessionMetadata, &PathBuf)> = inputs
+        .iter()
+        .map(|path| RecordingReader::frames_streaming(path).map(|s| (s.metadata().clone(), path)))
+        .collect::<Result<_>>()?;
+    ordered.sort_by_key(|(metadata, _)| metadata.recording_start);
+
+    let first = &ordered[0].0;
+    if !force {
+        for (metadata, path) in &ordered[1..] {
+            check_compatible(first, metadata, path)?;
+        }
+    }
+
+    let mut writer = RecordingWriter::create(output, first, Compression::DEFAULT)?;
+    let mut next_base_ns: u64 = 0;
+    let mut written: u64 = 0;
+
+    for (_, path) in &ordered {
+        let mut markers = PendingMarkers::read(path)?;
+        let mut first_ts = None;
+        let mut end_ns = next_base_ns;
+        let mut stream = RecordingReader::frames_streaming(path)?;
+        for (i, frame) in stream.by_ref().enumerate() {
+            let mut frame = frame.with_context(|| format!("failed to read {}", path.display()))?;
+            let ts = frame.computed.timestamp_ns;
+            let origin = *first_ts.get_or_insert(ts);
+            frame.computed.timestamp_ns = next_base_ns + ts.saturating_sub(origin);
+            end_ns = frame.computed.timestamp_ns + frame.computed.sample_period_ns;
+            writer.write_frame(&frame)?;
+            markers.copy_through(i as u64, &mut writer);
+            written += 1;
+        }
+        if stream.truncated() && !force {
+            bail!(
+                "{} was cut short; its last frames are missing (use --force to merge anyway)",
+                path.display()
+            );
+        }
+        next_base_ns = end_ns;
+    }
+
+    writer.finish()?;
+    Ok(written)
+}
+
+/// Writes a copy of `input` to `output` with every `factor` consecutive
+/// frames combined into one by `combine_frames`. A trailing partial group is
+/// combined as-is, so no frames are dropped. Markers move to the frame their
+/// group was combined into.
+///
+/// Returns the number of frames written.
+///
+/// # Errors
+///
+/// Returns an error if `factor` is zero, the input cannot be read, or the
+/// output cannot be written.
+pub fn downsample(input: &Path, output: &Path, factor: usize) -> Result<u64> {
+    if factor == 0 {
+        bail!("downsample factor must be at least 1");
+    }
+
+    let mut markers = PendingMarkers::read(input)?;
+    let stream = RecordingReader::frames_streaming(input)?;
+    let mut writer = RecordingWriter::create(output, stream.metadata(), Compression::DEFAULT)?;
+    let mut group = Vec::with_capacity(factor);
+    let mut read: u64 = 0;
+    let mut written: u64 = 0;
+
+    for frame in stream {
+        group.push(frame?);
+        read += 1;
+        if group.len() == factor {
+            writer.write_frame(&combine_frames(&group))?;
+            markers.copy_through(read - 1, &mut writer);
+            written += 1;
+            group.clear();
+        }
+    }
+    if !group.is_empty() {
+        writer.write_frame(&combine_frames(&group))?;
+        markers.copy_through(read - 1, &mut writer);
+        written += 1;
+    }
+
+    writer.finish()?;
+    Ok(written)
+}
+
+/// An input's markers, handed to the output as the frames they point at are
+/// written.
+struct PendingMarkers(std::iter::Peekable<std::vec::IntoIter<Marker>>);
+
+impl PendingMarkers {
+    fn read(path: &Path) -> Result<Self> {
+        let mut markers = RecordingReader::read_markers(path)?;
+        markers.sort_by_key(|marker| marker.frame_index);
+        Ok(Self(markers.into_iter().peekable()))
+    }
+
+    /// Puts every marker up to input frame `index` on the frame `writer`
+    /// wrote last, which takes on that frame's output index and timestamp.
+    fn copy_through(&mut self, index: u64, writer: &mut RecordingWriter) {
+        while let Some(marker) = self.0.next_if(|marker| marker.frame_index <= index) {
+            writer.add_marker(marker.label);
+        }
+    }
+}
+
+/// Combines consecutive frames into one covering their whole span. Per-sample
+/// counts and times are summed, loads are averaged, histogram flags are OR-ed,
+/// and point-in-time values (memory, running totals) come from the last frame.
+///
+/// # Panics
+///
+/// Panics if `frames` is empty.
+#[must_use]
+pub fn combine_frames(frames: &[Frame]) -> Frame {
+    let first = &frames[0].computed;
+    let last = &frames[frames.len() - 1].computed;
+    #[allow(clippy::cast_precision_loss)]
+    let n = frames.len() as f64;
+
+    let mut computed = ComputedFrame {
+        timestamp_ns: first.timestamp_ns,
+        total_jit_invocations: last.total_jit_invocations,
+        mem: last.mem.clone(),
+        cumulative: last.cumulative.clone(),
+        ..ComputedFrame::default()
+    };
+    let mut histogram = HistogramEntry::default();
+    let mut load_sum = 0.0;
+    let mut thread_sums: BTreeMap<u32, (f64, u64)> = BTreeMap::new();
+    let mut delta_sums: BTreeMap<u32, ThreadDelta> = BTreeMap::new();
+    let mut max_threads_shown = 0;
+
+    for frame in frames {
+        let f = &frame.computed;
+        computed.sample_period_ns += f.sample_period_ns;
+        computed.elapsed_ns += f.elapsed_ns;
+        computed.threads_sampled = computed.threads_sampled.max(f.threads_sampled);
+        computed.resets_detected += f.resets_detected;
+        computed.total_jit_time += f.total_jit_time;
+        computed.total_signal_time += f.total_signal_time;
+        computed.total_sigbus_count += f.total_sigbus_count;
+        computed.total_smc_count += f.total_smc_count;
+        computed.total_float_fallback_count += f.total_float_fallback_count;
+        computed.total_cache_miss_count += f.total_cache_miss_count;
+        computed.total_cache_read_lock_time += f.total_cache_read_lock_time;
+        computed.total_cache_write_lock_time += f.total_cache_write_lock_time;
+        computed.total_jit_count += f.total_jit_count;
+        load_sum += f.fex_load_percent;
+
+        histogram.high_jit_load |= f.histogram_entry.high_jit_load;
+        histogram.high_invalidation_or_smc |= f.histogram_entry.high_invalidation_or_smc;
+        histogram.high_sigbus |= f.histogram_entry.high_sigbus;
+        histogram.high_softfloat |= f.histogram_entry.high_softfloat;
+
+        max_threads_shown = max_threads_shown.max(f.thread_loads.len());
+        for tl in &f.thread_loads {
+            let entry = thread_sums.entry(tl.tid).or_default();
+            entry.0 += f64::from(tl.load_percent);
+            entry.1 += tl.total_cycles;
+        }
+        for d in &frame.per_thread_deltas {
+            let sum = delta_sums.entry(d.tid).or_insert_with(|| ThreadDelta {
+                tid: d.tid,
+                ..ThreadDelta::default()
+            });
+            sum.jit_time += d.jit_time;
+            sum.signal_time += d.signal_time;
+            sum.sigbus_count += d.sigbus_count;
+            sum.smc_count += d.smc_count;
+            sum.float_fallback_count += d.float_fallback_count;
+            sum.cache_miss_count += d.cache_miss_count;
+            sum.cache_read_lock_time += d.cache_read_lock_time;
+            sum.cache_write_lock_time += d.cache_write_lock_time;
+            sum.jit_count += d.jit_count;
+        }
+    }
+
+    computed.fex_load_percent = load_sum / n;
+    #[allow(clippy::cast_possible_truncation)]
+    let load_f32 = computed.fex_load_percent as f32;
+    histogram.load_percent = load_f32;
+    computed.histogram_entry = histogram;
+    computed.rates = RatesPerSecond::for_frame(&computed);
+
+    let mut thread_loads: Vec<ThreadLoad> = thread_sums
+        .into_iter()
+        .map(|(tid, (load_sum, total_cycles))| {
+            #[allow(clippy::cast_possible_truncation)]
+            let load_percent = (load_sum / n) as f32;
+            ThreadLoad {
+                tid,
+                load_percent,
+                total_cycles,
+            }
+        })
+        .collect();
+    thread_loads.sort_by(|a, b| b.total_cycles.cmp(&a.total_cycles));
+    thread_loads.truncate(max_threads_shown);
+    computed.thread_loads = thread_loads;
+
+    Frame {
+        computed,
+        per_thread_deltas: delta_sums.into_values().collect(),
+    }
+}
+
+fn check_compatible(first: &SessionMetadata, other: &SessionMetadata, path: &Path) -> Result<()> {
+    if other.pid != first.pid {
+        bail!(
+            "{} records PID {} but the first input records PID {} (use --force to merge anyway)",
+            path.display(),
+            other.pid,
+            first.pid
+        );
+    }
+    if other.fex_version != first.fex_version {
+        bail!(
+            "{} was recorded with FEX {} but the first input with FEX {} (use --force to merge anyway)",
+            path.display(),
+            other.fex_version,
+            first.fex_version
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::testing::make_metadata;
+
+    fn frame(index: u64, load: f64, tid_cycles: &[(u32, u64)]) -> Frame {
+        Frame {
+            computed: ComputedFrame {
+                timestamp_ns: index * 100_000_000,
+                sample_period_ns: 100_000_000,
+                threads_sampled: tid_cycles.len(),
+                total_jit_time: 1000 + index,
+                total_sigbus_count: index,
+                total_smc_count: 2,
+                total_jit_invocations: 10 * index,
+                fex_load_percent: load,
+                thread_loads: tid_cycles
+                    .iter()
+                    .map(|&(tid, total_cycles)| ThreadLoad {
+                        tid,
+                        #[allow(clippy::cast_precision_loss)]
+                        load_percent: total_cycles as f32 / 10.0,
+                        total_cycles,
+                    })
+                    .collect(),
+                histogram_entry: HistogramEntry {
+                    high_sigbus: index == 1,
+                    ..HistogramEntry::default()
+                },
+                ..ComputedFrame::default()
+            },
+            per_thread_deltas: tid_cycles
+                .iter()
+                .map(|&(tid, jit_time)| ThreadDelta {
+                    tid,
+                    jit_time,
+                    ..ThreadDelta::default()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn combine_sums_counts_and_averages_load() {
+        let frames = vec![
+            frame(0, 10.0, &[(1, 100), (2, 50)]),
+            frame(1, 20.0, &[(1, 300)]),
+            frame(2, 30.0, &[(2, 20)]),
+        ];
+        let combined = combine_frames(&frames).computed;
+
+        assert_eq!(combined.timestamp_ns, 0);
+        assert_eq!(combined.sample_period_ns, 300_000_000);
+        assert_eq!(combined.total_jit_time, 3003);
+        assert_eq!(combined.total_sigbus_count, 3);
+        // 3 events over 300ms
+        assert!((combined.rates.sigbus - 10.0).abs() < 1e-9);
+        assert_eq!(combined.total_smc_count, 6);
+        assert_eq!(combined.total_jit_invocations, 20);
+        assert!((combined.fex_load_percent - 20.0).abs() < 1e-9);
+        assert!(combined.histogram_entry.high_sigbus);
+        assert!(!combined.histogram_entry.high_jit_load);
+
+        assert_eq!(combined.thread_loads.len(), 2);
+        assert_eq!(combined.thread_loads[0].tid, 1);
+        assert_eq!(combined.thread_loads[0].total_cycles, 400);
+        // (10 + 30 + 0) / 3 frames
+        assert!((combined.thread_loads[0].load_percent - 40.0 / 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn combine_preserves_per_thread_delta_totals() {
+        let frames = vec![frame(0, 0.0, &[(1, 5), (2, 7)]), frame(1, 0.0, &[(2, 3)])];
+        let combined = combine_frames(&frames);
+        let jit: Vec<(u32, u64)> = combined
+            .per_thread_deltas
+            .iter()
+            .map(|d| (d.tid, d.jit_time))
+            .collect();
+        assert_eq!(jit, vec![(1, 5), (2, 10)]);
+    }
+
+    #[test]
+    fn grouped_totals_match_original() {
+        let frames: Vec<Frame> = (0..10)
+            .map(|i| {
+                #[allow(clippy::cast_precision_loss)]
+                let load = i as f64;
+                frame(i, load, &[(1, 10 * i)])
+            })
+            .collect();
+        let groups: Vec<Frame> = frames.chunks(3).map(combine_frames).collect();
+        assert_eq!(groups.len(), 4);
+
+        let sum = |fs: &[Frame], f: fn(&ComputedFrame) -> u64| -> u64 {
+            fs.iter().map(|fr| f(&fr.computed)).sum()
+        };
+        assert_eq!(
+            sum(&frames, |c| c.total_jit_time),
+            sum(&groups, |c| c.total_jit_time)
+        );
+        assert_eq!(
+            sum(&frames, |c| c.total_sigbus_count),
+            sum(&groups, |c| c.total_sigbus_count)
+        );
+        assert_eq!(
+            sum(&frames, |c| c.sample_period_ns),
+            sum(&groups, |c| c.sample_period_ns)
+        );
+
+        // Period-weighted mean load is preserved.
+        let weighted = |fs: &[Frame]| -> f64 {
+            let total: f64 = fs
+                .iter()
+                .map(|f| {
+                    #[allow(clippy::cast_precision_loss)]
+                    let period = f.computed.sample_period_ns as f64;
+                    f.computed.fex_load_percent * period
+                })
+                .sum();
+            #[allow(clippy::cast_precision_loss)]
+            let duration = sum(fs, |c| c.sample_period_ns) as f64;
+            total / duration
+        };
+        assert!((weighted(&frames) - weighted(&groups)).abs() < 1e-9);
+    }
+
+    /// Writes `count` frames starting at `start`, marking the frames in
+    /// `marked`. Unless `finish` is set the file is left without its EOF
+    /// marker, as if felix had been killed.
+    fn write_input(path: &Path, start: u64, count: u64, marked: &[u64], finish: bool) {
+        let metadata = SessionMetadata {
+            recording_start: SystemTime::UNIX_EPOCH + Duration::from_secs(start),
+            ..make_metadata()
+        };
+        let mut writer = RecordingWriter::create(path, &metadata, Compression::None).unwrap();
+        for i in 0..count {
+            writer.write_frame(&frame(i, 0.0, &[])).unwrap();
+            if marked.contains(&i) {
+                writer.add_marker(Some(format!("{start}/{i}")));
+            }
+        }
+        if finish {
+            writer.finish().unwrap();
+        }
+    }
+
+    fn marker_positions(path: &Path) -> Vec<(u64, u64, String)> {
+        RecordingReader::read_markers(path)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.frame_index, m.timestamp_ns, m.label.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn merge_rebases_markers_and_refuses_truncated_inputs() {
+        let dir = std::env::temp_dir().join("felix_transform_test_merge");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a, b, cut, out) = (
+            dir.join("a.felixr"),
+            dir.join("b.felixr"),
+            dir.join("cut.felixr"),
+            dir.join("out.felixr"),
+        );
+        write_input(&a, 10, 3, &[1], true);
+        write_input(&b, 20, 3, &[2], true);
+
+        assert_eq!(merge(&[b.clone(), a.clone()], &out, false).unwrap(), 6);
+        assert_eq!(
+            marker_positions(&out),
+            [
+                (1, 100_000_000, "10/1".to_string()),
+                (5, 500_000_000, "20/2".to_string())
+            ]
+        );
+
+        write_input(&cut, 30, 2, &[], false);
+        let err = merge(&[a.clone(), cut.clone()], &out, false).unwrap_err();
+        assert!(format!("{err:#}").contains("cut short"), "{err:#}");
+        assert_eq!(merge(&[a, cut], &out, true).unwrap(), 5);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn downsample_moves_markers_to_combined_frames() {
+        let dir = std::env::temp_dir().join("felix_transform_test_downsample");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, out) = (dir.join("in.felixr"), dir.join("out.felixr"));
+        write_input(&input, 0, 7, &[0, 4, 5, 6], true);
+
+        assert_eq!(downsample(&input, &out, 3).unwrap(), 3);
+        let positions: Vec<(u64, u64)> = marker_positions(&out)
+            .into_iter()
+            .map(|(index, ts, _)| (index, ts))
+            .collect();
+        assert_eq!(
+            positions,
+            [(0, 0), (1, 300_000_000), (1, 300_000_000), (2, 600_000_000)]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
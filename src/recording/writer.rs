@@ -1,49 +1,282 @@
 // SPDX-License-Identifier: MIT
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::Path;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 
-use super::format::{EOF_MARKER, FORMAT_VERSION, MAGIC};
+use super::format::{
+    ChunkEntry, EOF_MARKER, FORMAT_VERSION, FRAMES_PER_CHUNK, FrameIndex, INDEX_MAGIC, MAGIC,
+    Marker,
+};
 use crate::datasource::SessionMetadata;
 use crate::recording::format::{FileHeader, Frame};
+use crate::recording::reader::RecordingReader;
+
+/// zstd level used when the caller has no preference.
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// How a recording's frames are stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// zstd at the given level, one zstd frame per chunk.
+    Zstd(i32),
+    /// Length-prefixed frames written as they are, for the least CPU per
+    /// frame while capturing. The file can be compressed afterwards.
+    None,
+}
+
+impl Compression {
+    pub const DEFAULT: Self = Self::Zstd(DEFAULT_ZSTD_LEVEL);
+}
+
+/// Derives a related recording path from `base` by inserting `tag`, such
+/// as a pid, before the extension: `session.felixr` becomes
+/// `session-1234.felixr`.
+#[must_use]
+pub fn tagged_path(base: &Path, tag: impl fmt::Display) -> PathBuf {
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match base.extension() {
+        Some(ext) => format!("{stem}-{tag}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{tag}"),
+    };
+    base.with_file_name(name)
+}
+
+/// Passes writes through while counting bytes, so chunk offsets can be
+/// recorded without querying the file position.
+struct CountingWriter<W: Write> {
+    inner: W,
+    bytes_written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes_written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+type ChunkEncoder = zstd::Encoder<'static, CountingWriter<BufWriter<File>>>;
+
+/// Where the current chunk's bytes go: into a zstd frame, or straight to the
+/// file for uncompressed recordings.
+enum ChunkSink {
+    Zstd(ChunkEncoder),
+    Raw(CountingWriter<BufWriter<File>>),
+}
+
+impl ChunkSink {
+    fn new(counting: CountingWriter<BufWriter<File>>, compression: Compression) -> Result<Self> {
+        match compression {
+            Compression::Zstd(level) => Ok(Self::Zstd(
+                zstd::Encoder::new(counting, level).context("failed to create zstd encoder")?,
+            )),
+            Compression::None => Ok(Self::Raw(counting)),
+        }
+    }
+
+    fn counting(&self) -> &CountingWriter<BufWriter<File>> {
+        match self {
+            Self::Zstd(encoder) => encoder.get_ref(),
+            Self::Raw(counting) => counting,
+        }
+    }
+
+    /// Ends the chunk, completing its zstd frame if it has one.
+    fn finish(self) -> Result<CountingWriter<BufWriter<File>>> {
+        match self {
+            Self::Zstd(encoder) => encoder.finish().context("failed to finish zstd chunk"),
+            Self::Raw(counting) => Ok(counting),
+        }
+    }
+}
+
+impl Write for ChunkSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Zstd(encoder) => encoder.write(buf),
+            Self::Raw(counting) => counting.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Zstd(encoder) => encoder.flush(),
+            Self::Raw(counting) => counting.flush(),
+        }
+    }
+}
 
 pub struct RecordingWriter {
-    encoder: zstd::Encoder<'static, BufWriter<File>>,
+    encoder: Option<ChunkSink>,
+    index: FrameIndex,
+    /// Added to every frame's `timestamp_ns` so appended frames continue the
+    /// existing timeline instead of restarting at zero.
+    timestamp_offset_ns: u64,
+    /// Timeline position of the most recently written frame, for markers.
+    last_timestamp_ns: u64,
+    compression: Compression,
 }
 
 impl RecordingWriter {
-    /// Creates a new recording file at `path` and writes the file header.
+    /// Creates a new recording file at `path` and writes the file header,
+    /// storing frames with the given compression.
     ///
     /// # Errors
     ///
     /// Returns an error if the file cannot be created or the header cannot be written.
-    pub fn create(path: &Path, metadata: &SessionMetadata) -> Result<Self> {
+    pub fn create(
+        path: &Path,
+        metadata: &SessionMetadata,
+        compression: Compression,
+    ) -> Result<Self> {
         let file = File::create(path)
             .with_context(|| format!("failed to create recording file: {}", path.display()))?;
-        let buf_writer = BufWriter::new(file);
-        let mut encoder =
-            zstd::Encoder::new(buf_writer, 3).context("failed to create zstd encoder")?;
-
-        let header = FileHeader {
-            magic: MAGIC,
-            format_version: FORMAT_VERSION,
-            metadata: metadata.clone(),
+        let counting = CountingWriter {
+            inner: BufWriter::new(file),
+            bytes_written: 0,
         };
+        let mut encoder = ChunkSink::new(counting, compression)?;
+        write_header(&mut encoder, metadata)?;
 
-        let serialized = postcard::to_stdvec(&header).context("failed to serialize file header")?;
+        Ok(Self {
+            encoder: Some(encoder),
+            index: FrameIndex {
+                frame_count: 0,
+                chunks: vec![ChunkEntry {
+                    offset: 0,
+                    first_frame: 0,
+                }],
+                markers: Vec::new(),
+            },
+            timestamp_offset_ns: 0,
+            last_timestamp_ns: 0,
+            compression,
+        })
+    }
 
-        #[allow(clippy::cast_possible_truncation)]
-        let len = serialized.len() as u32;
-        encoder
-            .write_all(&len.to_le_bytes())
-            .context("failed to write header length")?;
-        encoder
-            .write_all(&serialized)
-            .context("failed to write header data")?;
+    /// Re-opens an existing recording so new frames are added after the
+    /// ones already in it.
+    ///
+    /// The EOF marker lives inside the final zstd frame, so that frame cannot
+    /// simply be extended. Instead its frames are decoded, the file is
+    /// truncated to where the frame began, and they are re-encoded into a
+    /// fresh zstd frame before new frames follow. If felix dies during that
+    /// rewrite, up to `FRAMES_PER_CHUNK` previously-recorded frames are lost.
+    ///
+    /// The existing header is kept; `metadata` must name the same pid and FEX
+    /// version. New frames' timestamps are shifted to follow the last frame.
+    /// They are stored the way the file already is: uncompressed files stay
+    /// uncompressed, and compressed ones use `compression`'s zstd level, or
+    /// the default level if it asks for none.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, was not written cleanly
+    /// by this format version, or belongs to a different session.
+    pub fn append(
+        path: &Path,
+        metadata: &SessionMetadata,
+        compression: Compression,
+    ) -> Result<Self> {
+        let mut reader = RecordingReader::open(path)?;
+        let existing = reader.metadata().clone();
+        if existing.pid != metadata.pid || existing.fex_version != metadata.fex_version {
+            bail!(
+                "cannot append to {}: it records PID {} ({}), not PID {} ({})",
+                path.display(),
+                existing.pid,
+                existing.fex_version,
+                metadata.pid,
+                metadata.fex_version,
+            );
+        }
+        if reader.format_version() != FORMAT_VERSION {
+            bail!(
+                "cannot append to {}: format v{} differs from current v{FORMAT_VERSION}",
+                path.display(),
+                reader.format_version(),
+            );
+        }
+
+        let compression = match (reader.compressed(), compression) {
+            (false, _) => Compression::None,
+            (true, Compression::None) => Compression::DEFAULT,
+            (true, zstd) => zstd,
+        };
+        let (mut index, tail) = reader.last_chunk()?;
+        drop(reader);
+        let last_chunk = *index.chunks.last().context("frame index has no chunks")?;
+        let timestamp_offset_ns = tail
+            .last()
+            .map_or(0, |f| f.computed.timestamp_ns + f.computed.sample_period_ns);
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .with_context(|| format!("failed to open recording file: {}", path.display()))?;
+        file.set_len(last_chunk.offset)
+            .context("failed to truncate recording tail")?;
+        file.seek(SeekFrom::Start(last_chunk.offset))
+            .context("failed to seek to recording tail")?;
 
-        Ok(Self { encoder })
+        let counting = CountingWriter {
+            inner: BufWriter::new(file),
+            bytes_written: last_chunk.offset,
+        };
+        let mut encoder = ChunkSink::new(counting, compression)?;
+        if last_chunk.offset == 0 {
+            write_header(&mut encoder, &existing)?;
+        }
+        index.frame_count = last_chunk.first_frame;
+
+        let mut writer = Self {
+            encoder: Some(encoder),
+            index,
+            timestamp_offset_ns: 0,
+            last_timestamp_ns: 0,
+            compression,
+        };
+        for frame in &tail {
+            writer.write_frame(frame)?;
+        }
+        writer.timestamp_offset_ns = timestamp_offset_ns;
+        Ok(writer)
+    }
+
+    /// Number of frames in the recording, including any it was appended to.
+    #[must_use]
+    pub fn frame_count(&self) -> u64 {
+        self.index.frame_count
+    }
+
+    /// Compressed bytes produced so far. The chunk being filled is not
+    /// counted until it is complete, so this trails the final file size by
+    /// up to one chunk plus the index. Uncompressed recordings count every
+    /// frame as it is written.
+    #[must_use]
+    pub fn compressed_size(&self) -> u64 {
+        self.encoder
+            .as_ref()
+            .map_or(0, |encoder| encoder.counting().bytes_written)
+    }
+
+    /// Bookmarks the most recently written frame (or the first frame, if none
+    /// has been written yet). Markers are stored in the frame index when the
+    /// recording is finished.
+    pub fn add_marker(&mut self, label: Option<String>) {
+        self.index.markers.push(Marker {
+            frame_index: self.index.frame_count.saturating_sub(1),
+            timestamp_ns: self.last_timestamp_ns,
+            label,
+        });
     }
 
     /// Writes a single frame to the recording.
@@ -52,36 +285,101 @@ impl RecordingWriter {
     ///
     /// Returns an error if serialization or writing fails.
     pub fn write_frame(&mut self, frame: &Frame) -> Result<()> {
-        let serialized = postcard::to_stdvec(frame).context("failed to serialize frame")?;
+        if self.index.frame_count > 0 && self.index.frame_count.is_multiple_of(FRAMES_PER_CHUNK) {
+            self.start_chunk()?;
+        }
+
+        self.last_timestamp_ns = frame.computed.timestamp_ns + self.timestamp_offset_ns;
+        let serialized = if self.timestamp_offset_ns == 0 {
+            postcard::to_stdvec(frame)
+        } else {
+            let mut shifted = frame.clone();
+            shifted.computed.timestamp_ns += self.timestamp_offset_ns;
+            postcard::to_stdvec(&shifted)
+        }
+        .context("failed to serialize frame")?;
 
         #[allow(clippy::cast_possible_truncation)]
         let len = serialized.len() as u32;
-        self.encoder
+        let crc = crc32fast::hash(&serialized);
+        let encoder = self
+            .encoder
+            .as_mut()
+            .context("recording already finished")?;
+        encoder
             .write_all(&len.to_le_bytes())
             .context("failed to write frame length")?;
-        self.encoder
+        encoder
+            .write_all(&crc.to_le_bytes())
+            .context("failed to write frame checksum")?;
+        encoder
             .write_all(&serialized)
             .context("failed to write frame data")?;
 
+        self.index.frame_count += 1;
         Ok(())
     }
 
-    /// Writes the EOF marker, finishes compression, and flushes the file.
+    /// Writes the EOF marker, finishes compression, appends the frame index,
+    /// and flushes the file.
     ///
     /// # Errors
     ///
     /// Returns an error if writing or flushing fails.
     pub fn finish(mut self) -> Result<()> {
-        self.encoder
+        let mut encoder = self.encoder.take().context("recording already finished")?;
+        encoder
             .write_all(&EOF_MARKER)
             .context("failed to write EOF marker")?;
-        let mut buf_writer = self
-            .encoder
-            .finish()
-            .context("failed to finish zstd encoder")?;
-        buf_writer
-            .flush()
-            .context("failed to flush recording file")?;
+        let mut counting = encoder.finish()?;
+
+        let index = postcard::to_stdvec(&self.index).context("failed to serialize frame index")?;
+        #[allow(clippy::cast_possible_truncation)]
+        let len = index.len() as u32;
+        counting
+            .write_all(&index)
+            .context("failed to write frame index")?;
+        counting
+            .write_all(&len.to_le_bytes())
+            .context("failed to write frame index length")?;
+        counting
+            .write_all(&INDEX_MAGIC)
+            .context("failed to write frame index magic")?;
+        counting.flush().context("failed to flush recording file")?;
+        Ok(())
+    }
+
+    /// Ends the current zstd frame and begins a new one, recording its offset
+    /// in the index so readers can start decoding there.
+    fn start_chunk(&mut self) -> Result<()> {
+        let encoder = self.encoder.take().context("recording already finished")?;
+        let counting = encoder.finish()?;
+        self.index.chunks.push(ChunkEntry {
+            offset: counting.bytes_written,
+            first_frame: self.index.frame_count,
+        });
+        self.encoder = Some(ChunkSink::new(counting, self.compression)?);
         Ok(())
     }
 }
+
+fn write_header(encoder: &mut ChunkSink, metadata: &SessionMetadata) -> Result<()> {
+    let header = FileHeader {
+        magic: MAGIC,
+        format_version: FORMAT_VERSION,
+        metadata: metadata.clone(),
+        compressed: matches!(encoder, ChunkSink::Zstd(_)),
+    };
+
+    let serialized = postcard::to_stdvec(&header).context("failed to serialize file header")?;
+
+    #[allow(clippy::cast_possible_truncation)]
+    let len = serialized.len() as u32;
+    encoder
+        .write_all(&len.to_le_bytes())
+        .context("failed to write header length")?;
+    encoder
+        .write_all(&serialized)
+        .context("failed to write header data")?;
+    Ok(())
+}
@@ -1,4 +1,6 @@
 // SPDX-License-Identifier: MIT
+use std::time::Instant;
+
 use serde::{Deserialize, Serialize};
 
 use super::thread_stats::SampleResult;
@@ -10,6 +12,10 @@ const HIGH_SMC_THRESHOLD: u64 = 500;
 const HIGH_SIGBUS_THRESHOLD: u64 = 5_000;
 const HIGH_SOFTFLOAT_THRESHOLD: u64 = 1_000_000;
 
+/// Event counts summed over time. In a frame, these are FEX's own running
+/// totals summed over the threads alive at that sample; `add_sample` instead
+/// builds session totals from per-sample counts, which keep the events of
+/// threads that have since exited.
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct CumulativeCountStats {
     pub sigbus: u64,
@@ -19,6 +25,19 @@ pub struct CumulativeCountStats {
     pub jit: u64,
 }
 
+impl CumulativeCountStats {
+    /// Adds the events counted in one sample.
+    pub fn add_sample(&mut self, frame: &ComputedFrame) {
+        self.sigbus = self.sigbus.saturating_add(frame.total_sigbus_count);
+        self.smc = self.smc.saturating_add(frame.total_smc_count);
+        self.float_fallback = self
+            .float_fallback
+            .saturating_add(frame.total_float_fallback_count);
+        self.cache_miss = self.cache_miss.saturating_add(frame.total_cache_miss_count);
+        self.jit = self.jit.saturating_add(frame.total_jit_count);
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ThreadLoad {
     pub tid: u32,
@@ -36,10 +55,55 @@ pub struct HistogramEntry {
     pub high_softfloat: bool,
 }
 
+/// Per-second event rates over one sample period.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RatesPerSecond {
+    pub sigbus: f64,
+    pub cache_miss: f64,
+    pub jit_count: f64,
+    /// `total_jit_invocations` averaged over the time since the first
+    /// sample. The total is the running sum of `jit_count`, so this is the
+    /// session's compile rate rather than the current one.
+    pub jit_invocations: f64,
+}
+
+impl RatesPerSecond {
+    /// Divides the frame's per-sample counts by its measured period in
+    /// seconds, and its invocation total by its timestamp. A zero-length
+    /// period or timestamp yields zero rates.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn for_frame(frame: &ComputedFrame) -> Self {
+        let session_seconds = frame.timestamp_ns as f64 / NANOSECONDS_IN_SECOND;
+        let jit_invocations = if session_seconds > 0.0 {
+            frame.total_jit_invocations as f64 / session_seconds
+        } else {
+            0.0
+        };
+        let seconds = frame.measured_period_ns() as f64 / NANOSECONDS_IN_SECOND;
+        if seconds <= 0.0 {
+            return Self {
+                jit_invocations,
+                ..Self::default()
+            };
+        }
+        Self {
+            sigbus: frame.total_sigbus_count as f64 / seconds,
+            cache_miss: frame.total_cache_miss_count as f64 / seconds,
+            jit_count: frame.total_jit_count as f64 / seconds,
+            jit_invocations,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct ComputedFrame {
     pub timestamp_ns: u64,
+    /// The configured sample period, which paces replay.
     pub sample_period_ns: u64,
+    /// Measured time since the previous sample, which scheduling jitter makes
+    /// differ from `sample_period_ns`. Load and rates are computed over this.
+    pub elapsed_ns: u64,
     pub threads_sampled: usize,
     pub total_jit_time: u64,
     pub total_signal_time: u64,
@@ -56,11 +120,44 @@ pub struct ComputedFrame {
     pub mem: MemSnapshot,
     pub histogram_entry: HistogramEntry,
     pub cumulative: CumulativeCountStats,
+    /// Counters that went backwards this sample and were treated as resets
+    /// rather than wrapped. Nonzero values mark frames with suspect totals.
+    pub resets_detected: u64,
+    /// Derived from the counts above, so it is not stored in recordings;
+    /// the reader recomputes it for every decoded frame.
+    #[serde(skip)]
+    pub rates: RatesPerSecond,
+}
+
+impl ComputedFrame {
+    /// The interval the frame's counts cover: `elapsed_ns`, or the nominal
+    /// period for frames that carry no measurement.
+    #[must_use]
+    pub fn measured_period_ns(&self) -> u64 {
+        if self.elapsed_ns > 0 {
+            self.elapsed_ns
+        } else {
+            self.sample_period_ns
+        }
+    }
+
+    /// JIT cycles spent per block compiled this sample, or `None` when no
+    /// block was compiled.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn average_block_cycles(&self) -> Option<f64> {
+        (self.total_jit_count > 0).then(|| self.total_jit_time as f64 / self.total_jit_count as f64)
+    }
 }
 
 pub struct Accumulator {
     cycle_freq: f64,
     hardware_concurrency: usize,
+    max_threads: usize,
+    first_sample: Option<Instant>,
+    last_sample: Option<Instant>,
+    /// Scratch for ranking threads by cycles, reused across frames.
+    per_thread_total_time: Vec<(u32, u64)>,
 }
 
 impl Accumulator {
@@ -69,31 +166,66 @@ impl Accumulator {
         Self {
             cycle_freq,
             hardware_concurrency,
+            max_threads: hardware_concurrency,
+            first_sample: None,
+            last_sample: None,
+            per_thread_total_time: Vec::new(),
         }
     }
 
+    /// Limits `thread_loads` to the `max_threads` busiest threads instead of
+    /// one per hardware core.
     #[must_use]
+    pub fn with_max_threads(mut self, max_threads: usize) -> Self {
+        self.max_threads = max_threads;
+        self
+    }
+
+    /// Builds a `ComputedFrame` from a sample. `timestamp_ns` is the offset of
+    /// the sample from the first sample this accumulator has seen, and
+    /// `elapsed_ns` the time since the previous one. The first sample has no
+    /// predecessor and falls back to the nominal `sample_period_ns`.
     pub fn compute_frame(
-        &self,
-        sample: &SampleResult,
+        &mut self,
+        sample: &SampleResult<'_>,
         mem: &MemSnapshot,
         sample_period_ns: u64,
         total_jit_invocations: u64,
         cumulative: CumulativeCountStats,
     ) -> ComputedFrame {
+        let first_sample = *self.first_sample.get_or_insert(sample.timestamp);
+        let timestamp_ns = u64::try_from(
+            sample
+                .timestamp
+                .saturating_duration_since(first_sample)
+                .as_nanos(),
+        )
+        .unwrap_or(u64::MAX);
+        let elapsed_ns = self
+            .last_sample
+            .replace(sample.timestamp)
+            .and_then(|prev| {
+                u64::try_from(sample.timestamp.saturating_duration_since(prev).as_nanos()).ok()
+            })
+            .filter(|&ns| ns > 0)
+            .unwrap_or(sample_period_ns);
+
         let mut frame = ComputedFrame {
+            timestamp_ns,
             sample_period_ns,
+            elapsed_ns,
             threads_sampled: sample.threads_sampled,
+            resets_detected: sample.resets_detected,
             total_jit_invocations,
             mem: mem.clone(),
             cumulative,
             ..ComputedFrame::default()
         };
 
-        let mut per_thread_total_time: Vec<(u32, u64)> =
-            Vec::with_capacity(sample.per_thread.len());
+        let per_thread_total_time = &mut self.per_thread_total_time;
+        per_thread_total_time.clear();
 
-        for delta in &sample.per_thread {
+        for delta in sample.per_thread {
             frame.total_jit_time += delta.jit_time;
             frame.total_signal_time += delta.signal_time;
             frame.total_sigbus_count += delta.sigbus_count;
@@ -113,9 +245,9 @@ impl Accumulator {
         let total_jit_time_all = frame.total_jit_time + frame.total_signal_time;
 
         #[allow(clippy::cast_precision_loss)]
-        let sample_period_ns_f64 = sample_period_ns as f64;
+        let elapsed_ns_f64 = elapsed_ns as f64;
         let max_cycles_in_sample_period =
-            self.cycle_freq * (sample_period_ns_f64 / NANOSECONDS_IN_SECOND);
+            self.cycle_freq * (elapsed_ns_f64 / NANOSECONDS_IN_SECOND);
 
         let max_cores_threads = if sample.threads_sampled == 0 {
             1.0
@@ -134,7 +266,7 @@ impl Accumulator {
                 (total_time_f64 / (max_cycles_in_sample_period * max_cores_threads)) * 100.0;
         }
 
-        let cap = self.hardware_concurrency.min(per_thread_total_time.len());
+        let cap = self.max_threads.min(per_thread_total_time.len());
         frame.thread_loads = per_thread_total_time[..cap]
             .iter()
             .map(|&(tid, total_cycles)| {
@@ -167,6 +299,7 @@ impl Accumulator {
             high_sigbus: frame.total_sigbus_count >= HIGH_SIGBUS_THRESHOLD,
             high_softfloat: frame.total_float_fallback_count >= HIGH_SOFTFLOAT_THRESHOLD,
         };
+        frame.rates = RatesPerSecond::for_frame(&frame);
 
         frame
     }
@@ -174,24 +307,26 @@ impl Accumulator {
 
 #[cfg(test)]
 mod tests {
-    use std::time::Instant;
+    use std::time::Duration;
 
     use super::*;
     use crate::sampler::thread_stats::ThreadDelta;
+    use crate::testing::count_allocations;
 
-    fn make_sample(deltas: Vec<ThreadDelta>) -> SampleResult {
+    fn make_sample(deltas: &[ThreadDelta]) -> SampleResult<'_> {
         let count = deltas.len();
         SampleResult {
             timestamp: Instant::now(),
             per_thread: deltas,
             threads_sampled: count,
+            resets_detected: 0,
         }
     }
 
     #[test]
     fn empty_sample_produces_zero_frame() {
-        let acc = Accumulator::new(1_000_000_000.0, 4);
-        let sample = make_sample(vec![]);
+        let mut acc = Accumulator::new(1_000_000_000.0, 4);
+        let sample = make_sample(&[]);
         let frame = acc.compute_frame(
             &sample,
             &MemSnapshot::default(),
@@ -207,13 +342,13 @@ mod tests {
 
     #[test]
     fn single_thread_full_load() {
-        let acc = Accumulator::new(1_000_000_000.0, 4);
+        let mut acc = Accumulator::new(1_000_000_000.0, 4);
         let delta = ThreadDelta {
             tid: 1,
             jit_time: 1_000_000_000,
             ..ThreadDelta::default()
         };
-        let sample = make_sample(vec![delta]);
+        let sample = make_sample(std::slice::from_ref(&delta));
         let frame = acc.compute_frame(
             &sample,
             &MemSnapshot::default(),
@@ -230,7 +365,7 @@ mod tests {
 
     #[test]
     fn histogram_thresholds() {
-        let acc = Accumulator::new(1_000_000_000.0, 4);
+        let mut acc = Accumulator::new(1_000_000_000.0, 4);
         let delta = ThreadDelta {
             tid: 1,
             jit_time: 100,
@@ -239,7 +374,7 @@ mod tests {
             float_fallback_count: 1_000_001,
             ..ThreadDelta::default()
         };
-        let sample = make_sample(vec![delta]);
+        let sample = make_sample(std::slice::from_ref(&delta));
         let frame = acc.compute_frame(
             &sample,
             &MemSnapshot::default(),
@@ -256,7 +391,7 @@ mod tests {
 
     #[test]
     fn thread_loads_capped_at_hardware_concurrency() {
-        let acc = Accumulator::new(1_000_000_000.0, 2);
+        let mut acc = Accumulator::new(1_000_000_000.0, 2);
         let deltas = vec![
             ThreadDelta {
                 tid: 1,
@@ -274,7 +409,7 @@ mod tests {
                 ..ThreadDelta::default()
             },
         ];
-        let sample = make_sample(deltas);
+        let sample = make_sample(&deltas);
         let frame = acc.compute_frame(
             &sample,
             &MemSnapshot::default(),
@@ -288,9 +423,34 @@ mod tests {
         assert_eq!(frame.thread_loads[1].tid, 2);
     }
 
+    #[test]
+    fn max_threads_overrides_hardware_concurrency() {
+        let deltas: Vec<ThreadDelta> = (1..=6)
+            .map(|tid| ThreadDelta {
+                tid,
+                jit_time: u64::from(tid) * 100,
+                ..ThreadDelta::default()
+            })
+            .collect();
+        let sample = make_sample(&deltas);
+
+        for (cores, top, expected) in [(2, 4, 4), (8, 3, 3)] {
+            let mut acc = Accumulator::new(1_000_000_000.0, cores).with_max_threads(top);
+            let frame = acc.compute_frame(
+                &sample,
+                &MemSnapshot::default(),
+                1_000_000_000,
+                0,
+                CumulativeCountStats::default(),
+            );
+            assert_eq!(frame.thread_loads.len(), expected);
+            assert_eq!(frame.thread_loads[0].tid, 6);
+        }
+    }
+
     #[test]
     fn totals_are_summed_across_threads() {
-        let acc = Accumulator::new(1_000_000_000.0, 4);
+        let mut acc = Accumulator::new(1_000_000_000.0, 4);
         let deltas = vec![
             ThreadDelta {
                 tid: 1,
@@ -317,7 +477,7 @@ mod tests {
                 jit_count: 120,
             },
         ];
-        let sample = make_sample(deltas);
+        let sample = make_sample(&deltas);
         let frame = acc.compute_frame(
             &sample,
             &MemSnapshot::default(),
@@ -338,10 +498,68 @@ mod tests {
         assert_eq!(frame.total_jit_invocations, 500);
     }
 
+    #[test]
+    fn rates_divide_counts_by_period() {
+        let mut acc = Accumulator::new(1_000_000_000.0, 4);
+        let delta = ThreadDelta {
+            tid: 1,
+            sigbus_count: 5,
+            cache_miss_count: 20,
+            jit_count: 3,
+            ..ThreadDelta::default()
+        };
+        let sample = make_sample(std::slice::from_ref(&delta));
+        // 1ms period: 5 events per millisecond is 5000 per second.
+        let frame = acc.compute_frame(
+            &sample,
+            &MemSnapshot::default(),
+            1_000_000,
+            0,
+            CumulativeCountStats::default(),
+        );
+
+        assert!((frame.rates.sigbus - 5_000.0).abs() < 1e-6);
+        assert!((frame.rates.cache_miss - 20_000.0).abs() < 1e-6);
+        assert!((frame.rates.jit_count - 3_000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn jit_throughput_is_derived_from_totals() {
+        let frame = ComputedFrame {
+            timestamp_ns: 4_000_000_000,
+            sample_period_ns: 1_000_000,
+            total_jit_time: 9_000,
+            total_jit_count: 3,
+            total_jit_invocations: 1_000,
+            ..ComputedFrame::default()
+        };
+        assert!((RatesPerSecond::for_frame(&frame).jit_invocations - 250.0).abs() < 1e-9);
+        assert_eq!(frame.average_block_cycles(), Some(3_000.0));
+
+        // The first frame has no time since the start, and a sample without
+        // compiles has no per-block cost.
+        let idle = ComputedFrame {
+            total_jit_time: 500,
+            total_jit_invocations: 20,
+            ..ComputedFrame::default()
+        };
+        assert!(RatesPerSecond::for_frame(&idle).jit_invocations.abs() < f64::EPSILON);
+        assert_eq!(idle.average_block_cycles(), None);
+    }
+
+    #[test]
+    fn rates_are_zero_without_a_period() {
+        let frame = ComputedFrame {
+            total_sigbus_count: 10,
+            ..ComputedFrame::default()
+        };
+        assert_eq!(RatesPerSecond::for_frame(&frame), RatesPerSecond::default());
+    }
+
     #[test]
     fn cumulative_stats_pass_through() {
-        let acc = Accumulator::new(1_000_000_000.0, 4);
-        let sample = make_sample(vec![]);
+        let mut acc = Accumulator::new(1_000_000_000.0, 4);
+        let sample = make_sample(&[]);
         let cumulative = CumulativeCountStats {
             sigbus: 100,
             smc: 200,
@@ -363,4 +581,110 @@ mod tests {
         assert_eq!(frame.cumulative.cache_miss, 400);
         assert_eq!(frame.cumulative.jit, 500);
     }
+
+    #[test]
+    fn timestamps_are_relative_to_first_sample() {
+        let mut acc = Accumulator::new(1_000_000_000.0, 4);
+        let t0 = Instant::now();
+        let mut timestamps = Vec::new();
+        for i in 0..3 {
+            let sample = SampleResult {
+                timestamp: t0 + Duration::from_millis(500 * i),
+                per_thread: &[],
+                threads_sampled: 0,
+                resets_detected: 0,
+            };
+            let frame = acc.compute_frame(
+                &sample,
+                &MemSnapshot::default(),
+                500_000_000,
+                0,
+                CumulativeCountStats::default(),
+            );
+            timestamps.push(frame.timestamp_ns);
+        }
+
+        assert_eq!(timestamps, vec![0, 500_000_000, 1_000_000_000]);
+    }
+
+    #[test]
+    fn load_uses_measured_interval_after_first_sample() {
+        let mut acc = Accumulator::new(1_000_000_000.0, 4);
+        let t0 = Instant::now();
+        let mut frames = Vec::new();
+        // Nominally 1s apart, but the second sample arrives 2s after the first.
+        for offset in [0, 2000] {
+            let sample = SampleResult {
+                timestamp: t0 + Duration::from_millis(offset),
+                per_thread: &[ThreadDelta {
+                    tid: 1,
+                    jit_time: 1_000_000_000,
+                    sigbus_count: 10,
+                    ..ThreadDelta::default()
+                }],
+                threads_sampled: 1,
+                resets_detected: 0,
+            };
+            frames.push(acc.compute_frame(
+                &sample,
+                &MemSnapshot::default(),
+                1_000_000_000,
+                0,
+                CumulativeCountStats::default(),
+            ));
+        }
+
+        assert_eq!(frames[0].elapsed_ns, 1_000_000_000);
+        assert!((frames[0].fex_load_percent - 100.0).abs() < 0.01);
+        assert_eq!(frames[1].sample_period_ns, 1_000_000_000);
+        assert_eq!(frames[1].elapsed_ns, 2_000_000_000);
+        assert!((frames[1].fex_load_percent - 50.0).abs() < 0.01);
+        assert!((frames[1].rates.sigbus - 5.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn steady_state_frames_allocate_only_their_thread_loads() {
+        let mut acc = Accumulator::new(1_000_000_000.0, 4);
+        let deltas: Vec<ThreadDelta> = (1..=6)
+            .map(|tid| ThreadDelta {
+                tid,
+                jit_time: u64::from(tid) * 1000,
+                ..ThreadDelta::default()
+            })
+            .collect();
+        let mut compute = || {
+            acc.compute_frame(
+                &make_sample(&deltas),
+                &MemSnapshot::default(),
+                1_000_000_000,
+                0,
+                CumulativeCountStats::default(),
+            )
+        };
+        compute();
+
+        let (frame, allocations) = count_allocations(&mut compute);
+        assert_eq!(frame.thread_loads.len(), 4);
+        assert_eq!(allocations, 1);
+    }
+
+    #[test]
+    fn session_totals_sum_per_sample_counts() {
+        let mut totals = CumulativeCountStats::default();
+        let frame = ComputedFrame {
+            total_sigbus_count: 3,
+            total_smc_count: 1,
+            total_float_fallback_count: 10,
+            total_cache_miss_count: 7,
+            total_jit_count: 2,
+            ..ComputedFrame::default()
+        };
+        totals.add_sample(&frame);
+        totals.add_sample(&frame);
+        assert_eq!(totals.sigbus, 6);
+        assert_eq!(totals.smc, 2);
+        assert_eq!(totals.float_fallback, 20);
+        assert_eq!(totals.cache_miss, 14);
+        assert_eq!(totals.jit, 4);
+    }
 }
@@ -1,41 +1,78 @@
 // SPDX-License-Identifier: MIT
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
-use crate::fex::smaps::{MemSampler, MemSnapshot};
+use crate::fex::smaps::{MemOptions, MemSampler, MemSnapshot};
+
+/// What the background thread last observed: the most recent successful
+/// snapshot, the error from the latest attempt if it failed, and the
+/// unrecognized regions seen so far if they are being tracked.
+#[derive(Default)]
+struct MemStatus {
+    snapshot: MemSnapshot,
+    error: Option<String>,
+    unknown_regions: Option<BTreeMap<String, u64>>,
+}
 
 pub struct MemStatsWorker {
-    latest: Arc<Mutex<MemSnapshot>>,
+    latest: Arc<Mutex<MemStatus>>,
     shutdown: Arc<AtomicBool>,
+    period_nanos: Arc<AtomicU64>,
     handle: Option<thread::JoinHandle<()>>,
 }
 
 impl MemStatsWorker {
-    /// Spawns a background thread that periodically samples `/proc/{pid}/smaps`.
+    /// Spawns a background thread that periodically samples `/proc/{pid}/smaps`
+    /// (or `smaps_rollup`, depending on `options.mode`).
     ///
     /// # Errors
     ///
     /// Returns an error if the initial `MemSampler` cannot be created.
-    pub fn spawn(pid: i32, sample_period: Duration) -> anyhow::Result<Self> {
-        let mut sampler = MemSampler::new(pid)?;
-        let latest = Arc::new(Mutex::new(MemSnapshot::default()));
+    pub fn spawn(pid: i32, sample_period: Duration, options: &MemOptions) -> anyhow::Result<Self> {
+        let mut sampler = MemSampler::open(pid, options)?;
+        let latest = Arc::new(Mutex::new(MemStatus::default()));
         let shutdown = Arc::new(AtomicBool::new(false));
+        let period_nanos = Arc::new(AtomicU64::new(duration_to_nanos(sample_period)));
 
         let latest_clone = Arc::clone(&latest);
         let shutdown_clone = Arc::clone(&shutdown);
+        let period_clone = Arc::clone(&period_nanos);
 
         let handle = thread::Builder::new()
             .name("mem-sampler".into())
             .spawn(move || {
                 while !shutdown_clone.load(Ordering::Relaxed) {
-                    if let Ok(snap) = sampler.sample()
-                        && let Ok(mut guard) = latest_clone.lock()
-                    {
-                        *guard = snap;
+                    let result = sampler.sample();
+                    if let Ok(mut status) = latest_clone.lock() {
+                        match result {
+                            Ok(snap) => {
+                                if status.error.take().is_some() {
+                                    tracing::info!(pid, "memory sampling recovered");
+                                }
+                                status.snapshot = snap;
+                            }
+                            Err(e) => {
+                                let error = format!("{e:#}");
+                                // Failures tend to repeat every period; log
+                                // only the first of a run.
+                                if status.error.is_none() {
+                                    tracing::warn!(
+                                        pid,
+                                        error = %error,
+                                        "memory sampling failed"
+                                    );
+                                }
+                                status.error = Some(error);
+                            }
+                        }
+                        if let Some(regions) = sampler.unknown_regions() {
+                            status.unknown_regions = Some(regions.clone());
+                        }
                     }
-                    thread::sleep(sample_period);
+                    thread::sleep(Duration::from_nanos(period_clone.load(Ordering::Relaxed)));
                 }
             })
             .map_err(|e| anyhow::anyhow!("failed to spawn mem-sampler thread: {e}"))?;
@@ -43,15 +80,45 @@ impl MemStatsWorker {
         Ok(Self {
             latest,
             shutdown,
+            period_nanos,
             handle: Some(handle),
         })
     }
 
+    /// Changes how often the background thread samples smaps. Takes effect
+    /// after the thread's current sleep finishes.
+    pub fn set_sample_period(&self, sample_period: Duration) {
+        self.period_nanos
+            .store(duration_to_nanos(sample_period), Ordering::Relaxed);
+    }
+
     #[must_use]
     pub fn latest(&self) -> MemSnapshot {
+        self.latest_with_status().0
+    }
+
+    /// Returns the latest snapshot along with the reason the most recent
+    /// sample failed, if it did. The snapshot is the last good one, or
+    /// empty if no sample has succeeded yet.
+    #[must_use]
+    pub fn latest_with_status(&self) -> (MemSnapshot, Option<String>) {
+        match self.latest.lock() {
+            Ok(status) => (status.snapshot.clone(), status.error.clone()),
+            Err(_) => (
+                MemSnapshot::default(),
+                Some("memory sampler thread panicked".into()),
+            ),
+        }
+    }
+
+    /// Unrecognized FEX region names with their peak resident bytes, or
+    /// `None` if the worker was not spawned with `track_unknown` set.
+    #[must_use]
+    pub fn unknown_regions(&self) -> Option<BTreeMap<String, u64>> {
         self.latest
             .lock()
-            .map_or_else(|_| MemSnapshot::default(), |guard| guard.clone())
+            .ok()
+            .and_then(|status| status.unknown_regions.clone())
     }
 
     pub fn shutdown(&mut self) {
@@ -62,6 +129,10 @@ impl MemStatsWorker {
     }
 }
 
+fn duration_to_nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
 impl Drop for MemStatsWorker {
     fn drop(&mut self) {
         self.shutdown();
@@ -22,62 +22,82 @@ pub struct ThreadDelta {
     pub jit_count: u64,
 }
 
-pub struct SampleResult {
-    #[allow(dead_code)]
+pub struct SampleResult<'a> {
     pub timestamp: Instant,
-    pub per_thread: Vec<ThreadDelta>,
+    /// Borrowed from the sampler, which reuses the buffer on the next sample.
+    pub per_thread: &'a [ThreadDelta],
     pub threads_sampled: usize,
+    /// Counters that were lower than on the previous sample, from a reused
+    /// tid or a reset on the FEX side. Their deltas are reported as zero.
+    pub resets_detected: u64,
 }
 
 pub struct ThreadSampler {
     previous: BTreeMap<u32, ThreadStats>,
     last_seen: BTreeMap<u32, Instant>,
     stale_timeout: Duration,
+    deltas: Vec<ThreadDelta>,
+    /// A buffer handed back through `recycle`, swapped in by `take_deltas`.
+    spare: Vec<ThreadDelta>,
 }
 
 impl ThreadSampler {
     #[must_use]
     pub fn new() -> Self {
+        Self::with_stale_timeout(DEFAULT_STALE_TIMEOUT)
+    }
+
+    /// Creates a sampler that forgets a thread once it has been missing
+    /// from the stats list for `stale_timeout`.
+    #[must_use]
+    pub fn with_stale_timeout(stale_timeout: Duration) -> Self {
         Self {
             previous: BTreeMap::new(),
             last_seen: BTreeMap::new(),
-            stale_timeout: DEFAULT_STALE_TIMEOUT,
+            stale_timeout,
+            deltas: Vec::new(),
+            spare: Vec::new(),
         }
     }
 
-    pub fn sample(&mut self, raw_stats: &[ThreadStats], now: Instant) -> SampleResult {
-        let mut deltas = Vec::with_capacity(raw_stats.len());
+    pub fn sample(&mut self, raw_stats: &[ThreadStats], now: Instant) -> SampleResult<'_> {
+        self.deltas.clear();
+        let mut resets_detected = 0;
 
         for stat in raw_stats {
             let tid = stat.tid;
             self.last_seen.insert(tid, now);
 
             let delta = if let Some(prev) = self.previous.get(&tid) {
+                let mut delta = |current: u64, previous: u64| {
+                    current.checked_sub(previous).unwrap_or_else(|| {
+                        resets_detected += 1;
+                        0
+                    })
+                };
                 ThreadDelta {
                     tid,
-                    jit_time: stat
-                        .accumulated_jit_time
-                        .wrapping_sub(prev.accumulated_jit_time),
-                    signal_time: stat
-                        .accumulated_signal_time
-                        .wrapping_sub(prev.accumulated_signal_time),
-                    sigbus_count: stat.sigbus_count.wrapping_sub(prev.sigbus_count),
-                    smc_count: stat.smc_count.wrapping_sub(prev.smc_count),
-                    float_fallback_count: stat
-                        .float_fallback_count
-                        .wrapping_sub(prev.float_fallback_count),
-                    cache_miss_count: stat
-                        .accumulated_cache_miss_count
-                        .wrapping_sub(prev.accumulated_cache_miss_count),
-                    cache_read_lock_time: stat
-                        .accumulated_cache_read_lock_time
-                        .wrapping_sub(prev.accumulated_cache_read_lock_time),
-                    cache_write_lock_time: stat
-                        .accumulated_cache_write_lock_time
-                        .wrapping_sub(prev.accumulated_cache_write_lock_time),
-                    jit_count: stat
-                        .accumulated_jit_count
-                        .wrapping_sub(prev.accumulated_jit_count),
+                    jit_time: delta(stat.accumulated_jit_time, prev.accumulated_jit_time),
+                    signal_time: delta(stat.accumulated_signal_time, prev.accumulated_signal_time),
+                    sigbus_count: delta(stat.sigbus_count, prev.sigbus_count),
+                    smc_count: delta(stat.smc_count, prev.smc_count),
+                    float_fallback_count: delta(
+                        stat.float_fallback_count,
+                        prev.float_fallback_count,
+                    ),
+                    cache_miss_count: delta(
+                        stat.accumulated_cache_miss_count,
+                        prev.accumulated_cache_miss_count,
+                    ),
+                    cache_read_lock_time: delta(
+                        stat.accumulated_cache_read_lock_time,
+                        prev.accumulated_cache_read_lock_time,
+                    ),
+                    cache_write_lock_time: delta(
+                        stat.accumulated_cache_write_lock_time,
+                        prev.accumulated_cache_write_lock_time,
+                    ),
+                    jit_count: delta(stat.accumulated_jit_count, prev.accumulated_jit_count),
                 }
             } else {
                 ThreadDelta {
@@ -87,10 +107,10 @@ impl ThreadSampler {
             };
 
             self.previous.insert(tid, *stat);
-            deltas.push(delta);
+            self.deltas.push(delta);
         }
 
-        let threads_sampled = deltas.len();
+        let threads_sampled = self.deltas.len();
 
         self.last_seen
             .retain(|_, seen| now.duration_since(*seen) < self.stale_timeout);
@@ -99,12 +119,29 @@ impl ThreadSampler {
 
         SampleResult {
             timestamp: now,
-            per_thread: deltas,
+            per_thread: &self.deltas,
             threads_sampled,
+            resets_detected,
         }
     }
 }
 
+impl ThreadSampler {
+    /// Hands over the deltas of the latest sample, swapping in the buffer
+    /// last passed to `recycle` so the next sample does not allocate.
+    pub fn take_deltas(&mut self) -> Vec<ThreadDelta> {
+        let mut spare = std::mem::take(&mut self.spare);
+        spare.clear();
+        spare.reserve(self.deltas.len());
+        std::mem::replace(&mut self.deltas, spare)
+    }
+
+    /// Returns a buffer from `take_deltas` once its frame has been written.
+    pub fn recycle(&mut self, buffer: Vec<ThreadDelta>) {
+        self.spare = buffer;
+    }
+}
+
 impl Default for ThreadSampler {
     fn default() -> Self {
         Self::new()
@@ -114,6 +151,7 @@ impl Default for ThreadSampler {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::count_allocations;
 
     fn make_stats(tid: u32, jit_time: u64, signal_time: u64) -> ThreadStats {
         ThreadStats {
@@ -163,6 +201,21 @@ mod tests {
         assert!(!sampler.previous.contains_key(&2));
     }
 
+    #[test]
+    fn custom_stale_timeout() {
+        let mut sampler = ThreadSampler::with_stale_timeout(Duration::from_millis(500));
+        let t0 = Instant::now();
+        sampler.sample(&[make_stats(1, 100, 50), make_stats(2, 200, 100)], t0);
+
+        let t1 = t0 + Duration::from_millis(400);
+        sampler.sample(&[make_stats(1, 200, 60)], t1);
+        assert!(sampler.previous.contains_key(&2));
+
+        let t2 = t0 + Duration::from_millis(600);
+        sampler.sample(&[make_stats(1, 300, 70)], t2);
+        assert!(!sampler.previous.contains_key(&2));
+    }
+
     #[test]
     fn multiple_threads_deltas() {
         let mut sampler = ThreadSampler::new();
@@ -178,4 +231,54 @@ mod tests {
         assert_eq!(result.per_thread[1].tid, 20);
         assert_eq!(result.per_thread[1].jit_time, 1000);
     }
+
+    #[test]
+    fn steady_state_sampling_does_not_allocate() {
+        let mut sampler = ThreadSampler::new();
+        let stats = [make_stats(1, 100, 50), make_stats(2, 200, 100)];
+        let t0 = Instant::now();
+        let mut sample_and_recycle = |secs| {
+            sampler.sample(&stats, t0 + Duration::from_secs(secs));
+            let deltas = sampler.take_deltas();
+            assert_eq!(deltas.len(), 2);
+            sampler.recycle(deltas);
+        };
+        // The first samples size the maps and both delta buffers.
+        sample_and_recycle(0);
+        sample_and_recycle(1);
+
+        let ((), allocations) = count_allocations(|| (2..10).for_each(&mut sample_and_recycle));
+        assert_eq!(allocations, 0);
+    }
+
+    #[test]
+    fn counter_reset_yields_zero_delta() {
+        let mut sampler = ThreadSampler::new();
+        let t0 = Instant::now();
+        sampler.sample(&[make_stats(1, 5000, 800)], t0);
+
+        // The tid was reused: jit time restarted, signal time kept counting.
+        let result = sampler.sample(&[make_stats(1, 300, 900)], t0 + Duration::from_secs(1));
+        assert_eq!(result.resets_detected, 1);
+        assert_eq!(result.per_thread[0].jit_time, 0);
+        assert_eq!(result.per_thread[0].signal_time, 100);
+
+        // Deltas resume from the reset value.
+        let result = sampler.sample(&[make_stats(1, 700, 900)], t0 + Duration::from_secs(2));
+        assert_eq!(result.resets_detected, 0);
+        assert_eq!(result.per_thread[0].jit_time, 400);
+    }
+
+    #[test]
+    fn resets_are_counted_per_counter() {
+        let mut sampler = ThreadSampler::new();
+        let t0 = Instant::now();
+        sampler.sample(&[make_stats(1, 5000, 800), make_stats(2, 10, 10)], t0);
+
+        let stats = [make_stats(1, 0, 0), make_stats(2, 0, 20)];
+        let result = sampler.sample(&stats, t0 + Duration::from_secs(1));
+        assert_eq!(result.resets_detected, 3);
+        assert!(result.per_thread.iter().all(|d| d.jit_time == 0));
+        assert_eq!(result.per_thread[1].signal_time, 10);
+    }
 }
@@ -0,0 +1,184 @@
+// SPDX-License-Identifier: MIT
+//! Exact percentiles over every frame of a recording.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::sampler::accumulator::ComputedFrame;
+use crate::tui::panels::mem_stats::format_bytes;
+
+/// Percentiles reported for each series.
+const PERCENTILES: [f64; 3] = [50.0, 95.0, 99.0];
+
+/// Nearest-rank percentile of an ascending slice: the smallest value with at
+/// least `percent`% of the samples at or below it. Empty slices yield zero.
+#[must_use]
+pub fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[derive(Debug, Serialize)]
+pub struct SeriesPercentiles {
+    pub name: &'static str,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl SeriesPercentiles {
+    fn from_values(name: &'static str, mut values: Vec<f64>) -> Self {
+        values.sort_by(f64::total_cmp);
+        let [p50, p95, p99] = PERCENTILES.map(|p| percentile(&values, p));
+        Self {
+            name,
+            p50,
+            p95,
+            p99,
+            max: values.last().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Highest resident size of one memory region across the recording.
+#[derive(Debug, Serialize)]
+pub struct MemPeak {
+    pub region: &'static str,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsReport {
+    pub frames: usize,
+    pub series: Vec<SeriesPercentiles>,
+    pub mem_peaks: Vec<MemPeak>,
+}
+
+/// Converts a per-frame count to events per second.
+#[allow(clippy::cast_precision_loss)]
+fn per_second(count: u64, period_ns: u64) -> f64 {
+    if period_ns == 0 {
+        return 0.0;
+    }
+    count as f64 * 1e9 / period_ns as f64
+}
+
+/// Computes load and rate percentiles plus memory high-water marks.
+#[must_use]
+pub fn compute(frames: &[ComputedFrame]) -> StatsReport {
+    let rate = |count: fn(&ComputedFrame) -> u64| -> Vec<f64> {
+        frames
+            .iter()
+            .map(|f| per_second(count(f), f.measured_period_ns()))
+            .collect()
+    };
+    let series = vec![
+        SeriesPercentiles::from_values(
+            "fex_load_percent",
+            frames.iter().map(|f| f.fex_load_percent).collect(),
+        ),
+        SeriesPercentiles::from_values("sigbus_per_second", rate(|f| f.total_sigbus_count)),
+        SeriesPercentiles::from_values("smc_per_second", rate(|f| f.total_smc_count)),
+        SeriesPercentiles::from_values(
+            "softfloat_per_second",
+            rate(|f| f.total_float_fallback_count),
+        ),
+    ];
+
+    let mut mem_peaks: Vec<MemPeak> = ComputedFrame::default()
+        .mem
+        .regions()
+        .iter()
+        .map(|&(region, _)| MemPeak { region, bytes: 0 })
+        .collect();
+    for frame in frames {
+        for (peak, (_, bytes)) in mem_peaks.iter_mut().zip(frame.mem.regions()) {
+            peak.bytes = peak.bytes.max(bytes);
+        }
+    }
+
+    StatsReport {
+        frames: frames.len(),
+        series,
+        mem_peaks,
+    }
+}
+
+impl fmt::Display for StatsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Frames: {}", self.frames)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<22} {:>12} {:>12} {:>12} {:>12}",
+            "series", "p50", "p95", "p99", "max"
+        )?;
+        for s in &self.series {
+            writeln!(
+                f,
+                "{:<22} {:>12.2} {:>12.2} {:>12.2} {:>12.2}",
+                s.name, s.p50, s.p95, s.p99, s.max
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Memory high-water marks:")?;
+        for peak in &self.mem_peaks {
+            writeln!(f, "  {:<16} {}", peak.region, format_bytes(peak.bytes))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let values: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert!((percentile(&values, 50.0) - 50.0).abs() < f64::EPSILON);
+        assert!((percentile(&values, 95.0) - 95.0).abs() < f64::EPSILON);
+        assert!((percentile(&values, 99.0) - 99.0).abs() < f64::EPSILON);
+        assert!((percentile(&values, 0.0) - 1.0).abs() < f64::EPSILON);
+        assert!((percentile(&[7.0], 99.0) - 7.0).abs() < f64::EPSILON);
+        assert!(percentile(&[], 50.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn reports_rates_and_memory_peaks() {
+        let frames: Vec<ComputedFrame> = [(10, 4096), (40, 1024), (20, 2048)]
+            .into_iter()
+            .map(|(sigbus, jit)| {
+                let mut frame = ComputedFrame {
+                    sample_period_ns: 500_000_000,
+                    total_sigbus_count: sigbus,
+                    ..ComputedFrame::default()
+                };
+                frame.mem.jit_code = jit;
+                frame
+            })
+            .collect();
+        let report = compute(&frames);
+
+        let sigbus = &report.series[1];
+        assert_eq!(sigbus.name, "sigbus_per_second");
+        assert!((sigbus.p50 - 40.0).abs() < f64::EPSILON);
+        assert!((sigbus.max - 80.0).abs() < f64::EPSILON);
+        let jit = report
+            .mem_peaks
+            .iter()
+            .find(|p| p.region == "jit_code")
+            .unwrap();
+        assert_eq!(jit.bytes, 4096);
+    }
+}
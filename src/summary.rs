@@ -0,0 +1,220 @@
+// SPDX-License-Identifier: MIT
+use std::collections::BTreeSet;
+use std::fmt;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::recording::format::Frame;
+use crate::sampler::accumulator::ComputedFrame;
+use crate::tui::panels::mem_stats::format_bytes;
+
+/// Running aggregate over a sequence of frames.
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    pub frames: u64,
+    pub duration_ns: u64,
+    pub min_load: f64,
+    pub max_load: f64,
+    pub total_sigbus: u64,
+    pub total_smc: u64,
+    pub total_float_fallback: u64,
+    pub total_cache_miss: u64,
+    pub total_jit: u64,
+    /// Largest resident JIT code size seen in any frame, in bytes.
+    pub peak_jit_code: u64,
+    /// Largest total anonymous memory seen in any frame, in bytes.
+    pub peak_mem: u64,
+    load_sum: f64,
+}
+
+impl Summary {
+    pub fn add(&mut self, frame: &ComputedFrame) {
+        let load = frame.fex_load_percent;
+        if self.frames == 0 {
+            self.min_load = load;
+            self.max_load = load;
+        } else {
+            self.min_load = self.min_load.min(load);
+            self.max_load = self.max_load.max(load);
+        }
+        self.load_sum += load;
+        self.total_sigbus += frame.total_sigbus_count;
+        self.total_smc += frame.total_smc_count;
+        self.total_float_fallback += frame.total_float_fallback_count;
+        self.total_cache_miss += frame.total_cache_miss_count;
+        self.total_jit += frame.total_jit_count;
+        self.peak_jit_code = self.peak_jit_code.max(frame.mem.jit_code);
+        self.peak_mem = self.peak_mem.max(frame.mem.total_anon);
+        self.frames += 1;
+        self.duration_ns = self.duration_ns.saturating_add(frame.sample_period_ns);
+    }
+
+    #[must_use]
+    pub fn mean_load(&self) -> f64 {
+        if self.frames == 0 {
+            return 0.0;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let mean = self.load_sum / self.frames as f64;
+        mean
+    }
+
+    /// SIGBUS events per second over the whole duration.
+    #[must_use]
+    pub fn sigbus_rate(&self) -> f64 {
+        if self.duration_ns == 0 {
+            return 0.0;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        let rate = self.total_sigbus as f64 * 1e9 / self.duration_ns as f64;
+        rate
+    }
+}
+
+/// Totals `record` keeps while it runs, for the report it prints when it
+/// stops.
+#[derive(Default)]
+pub struct RecordTally {
+    summary: Summary,
+    tids: BTreeSet<u32>,
+}
+
+impl RecordTally {
+    pub fn add(&mut self, frame: &Frame) {
+        self.summary.add(&frame.computed);
+        self.tids
+            .extend(frame.per_thread_deltas.iter().map(|delta| delta.tid));
+    }
+
+    /// The report for a capture that ran for `duration` of wall-clock time.
+    #[must_use]
+    pub fn report(&self, duration: Duration) -> RecordReport {
+        RecordReport {
+            duration_secs: duration.as_secs_f64(),
+            frames: self.summary.frames,
+            mean_load_percent: self.summary.mean_load(),
+            peak_load_percent: self.summary.max_load,
+            total_sigbus: self.summary.total_sigbus,
+            total_smc: self.summary.total_smc,
+            total_float_fallback: self.summary.total_float_fallback,
+            peak_mem_bytes: self.summary.peak_mem,
+            distinct_threads: self.tids.len(),
+        }
+    }
+}
+
+/// End-of-capture report, printed by `record` and optionally written as
+/// JSON next to the recording.
+#[derive(Debug, Serialize)]
+pub struct RecordReport {
+    pub duration_secs: f64,
+    pub frames: u64,
+    pub mean_load_percent: f64,
+    pub peak_load_percent: f64,
+    pub total_sigbus: u64,
+    pub total_smc: u64,
+    pub total_float_fallback: u64,
+    pub peak_mem_bytes: u64,
+    pub distinct_threads: usize,
+}
+
+impl fmt::Display for RecordReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "  Duration:       {:.1} s, {} frames",
+            self.duration_secs, self.frames
+        )?;
+        writeln!(
+            f,
+            "  FEX load:       mean {:.2}%  peak {:.2}%",
+            self.mean_load_percent, self.peak_load_percent
+        )?;
+        writeln!(
+            f,
+            "  Events:         {} SIGBUS, {} SMC, {} softfloat",
+            self.total_sigbus, self.total_smc, self.total_float_fallback
+        )?;
+        writeln!(f, "  Peak memory:    {}", format_bytes(self.peak_mem_bytes))?;
+        writeln!(f, "  Threads seen:   {}", self.distinct_threads)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(load: f64) -> ComputedFrame {
+        ComputedFrame {
+            fex_load_percent: load,
+            sample_period_ns: 500_000_000,
+            ..ComputedFrame::default()
+        }
+    }
+
+    #[test]
+    fn empty_summary_is_zero() {
+        let summary = Summary::default();
+        assert_eq!(summary.frames, 0);
+        assert!(summary.mean_load().abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn load_min_max_mean() {
+        let mut summary = Summary::default();
+        for load in [10.0, 40.0, 25.0] {
+            summary.add(&frame(load));
+        }
+        assert_eq!(summary.frames, 3);
+        assert_eq!(summary.duration_ns, 1_500_000_000);
+        assert!((summary.min_load - 10.0).abs() < f64::EPSILON);
+        assert!((summary.max_load - 40.0).abs() < f64::EPSILON);
+        assert!((summary.mean_load() - 25.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn sigbus_rate_and_peak_jit_code() {
+        let mut summary = Summary::default();
+        for (sigbus, jit) in [(10, 4096), (20, 8192), (0, 1024)] {
+            let mut f = frame(0.0);
+            f.total_sigbus_count = sigbus;
+            f.mem.jit_code = jit;
+            summary.add(&f);
+        }
+        // 30 events over 1.5 s.
+        assert!((summary.sigbus_rate() - 20.0).abs() < 1e-9);
+        assert_eq!(summary.peak_jit_code, 8192);
+    }
+
+    #[test]
+    fn record_report_counts_events_memory_and_distinct_threads() {
+        use crate::sampler::thread_stats::ThreadDelta;
+
+        let mut tally = RecordTally::default();
+        for (load, tids, anon) in [(20.0, [1, 2], 4096), (60.0, [2, 3], 1024)] {
+            let mut computed = frame(load);
+            computed.total_smc_count = 2;
+            computed.total_float_fallback_count = 5;
+            computed.mem.total_anon = anon;
+            tally.add(&Frame {
+                computed,
+                per_thread_deltas: tids
+                    .into_iter()
+                    .map(|tid| ThreadDelta {
+                        tid,
+                        ..ThreadDelta::default()
+                    })
+                    .collect(),
+            });
+        }
+        let report = tally.report(Duration::from_millis(1500));
+        assert_eq!(report.frames, 2);
+        assert!((report.mean_load_percent - 40.0).abs() < f64::EPSILON);
+        assert!((report.peak_load_percent - 60.0).abs() < f64::EPSILON);
+        assert_eq!((report.total_smc, report.total_float_fallback), (4, 10));
+        assert_eq!(report.peak_mem_bytes, 4096);
+        assert_eq!(report.distinct_threads, 3);
+        assert!(report.to_string().contains("Threads seen:   3"));
+    }
+}
@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: MIT
+//! Helpers shared by the unit tests.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::time::SystemTime;
+
+use crate::datasource::SessionMetadata;
+use crate::fex::types::AppType;
+
+/// Session metadata for tests; override the fields a test depends on with
+/// struct update syntax.
+pub fn make_metadata() -> SessionMetadata {
+    SessionMetadata {
+        pid: 1234,
+        fex_version: "FEX-2501".to_string(),
+        app_type: AppType::Linux64,
+        stats_version: 3,
+        cycle_counter_frequency: 1_000_000_000,
+        hardware_concurrency: 8,
+        recording_start: SystemTime::UNIX_EPOCH,
+        head: 0x40,
+        size: 0x1000,
+    }
+}
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts allocations per thread, so a test can check that a steady-state
+/// path does not allocate while other tests run alongside it.
+struct CountingAllocator;
+
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+// SAFETY: Every call is forwarded to the system allocator unchanged.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        // SAFETY: Upheld by the caller.
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: Upheld by the caller.
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        // SAFETY: Upheld by the caller.
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Runs `f` and returns its result with the number of allocations and
+/// reallocations it made on this thread.
+pub fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
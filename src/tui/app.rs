@@ -1,51 +1,181 @@
 // SPDX-License-Identifier: MIT
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
-use ratatui::layout::{Constraint, Direction, Layout};
+use anyhow::{Context, Result};
+use crossterm::event::KeyCode;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Paragraph};
 
-use super::input::Action;
-use super::layout::{PanelState, build_layout};
-use super::panels::{header, histogram, jit_stats, mem_stats};
+use super::help;
+use super::input::{Action, Keymap};
+use super::layout::{PanelState, build_layout, build_wide_layout, too_short, too_short_wide};
+use super::panels::cache_efficiency::{self, CachePoint};
+use super::panels::cache_locks::{self, LockPoint};
+use super::panels::header::{self, HeaderStatus};
+use super::panels::jit_stats::{SessionTotals, ThreadSort, ThreadView, TimeUnit};
+use super::panels::mem_history::{self, MemPoint};
+use super::panels::mem_stats::{self, MemView};
+use super::panels::thread_history::{self, FollowedThread};
+use super::panels::{histogram, jit_stats};
 use super::replay_controls::{self, ReplayControls};
 use super::theme::{COLLAPSED_MARKER, SELECTED_MARKER, Theme};
 use crate::datasource::SessionMetadata;
-use crate::sampler::accumulator::{ComputedFrame, HistogramEntry};
+use crate::fex::types::stats_version_warning;
+use crate::sampler::accumulator::{ComputedFrame, CumulativeCountStats, HistogramEntry};
+use crate::wallclock;
 
-const HISTOGRAM_CAPACITY: usize = 200;
+/// Default number of samples kept for the histogram and memory history.
+pub const DEFAULT_HISTORY: usize = 200;
 const REPLAY_BAR_HEIGHT: u16 = 4;
+// Panel names, which identify panels wherever `App::panels` has put them.
+const JIT_STATS: &str = "FEX JIT Stats";
+const MEM_STATS: &str = "FEX Memory Usage";
+const MEM_HISTORY: &str = "Memory over time";
+const HISTOGRAM: &str = "Total JIT usage";
+const CACHE_LOCKS: &str = "Cache lock contention";
+const THREAD_HISTORY: &str = "Thread load over time";
+const CACHE_EFFICIENCY: &str = "Lookup cache efficiency";
+/// Narrowest body the wide layout splits into columns; below it the panels
+/// stack as usual.
+const WIDE_MIN_WIDTH: u16 = 160;
+/// How long the header shows the result of saving a snapshot.
+const SNAPSHOT_NOTICE_DURATION: Duration = Duration::from_secs(3);
 
+#[allow(clippy::struct_excessive_bools)] // independent UI flags
 pub struct App {
     pub panels: Vec<PanelState>,
     pub selected_panel: usize,
     pub latest_frame: Option<ComputedFrame>,
     pub histogram: VecDeque<HistogramEntry>,
+    pub mem_history: VecDeque<MemPoint>,
+    pub lock_history: VecDeque<LockPoint>,
+    pub cache_history: VecDeque<CachePoint>,
+    /// Load of each recently reported thread per kept sample, `None` for
+    /// samples it was missing from. A thread is dropped once it has been
+    /// missing for the whole history.
+    pub thread_history: BTreeMap<u32, VecDeque<Option<f32>>>,
+    /// Thread shown in the thread history panel, picked with 1-9.
+    pub followed_thread: Option<u32>,
+    /// Thread listed first and highlighted in the JIT panel, picked by tid
+    /// with `/`.
+    pub pinned_thread: Option<u32>,
+    /// Tid typed so far while the `/` prompt is open.
+    tid_input: Option<String>,
     pub metadata: SessionMetadata,
     pub is_replay: bool,
     pub should_quit: bool,
+    pub show_help: bool,
+    pub thread_sort: ThreadSort,
+    /// Unit of the JIT panel's times.
+    pub time_unit: TimeUnit,
+    /// Threads below this load percentage are hidden from the JIT panel
+    /// while `thread_filter` is on.
+    pub min_thread_load: f32,
+    pub thread_filter: bool,
+    /// Show Pss and Swap instead of Rss in the memory panel's total.
+    pub show_pss: bool,
+    /// Draw the memory regions as a stacked bar instead of a list.
+    pub mem_bar: bool,
+    /// Show only the selected panel, full height, even when all would fit.
+    pub compact: bool,
+    /// Put the JIT stats panel in a left column beside the other panels when
+    /// the terminal is wide enough.
+    pub wide: bool,
+    /// Key bindings, the defaults unless `--keymap` overrides some.
+    pub keymap: Keymap,
+    /// MiB per minute of JIT or total anonymous growth above which the
+    /// memory panel warns of a possible leak.
+    pub mem_growth_warn: Option<f64>,
     pub theme: Theme,
+    /// Weight of the newest sample in the displayed loads' moving average;
+    /// 0 shows raw values.
+    pub smoothing: f64,
+    /// Smoothed FEX load and per-thread loads from the previous frame.
+    smoothed_load: Option<f64>,
+    smoothed_thread_loads: BTreeMap<u32, f32>,
+    /// Highest raw FEX load and resident anonymous memory since the start,
+    /// the last reset, or (in replay) playback last showed the first frame.
+    peak_load: f64,
+    peak_mem_total: u64,
+    /// Per-sample event counts summed since attaching, shown live only.
+    session_counts: CumulativeCountStats,
     replay_controls: Option<ReplayControls>,
+    history_capacity: usize,
+    waiting_for_process: bool,
+    /// Latest memory sampling failure, shown in place of the memory panel.
+    mem_error: Option<String>,
+    /// Snapshot shown while the live view is frozen. Sampling keeps updating
+    /// `latest_frame` and `histogram` underneath it.
+    frozen: Option<FrozenView>,
+    /// Set when something visible changed since the last draw.
+    dirty: bool,
+    /// Sample periods skipped because sampling fell behind schedule.
+    dropped_samples: u64,
+    /// When the live view started, for the header's elapsed time.
+    session_start: Instant,
+    /// Elapsed whole seconds as of the last draw.
+    shown_elapsed_secs: u64,
+    /// Outcome of the last `s` snapshot and when it was taken.
+    snapshot_notice: Option<(String, Instant)>,
+    /// File a live `--record` session is writing, and the bytes written to
+    /// it as of the last sample, for the header's REC badge.
+    recording: Option<(PathBuf, u64)>,
+}
+
+struct FrozenView {
+    frame: Option<ComputedFrame>,
+    histogram: VecDeque<HistogramEntry>,
+    mem_history: VecDeque<MemPoint>,
+    lock_history: VecDeque<LockPoint>,
+    cache_history: VecDeque<CachePoint>,
+    thread_history: BTreeMap<u32, VecDeque<Option<f32>>>,
 }
 
 impl App {
     #[must_use]
-    pub fn new(metadata: SessionMetadata, is_replay: bool) -> Self {
+    pub fn new(metadata: SessionMetadata, is_replay: bool, history_capacity: usize) -> Self {
         let panels = vec![
             PanelState {
-                name: "FEX JIT Stats",
+                name: JIT_STATS,
                 collapsed: false,
                 min_height: 26,
             },
             PanelState {
-                name: "FEX Memory Usage",
+                name: MEM_STATS,
                 collapsed: false,
                 min_height: 15,
             },
             PanelState {
-                name: "Total JIT usage",
+                name: MEM_HISTORY,
+                collapsed: false,
+                min_height: 8,
+            },
+            PanelState {
+                name: HISTOGRAM,
                 collapsed: false,
                 min_height: 12,
             },
+            PanelState {
+                name: CACHE_LOCKS,
+                collapsed: false,
+                min_height: 7,
+            },
+            // Empty until a thread is followed, which expands it.
+            PanelState {
+                name: THREAD_HISTORY,
+                collapsed: true,
+                min_height: 6,
+            },
+            PanelState {
+                name: CACHE_EFFICIENCY,
+                collapsed: false,
+                min_height: 7,
+            },
         ];
 
         let replay_controls = if is_replay {
@@ -58,23 +188,315 @@ impl App {
             panels,
             selected_panel: 0,
             latest_frame: None,
-            histogram: VecDeque::with_capacity(HISTOGRAM_CAPACITY),
+            histogram: VecDeque::with_capacity(history_capacity),
+            mem_history: VecDeque::with_capacity(history_capacity),
+            lock_history: VecDeque::with_capacity(history_capacity),
+            cache_history: VecDeque::with_capacity(history_capacity),
+            thread_history: BTreeMap::new(),
+            followed_thread: None,
+            pinned_thread: None,
+            tid_input: None,
             metadata,
             is_replay,
             should_quit: false,
+            show_help: false,
+            thread_sort: ThreadSort::default(),
+            time_unit: TimeUnit::default(),
+            min_thread_load: 0.0,
+            thread_filter: true,
+            show_pss: false,
+            mem_bar: false,
+            compact: false,
+            wide: false,
+            keymap: Keymap::default(),
+            mem_growth_warn: None,
             theme: Theme::default(),
+            smoothing: 0.0,
+            smoothed_load: None,
+            smoothed_thread_loads: BTreeMap::new(),
+            peak_load: 0.0,
+            peak_mem_total: 0,
+            session_counts: CumulativeCountStats::default(),
             replay_controls,
+            history_capacity,
+            waiting_for_process: false,
+            mem_error: None,
+            frozen: None,
+            dirty: true,
+            dropped_samples: 0,
+            session_start: Instant::now(),
+            shown_elapsed_secs: 0,
+            snapshot_notice: None,
+            recording: None,
         }
     }
 
-    pub fn update_frame(&mut self, frame: ComputedFrame) {
+    /// Requests a redraw, e.g. after the terminal was resized.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Returns whether a redraw is needed and clears the request.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Shows replay frame `index`. Peaks restart whenever playback reaches
+    /// the first frame, whether by seeking there or by a loop wrapping
+    /// around.
+    pub fn update_replay_frame(&mut self, frame: ComputedFrame, index: usize) {
+        if index == 0 {
+            self.reset_peaks();
+        }
+        self.update_frame(frame);
+    }
+
+    pub fn update_frame(&mut self, mut frame: ComputedFrame) {
+        self.peak_load = self.peak_load.max(frame.fex_load_percent);
+        self.peak_mem_total = self.peak_mem_total.max(frame.mem.total_anon);
+        self.session_counts.add_sample(&frame);
+        if self.smoothing > 0.0 {
+            self.smooth_loads(&mut frame);
+        }
         let entry = frame.histogram_entry.clone();
+        let mem_point = MemPoint {
+            timestamp_ns: frame.timestamp_ns,
+            total_anon: frame.mem.total_anon,
+            jit_code: frame.mem.jit_code,
+        };
+        let lock_point = LockPoint::from_frame(&frame, &self.metadata);
+        let cache_point = CachePoint::from_frame(&frame);
+        self.push_thread_loads(&frame);
         self.latest_frame = Some(frame);
+        self.dirty = true;
 
-        if self.histogram.len() >= HISTOGRAM_CAPACITY {
+        if self.histogram.len() >= self.history_capacity {
             self.histogram.pop_front();
         }
         self.histogram.push_back(entry);
+
+        if self.mem_history.len() >= self.history_capacity {
+            self.mem_history.pop_front();
+        }
+        self.mem_history.push_back(mem_point);
+
+        if self.lock_history.len() >= self.history_capacity {
+            self.lock_history.pop_front();
+        }
+        self.lock_history.push_back(lock_point);
+
+        if self.cache_history.len() >= self.history_capacity {
+            self.cache_history.pop_front();
+        }
+        self.cache_history.push_back(cache_point);
+    }
+
+    /// Appends the frame's thread loads to the per-thread history, with a gap
+    /// for every tracked thread the frame does not report.
+    fn push_thread_loads(&mut self, frame: &ComputedFrame) {
+        let capacity = self.history_capacity;
+        let loads: BTreeMap<u32, f32> = frame
+            .thread_loads
+            .iter()
+            .map(|tl| (tl.tid, tl.load_percent))
+            .collect();
+        for &tid in loads.keys() {
+            self.thread_history
+                .entry(tid)
+                .or_insert_with(|| VecDeque::with_capacity(capacity));
+        }
+        self.thread_history.retain(|tid, history| {
+            if history.len() >= capacity {
+                history.pop_front();
+            }
+            history.push_back(loads.get(tid).copied());
+            history.iter().any(Option::is_some)
+        });
+    }
+
+    /// Follows the `n`th thread (from 1) of the JIT panel's list in the
+    /// thread history panel.
+    fn follow_listed_thread(&mut self, n: usize) {
+        let view = self.thread_view();
+        if let Some(tid) = self.shown_frame().and_then(|frame| {
+            jit_stats::listed_threads(frame, view)
+                .get(n - 1)
+                .map(|tl| tl.tid)
+        }) {
+            self.follow_thread(tid);
+        }
+    }
+
+    /// Shows `tid` in the thread history panel, expanding that panel.
+    fn follow_thread(&mut self, tid: u32) {
+        self.followed_thread = Some(tid);
+        if let Some(panel) = self.panels.iter_mut().find(|p| p.name == THREAD_HISTORY) {
+            panel.collapsed = false;
+        }
+    }
+
+    /// Position of the panel called `name` in the current order.
+    fn panel_index(&self, name: &str) -> usize {
+        self.panels
+            .iter()
+            .position(|p| p.name == name)
+            .unwrap_or_default()
+    }
+
+    /// Swaps the selected panel with the one above (`up`) or below it,
+    /// keeping it selected.
+    fn move_selected_panel(&mut self, up: bool) {
+        let from = self.selected_panel;
+        let to = if up {
+            from.checked_sub(1)
+        } else {
+            Some(from + 1).filter(|&to| to < self.panels.len())
+        };
+        if let Some(to) = to {
+            self.panels.swap(from, to);
+            self.selected_panel = to;
+        }
+    }
+
+    fn thread_view(&self) -> ThreadView {
+        ThreadView {
+            sort: self.thread_sort,
+            unit: self.time_unit,
+            min_load: if self.thread_filter {
+                self.min_thread_load
+            } else {
+                0.0
+            },
+            followed: self.followed_thread,
+            pinned: self.pinned_thread,
+            hide_follow_keys: self.keymap.action(KeyCode::Char('1'), self.is_replay)
+                != Action::FollowThread(1),
+        }
+    }
+
+    /// Replaces the frame's FEX and per-thread loads with their moving
+    /// averages. Threads absent from the frame are forgotten.
+    fn smooth_loads(&mut self, frame: &mut ComputedFrame) {
+        let alpha = self.smoothing;
+        let load = ewma(self.smoothed_load, frame.fex_load_percent, alpha);
+        frame.fex_load_percent = load;
+        self.smoothed_load = Some(load);
+
+        let previous = std::mem::take(&mut self.smoothed_thread_loads);
+        for thread in &mut frame.thread_loads {
+            let prev = previous.get(&thread.tid).copied().map(f64::from);
+            #[allow(clippy::cast_possible_truncation)]
+            let smoothed = ewma(prev, f64::from(thread.load_percent), alpha) as f32;
+            thread.load_percent = smoothed;
+            self.smoothed_thread_loads.insert(thread.tid, smoothed);
+        }
+    }
+
+    fn set_all_collapsed(&mut self, collapsed: bool) {
+        for panel in &mut self.panels {
+            panel.collapsed = collapsed;
+        }
+    }
+
+    fn reset_peaks(&mut self) {
+        self.peak_load = 0.0;
+        self.peak_mem_total = 0;
+    }
+
+    #[must_use]
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.is_some()
+    }
+
+    /// Freezes the display on the current frame, or unfreezes it so the
+    /// newest sample is shown immediately.
+    pub fn toggle_freeze(&mut self) {
+        self.frozen = match self.frozen {
+            Some(_) => None,
+            None => Some(FrozenView {
+                frame: self.latest_frame.clone(),
+                histogram: self.histogram.clone(),
+                mem_history: self.mem_history.clone(),
+                lock_history: self.lock_history.clone(),
+                cache_history: self.cache_history.clone(),
+                thread_history: self.thread_history.clone(),
+            }),
+        };
+    }
+
+    /// Marks the view dirty when the header's elapsed time has moved on to
+    /// the next second, so the clock keeps ticking between samples.
+    pub fn tick_clock(&mut self) {
+        let elapsed = self.session_start.elapsed().as_secs();
+        if elapsed != self.shown_elapsed_secs {
+            self.shown_elapsed_secs = elapsed;
+            self.dirty = true;
+        }
+    }
+
+    /// Shows the REC badge for `path`, or hides it for `None`.
+    pub fn set_recording(&mut self, path: Option<PathBuf>) {
+        self.recording = path.map(|path| (path, 0));
+        self.dirty = true;
+    }
+
+    /// Counts sample periods that passed without a sample.
+    pub fn add_dropped_samples(&mut self, count: u64) {
+        if count > 0 {
+            self.dropped_samples += count;
+            self.dirty = true;
+        }
+    }
+
+    /// Marks the live view as detached while a new process is awaited.
+    pub fn set_waiting_for_process(&mut self, waiting: bool) {
+        self.waiting_for_process = waiting;
+        self.dirty = true;
+    }
+
+    /// Updates the REC badge's size, in compressed bytes written so far.
+    pub fn set_recorded_bytes(&mut self, recorded: u64) {
+        if let Some((_, bytes)) = &mut self.recording {
+            *bytes = recorded;
+        }
+    }
+
+    /// Records why memory sampling is failing, or clears it with `None`.
+    pub fn set_mem_error(&mut self, error: Option<String>) {
+        if self.mem_error != error {
+            self.mem_error = error;
+            self.dirty = true;
+        }
+    }
+
+    /// Switches to a newly attached process. Unless `keep_history` is set,
+    /// the previous process's frames and history are cleared.
+    pub fn reattach(&mut self, metadata: SessionMetadata, keep_history: bool) {
+        self.metadata = metadata;
+        self.dirty = true;
+        self.waiting_for_process = false;
+        self.frozen = None;
+        self.mem_error = None;
+        if !keep_history {
+            self.latest_frame = None;
+            self.histogram.clear();
+            self.mem_history.clear();
+            self.lock_history.clear();
+            self.cache_history.clear();
+            self.thread_history.clear();
+            self.followed_thread = None;
+            self.pinned_thread = None;
+            self.smoothed_load = None;
+            self.smoothed_thread_loads.clear();
+            self.reset_peaks();
+            self.session_counts = CumulativeCountStats::default();
+            self.dropped_samples = 0;
+        }
     }
 
     pub fn set_replay_total_frames(&mut self, total: usize) {
@@ -83,6 +505,12 @@ impl App {
         }
     }
 
+    pub fn set_replay_markers(&mut self, markers: Vec<usize>) {
+        if let Some(ref mut controls) = self.replay_controls {
+            controls.markers = markers;
+        }
+    }
+
     #[must_use]
     pub fn replay_controls(&self) -> Option<&ReplayControls> {
         self.replay_controls.as_ref()
@@ -93,6 +521,7 @@ impl App {
     }
 
     pub fn handle_action(&mut self, action: &Action) {
+        self.dirty = true;
         match *action {
             Action::Quit => self.should_quit = true,
             Action::PanelUp => {
@@ -105,54 +534,167 @@ impl App {
                     self.selected_panel += 1;
                 }
             }
+            Action::MovePanelUp => self.move_selected_panel(true),
+            Action::MovePanelDown => self.move_selected_panel(false),
             Action::ToggleCollapse => {
                 if let Some(panel) = self.panels.get_mut(self.selected_panel) {
                     panel.collapsed = !panel.collapsed;
                 }
             }
-            Action::TogglePause => {
-                if let Some(ref mut controls) = self.replay_controls {
-                    controls.toggle_pause();
+            Action::CollapseAll => self.set_all_collapsed(true),
+            Action::ExpandAll => self.set_all_collapsed(false),
+            Action::TogglePause
+            | Action::ToggleDirection
+            | Action::ToggleLoop
+            | Action::SeekForward
+            | Action::SeekBackward
+            | Action::SpeedUp
+            | Action::SpeedDown
+            | Action::SeekStart
+            | Action::SeekEnd
+            | Action::SeekToTime
+            | Action::SeekPercent(_)
+            | Action::NextMarker
+            | Action::PrevMarker => self.handle_replay_action(action),
+            Action::ToggleFreeze => self.toggle_freeze(),
+            Action::CycleThreadSort => self.thread_sort = self.thread_sort.next(),
+            Action::CycleTimeUnit => self.time_unit = self.time_unit.next(),
+            Action::ToggleMemPss => self.show_pss = !self.show_pss,
+            Action::ToggleMemBar => self.mem_bar = !self.mem_bar,
+            Action::ResetPeaks => self.reset_peaks(),
+            Action::ToggleThreadFilter => self.thread_filter = !self.thread_filter,
+            Action::FollowThread(n) => self.follow_listed_thread(n.into()),
+            Action::FilterThreads => self.tid_input = Some(String::new()),
+            Action::SaveSnapshot => {
+                let notice = match self.save_snapshot(Path::new(".")) {
+                    Ok(path) => format!("Saved {}", path.display()),
+                    Err(e) => format!("Snapshot failed: {e:#}"),
+                };
+                self.snapshot_notice = Some((notice, Instant::now()));
+            }
+            Action::ToggleHelp => self.show_help = !self.show_help,
+            Action::CloseHelp => {
+                // Esc closes the help first, then clears the pinned thread.
+                if self.show_help {
+                    self.show_help = false;
+                } else {
+                    self.pinned_thread = None;
                 }
             }
+            Action::IncreaseSampleRate
+            | Action::DecreaseSampleRate
+            | Action::NextProcess
+            | Action::PrevProcess
+            | Action::AddMarker
+            | Action::None => {}
+        }
+    }
+
+    /// Applies a playback action to the replay controls; live views have
+    /// none and ignore it.
+    fn handle_replay_action(&mut self, action: &Action) {
+        let Some(controls) = self.replay_controls.as_mut() else {
+            return;
+        };
+        match *action {
+            Action::TogglePause => controls.toggle_pause(),
+            Action::ToggleDirection => controls.toggle_direction(),
+            Action::ToggleLoop => controls.toggle_loop(),
             Action::SeekForward => {
-                if let Some(ref mut controls) = self.replay_controls {
-                    controls.seek_forward();
-                    controls.paused = true;
-                }
+                controls.seek_forward();
+                controls.paused = true;
             }
             Action::SeekBackward => {
-                if let Some(ref mut controls) = self.replay_controls {
-                    controls.seek_backward();
-                    controls.paused = true;
-                }
+                controls.seek_backward();
+                controls.paused = true;
             }
-            Action::SpeedUp => {
-                if let Some(ref mut controls) = self.replay_controls {
-                    controls.speed_up();
-                }
+            Action::SpeedUp => controls.speed_up(),
+            Action::SpeedDown => controls.speed_down(),
+            Action::SeekStart => controls.seek_start(),
+            Action::SeekEnd => controls.seek_end(),
+            Action::SeekToTime => controls.begin_time_input(),
+            Action::SeekPercent(tenths) => controls.seek_percent(tenths * 10),
+            Action::NextMarker => {
+                controls.seek_next_marker();
+                controls.paused = true;
             }
-            Action::SpeedDown => {
-                if let Some(ref mut controls) = self.replay_controls {
-                    controls.speed_down();
-                }
+            Action::PrevMarker => {
+                controls.seek_prev_marker();
+                controls.paused = true;
             }
-            Action::SeekStart => {
-                if let Some(ref mut controls) = self.replay_controls {
-                    controls.seek_start();
-                }
+            _ => {}
+        }
+    }
+
+    /// True while the go-to-time or tid prompt is capturing keys.
+    #[must_use]
+    pub fn is_prompting(&self) -> bool {
+        self.tid_input.is_some()
+            || self
+                .replay_controls
+                .as_ref()
+                .is_some_and(|c| c.time_input().is_some())
+    }
+
+    /// Feeds a key to whichever prompt is open.
+    pub fn handle_prompt_key(&mut self, key: KeyCode) {
+        self.dirty = true;
+        if self.tid_input.is_some() {
+            self.handle_tid_input(key);
+        } else {
+            self.handle_time_input(key);
+        }
+    }
+
+    /// Feeds a key to the tid prompt. Enter pins the typed thread, or
+    /// clears the pin if nothing was typed.
+    fn handle_tid_input(&mut self, key: KeyCode) {
+        let Some(input) = self.tid_input.as_mut() else {
+            return;
+        };
+        match key {
+            KeyCode::Char(c) if c.is_ascii_digit() && input.len() < 10 => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
             }
-            Action::SeekEnd => {
-                if let Some(ref mut controls) = self.replay_controls {
-                    controls.seek_end();
+            KeyCode::Enter => {
+                let tid = input.parse().ok();
+                self.tid_input = None;
+                self.pinned_thread = tid;
+                if let Some(tid) = tid {
+                    self.follow_thread(tid);
                 }
             }
-            Action::IncreaseSamplePeriod | Action::DecreaseSamplePeriod | Action::None => {}
+            KeyCode::Esc => self.tid_input = None,
+            _ => {}
+        }
+    }
+
+    /// Feeds a key to the go-to-time prompt.
+    fn handle_time_input(&mut self, key: KeyCode) {
+        let period = self
+            .latest_frame
+            .as_ref()
+            .map_or(1_000_000_000, |f| f.sample_period_ns);
+        let Some(controls) = self.replay_controls.as_mut() else {
+            return;
+        };
+        match key {
+            KeyCode::Char(c) => controls.push_time_char(c),
+            KeyCode::Backspace => controls.pop_time_char(),
+            KeyCode::Enter => controls.submit_time_input(period),
+            KeyCode::Esc => controls.cancel_time_input(),
+            _ => {}
         }
     }
 
     pub fn render(&self, frame: &mut ratatui::Frame) {
-        let outer = frame.area();
+        self.render_in(frame, frame.area());
+    }
+
+    /// Renders the whole UI into `outer`, which may be a sub-area of the
+    /// terminal when several sessions share the screen.
+    pub fn render_in(&self, frame: &mut ratatui::Frame, outer: Rect) {
         if outer.height < 2 || outer.width < 5 {
             return;
         }
@@ -179,12 +721,25 @@ impl App {
         let body_area = vertical[1];
 
         let sample_period_ns = self.latest_frame.as_ref().map(|f| f.sample_period_ns);
+        let stats_warning = stats_version_warning(self.metadata.stats_version);
         header::render(
             frame,
             header_area,
             &self.metadata,
             self.is_replay,
-            sample_period_ns,
+            &HeaderStatus {
+                sample_period_ns,
+                notice: self.header_notice(),
+                dropped_samples: self.dropped_samples,
+                elapsed_secs: self.session_start.elapsed().as_secs(),
+                playback_secs: self
+                    .replay_controls
+                    .as_ref()
+                    .zip(sample_period_ns)
+                    .map(|(controls, period)| controls.playback_secs(period)),
+                warning: stats_warning.as_deref(),
+                recording_bytes: self.recording.as_ref().map(|(_, bytes)| *bytes),
+            },
             &self.theme,
         );
 
@@ -194,23 +749,36 @@ impl App {
             replay_controls::render(frame, controls_area, controls, period, &self.theme);
         }
 
-        let areas = build_layout(&self.panels, body_area);
+        // A collapsed JIT panel would leave the left column empty, so the
+        // wide layout only applies while it is expanded.
+        let jit_panel = self.panel_index(JIT_STATS);
+        let wide =
+            self.wide && body_area.width >= WIDE_MIN_WIDTH && !self.panels[jit_panel].collapsed;
+        // Too little room for every panel falls back to the compact view
+        // rather than clipping them all.
+        let single = self.compact
+            || if wide {
+                too_short_wide(&self.panels, body_area, jit_panel)
+            } else {
+                too_short(&self.panels, body_area)
+            };
+        let areas = if wide && !single {
+            build_wide_layout(&self.panels, body_area, jit_panel)
+        } else {
+            build_layout(
+                &self.panels,
+                body_area,
+                single.then_some(self.selected_panel),
+            )
+        };
 
         for (i, (panel, area)) in self.panels.iter().zip(areas.iter()).enumerate() {
+            if area.height == 0 {
+                continue;
+            }
             let is_selected = i == self.selected_panel;
 
-            let sel_mark = if is_selected {
-                SELECTED_MARKER[1]
-            } else {
-                SELECTED_MARKER[0]
-            };
-            let col_mark = if panel.collapsed {
-                COLLAPSED_MARKER[1]
-            } else {
-                COLLAPSED_MARKER[0]
-            };
-
-            let title = format!("{sel_mark} {col_mark} {}", panel.name);
+            let title = self.panel_title(i, single);
 
             let border_style = if is_selected {
                 self.theme.border_selected
@@ -224,7 +792,9 @@ impl App {
                 .border_style(border_style)
                 .title_style(self.theme.title);
 
-            if panel.collapsed {
+            // The one panel shown is drawn in full even if collapsed, since
+            // its title bar alone would leave the screen empty.
+            if panel.collapsed && !single {
                 frame.render_widget(block, *area);
             } else {
                 let inner = block.inner(*area);
@@ -234,21 +804,583 @@ impl App {
                     continue;
                 }
 
-                match (i, &self.latest_frame) {
-                    (0, Some(data)) => {
-                        jit_stats::render(frame, inner, data, &self.metadata, &self.theme);
-                    }
-                    (1, Some(data)) => {
-                        mem_stats::render(frame, inner, data, &self.theme);
-                    }
-                    (2, _) => {
-                        histogram::render(frame, inner, &self.histogram, &self.theme);
-                    }
-                    _ => {
-                        frame.render_widget(Paragraph::new("Waiting for data..."), inner);
-                    }
+                self.render_panel_body(frame, i, inner);
+            }
+        }
+
+        if self.show_help {
+            help::render(frame, outer, self.is_replay, &self.theme);
+        }
+    }
+
+    /// Title bar text for panel `i`: selection and collapse markers, or in
+    /// the single-panel view its position among the panels instead.
+    fn panel_title(&self, i: usize, single: bool) -> String {
+        let panel = &self.panels[i];
+        let sel_mark = if i == self.selected_panel {
+            SELECTED_MARKER[1]
+        } else {
+            SELECTED_MARKER[0]
+        };
+        if single {
+            return format!(
+                "{sel_mark} {} ({}/{}, Up/Down for others)",
+                panel.name,
+                i + 1,
+                self.panels.len()
+            );
+        }
+        let col_mark = if panel.collapsed {
+            COLLAPSED_MARKER[1]
+        } else {
+            COLLAPSED_MARKER[0]
+        };
+        format!("{sel_mark} {col_mark} {}", panel.name)
+    }
+
+    /// The frame the panels are showing: the frozen one while the live view
+    /// is paused, otherwise the latest.
+    fn shown_frame(&self) -> Option<&ComputedFrame> {
+        match &self.frozen {
+            Some(view) => view.frame.as_ref(),
+            None => self.latest_frame.as_ref(),
+        }
+    }
+
+    /// Writes the shown frame to a new pretty-printed JSON file in `dir`,
+    /// named for the process and the current time, and returns its path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no frame yet or the file cannot be
+    /// written.
+    pub fn save_snapshot(&self, dir: &Path) -> Result<PathBuf> {
+        let frame = self.shown_frame().context("no frame to save yet")?;
+        let json = serde_json::to_string_pretty(frame).context("failed to serialize frame")?;
+        let stem = format!(
+            "felix-snapshot-{}-{}",
+            self.metadata.pid,
+            wallclock::format_compact(SystemTime::now())
+        );
+        // Snapshots taken within the same second get a numbered suffix
+        // rather than replacing each other.
+        for n in 0u32.. {
+            let path = if n == 0 {
+                dir.join(format!("{stem}.json"))
+            } else {
+                dir.join(format!("{stem}-{n}.json"))
+            };
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    writeln!(file, "{json}")
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    return Ok(path);
                 }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("failed to create {}", path.display()));
+                }
+            }
+        }
+        unreachable!("snapshot suffixes exhausted")
+    }
+
+    fn header_notice(&self) -> Option<&str> {
+        if let Some((notice, at)) = &self.snapshot_notice
+            && at.elapsed() < SNAPSHOT_NOTICE_DURATION
+        {
+            Some(notice)
+        } else if self.waiting_for_process {
+            Some("WAITING FOR PROCESS")
+        } else if self.is_frozen() {
+            Some("PAUSED")
+        } else {
+            None
+        }
+    }
+
+    /// Draws the tid prompt on the first row of `area` while it is open, and
+    /// returns the space left below it.
+    fn render_tid_prompt(&self, frame: &mut ratatui::Frame, area: Rect) -> Rect {
+        let Some(input) = &self.tid_input else {
+            return area;
+        };
+        let prompt = Line::from(vec![
+            Span::styled("Pin thread (tid): ", self.theme.title),
+            Span::raw(format!("{input}_")),
+            Span::raw("  [Enter] Pin  [Esc] Cancel"),
+        ]);
+        frame.render_widget(Paragraph::new(prompt), Rect { height: 1, ..area });
+        Rect {
+            y: area.y + 1,
+            height: area.height.saturating_sub(1),
+            ..area
+        }
+    }
+
+    /// Draws the contents of panel `index` inside its border, from the frozen
+    /// snapshot when the live view is paused.
+    fn render_panel_body(&self, frame: &mut ratatui::Frame, index: usize, inner: Rect) {
+        let (latest_frame, histogram_entries, mem_points, lock_points, cache_points, thread_points) =
+            match &self.frozen {
+                Some(view) => (
+                    &view.frame,
+                    &view.histogram,
+                    &view.mem_history,
+                    &view.lock_history,
+                    &view.cache_history,
+                    &view.thread_history,
+                ),
+                None => (
+                    &self.latest_frame,
+                    &self.histogram,
+                    &self.mem_history,
+                    &self.lock_history,
+                    &self.cache_history,
+                    &self.thread_history,
+                ),
+            };
+
+        match (self.panels[index].name, latest_frame) {
+            (JIT_STATS, Some(data)) => {
+                let inner = self.render_tid_prompt(frame, inner);
+                jit_stats::render(
+                    frame,
+                    inner,
+                    data,
+                    &self.metadata,
+                    self.thread_view(),
+                    &SessionTotals {
+                        peak_load: self.peak_load,
+                        counts: (!self.is_replay).then_some(&self.session_counts),
+                    },
+                    &self.theme,
+                );
+            }
+            (MEM_STATS, Some(data)) => {
+                let growth = self
+                    .mem_growth_warn
+                    .map(|threshold| mem_history::growth_warnings(mem_points, threshold))
+                    .unwrap_or_default();
+                mem_stats::render(
+                    frame,
+                    inner,
+                    data,
+                    self.mem_error.as_deref(),
+                    MemView {
+                        show_pss: self.show_pss,
+                        peak: self.peak_mem_total,
+                        bar: self.mem_bar,
+                        growth: &growth,
+                    },
+                    &self.theme,
+                );
+            }
+            (MEM_HISTORY, _) => {
+                mem_history::render(frame, inner, mem_points, &self.theme);
+            }
+            (HISTOGRAM, _) => {
+                histogram::render(
+                    frame,
+                    inner,
+                    histogram_entries,
+                    latest_frame.as_ref().map(|f| f.sample_period_ns),
+                    &self.theme,
+                );
             }
+            (CACHE_LOCKS, _) => {
+                cache_locks::render(frame, inner, lock_points, &self.theme);
+            }
+            (THREAD_HISTORY, _) => {
+                let followed = self.followed_thread.and_then(|tid| {
+                    thread_points
+                        .get(&tid)
+                        .map(|loads| FollowedThread { tid, loads })
+                });
+                thread_history::render(frame, inner, followed, &self.theme);
+            }
+            (CACHE_EFFICIENCY, Some(data)) => {
+                cache_efficiency::render(frame, inner, data, cache_points, &self.theme);
+            }
+            _ => {
+                frame.render_widget(Paragraph::new("Waiting for data..."), inner);
+            }
+        }
+    }
+}
+
+/// Exponentially weighted moving average: `alpha` is the weight of the new
+/// `value`. The first value passes through unchanged.
+#[must_use]
+pub fn ewma(previous: Option<f64>, value: f64, alpha: f64) -> f64 {
+    match previous {
+        Some(previous) => alpha * value + (1.0 - alpha) * previous,
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::accumulator::ThreadLoad;
+    use crate::testing::make_metadata;
+
+    fn make_app() -> App {
+        App::new(
+            SessionMetadata {
+                cycle_counter_frequency: 1,
+                hardware_concurrency: 1,
+                ..make_metadata()
+            },
+            false,
+            DEFAULT_HISTORY,
+        )
+    }
+
+    fn frame_with_load(load: f64) -> ComputedFrame {
+        ComputedFrame {
+            fex_load_percent: load,
+            ..ComputedFrame::default()
         }
     }
+
+    #[test]
+    fn thread_history_follows_listed_thread_with_gaps() {
+        let mut app = make_app();
+        let with_threads = |threads: &[(u32, f32)]| ComputedFrame {
+            thread_loads: threads
+                .iter()
+                .map(|&(tid, load_percent)| ThreadLoad {
+                    tid,
+                    load_percent,
+                    total_cycles: 0,
+                })
+                .collect(),
+            ..ComputedFrame::default()
+        };
+        app.update_frame(with_threads(&[(7, 10.0), (8, 60.0)]));
+        app.update_frame(with_threads(&[(8, 50.0)]));
+        app.update_frame(with_threads(&[(7, 30.0), (8, 40.0)]));
+
+        assert_eq!(
+            app.thread_history[&7],
+            VecDeque::from([Some(10.0), None, Some(30.0)])
+        );
+        // Sorted by load, thread 7 is listed second.
+        assert!(app.panels[app.panel_index(THREAD_HISTORY)].collapsed);
+        app.handle_action(&Action::FollowThread(2));
+        assert_eq!(app.followed_thread, Some(7));
+        assert!(!app.panels[app.panel_index(THREAD_HISTORY)].collapsed);
+        app.handle_action(&Action::FollowThread(9));
+        assert_eq!(app.followed_thread, Some(7));
+    }
+
+    #[test]
+    fn tid_prompt_pins_and_esc_unpins() {
+        let mut app = make_app();
+        app.handle_action(&Action::FilterThreads);
+        assert!(app.is_prompting());
+        for key in [KeyCode::Char('4'), KeyCode::Char('x'), KeyCode::Char('2')] {
+            app.handle_prompt_key(key);
+        }
+        assert_eq!(app.tid_input.as_deref(), Some("42"));
+        app.handle_prompt_key(KeyCode::Enter);
+        assert!(!app.is_prompting());
+        assert_eq!(app.pinned_thread, Some(42));
+        assert_eq!(app.followed_thread, Some(42));
+
+        app.handle_action(&Action::ToggleHelp);
+        app.handle_action(&Action::CloseHelp);
+        assert_eq!(app.pinned_thread, Some(42));
+        app.handle_action(&Action::CloseHelp);
+        assert_eq!(app.pinned_thread, None);
+    }
+
+    #[test]
+    fn snapshot_saves_the_shown_frame() {
+        let dir = std::env::temp_dir().join(format!("felix_snapshot_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut app = make_app();
+        assert!(app.save_snapshot(&dir).is_err());
+
+        app.update_frame(frame_with_load(25.0));
+        app.toggle_freeze();
+        app.update_frame(frame_with_load(75.0));
+        let first = app.save_snapshot(&dir).unwrap();
+        let second = app.save_snapshot(&dir).unwrap();
+        assert_ne!(first, second);
+
+        let text = std::fs::read_to_string(&first).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert!((value["fex_load_percent"].as_f64().unwrap() - 25.0).abs() < f64::EPSILON);
+        assert!(value["mem"].is_object());
+    }
+
+    #[test]
+    fn freeze_keeps_sampling_and_unfreeze_jumps_to_latest() {
+        let mut app = make_app();
+        app.update_frame(frame_with_load(1.0));
+        app.toggle_freeze();
+        assert!(app.is_frozen());
+
+        app.update_frame(frame_with_load(2.0));
+        app.update_frame(frame_with_load(3.0));
+        let view = app.frozen.as_ref().unwrap();
+        assert!((view.frame.as_ref().unwrap().fex_load_percent - 1.0).abs() < f64::EPSILON);
+        assert_eq!(view.histogram.len(), 1);
+        assert_eq!(app.histogram.len(), 3);
+
+        app.toggle_freeze();
+        assert!(!app.is_frozen());
+        let latest = app.latest_frame.as_ref().unwrap();
+        assert!((latest.fex_load_percent - 3.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn reattach_clears_history_unless_kept() {
+        let mut app = make_app();
+        app.update_frame(frame_with_load(1.0));
+        app.set_waiting_for_process(true);
+        assert_eq!(app.header_notice(), Some("WAITING FOR PROCESS"));
+
+        let mut metadata = app.metadata.clone();
+        metadata.pid = 2;
+        app.reattach(metadata.clone(), true);
+        assert_eq!(app.metadata.pid, 2);
+        assert_eq!(app.histogram.len(), 1);
+        assert_eq!(app.header_notice(), None);
+
+        app.reattach(metadata, false);
+        assert!(app.latest_frame.is_none());
+        assert!(app.histogram.is_empty());
+        assert!(app.mem_history.is_empty());
+    }
+
+    #[test]
+    fn short_terminal_shows_only_selected_panel() {
+        let mut app = make_app();
+        app.update_frame(frame_with_load(10.0));
+        app.handle_action(&Action::PanelDown);
+
+        // Transient tiny sizes while a window is dragged must not panic.
+        for (width, height) in [(1, 1), (4, 2), (10, 3), (30, 6), (160, 20)] {
+            let backend = ratatui::backend::TestBackend::new(width, height);
+            let mut terminal = ratatui::Terminal::new(backend).unwrap();
+            terminal.draw(|f| app.render(f)).unwrap();
+        }
+
+        let backend = ratatui::backend::TestBackend::new(80, 20);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal.draw(|f| app.render(f)).unwrap();
+        let text: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+        assert!(text.contains("FEX Memory Usage (2/7"));
+        assert!(!text.contains("FEX JIT Stats"));
+    }
+
+    #[test]
+    fn moved_panel_stays_selected_and_keeps_its_contents() {
+        let mut app = make_app();
+        app.update_frame(frame_with_load(10.0));
+        app.handle_action(&Action::PanelDown);
+        app.handle_action(&Action::MovePanelUp);
+        assert_eq!(app.selected_panel, 0);
+        assert_eq!(app.panels[0].name, MEM_STATS);
+        assert_eq!(app.panels[1].name, JIT_STATS);
+        // Already at the top: nothing moves.
+        app.handle_action(&Action::MovePanelUp);
+        assert_eq!(app.panels[0].name, MEM_STATS);
+
+        let backend = ratatui::backend::TestBackend::new(120, 120);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal.draw(|f| app.render(f)).unwrap();
+        let text: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+        let memory = text.find("FEX Memory Usage").unwrap();
+        let jit = text.find("FEX JIT Stats").unwrap();
+        assert!(memory < jit);
+        // The JIT panel's body follows it to its new position.
+        assert!(text[jit..].contains("Total ("));
+
+        app.selected_panel = app.panels.len() - 1;
+        app.handle_action(&Action::MovePanelDown);
+        assert_eq!(app.selected_panel, app.panels.len() - 1);
+    }
+
+    #[test]
+    fn wide_mode_puts_the_jit_panel_in_its_own_column() {
+        let mut app = make_app();
+        app.update_frame(frame_with_load(10.0));
+        app.wide = true;
+        let rows = |app: &App| {
+            let backend = ratatui::backend::TestBackend::new(200, 60);
+            let mut terminal = ratatui::Terminal::new(backend).unwrap();
+            terminal.draw(|f| app.render(f)).unwrap();
+            let buffer = terminal.backend().buffer().clone();
+            buffer
+                .content()
+                .chunks(200)
+                .map(|row| {
+                    row.iter()
+                        .map(ratatui::buffer::Cell::symbol)
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let wide = rows(&app);
+        // Both columns start on the first body row.
+        assert!(wide[1].contains("FEX JIT Stats"));
+        assert!(wide[1].contains("FEX Memory Usage"));
+
+        app.handle_action(&Action::ToggleCollapse);
+        let stacked = rows(&app);
+        assert!(stacked[1].contains("FEX JIT Stats"));
+        assert!(!stacked[1].contains("FEX Memory Usage"));
+    }
+
+    #[test]
+    fn compact_mode_shows_one_panel_on_a_tall_terminal() {
+        let mut app = make_app();
+        app.update_frame(frame_with_load(10.0));
+        app.compact = true;
+        app.handle_action(&Action::CollapseAll);
+
+        let backend = ratatui::backend::TestBackend::new(120, 80);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal.draw(|f| app.render(f)).unwrap();
+        let text: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(ratatui::buffer::Cell::symbol)
+            .collect();
+        assert!(text.contains("FEX JIT Stats (1/7"));
+        assert!(!text.contains("FEX Memory Usage"));
+        // Collapsed or not, the one visible panel shows its body.
+        assert!(text.contains("Total ("));
+    }
+
+    #[test]
+    fn collapse_and_expand_all_panels() {
+        let mut app = make_app();
+        app.handle_action(&Action::ToggleCollapse);
+        assert!(app.panels[0].collapsed);
+
+        app.handle_action(&Action::CollapseAll);
+        assert!(app.panels.iter().all(|p| p.collapsed));
+        app.handle_action(&Action::ExpandAll);
+        assert!(app.panels.iter().all(|p| !p.collapsed));
+
+        app.handle_action(&Action::ToggleCollapse);
+        assert!(app.panels[0].collapsed);
+        assert!(!app.panels[1].collapsed);
+    }
+
+    #[test]
+    fn ewma_weights_newest_value_by_alpha() {
+        assert!((ewma(None, 80.0, 0.3) - 80.0).abs() < f64::EPSILON);
+        assert!((ewma(Some(100.0), 0.0, 0.3) - 70.0).abs() < 1e-9);
+        assert!((ewma(Some(100.0), 0.0, 1.0)).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn smoothing_is_off_by_default_and_tracks_threads() {
+        let mut app = make_app();
+        app.update_frame(frame_with_load(100.0));
+        app.update_frame(frame_with_load(0.0));
+        assert!(app.latest_frame.as_ref().unwrap().fex_load_percent.abs() < f64::EPSILON);
+
+        app.smoothing = 0.5;
+        let with_thread = |load: f64, thread_load: f32| ComputedFrame {
+            thread_loads: vec![ThreadLoad {
+                tid: 7,
+                load_percent: thread_load,
+                total_cycles: 0,
+            }],
+            ..frame_with_load(load)
+        };
+        app.update_frame(with_thread(100.0, 40.0));
+        app.update_frame(with_thread(0.0, 0.0));
+        let latest = app.latest_frame.as_ref().unwrap();
+        assert!((latest.fex_load_percent - 50.0).abs() < 1e-9);
+        assert!((latest.thread_loads[0].load_percent - 20.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn peaks_track_maximum_until_reset() {
+        let mut app = make_app();
+        let mut frame = frame_with_load(90.0);
+        frame.mem.total_anon = 4096;
+        app.update_frame(frame);
+        app.update_frame(frame_with_load(10.0));
+        assert!((app.peak_load - 90.0).abs() < f64::EPSILON);
+        assert_eq!(app.peak_mem_total, 4096);
+
+        app.handle_action(&Action::ResetPeaks);
+        app.update_frame(frame_with_load(20.0));
+        assert!((app.peak_load - 20.0).abs() < f64::EPSILON);
+        assert_eq!(app.peak_mem_total, 0);
+    }
+
+    fn make_replay_app(total_frames: usize) -> App {
+        let mut app = App::new(make_metadata(), true, DEFAULT_HISTORY);
+        app.set_replay_total_frames(total_frames);
+        app
+    }
+
+    #[test]
+    fn peaks_restart_when_percent_seek_reaches_start() {
+        let mut app = make_replay_app(10);
+        for (index, load) in [30.0, 90.0, 40.0].into_iter().enumerate() {
+            app.update_replay_frame(frame_with_load(load), index);
+        }
+        app.handle_action(&Action::SeekPercent(5));
+        let index = app.replay_controls().unwrap().current_frame;
+        app.update_replay_frame(frame_with_load(20.0), index);
+        assert!((app.peak_load - 90.0).abs() < f64::EPSILON);
+
+        app.handle_action(&Action::SeekPercent(0));
+        let index = app.replay_controls().unwrap().current_frame;
+        assert_eq!(index, 0);
+        app.update_replay_frame(frame_with_load(30.0), index);
+        assert!((app.peak_load - 30.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn peaks_restart_when_loop_wraps() {
+        let mut app = make_replay_app(3);
+        app.handle_action(&Action::ToggleLoop);
+        for (index, load) in [10.0, 90.0, 20.0, 30.0, 40.0].into_iter().enumerate() {
+            app.update_replay_frame(frame_with_load(load), index % 3);
+        }
+        assert!((app.peak_load - 40.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn redraw_needed_only_after_changes() {
+        let mut app = make_app();
+        assert!(app.take_dirty());
+        assert!(!app.is_dirty());
+
+        app.update_frame(frame_with_load(1.0));
+        assert!(app.take_dirty());
+        app.set_mem_error(None);
+        assert!(!app.is_dirty());
+        app.set_mem_error(Some("smaps unreadable".to_string()));
+        assert!(app.take_dirty());
+        app.handle_action(&Action::PanelDown);
+        assert!(app.is_dirty());
+    }
 }
@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: MIT
+use ratatui::layout::{Constraint, Flex, Layout, Rect};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::tui::theme::Theme;
+
+const GENERAL_KEYS: &[(&str, &str)] = &[
+    ("q", "Quit"),
+    ("Up/Down j/k", "Select panel"),
+    ("K/J", "Move panel up/down"),
+    ("Enter", "Collapse/expand panel"),
+    ("c/e", "Collapse/expand all panels"),
+    ("o", "Cycle thread sort (load/cycles/tid)"),
+    ("u", "Cycle time unit (ms/cycles/% of sample)"),
+    ("m", "Show Pss/Swap or Rss memory total"),
+    ("v", "Memory regions as list or stacked bar"),
+    ("R", "Reset peak load and memory"),
+    ("f", "Show/hide threads below --min-thread-load"),
+    ("s", "Save the shown frame as JSON"),
+    ("/", "Pin a thread by tid (Esc unpins)"),
+    ("?/Esc", "Close this help"),
+];
+
+const LIVE_KEYS: &[(&str, &str)] = &[
+    ("Right", "Collapse/expand panel"),
+    ("+/-", "Sample faster/slower"),
+    ("p", "Freeze/unfreeze display"),
+    ("b", "Bookmark this frame (when recording)"),
+    ("Tab/S-Tab", "Next/previous process (watch --all)"),
+    ("1-9", "Follow that thread's load over time"),
+];
+
+const REPLAY_KEYS: &[(&str, &str)] = &[
+    ("Space", "Pause/resume"),
+    ("r", "Reverse playback direction"),
+    ("L", "Loop playback at the ends"),
+    ("Left/Right h/l", "Seek backward/forward"),
+    ("[/]", "Speed down/up"),
+    ("Home/End", "Seek to start/end"),
+    ("g", "Go to a time (mm:ss)"),
+    ("0-9", "Seek to 0%-90% (live: follow a thread)"),
+    ("n/N", "Jump to next/previous marker"),
+];
+
+/// Key bindings that apply in the current mode, in display order.
+#[must_use]
+pub fn key_bindings(is_replay: bool) -> Vec<(&'static str, &'static str)> {
+    let mode_keys = if is_replay { REPLAY_KEYS } else { LIVE_KEYS };
+    GENERAL_KEYS.iter().chain(mode_keys).copied().collect()
+}
+
+/// Draws the key binding popup centered over `area`.
+pub fn render(frame: &mut ratatui::Frame, area: Rect, is_replay: bool, theme: &Theme) {
+    let bindings = key_bindings(is_replay);
+    let key_width = bindings.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+    let desc_width = bindings.iter().map(|(_, d)| d.len()).max().unwrap_or(0);
+
+    let lines: Vec<Line> = bindings
+        .iter()
+        .map(|(key, desc)| {
+            Line::from(vec![
+                Span::styled(format!(" {key:<key_width$}  "), theme.title),
+                Span::raw(*desc),
+            ])
+        })
+        .collect();
+
+    // Borders plus the padding around the key column.
+    #[allow(clippy::cast_possible_truncation)]
+    let width = (key_width + desc_width + 5) as u16;
+    #[allow(clippy::cast_possible_truncation)]
+    let height = lines.len() as u16 + 2;
+    let popup = centered(area, width, height);
+
+    let block = Block::default()
+        .title(" Keys ")
+        .borders(Borders::ALL)
+        .border_style(theme.border_selected)
+        .title_style(theme.title);
+
+    frame.render_widget(Clear, popup);
+    frame.render_widget(Paragraph::new(lines).block(block), popup);
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    let [row] = Layout::vertical([Constraint::Length(height.min(area.height))])
+        .flex(Flex::Center)
+        .areas(area);
+    let [popup] = Layout::horizontal([Constraint::Length(width.min(area.width))])
+        .flex(Flex::Center)
+        .areas(row);
+    popup
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_keys_only_in_replay_mode() {
+        let live = key_bindings(false);
+        let replay = key_bindings(true);
+        assert!(live.iter().any(|(k, _)| *k == "+/-"));
+        assert!(!live.iter().any(|(k, _)| *k == "Space"));
+        assert!(replay.iter().any(|(k, _)| *k == "Space"));
+        assert!(replay.iter().any(|(k, _)| *k == "Home/End"));
+        assert!(!replay.iter().any(|(k, _)| *k == "+/-"));
+    }
+
+    #[test]
+    fn popup_is_centered_and_clamped() {
+        let area = Rect::new(0, 0, 80, 24);
+        let popup = centered(area, 20, 10);
+        assert_eq!(popup, Rect::new(30, 7, 20, 10));
+
+        let small = centered(Rect::new(0, 0, 10, 4), 20, 10);
+        assert_eq!(small, Rect::new(0, 0, 10, 4));
+    }
+}
@@ -1,38 +1,455 @@
 // SPDX-License-Identifier: MIT
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
 use crossterm::event::KeyCode;
+use serde::Deserialize;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Action {
     Quit,
     PanelUp,
     PanelDown,
+    /// Swap the selected panel with its neighbor, reordering the panels.
+    MovePanelUp,
+    MovePanelDown,
     ToggleCollapse,
+    CollapseAll,
+    ExpandAll,
     TogglePause,
+    ToggleDirection,
+    ToggleLoop,
     SeekForward,
     SeekBackward,
     SpeedUp,
     SpeedDown,
     SeekStart,
     SeekEnd,
-    IncreaseSamplePeriod,
-    DecreaseSamplePeriod,
+    SeekToTime,
+    /// Seek to N tenths (0-9) of the way through the recording.
+    SeekPercent(u8),
+    NextMarker,
+    PrevMarker,
+    AddMarker,
+    IncreaseSampleRate,
+    DecreaseSampleRate,
+    ToggleFreeze,
+    CycleThreadSort,
+    /// Show the JIT panel's times in milliseconds, cycles, or percent of
+    /// the sample period.
+    CycleTimeUnit,
+    ToggleMemPss,
+    ToggleMemBar,
+    ResetPeaks,
+    ToggleThreadFilter,
+    /// Follow the JIT panel's Nth listed thread (1-9) in the thread history
+    /// panel.
+    FollowThread(u8),
+    /// Open the prompt for a tid to pin to the top of the JIT panel.
+    FilterThreads,
+    SaveSnapshot,
+    NextProcess,
+    PrevProcess,
+    ToggleHelp,
+    CloseHelp,
     None,
 }
 
-pub fn handle_key(key: KeyCode, is_replay: bool) -> Action {
-    match key {
-        KeyCode::Char('q') => Action::Quit,
-        KeyCode::Up => Action::PanelUp,
-        KeyCode::Down => Action::PanelDown,
-        KeyCode::Right if is_replay => Action::SeekForward,
-        KeyCode::Right => Action::ToggleCollapse,
-        KeyCode::Char('+' | '=') => Action::IncreaseSamplePeriod,
-        KeyCode::Char('-' | '_') => Action::DecreaseSamplePeriod,
-        KeyCode::Char(' ') if is_replay => Action::TogglePause,
-        KeyCode::Left if is_replay => Action::SeekBackward,
-        KeyCode::Char(']') if is_replay => Action::SpeedUp,
-        KeyCode::Char('[') if is_replay => Action::SpeedDown,
-        KeyCode::Home if is_replay => Action::SeekStart,
-        KeyCode::End if is_replay => Action::SeekEnd,
-        _ => Action::None,
+impl Action {
+    /// Every bindable action with the name a keymap file uses for it.
+    const ALL: [(&'static str, Self); 55] = [
+        ("quit", Self::Quit),
+        ("panel_up", Self::PanelUp),
+        ("panel_down", Self::PanelDown),
+        ("move_panel_up", Self::MovePanelUp),
+        ("move_panel_down", Self::MovePanelDown),
+        ("toggle_collapse", Self::ToggleCollapse),
+        ("collapse_all", Self::CollapseAll),
+        ("expand_all", Self::ExpandAll),
+        ("toggle_pause", Self::TogglePause),
+        ("toggle_direction", Self::ToggleDirection),
+        ("toggle_loop", Self::ToggleLoop),
+        ("seek_forward", Self::SeekForward),
+        ("seek_backward", Self::SeekBackward),
+        ("speed_up", Self::SpeedUp),
+        ("speed_down", Self::SpeedDown),
+        ("seek_start", Self::SeekStart),
+        ("seek_end", Self::SeekEnd),
+        ("seek_to_time", Self::SeekToTime),
+        ("seek_percent_0", Self::SeekPercent(0)),
+        ("seek_percent_10", Self::SeekPercent(1)),
+        ("seek_percent_20", Self::SeekPercent(2)),
+        ("seek_percent_30", Self::SeekPercent(3)),
+        ("seek_percent_40", Self::SeekPercent(4)),
+        ("seek_percent_50", Self::SeekPercent(5)),
+        ("seek_percent_60", Self::SeekPercent(6)),
+        ("seek_percent_70", Self::SeekPercent(7)),
+        ("seek_percent_80", Self::SeekPercent(8)),
+        ("seek_percent_90", Self::SeekPercent(9)),
+        ("next_marker", Self::NextMarker),
+        ("prev_marker", Self::PrevMarker),
+        ("add_marker", Self::AddMarker),
+        ("increase_sample_rate", Self::IncreaseSampleRate),
+        ("decrease_sample_rate", Self::DecreaseSampleRate),
+        ("toggle_freeze", Self::ToggleFreeze),
+        ("cycle_thread_sort", Self::CycleThreadSort),
+        ("cycle_time_unit", Self::CycleTimeUnit),
+        ("toggle_mem_pss", Self::ToggleMemPss),
+        ("toggle_mem_bar", Self::ToggleMemBar),
+        ("reset_peaks", Self::ResetPeaks),
+        ("toggle_thread_filter", Self::ToggleThreadFilter),
+        ("follow_thread_1", Self::FollowThread(1)),
+        ("follow_thread_2", Self::FollowThread(2)),
+        ("follow_thread_3", Self::FollowThread(3)),
+        ("follow_thread_4", Self::FollowThread(4)),
+        ("follow_thread_5", Self::FollowThread(5)),
+        ("follow_thread_6", Self::FollowThread(6)),
+        ("follow_thread_7", Self::FollowThread(7)),
+        ("follow_thread_8", Self::FollowThread(8)),
+        ("follow_thread_9", Self::FollowThread(9)),
+        ("filter_threads", Self::FilterThreads),
+        ("save_snapshot", Self::SaveSnapshot),
+        ("next_process", Self::NextProcess),
+        ("prev_process", Self::PrevProcess),
+        ("toggle_help", Self::ToggleHelp),
+        ("close_help", Self::CloseHelp),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(action, _)| *action == name)
+            .map(|(_, action)| action.clone())
+    }
+}
+
+/// Which views a binding applies to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Both,
+    Live,
+    Replay,
+}
+
+/// The built-in bindings. A key bound separately for live and replay, like
+/// `Right`, does something different in each. A later entry takes a key
+/// from an earlier one in the views it applies to, which is how the digits
+/// seek in replay while still counting as `FollowThread` keys there for
+/// keymap rebinding.
+const DEFAULT_BINDINGS: &[(KeyCode, Mode, Action)] = &[
+    (KeyCode::Char('q'), Mode::Both, Action::Quit),
+    (KeyCode::Up, Mode::Both, Action::PanelUp),
+    (KeyCode::Down, Mode::Both, Action::PanelDown),
+    (KeyCode::Char('k'), Mode::Both, Action::PanelUp),
+    (KeyCode::Char('j'), Mode::Both, Action::PanelDown),
+    (KeyCode::Char('K'), Mode::Both, Action::MovePanelUp),
+    (KeyCode::Char('J'), Mode::Both, Action::MovePanelDown),
+    (KeyCode::Right, Mode::Replay, Action::SeekForward),
+    (KeyCode::Char('l'), Mode::Replay, Action::SeekForward),
+    (KeyCode::Right, Mode::Live, Action::ToggleCollapse),
+    (KeyCode::Enter, Mode::Both, Action::ToggleCollapse),
+    (KeyCode::Char('c'), Mode::Both, Action::CollapseAll),
+    (KeyCode::Char('e'), Mode::Both, Action::ExpandAll),
+    (KeyCode::Char('o'), Mode::Both, Action::CycleThreadSort),
+    (KeyCode::Char('u'), Mode::Both, Action::CycleTimeUnit),
+    (KeyCode::Char('m'), Mode::Both, Action::ToggleMemPss),
+    (KeyCode::Char('v'), Mode::Both, Action::ToggleMemBar),
+    (KeyCode::Char('R'), Mode::Both, Action::ResetPeaks),
+    (KeyCode::Char('f'), Mode::Both, Action::ToggleThreadFilter),
+    (KeyCode::Char('s'), Mode::Both, Action::SaveSnapshot),
+    (KeyCode::Char('/'), Mode::Both, Action::FilterThreads),
+    (KeyCode::Char('1'), Mode::Both, Action::FollowThread(1)),
+    (KeyCode::Char('2'), Mode::Both, Action::FollowThread(2)),
+    (KeyCode::Char('3'), Mode::Both, Action::FollowThread(3)),
+    (KeyCode::Char('4'), Mode::Both, Action::FollowThread(4)),
+    (KeyCode::Char('5'), Mode::Both, Action::FollowThread(5)),
+    (KeyCode::Char('6'), Mode::Both, Action::FollowThread(6)),
+    (KeyCode::Char('7'), Mode::Both, Action::FollowThread(7)),
+    (KeyCode::Char('8'), Mode::Both, Action::FollowThread(8)),
+    (KeyCode::Char('9'), Mode::Both, Action::FollowThread(9)),
+    (KeyCode::Tab, Mode::Both, Action::NextProcess),
+    (KeyCode::BackTab, Mode::Both, Action::PrevProcess),
+    (KeyCode::Char('?'), Mode::Both, Action::ToggleHelp),
+    (KeyCode::Esc, Mode::Both, Action::CloseHelp),
+    (KeyCode::Char('+'), Mode::Both, Action::IncreaseSampleRate),
+    (KeyCode::Char('='), Mode::Both, Action::IncreaseSampleRate),
+    (KeyCode::Char('-'), Mode::Both, Action::DecreaseSampleRate),
+    (KeyCode::Char('_'), Mode::Both, Action::DecreaseSampleRate),
+    (KeyCode::Char(' '), Mode::Replay, Action::TogglePause),
+    (KeyCode::Char('r'), Mode::Replay, Action::ToggleDirection),
+    (KeyCode::Char('L'), Mode::Replay, Action::ToggleLoop),
+    (KeyCode::Char('p'), Mode::Live, Action::ToggleFreeze),
+    (KeyCode::Char('b'), Mode::Live, Action::AddMarker),
+    (KeyCode::Left, Mode::Replay, Action::SeekBackward),
+    (KeyCode::Char('h'), Mode::Replay, Action::SeekBackward),
+    (KeyCode::Char(']'), Mode::Replay, Action::SpeedUp),
+    (KeyCode::Char('['), Mode::Replay, Action::SpeedDown),
+    (KeyCode::Home, Mode::Replay, Action::SeekStart),
+    (KeyCode::End, Mode::Replay, Action::SeekEnd),
+    (KeyCode::Char('g'), Mode::Replay, Action::SeekToTime),
+    (KeyCode::Char('n'), Mode::Replay, Action::NextMarker),
+    (KeyCode::Char('N'), Mode::Replay, Action::PrevMarker),
+    (KeyCode::Char('0'), Mode::Replay, Action::SeekPercent(0)),
+    (KeyCode::Char('1'), Mode::Replay, Action::SeekPercent(1)),
+    (KeyCode::Char('2'), Mode::Replay, Action::SeekPercent(2)),
+    (KeyCode::Char('3'), Mode::Replay, Action::SeekPercent(3)),
+    (KeyCode::Char('4'), Mode::Replay, Action::SeekPercent(4)),
+    (KeyCode::Char('5'), Mode::Replay, Action::SeekPercent(5)),
+    (KeyCode::Char('6'), Mode::Replay, Action::SeekPercent(6)),
+    (KeyCode::Char('7'), Mode::Replay, Action::SeekPercent(7)),
+    (KeyCode::Char('8'), Mode::Replay, Action::SeekPercent(8)),
+    (KeyCode::Char('9'), Mode::Replay, Action::SeekPercent(9)),
+];
+
+/// On-disk keymap: a `[keys]` table from action name to one key or a list
+/// of keys.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeymapConfig {
+    #[serde(default)]
+    keys: BTreeMap<String, KeyList>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Parses a key name: a single character, or a named key such as `Up`,
+/// `Enter`, `Space` or `F5` (names are case-insensitive).
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    let lower = name.to_ascii_lowercase();
+    let key = match lower.as_str() {
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "space" => KeyCode::Char(' '),
+        _ => {
+            let n = lower.strip_prefix('f')?.parse().ok()?;
+            return (1..=12).contains(&n).then_some(KeyCode::F(n));
+        }
+    };
+    Some(key)
+}
+
+/// Key to action lookup for the live and replay views.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    live: HashMap<KeyCode, Action>,
+    replay: HashMap<KeyCode, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Self {
+            live: HashMap::new(),
+            replay: HashMap::new(),
+        };
+        for (key, mode, action) in DEFAULT_BINDINGS {
+            if *mode != Mode::Replay {
+                keymap.live.insert(*key, action.clone());
+            }
+            if *mode != Mode::Live {
+                keymap.replay.insert(*key, action.clone());
+            }
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// Loads a keymap file over the defaults, or returns the defaults if no
+    /// path is given.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, or names an
+    /// unknown action or key.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read keymap: {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("invalid keymap file: {}", path.display()))
+    }
+
+    /// Each action listed replaces all of its default keys, in the views
+    /// that action has default keys in; an empty list unbinds it. A key
+    /// taken from another action no longer triggers that action.
+    fn parse(text: &str) -> Result<Self> {
+        let config: KeymapConfig = toml::from_str(text)?;
+        let mut keymap = Self::default();
+        for (name, keys) in config.keys {
+            let Some(action) = Action::from_name(&name) else {
+                let known: Vec<_> = Action::ALL.iter().map(|(name, _)| *name).collect();
+                bail!(
+                    "unknown action \"{name}\" (expected one of: {})",
+                    known.join(", ")
+                );
+            };
+            let keys = match keys {
+                KeyList::One(key) => vec![key],
+                KeyList::Many(keys) => keys,
+            };
+            let codes = keys
+                .iter()
+                .map(|key| {
+                    parse_key(key)
+                        .with_context(|| format!("unknown key \"{key}\" for action \"{name}\""))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            keymap.rebind(&action, &codes);
+        }
+        Ok(keymap)
+    }
+
+    fn rebind(&mut self, action: &Action, keys: &[KeyCode]) {
+        let in_mode = |wanted: Mode| {
+            DEFAULT_BINDINGS
+                .iter()
+                .any(|(_, mode, a)| a == action && *mode != wanted)
+        };
+        let (live, replay) = (in_mode(Mode::Replay), in_mode(Mode::Live));
+        for (map, applies) in [(&mut self.live, live), (&mut self.replay, replay)] {
+            if !applies {
+                continue;
+            }
+            map.retain(|_, bound| bound != action);
+            for &key in keys {
+                map.insert(key, action.clone());
+            }
+        }
+    }
+
+    /// The action `key` is bound to in the live or replay view.
+    #[must_use]
+    pub fn action(&self, key: KeyCode, is_replay: bool) -> Action {
+        let map = if is_replay { &self.replay } else { &self.live };
+        map.get(&key).cloned().unwrap_or(Action::None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_depend_on_mode() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action(KeyCode::Right, true), Action::SeekForward);
+        assert_eq!(keymap.action(KeyCode::Right, false), Action::ToggleCollapse);
+        assert_eq!(
+            keymap.action(KeyCode::Char('p'), false),
+            Action::ToggleFreeze
+        );
+        assert_eq!(keymap.action(KeyCode::Char('p'), true), Action::None);
+        assert_eq!(
+            keymap.action(KeyCode::Char('='), true),
+            Action::IncreaseSampleRate
+        );
+        assert_eq!(keymap.action(KeyCode::Char('x'), false), Action::None);
+        assert_eq!(
+            keymap.action(KeyCode::Char('5'), false),
+            Action::FollowThread(5)
+        );
+        assert_eq!(
+            keymap.action(KeyCode::Char('5'), true),
+            Action::SeekPercent(5)
+        );
+        assert_eq!(
+            keymap.action(KeyCode::Char('0'), true),
+            Action::SeekPercent(0)
+        );
+        assert_eq!(keymap.action(KeyCode::Char('0'), false), Action::None);
+    }
+
+    #[test]
+    fn following_can_be_rebound_for_replay() {
+        let keymap = Keymap::parse("[keys]\nfollow_thread_1 = \"F1\"\n").unwrap();
+        assert_eq!(keymap.action(KeyCode::F(1), true), Action::FollowThread(1));
+        assert_eq!(keymap.action(KeyCode::F(1), false), Action::FollowThread(1));
+        assert_eq!(
+            keymap.action(KeyCode::Char('1'), true),
+            Action::SeekPercent(1)
+        );
+    }
+
+    #[test]
+    fn vim_keys_navigate() {
+        let keymap = Keymap::default();
+        for is_replay in [false, true] {
+            assert_eq!(
+                keymap.action(KeyCode::Char('j'), is_replay),
+                Action::PanelDown
+            );
+            assert_eq!(
+                keymap.action(KeyCode::Char('k'), is_replay),
+                Action::PanelUp
+            );
+        }
+        assert_eq!(
+            keymap.action(KeyCode::Char('h'), true),
+            Action::SeekBackward
+        );
+        assert_eq!(keymap.action(KeyCode::Char('l'), true), Action::SeekForward);
+        assert_eq!(keymap.action(KeyCode::Char('L'), true), Action::ToggleLoop);
+        assert_eq!(keymap.action(KeyCode::Char('h'), false), Action::None);
+        assert_eq!(keymap.action(KeyCode::Char('l'), false), Action::None);
+    }
+
+    #[test]
+    fn file_replaces_an_actions_keys() {
+        let keymap = Keymap::parse(
+            r#"
+            [keys]
+            panel_down = ["j", "Down"]
+            seek_forward = "L"
+            quit = []
+            "#,
+        )
+        .unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('j'), false), Action::PanelDown);
+        assert_eq!(keymap.action(KeyCode::Down, true), Action::PanelDown);
+        assert_eq!(keymap.action(KeyCode::Char('L'), true), Action::SeekForward);
+        // Seeking only has keys in replay, and Right keeps its live binding.
+        assert_eq!(keymap.action(KeyCode::Char('L'), false), Action::None);
+        assert_eq!(keymap.action(KeyCode::Right, true), Action::None);
+        assert_eq!(keymap.action(KeyCode::Right, false), Action::ToggleCollapse);
+        assert_eq!(keymap.action(KeyCode::Char('q'), false), Action::None);
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let err = Keymap::parse("[keys]\njump = \"j\"\n").unwrap_err();
+        let message = format!("{err:#}");
+        assert!(message.contains("unknown action \"jump\""));
+        assert!(message.contains("panel_down"));
+
+        let err = Keymap::parse("[keys]\nquit = \"Hyper\"\n").unwrap_err();
+        assert!(format!("{err:#}").contains("unknown key \"Hyper\""));
+
+        assert_eq!(parse_key("F5"), Some(KeyCode::F(5)));
+        assert_eq!(parse_key("space"), Some(KeyCode::Char(' ')));
+        assert_eq!(parse_key("F13"), None);
     }
 }
@@ -1,18 +1,88 @@
 // SPDX-License-Identifier: MIT
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 
+/// Height of a collapsed panel: its top and bottom border around the title.
+const COLLAPSED_HEIGHT: u16 = 3;
+
 pub struct PanelState {
     pub name: &'static str,
     pub collapsed: bool,
     pub min_height: u16,
 }
 
-pub fn build_layout(panels: &[PanelState], area: Rect) -> Vec<Rect> {
-    let constraints: Vec<Constraint> = panels
+impl PanelState {
+    fn required_height(&self) -> u16 {
+        if self.collapsed {
+            COLLAPSED_HEIGHT
+        } else {
+            self.min_height
+        }
+    }
+}
+
+/// Whether `area` is too short for every panel at its minimum height, so
+/// stacking them would clip each one.
+#[must_use]
+pub fn too_short(panels: &[PanelState], area: Rect) -> bool {
+    area.height < panels.iter().map(PanelState::required_height).sum()
+}
+
+/// Whether the two columns of `build_wide_layout` are too short: the `left`
+/// panel for the left column, or the others stacked in the right one.
+#[must_use]
+pub fn too_short_wide(panels: &[PanelState], area: Rect, left: usize) -> bool {
+    let right: u16 = panels
         .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != left)
+        .map(|(_, p)| p.required_height())
+        .sum();
+    let left = panels.get(left).map_or(0, PanelState::required_height);
+    area.height < left.max(right)
+}
+
+/// Splits `area` between the panels. With `only`, that panel gets the whole
+/// area and the others an empty rect.
+pub fn build_layout(panels: &[PanelState], area: Rect, only: Option<usize>) -> Vec<Rect> {
+    if let Some(only) = only.filter(|&i| i < panels.len()) {
+        let mut areas = vec![Rect::new(area.x, area.y, area.width, 0); panels.len()];
+        areas[only] = area;
+        return areas;
+    }
+    stack(panels.iter(), area)
+}
+
+/// Splits `area` into two columns: the `left` panel fills the left one and
+/// the others are stacked in the right one, in order.
+pub fn build_wide_layout(panels: &[PanelState], area: Rect, left: usize) -> Vec<Rect> {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .split(area);
+    let others = panels
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != left)
+        .map(|(_, p)| p);
+    let mut right = stack(others, columns[1]).into_iter();
+    (0..panels.len())
+        .map(|i| {
+            if i == left {
+                columns[0]
+            } else {
+                right.next().unwrap_or_default()
+            }
+        })
+        .collect()
+}
+
+/// Stacks `panels` top to bottom in `area`, each at least its minimum
+/// height.
+fn stack<'a>(panels: impl Iterator<Item = &'a PanelState>, area: Rect) -> Vec<Rect> {
+    let constraints: Vec<Constraint> = panels
         .map(|p| {
             if p.collapsed {
-                Constraint::Length(3)
+                Constraint::Length(COLLAPSED_HEIGHT)
             } else {
                 Constraint::Min(p.min_height)
             }
@@ -25,3 +95,60 @@ pub fn build_layout(panels: &[PanelState], area: Rect) -> Vec<Rect> {
         .split(area)
         .to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn panels() -> Vec<PanelState> {
+        ["a", "b", "c"]
+            .into_iter()
+            .map(|name| PanelState {
+                name,
+                collapsed: false,
+                min_height: 10,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_panel_gets_the_whole_area() {
+        let panels = panels();
+        let tall = Rect::new(0, 0, 80, 40);
+        assert!(!too_short(&panels, tall));
+        let areas = build_layout(&panels, tall, None);
+        assert!(areas.iter().all(|a| a.height >= 10));
+
+        let short = Rect::new(0, 0, 80, 20);
+        assert!(too_short(&panels, short));
+        let areas = build_layout(&panels, short, Some(1));
+        assert_eq!(areas[1], short);
+        assert_eq!(areas[0].height, 0);
+        assert_eq!(areas[2].height, 0);
+    }
+
+    #[test]
+    fn collapsing_panels_makes_room_for_all() {
+        let mut panels = panels();
+        panels[0].collapsed = true;
+        panels[2].collapsed = true;
+        let area = Rect::new(0, 0, 80, 20);
+        assert!(!too_short(&panels, area));
+        let areas = build_layout(&panels, area, None);
+        assert_eq!(areas[0].height, COLLAPSED_HEIGHT);
+        assert_eq!(areas[1].height, 20 - 2 * COLLAPSED_HEIGHT);
+    }
+
+    #[test]
+    fn wide_layout_puts_one_panel_beside_the_rest() {
+        let panels = panels();
+        let area = Rect::new(0, 0, 200, 20);
+        assert!(too_short(&panels, area));
+        assert!(!too_short_wide(&panels, area, 0));
+        let areas = build_wide_layout(&panels, area, 0);
+        assert_eq!(areas[0], Rect::new(0, 0, 100, 20));
+        assert_eq!(areas[1], Rect::new(100, 0, 100, 10));
+        assert_eq!(areas[2], Rect::new(100, 10, 100, 10));
+        assert!(too_short_wide(&panels, Rect::new(0, 0, 200, 15), 0));
+    }
+}
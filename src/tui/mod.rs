@@ -1,7 +1,10 @@
 // SPDX-License-Identifier: MIT
 pub mod app;
+pub mod help;
 pub mod input;
 pub mod layout;
 pub mod panels;
+pub mod process_tabs;
 pub mod replay_controls;
 pub mod theme;
+pub mod ui_state;
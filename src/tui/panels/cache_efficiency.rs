@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: MIT
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+
+use crate::sampler::accumulator::ComputedFrame;
+use crate::tui::panels::mem_history::SparklineWidget;
+use crate::tui::panels::mem_stats::format_bytes;
+use crate::tui::theme::Theme;
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Block lookup cache size and miss rate in one sample.
+#[derive(Clone, Copy, Default)]
+pub struct CachePoint {
+    pub misses_per_second: f64,
+    /// Resident lookup, L1 lookup and block link memory together.
+    pub cache_bytes: u64,
+}
+
+impl CachePoint {
+    #[must_use]
+    pub fn from_frame(data: &ComputedFrame) -> Self {
+        Self {
+            misses_per_second: data.rates.cache_miss,
+            cache_bytes: data.mem.lookup + data.mem.lookup_l1 + data.mem.block_links,
+        }
+    }
+}
+
+/// The cache sizes and the latest miss rate, with the misses per MiB of
+/// cache once memory has been sampled.
+fn summary_lines<'a>(data: &ComputedFrame, theme: &Theme) -> Vec<Line<'a>> {
+    let point = CachePoint::from_frame(data);
+    let sizes = if point.cache_bytes == 0 {
+        Span::raw("waiting for memory data...")
+    } else {
+        Span::raw(format!(
+            "lookup {}, L1 {}, block links {} ({} total)",
+            format_bytes(data.mem.lookup),
+            format_bytes(data.mem.lookup_l1),
+            format_bytes(data.mem.block_links),
+            format_bytes(point.cache_bytes),
+        ))
+    };
+    let mut misses = format!(
+        "{} this sample, {:.2} per second",
+        data.total_cache_miss_count, point.misses_per_second
+    );
+    if point.cache_bytes > 0 {
+        #[allow(clippy::cast_precision_loss)]
+        let per_mib = point.misses_per_second / (point.cache_bytes as f64 / MIB);
+        let _ = write!(misses, ", {per_mib:.2} per second per MiB of cache");
+    }
+    vec![
+        Line::from(vec![Span::styled("Cache memory: ", theme.title), sizes]),
+        Line::from(vec![
+            Span::styled("Cache misses: ", theme.title),
+            Span::raw(misses),
+        ]),
+    ]
+}
+
+/// Draws the lookup cache's memory next to its miss rate, followed by a
+/// sparkline of the miss rate over the kept history. A miss rate that stays
+/// high while the cache stops growing suggests it is undersized.
+pub fn render(
+    frame: &mut ratatui::Frame,
+    area: Rect,
+    data: &ComputedFrame,
+    history: &VecDeque<CachePoint>,
+    theme: &Theme,
+) {
+    if area.height < 2 || area.width < 30 {
+        return;
+    }
+    let bottom = area.y + area.height;
+    let mut y = area.y;
+    for line in summary_lines(data, theme) {
+        if y >= bottom {
+            return;
+        }
+        frame.render_widget(Paragraph::new(line), Rect::new(area.x, y, area.width, 1));
+        y += 1;
+    }
+
+    if y + 1 >= bottom {
+        return;
+    }
+    let peak = history
+        .iter()
+        .map(|p| p.misses_per_second)
+        .fold(0.0, f64::max);
+    let growth = match (history.front(), history.back()) {
+        (Some(first), Some(last)) if first.cache_bytes > 0 => format!(
+            ", cache {} -> {}",
+            format_bytes(first.cache_bytes),
+            format_bytes(last.cache_bytes)
+        ),
+        _ => String::new(),
+    };
+    let label = Line::from(vec![
+        Span::styled("Trend (misses/s): ", theme.title),
+        Span::raw(format!("peak {peak:.2}{growth}")),
+    ]);
+    frame.render_widget(Paragraph::new(label), Rect::new(area.x, y, area.width, 1));
+    y += 1;
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let values: Vec<u64> = history
+        .iter()
+        .map(|p| p.misses_per_second.round() as u64)
+        .collect();
+    frame.render_widget(
+        SparklineWidget {
+            values: &values,
+            style: theme.histo_jit_load,
+            max: None,
+        },
+        Rect::new(area.x, y, area.width, bottom - y),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::accumulator::RatesPerSecond;
+
+    fn text(data: &ComputedFrame) -> Vec<String> {
+        summary_lines(data, &Theme::default())
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn miss_rate_is_scaled_by_cache_size_once_memory_is_known() {
+        let mut data = ComputedFrame {
+            total_cache_miss_count: 50,
+            rates: RatesPerSecond {
+                cache_miss: 100.0,
+                ..RatesPerSecond::default()
+            },
+            ..ComputedFrame::default()
+        };
+        let early = text(&data);
+        assert_eq!(early[0], "Cache memory: waiting for memory data...");
+        assert_eq!(early[1], "Cache misses: 50 this sample, 100.00 per second");
+
+        data.mem.lookup = 3 * 1024 * 1024;
+        data.mem.block_links = 1024 * 1024;
+        let lines = text(&data);
+        assert_eq!(
+            lines[0],
+            "Cache memory: lookup 3 MiB, L1 0 B, block links 1 MiB (4 MiB total)"
+        );
+        assert!(lines[1].ends_with(", 25.00 per second per MiB of cache"));
+    }
+}
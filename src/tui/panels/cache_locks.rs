@@ -0,0 +1,174 @@
+// SPDX-License-Identifier: MIT
+use std::collections::VecDeque;
+
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+
+use crate::datasource::SessionMetadata;
+use crate::sampler::accumulator::ComputedFrame;
+use crate::tui::panels::mem_history::SparklineWidget;
+use crate::tui::theme::{BLOCK_FULL, Theme};
+
+/// Block-cache lock time in one sample, as percentages of the time the
+/// sampled threads could have spent in the sample period.
+#[derive(Clone, Copy, Default)]
+pub struct LockPoint {
+    pub read_pct: f64,
+    pub write_pct: f64,
+}
+
+impl LockPoint {
+    /// Converts the frame's lock cycles to shares of the sample period,
+    /// summed over at most one thread per hardware core.
+    #[allow(clippy::cast_precision_loss)]
+    #[must_use]
+    pub fn from_frame(data: &ComputedFrame, metadata: &SessionMetadata) -> Self {
+        let active = data
+            .threads_sampled
+            .min(metadata.hardware_concurrency)
+            .max(1) as f64;
+        let available_seconds = data.sample_period_ns as f64 / 1e9 * active;
+        let freq = metadata.cycle_counter_frequency as f64;
+        if available_seconds <= 0.0 || freq <= 0.0 {
+            return Self::default();
+        }
+        let percent = |cycles: u64| cycles as f64 / freq / available_seconds * 100.0;
+        Self {
+            read_pct: percent(data.total_cache_read_lock_time),
+            write_pct: percent(data.total_cache_write_lock_time),
+        }
+    }
+}
+
+/// A horizontal bar `width` cells wide, filled in proportion to `percent`.
+fn bar(percent: f64, width: usize) -> (String, String) {
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let filled = ((percent.clamp(0.0, 100.0) / 100.0 * width as f64).round() as usize).min(width);
+    (
+        BLOCK_FULL.to_string().repeat(filled),
+        "\u{2591}".repeat(width - filled),
+    )
+}
+
+fn lock_style(percent: f64, theme: &Theme) -> Style {
+    if percent >= 50.0 {
+        theme.load_high
+    } else if percent >= 10.0 {
+        theme.load_medium
+    } else {
+        theme.load_normal
+    }
+}
+
+/// Draws read and write lock time as bars for the latest sample, followed by
+/// a sparkline of their combined share over the kept history.
+pub fn render(
+    frame: &mut ratatui::Frame,
+    area: Rect,
+    history: &VecDeque<LockPoint>,
+    theme: &Theme,
+) {
+    if area.height < 2 || area.width < 30 {
+        return;
+    }
+    let Some(latest) = history.back() else {
+        frame.render_widget(Paragraph::new("Waiting for data..."), area);
+        return;
+    };
+
+    let bar_width = usize::from(area.width).saturating_sub(22);
+    let rows = [
+        ("Read lock: ", latest.read_pct),
+        ("Write lock:", latest.write_pct),
+    ];
+    let mut y = area.y;
+    for (label, percent) in rows {
+        if y >= area.y + area.height {
+            return;
+        }
+        let style = lock_style(percent, theme);
+        let (filled, empty) = bar(percent, bar_width);
+        let line = Line::from(vec![
+            Span::styled(format!("{label} "), theme.title),
+            Span::raw(format!("{percent:6.2}% ")),
+            Span::styled(filled, style),
+            Span::styled(empty, theme.border_normal),
+        ]);
+        frame.render_widget(Paragraph::new(line), Rect::new(area.x, y, area.width, 1));
+        y += 1;
+    }
+
+    let bottom = area.y + area.height;
+    if y + 1 >= bottom {
+        return;
+    }
+    let peak = history
+        .iter()
+        .map(|p| p.read_pct + p.write_pct)
+        .fold(0.0, f64::max);
+    let label = Line::from(vec![
+        Span::styled("Trend (read + write): ", theme.title),
+        Span::raw(format!("peak {peak:.2}%")),
+    ]);
+    frame.render_widget(Paragraph::new(label), Rect::new(area.x, y, area.width, 1));
+    y += 1;
+
+    // Hundredths of a percent, so small shares still register.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let values: Vec<u64> = history
+        .iter()
+        .map(|p| ((p.read_pct + p.write_pct) * 100.0).round() as u64)
+        .collect();
+    frame.render_widget(
+        SparklineWidget {
+            values: &values,
+            style: theme.load_medium,
+            max: None,
+        },
+        Rect::new(area.x, y, area.width, bottom - y),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::make_metadata;
+
+    #[test]
+    fn lock_time_is_share_of_sample_period() {
+        let metadata = SessionMetadata {
+            cycle_counter_frequency: 1_000_000,
+            ..make_metadata()
+        };
+        // Two threads over half a second have one second between them;
+        // 100k and 250k cycles at 1 MHz are 10% and 25% of it.
+        let frame = ComputedFrame {
+            sample_period_ns: 500_000_000,
+            threads_sampled: 2,
+            total_cache_read_lock_time: 100_000,
+            total_cache_write_lock_time: 250_000,
+            ..ComputedFrame::default()
+        };
+        let point = LockPoint::from_frame(&frame, &metadata);
+        assert!((point.read_pct - 10.0).abs() < 1e-9);
+        assert!((point.write_pct - 25.0).abs() < 1e-9);
+
+        let empty = LockPoint::from_frame(&ComputedFrame::default(), &metadata);
+        assert!(empty.read_pct.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn bar_fills_in_proportion() {
+        let (filled, empty) = bar(25.0, 8);
+        assert_eq!(filled.chars().count(), 2);
+        assert_eq!(empty.chars().count(), 6);
+        let (filled, _) = bar(150.0, 8);
+        assert_eq!(filled.chars().count(), 8);
+    }
+}
@@ -1,43 +1,166 @@
 // SPDX-License-Identifier: MIT
 use ratatui::layout::Rect;
+use ratatui::style::Modifier;
 use ratatui::text::{Line, Span};
 use ratatui::widgets::Paragraph;
 
 use crate::datasource::SessionMetadata;
+use crate::tui::panels::mem_stats::format_bytes;
 use crate::tui::theme::Theme;
 
-pub fn render(
-    frame: &mut ratatui::Frame,
-    area: Rect,
-    metadata: &SessionMetadata,
-    is_replay: bool,
-    sample_period_ns: Option<u64>,
-    theme: &Theme,
-) {
-    if area.height == 0 || area.width == 0 {
-        return;
+/// Live session state shown after the process details.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeaderStatus<'a> {
+    pub sample_period_ns: Option<u64>,
+    pub notice: Option<&'a str>,
+    /// Sample periods that passed without a sample because felix fell behind.
+    pub dropped_samples: u64,
+    /// Whole seconds since the live session started.
+    pub elapsed_secs: u64,
+    /// Replay position and recording length, in whole seconds.
+    pub playback_secs: Option<(u64, u64)>,
+    /// Shown first, highlighted, e.g. for an unexpected stats version.
+    pub warning: Option<&'a str>,
+    /// Size of the file a live `--record` session is writing; `None` when
+    /// nothing is being recorded.
+    pub recording_bytes: Option<u64>,
+}
+
+/// Formats a duration as `mm:ss`, or `hh:mm:ss` from one hour on.
+#[must_use]
+pub fn format_clock(total_seconds: u64) -> String {
+    let (hours, minutes, seconds) = (
+        total_seconds / 3600,
+        total_seconds / 60 % 60,
+        total_seconds % 60,
+    );
+    if hours > 0 {
+        format!("{hours:02}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes:02}:{seconds:02}")
     }
+}
+
+/// The REC badge's text after its blinking dot.
+fn recording_badge(bytes: u64) -> String {
+    format!(" REC {} ", format_bytes(bytes))
+}
 
+fn header_text(metadata: &SessionMetadata, is_replay: bool, status: &HeaderStatus) -> String {
     let version = env!("CARGO_PKG_VERSION");
 
-    let text = if is_replay {
+    if is_replay {
+        let playback_part = status
+            .playback_secs
+            .map_or_else(String::new, |(pos, total)| {
+                format!(" | {} / {}", format_clock(pos), format_clock(total))
+            });
+        let notice_part = status
+            .notice
+            .map_or_else(String::new, |n| format!(" | {n}"));
         format!(
-            "felix v{version} | REPLAY | FEX: {} | Type: {} | Head: {:#x} | Size: {:#x}",
+            "felix v{version} | REPLAY | FEX: {} | Type: {} | Head: {:#x} | Size: {:#x}{playback_part}{notice_part}",
             metadata.fex_version, metadata.app_type, metadata.head, metadata.size,
         )
     } else {
-        let sample_part = sample_period_ns
+        let sample_part = status
+            .sample_period_ns
             .map_or_else(String::new, |ns| format!(" | Sample: {}ms", ns / 1_000_000));
+        let dropped_part = if status.dropped_samples > 0 {
+            format!(" | dropped: {}", status.dropped_samples)
+        } else {
+            String::new()
+        };
+        let notice_part = status
+            .notice
+            .map_or_else(String::new, |n| format!(" | {n}"));
         format!(
-            "felix v{version} | PID: {} | FEX: {} | Type: {} | Head: {:#x} | Size: {:#x}{sample_part}",
-            metadata.pid, metadata.fex_version, metadata.app_type, metadata.head, metadata.size,
+            "felix v{version} | PID: {} | FEX: {} | Type: {} | Head: {:#x} | Size: {:#x} | Elapsed: {}{sample_part}{dropped_part}{notice_part}",
+            metadata.pid,
+            metadata.fex_version,
+            metadata.app_type,
+            metadata.head,
+            metadata.size,
+            format_clock(status.elapsed_secs),
         )
-    };
+    }
+}
+
+pub fn render(
+    frame: &mut ratatui::Frame,
+    area: Rect,
+    metadata: &SessionMetadata,
+    is_replay: bool,
+    status: &HeaderStatus,
+    theme: &Theme,
+) {
+    if area.height == 0 || area.width == 0 {
+        return;
+    }
 
-    let line = Line::from(vec![Span::styled(
-        format!("{text:<width$}", width = area.width as usize),
-        theme.status_bar,
-    )]);
+    let text = header_text(metadata, is_replay, status);
+    let mut spans = Vec::new();
+    let mut width = area.width as usize;
+    if let Some(warning) = status.warning {
+        let banner = format!(" {warning} ");
+        width = width.saturating_sub(banner.chars().count());
+        spans.push(Span::styled(banner, theme.recording_indicator));
+    }
+    if let Some(bytes) = status.recording_bytes {
+        let badge = recording_badge(bytes);
+        width = width.saturating_sub(badge.chars().count() + 2);
+        spans.push(Span::styled(
+            " \u{25cf}",
+            theme.recording_indicator.add_modifier(Modifier::SLOW_BLINK),
+        ));
+        spans.push(Span::styled(badge, theme.recording_indicator));
+    }
+    spans.push(Span::styled(format!("{text:<width$}"), theme.status_bar));
+    let line = Line::from(spans);
 
     frame.render_widget(Paragraph::new(line), area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fex::types::STATS_VERSION;
+    use crate::testing::make_metadata;
+
+    #[test]
+    fn live_header_shows_dropped_samples_only_when_nonzero() {
+        let metadata = SessionMetadata {
+            stats_version: STATS_VERSION,
+            ..make_metadata()
+        };
+        let mut status = HeaderStatus {
+            sample_period_ns: Some(250_000_000),
+            notice: Some("PAUSED"),
+            elapsed_secs: 75,
+            ..HeaderStatus::default()
+        };
+        let text = header_text(&metadata, false, &status);
+        assert!(text.ends_with("| Elapsed: 01:15 | Sample: 250ms | PAUSED"));
+
+        status.dropped_samples = 3;
+        let text = header_text(&metadata, false, &status);
+        assert!(text.ends_with("| Sample: 250ms | dropped: 3 | PAUSED"));
+        assert!(!header_text(&metadata, true, &status).contains("dropped"));
+
+        status.playback_secs = Some((83, 600));
+        assert!(header_text(&metadata, true, &status).ends_with("| 01:23 / 10:00 | PAUSED"));
+    }
+
+    #[test]
+    fn recording_badge_shows_file_size() {
+        assert_eq!(recording_badge(0), " REC 0 B ");
+        assert_eq!(recording_badge(3 * 1024 * 1024), " REC 3 MiB ");
+    }
+
+    #[test]
+    fn clock_adds_hours_when_needed() {
+        assert_eq!(format_clock(0), "00:00");
+        assert_eq!(format_clock(3599), "59:59");
+        assert_eq!(format_clock(3723), "01:02:03");
+    }
+}
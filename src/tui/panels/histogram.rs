@@ -10,11 +10,91 @@ use ratatui::widgets::{Paragraph, Widget};
 use crate::sampler::accumulator::HistogramEntry;
 use crate::tui::theme::{BLOCK_CHARS, BLOCK_FULL, Theme};
 
+/// Rows a full-scale bar takes, one per ten percent of load.
+const FULL_SCALE_ROWS: u16 = 10;
+/// Fewest columns between time axis ticks, so their labels do not touch.
+const MIN_TICK_SPACING: u64 = 10;
+/// Tick intervals the time axis picks from, in seconds.
+const TICK_STEPS_SECS: [u64; 12] = [1, 2, 5, 10, 15, 30, 60, 120, 300, 600, 1800, 3600];
+
 struct HistogramWidget<'a> {
     entries: &'a VecDeque<HistogramEntry>,
+    /// Time between columns, for the time axis; `None` leaves it out.
+    sample_period_ns: Option<u64>,
     theme: &'a Theme,
 }
 
+/// How long ago a time axis tick was, as `-30s`, `-2m`, `-1m30s` or `-1h`.
+fn format_offset(secs: u64) -> String {
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("-{s}s"),
+        (0, m, 0) => format!("-{m}m"),
+        (0, m, s) => format!("-{m}m{s}s"),
+        (h, 0, _) => format!("-{h}h"),
+        (h, m, _) => format!("-{h}h{m}m"),
+    }
+}
+
+/// Time axis labels for a chart `columns` wide whose rightmost column is
+/// the latest sample. Each label ends at its tick's column and is given as
+/// its starting column and text. Ticks fall on the first interval that
+/// leaves `MIN_TICK_SPACING` columns between them, and labels that would
+/// start left of `first_column` or overlap the previous one are left out.
+fn axis_labels(columns: u16, sample_period_ns: u64, first_column: u16) -> Vec<(u16, String)> {
+    if sample_period_ns == 0 || columns == 0 {
+        return Vec::new();
+    }
+    let Some((step_secs, step_columns)) = TICK_STEPS_SECS.iter().find_map(|&secs| {
+        let step_columns = secs * 1_000_000_000 / sample_period_ns;
+        (step_columns >= MIN_TICK_SPACING).then_some((secs, step_columns))
+    }) else {
+        return Vec::new();
+    };
+
+    let mut labels = vec![(columns.saturating_sub(3), "now".to_string())];
+    let mut next_free = labels[0].0;
+    for tick in 1.. {
+        let Ok(back) = u16::try_from(tick * step_columns) else {
+            break;
+        };
+        if back >= columns {
+            break;
+        }
+        let text = format_offset(tick * step_secs);
+        let end = columns - back;
+        #[allow(clippy::cast_possible_truncation)]
+        let Some(start) = end.checked_sub(text.len() as u16) else {
+            break;
+        };
+        if start < first_column {
+            break;
+        }
+        if end < next_free {
+            labels.push((start, text));
+            next_free = start;
+        }
+    }
+    labels
+}
+
+impl HistogramWidget<'_> {
+    /// Draws the time axis into `row`, right of `first_column` when it
+    /// shares the row with the legend.
+    fn render_axis(&self, row: Rect, first_column: u16, buf: &mut Buffer) {
+        let Some(period) = self.sample_period_ns else {
+            return;
+        };
+        #[allow(clippy::cast_possible_truncation)]
+        let columns = row.width.min(self.entries.len() as u16);
+        // With fewer samples than columns the bars are right-aligned.
+        let left = row.width - columns;
+        let first_column = first_column.saturating_sub(left);
+        for (x, text) in axis_labels(columns, period, first_column) {
+            buf.set_string(row.x + left + x, row.y, text, self.theme.border_normal);
+        }
+    }
+}
+
 impl Widget for HistogramWidget<'_> {
     #[allow(clippy::cast_possible_truncation)]
     fn render(self, area: Rect, buf: &mut Buffer) {
@@ -22,7 +102,10 @@ impl Widget for HistogramWidget<'_> {
             return;
         }
 
-        let legend_height: u16 = 1;
+        // The time axis gets its own row when the bars can still reach full
+        // scale, otherwise it shares the legend's.
+        let axis_row = self.sample_period_ns.is_some() && area.height > FULL_SCALE_ROWS + 1;
+        let legend_height: u16 = 1 + u16::from(axis_row);
         let chart_height = area.height.saturating_sub(legend_height);
         if chart_height == 0 {
             return;
@@ -107,15 +190,32 @@ impl Widget for HistogramWidget<'_> {
                 Span::raw("  "),
                 Span::styled("\u{25A0} Softfloat", self.theme.histo_softfloat),
             ]);
+            #[allow(clippy::cast_possible_truncation)]
+            let legend_width = legend.width() as u16;
             Paragraph::new(legend).render(legend_area, buf);
+            if axis_row {
+                self.render_axis(
+                    Rect {
+                        y: legend_y + 1,
+                        ..legend_area
+                    },
+                    0,
+                    buf,
+                );
+            } else {
+                self.render_axis(legend_area, legend_width + 2, buf);
+            }
         }
     }
 }
 
+/// Draws the load bars with their event pips, most recent on the right,
+/// over a legend and, given the sample period, a time axis.
 pub fn render(
     frame: &mut ratatui::Frame,
     area: Rect,
     histogram: &VecDeque<HistogramEntry>,
+    sample_period_ns: Option<u64>,
     theme: &Theme,
 ) {
     if area.height < 2 || area.width < 2 {
@@ -130,7 +230,79 @@ pub fn render(
 
     let widget = HistogramWidget {
         entries: histogram,
+        sample_period_ns,
         theme,
     };
     frame.render_widget(widget, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_read_as_time_ago() {
+        assert_eq!(format_offset(30), "-30s");
+        assert_eq!(format_offset(120), "-2m");
+        assert_eq!(format_offset(90), "-1m30s");
+        assert_eq!(format_offset(3600), "-1h");
+        assert_eq!(format_offset(5400), "-1h30m");
+    }
+
+    #[test]
+    fn ticks_are_spaced_by_a_round_interval() {
+        // 100 ms samples: 10 columns per second, so a tick every second.
+        let labels = axis_labels(40, 100_000_000, 0);
+        assert_eq!(
+            labels,
+            [
+                (37, "now".to_string()),
+                (27, "-1s".to_string()),
+                (17, "-2s".to_string()),
+                (7, "-3s".to_string()),
+            ]
+        );
+        // 1 s samples need 10 s between ticks to leave room for the labels.
+        let labels = axis_labels(40, 1_000_000_000, 0);
+        assert_eq!(labels[1], (26, "-10s".to_string()));
+        // Labels starting before the first free column are left out.
+        assert_eq!(axis_labels(40, 100_000_000, 20).len(), 2);
+        assert!(axis_labels(40, 0, 0).is_empty());
+    }
+
+    #[test]
+    fn axis_gets_its_own_row_when_there_is_room() {
+        let entries: VecDeque<HistogramEntry> = (0..60)
+            .map(|_| HistogramEntry {
+                load_percent: 50.0,
+                ..HistogramEntry::default()
+            })
+            .collect();
+        let rows = |height: u16| {
+            let backend = ratatui::backend::TestBackend::new(80, height);
+            let mut terminal = ratatui::Terminal::new(backend).unwrap();
+            terminal
+                .draw(|f| render(f, f.area(), &entries, Some(100_000_000), &Theme::default()))
+                .unwrap();
+            let buffer = terminal.backend().buffer().clone();
+            buffer
+                .content()
+                .chunks(80)
+                .map(|row| {
+                    row.iter()
+                        .map(ratatui::buffer::Cell::symbol)
+                        .collect::<String>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let tall = rows(12);
+        assert!(tall[10].contains("Softfloat"));
+        assert!(!tall[10].contains("now"));
+        assert!(tall[11].ends_with("now"));
+        assert!(tall[11].contains("-1s"));
+
+        let short = rows(10);
+        assert!(short[9].contains("Softfloat"));
+        assert!(short[9].ends_with("now"));
+    }
+}
@@ -3,15 +3,137 @@ use num_format::{Locale, ToFormattedString};
 use ratatui::layout::Rect;
 use ratatui::text::{Line, Span};
 use ratatui::widgets::Paragraph;
+use serde::{Deserialize, Serialize};
 
 use crate::datasource::SessionMetadata;
-use crate::sampler::accumulator::ComputedFrame;
+use crate::sampler::accumulator::{ComputedFrame, CumulativeCountStats, ThreadLoad};
 use crate::tui::theme::{BLOCK_CHARS, BLOCK_FULL, Theme};
 
-const NANOSECONDS_IN_SECOND: f64 = 1_000_000_000.0;
 const SCALE: f64 = 1000.0;
 const SCALE_STR: &str = "ms/second";
 
+/// What the view has gathered across samples.
+pub struct SessionTotals<'a> {
+    /// Highest FEX load seen.
+    pub peak_load: f64,
+    /// Per-sample event counts summed since attaching. `None` in replay,
+    /// where seeking would make a running sum meaningless.
+    pub counts: Option<&'a CumulativeCountStats>,
+}
+
+/// Display order of the per-thread load list. Only affects presentation; the
+/// recorded `thread_loads` keep the accumulator's order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThreadSort {
+    #[default]
+    #[serde(rename = "load")]
+    LoadDesc,
+    #[serde(rename = "cycles")]
+    CyclesDesc,
+    #[serde(rename = "tid")]
+    TidAsc,
+}
+
+impl ThreadSort {
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            Self::LoadDesc => Self::CyclesDesc,
+            Self::CyclesDesc => Self::TidAsc,
+            Self::TidAsc => Self::LoadDesc,
+        }
+    }
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::LoadDesc => "load",
+            Self::CyclesDesc => "cycles",
+            Self::TidAsc => "tid",
+        }
+    }
+
+    fn sorted(self, loads: &[ThreadLoad]) -> Vec<&ThreadLoad> {
+        let mut sorted: Vec<&ThreadLoad> = loads.iter().collect();
+        match self {
+            Self::LoadDesc => sorted.sort_by(|a, b| b.load_percent.total_cmp(&a.load_percent)),
+            Self::CyclesDesc => sorted.sort_by(|a, b| b.total_cycles.cmp(&a.total_cycles)),
+            Self::TidAsc => sorted.sort_by_key(|t| t.tid),
+        }
+        sorted
+    }
+}
+
+/// Unit of the times the panel shows, which FEX counts in cycles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeUnit {
+    /// Milliseconds spent in the sample.
+    #[default]
+    #[serde(rename = "ms")]
+    Millis,
+    /// Raw cycle counts, which need no cycle counter frequency.
+    #[serde(rename = "cycles")]
+    Cycles,
+    /// Percent of the time between samples.
+    #[serde(rename = "percent")]
+    Percent,
+}
+
+impl TimeUnit {
+    #[must_use]
+    pub fn next(self) -> Self {
+        match self {
+            Self::Millis => Self::Cycles,
+            Self::Cycles => Self::Percent,
+            Self::Percent => Self::Millis,
+        }
+    }
+}
+
+/// Seconds the frame covers, `None` if unknown.
+fn frame_seconds(data: &ComputedFrame) -> Option<f64> {
+    let ns = if data.elapsed_ns > 0 {
+        data.elapsed_ns
+    } else {
+        data.sample_period_ns
+    };
+    #[allow(clippy::cast_precision_loss)]
+    (ns > 0).then(|| ns as f64 / 1e9)
+}
+
+/// How the per-thread load list is presented.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadView {
+    pub sort: ThreadSort,
+    /// Unit of the thread and aggregate times.
+    pub unit: TimeUnit,
+    /// Threads below this load percentage are left out of the list and
+    /// counted in a summary line instead.
+    pub min_load: f32,
+    /// Thread followed by the history panel, its number highlighted.
+    pub followed: Option<u32>,
+    /// Thread listed first and highlighted whatever its load.
+    pub pinned: Option<u32>,
+    /// Leave out the follow key numbers, for views where the digits do
+    /// something else (replay seeks with them).
+    pub hide_follow_keys: bool,
+}
+
+/// Threads the list shows, in display order; number keys pick from these.
+#[must_use]
+pub fn listed_threads(data: &ComputedFrame, view: ThreadView) -> Vec<&ThreadLoad> {
+    let (mut pinned, rest): (Vec<&ThreadLoad>, Vec<&ThreadLoad>) = view
+        .sort
+        .sorted(&data.thread_loads)
+        .into_iter()
+        .partition(|tl| view.pinned == Some(tl.tid));
+    pinned.extend(
+        rest.into_iter()
+            .filter(|tl| tl.load_percent >= view.min_load),
+    );
+    pinned
+}
+
 fn load_style(load: f32, theme: &Theme) -> ratatui::style::Style {
     if load >= 75.0 {
         theme.load_high
@@ -64,36 +186,153 @@ fn render_thread_loads<'a>(
     metadata: &SessionMetadata,
     theme: &Theme,
     bar_width: usize,
+    view: ThreadView,
 ) -> Vec<Line<'a>> {
     #[allow(clippy::cast_precision_loss)]
     let freq = metadata.cycle_counter_frequency as f64;
     let mut lines: Vec<Line<'a>> = Vec::new();
 
     lines.push(Line::from(format!(
-        "Top {} threads executing ({} total)",
+        "Top {} threads executing ({} total, sorted by {})",
         data.thread_loads.len(),
         data.threads_sampled,
+        view.sort.label(),
     )));
 
-    for tl in &data.thread_loads {
+    // Without a cycle counter frequency cycles cannot become times, so the
+    // raw counts are shown grayed out instead.
+    let unknown_freq = match view.unit {
+        TimeUnit::Cycles => false,
+        TimeUnit::Millis => freq <= 0.0,
+        TimeUnit::Percent => freq <= 0.0 || frame_seconds(data).is_none(),
+    };
+    let shown = listed_threads(data, view);
+    let hidden = data.thread_loads.len() - shown.len();
+    if let Some(tid) = view.pinned
+        && shown.first().is_none_or(|tl| tl.tid != tid)
+    {
+        lines.push(Line::styled(
+            format!("  tid {tid} (pinned): not among the reported threads"),
+            theme.border_selected,
+        ));
+    }
+    for (i, tl) in shown.into_iter().enumerate() {
         let load = tl.load_percent.min(100.0);
         let bar = build_bar(load, bar_width);
-        let ms = cycles_to_ms(tl.total_cycles, freq);
 
+        // The first nine are numbered for the keys that follow them.
+        let key = if i < 9 && !view.hide_follow_keys {
+            format!("{} ", i + 1)
+        } else {
+            "  ".to_string()
+        };
+        let key_style = if view.followed == Some(tl.tid) {
+            theme.border_selected
+        } else {
+            theme.title
+        };
         let style = load_style(tl.load_percent, theme);
         let bar_span = Span::styled(format!("[{bar}]"), style);
-        let info_span = Span::raw(format!(
-            ": {load:.2}% ({ms} ms/S, {} cycles)",
-            tl.total_cycles
-        ));
-        lines.push(Line::from(vec![bar_span, info_span]));
+        let is_pinned = view.pinned == Some(tl.tid);
+        let info_style = if is_pinned {
+            theme.border_selected
+        } else {
+            ratatui::style::Style::default()
+        };
+        let cycles = tl.total_cycles;
+        let time = match view.unit {
+            _ if unknown_freq => format!("{cycles} cycles"),
+            TimeUnit::Millis => format!("{} ms/S, {cycles} cycles", cycles_to_ms(cycles, freq)),
+            TimeUnit::Cycles => format!("{cycles} cycles"),
+            TimeUnit::Percent => {
+                #[allow(clippy::cast_precision_loss)]
+                let percent = cycles as f64 / freq / frame_seconds(data).unwrap_or(1.0) * 100.0;
+                format!("{percent:.2}% of sample")
+            }
+        };
+        lines.push(Line::from(vec![
+            Span::styled(key, key_style),
+            bar_span,
+            Span::styled(format!(": {load:.2}% (tid {}, ", tl.tid), info_style),
+            Span::styled(
+                time,
+                if unknown_freq {
+                    theme.border_normal
+                } else {
+                    info_style
+                },
+            ),
+            Span::styled(
+                format!("){}", if is_pinned { " pinned" } else { "" }),
+                info_style,
+            ),
+        ]));
+    }
+    if hidden > 0 {
+        lines.push(Line::from(format!(
+            " +{hidden} more hidden (below {:.1}%)",
+            view.min_load,
+        )));
     }
 
     lines
 }
 
+/// Formats the running totals shown after a per-sample count: FEX's own
+/// total, then the sum since attaching.
+fn count_suffix(total: Option<u64>, since_start: Option<u64>) -> String {
+    let total = total.map(|n| format!("  [total: {}]", n.to_formatted_string(&Locale::en)));
+    let since_start =
+        since_start.map(|n| format!("  [since start: {}]", n.to_formatted_string(&Locale::en)));
+    total.unwrap_or_default() + &since_start.unwrap_or_default()
+}
+
+/// What the aggregate times need to be shown in `unit`.
+struct TimeScale {
+    unit: TimeUnit,
+    freq: f64,
+    /// Threads the percent in the millisecond view is divided over.
+    max_active: f64,
+    frame_seconds: Option<f64>,
+}
+
+impl TimeScale {
+    /// Cycles in the sample as a line `label: time`, grayed out as raw
+    /// cycles when the unit needs a frequency or period that is unknown.
+    fn line<'a>(&self, label: &str, cycles: u64, theme: &Theme) -> Line<'a> {
+        #[allow(clippy::cast_precision_loss)]
+        let seconds = cycles as f64 / self.freq;
+        match (self.unit, self.frame_seconds) {
+            (TimeUnit::Cycles, _) => Line::from(format!("{label}: {cycles} cycles")),
+            _ if self.freq <= 0.0 => Line::styled(
+                format!("{label}: {cycles} cycles (cycle counter frequency unknown)"),
+                theme.border_normal,
+            ),
+            (TimeUnit::Millis, _) => Line::from(format!(
+                "{label}: {:.6} {SCALE_STR} ({:.2} percent)",
+                seconds * SCALE,
+                seconds / self.max_active * 100.0,
+            )),
+            (TimeUnit::Percent, Some(frame_seconds)) => Line::from(format!(
+                "{label}: {:.2}% of the sample period",
+                seconds / frame_seconds * 100.0,
+            )),
+            (TimeUnit::Percent, None) => Line::styled(
+                format!("{label}: {cycles} cycles (sample period unknown)"),
+                theme.border_normal,
+            ),
+        }
+    }
+}
+
 #[allow(clippy::cast_precision_loss)]
-fn render_aggregate_stats<'a>(data: &ComputedFrame, metadata: &SessionMetadata) -> Vec<Line<'a>> {
+fn render_aggregate_stats<'a>(
+    data: &ComputedFrame,
+    metadata: &SessionMetadata,
+    session: &SessionTotals<'_>,
+    unit: TimeUnit,
+    theme: &Theme,
+) -> Vec<Line<'a>> {
     let freq = metadata.cycle_counter_frequency as f64;
     let max_active = if data.threads_sampled == 0 {
         1.0
@@ -103,24 +342,30 @@ fn render_aggregate_stats<'a>(data: &ComputedFrame, metadata: &SessionMetadata)
         ts.min(hc)
     };
 
-    let jit_seconds = data.total_jit_time as f64 / freq;
-    let signal_seconds = data.total_signal_time as f64 / freq;
-    let cache_read_lock_seconds = data.total_cache_read_lock_time as f64 / freq;
-    let cache_write_lock_seconds = data.total_cache_write_lock_time as f64 / freq;
+    let scale = TimeScale {
+        unit,
+        freq,
+        max_active,
+        frame_seconds: frame_seconds(data),
+    };
+    let time_line = |label: &str, cycles: u64| scale.line(label, cycles, theme);
 
-    let sample_period_ns_f64 = data.sample_period_ns as f64;
-    let sigbus_per_second =
-        data.total_sigbus_count as f64 * (sample_period_ns_f64 / NANOSECONDS_IN_SECOND);
-    let cache_miss_per_second =
-        data.total_cache_miss_count as f64 * (sample_period_ns_f64 / NANOSECONDS_IN_SECOND);
-    let jit_cnt_per_second =
-        data.total_jit_count as f64 * (sample_period_ns_f64 / NANOSECONDS_IN_SECOND);
+    let sigbus_per_second = data.rates.sigbus;
+    let cache_miss_per_second = data.rates.cache_miss;
+    let jit_cnt_per_second = data.rates.jit_count;
+    let invocations_per_second = data.rates.jit_invocations;
+    let avg_block = data
+        .average_block_cycles()
+        .map(|cycles| {
+            if freq > 0.0 {
+                format!(" (avg {:.3} us/block)", cycles / freq * 1e6)
+            } else {
+                " (avg - us/block)".to_string()
+            }
+        })
+        .unwrap_or_default();
 
     let sample_period_ms = data.sample_period_ns / 1_000_000;
-    let jit_pct = jit_seconds / max_active * 100.0;
-    let signal_pct = signal_seconds / max_active * 100.0;
-    let rd_pct = cache_read_lock_seconds / max_active * 100.0;
-    let wr_pct = cache_write_lock_seconds / max_active * 100.0;
 
     let softfloat_fmt = data
         .total_float_fallback_count
@@ -135,63 +380,56 @@ fn render_aggregate_stats<'a>(data: &ComputedFrame, metadata: &SessionMetadata)
         || cum.cache_miss > 0
         || cum.jit > 0;
 
-    let cum_suffix = |val: u64| -> String {
-        if has_cumulative {
-            format!("  [total: {}]", val.to_formatted_string(&Locale::en))
-        } else {
-            String::new()
-        }
+    let cum_suffix = |val: u64, since_start: Option<u64>| {
+        count_suffix(has_cumulative.then_some(val), since_start)
     };
+    let counts = session.counts;
+    let peak_load = session.peak_load;
 
-    vec![
+    let mut lines = vec![
         Line::from(format!(
             "Total ({sample_period_ms} millisecond sample period):"
         )),
-        Line::from(format!(
-            "       JIT Time: {:.6} {SCALE_STR} ({jit_pct:.2} percent)",
-            jit_seconds * SCALE,
-        )),
-        Line::from(format!(
-            "    Signal Time: {:.6} {SCALE_STR} ({signal_pct:.2} percent)",
-            signal_seconds * SCALE,
-        )),
+        time_line("       JIT Time", data.total_jit_time),
+        time_line("    Signal Time", data.total_signal_time),
         Line::from(format!(
             "     SIGBUS Cnt: {} ({sigbus_per_second:.2} per second){}",
             data.total_sigbus_count,
-            cum_suffix(cum.sigbus),
+            cum_suffix(cum.sigbus, counts.map(|c| c.sigbus)),
         )),
         Line::from(format!(
             "        SMC Cnt: {}{}",
             data.total_smc_count,
-            cum_suffix(cum.smc),
+            cum_suffix(cum.smc, counts.map(|c| c.smc)),
         )),
         Line::from(format!(
             "  Softfloat Cnt: {softfloat_fmt}{}",
-            cum_suffix(cum.float_fallback),
+            cum_suffix(cum.float_fallback, counts.map(|c| c.float_fallback)),
         )),
         Line::from(format!(
-            "  CacheMiss Cnt: {} ({cache_miss_per_second:.2} per second) ({total_invocations_fmt} total JIT invocations){}",
+            "  CacheMiss Cnt: {} ({cache_miss_per_second:.2} per second) ({total_invocations_fmt} total JIT invocations, {invocations_per_second:.2} per second avg){}",
             data.total_cache_miss_count,
-            cum_suffix(cum.cache_miss),
-        )),
-        Line::from(format!(
-            "    $RDLck Time: {:.6} {SCALE_STR} ({rd_pct:.2} percent)",
-            cache_read_lock_seconds * SCALE,
+            cum_suffix(cum.cache_miss, counts.map(|c| c.cache_miss)),
         )),
+        time_line("    $RDLck Time", data.total_cache_read_lock_time),
+        time_line("    $WRLck Time", data.total_cache_write_lock_time),
         Line::from(format!(
-            "    $WRLck Time: {:.6} {SCALE_STR} ({wr_pct:.2} percent)",
-            cache_write_lock_seconds * SCALE,
-        )),
-        Line::from(format!(
-            "        JIT Cnt: {} ({jit_cnt_per_second:.2} per second){}",
+            "        JIT Cnt: {} ({jit_cnt_per_second:.2} per second){avg_block}{}",
             data.total_jit_count,
-            cum_suffix(cum.jit),
+            cum_suffix(cum.jit, counts.map(|c| c.jit)),
         )),
         Line::from(format!(
-            "FEX JIT Load:    {:.6} (cycles: {total_jit_time_all})",
+            "FEX JIT Load:    {:.6} (cycles: {total_jit_time_all}) (peak {peak_load:.2}%)",
             data.fex_load_percent,
         )),
-    ]
+    ];
+    if data.resets_detected > 0 {
+        lines.push(Line::from(format!(
+            " Counter resets: {} (deltas zeroed this sample)",
+            data.resets_detected,
+        )));
+    }
+    lines
 }
 
 pub fn render(
@@ -199,6 +437,8 @@ pub fn render(
     area: Rect,
     data: &ComputedFrame,
     metadata: &SessionMetadata,
+    view: ThreadView,
+    session: &SessionTotals<'_>,
     theme: &Theme,
 ) {
     if area.height < 2 || area.width < 10 {
@@ -207,10 +447,242 @@ pub fn render(
 
     let bar_width = (area.width.saturating_sub(20) as usize).clamp(4, 48);
 
-    let mut lines = render_thread_loads(data, metadata, theme, bar_width);
+    let mut lines = render_thread_loads(data, metadata, theme, bar_width, view);
     lines.push(Line::from(""));
-    lines.extend(render_aggregate_stats(data, metadata));
+    lines.extend(render_aggregate_stats(
+        data, metadata, session, view.unit, theme,
+    ));
 
     let paragraph = Paragraph::new(lines);
     frame.render_widget(paragraph, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::accumulator::RatesPerSecond;
+    use crate::testing::make_metadata;
+
+    fn load(tid: u32, load_percent: f32, total_cycles: u64) -> ThreadLoad {
+        ThreadLoad {
+            tid,
+            load_percent,
+            total_cycles,
+        }
+    }
+
+    fn metadata() -> SessionMetadata {
+        SessionMetadata {
+            hardware_concurrency: 4,
+            ..make_metadata()
+        }
+    }
+
+    #[test]
+    fn sort_modes_order_threads() {
+        let loads = vec![load(30, 5.0, 50), load(10, 20.0, 200), load(20, 10.0, 100)];
+        let tids =
+            |sort: ThreadSort| -> Vec<u32> { sort.sorted(&loads).iter().map(|t| t.tid).collect() };
+        assert_eq!(tids(ThreadSort::LoadDesc), vec![10, 20, 30]);
+        assert_eq!(tids(ThreadSort::CyclesDesc), vec![10, 20, 30]);
+        assert_eq!(tids(ThreadSort::TidAsc), vec![10, 20, 30]);
+
+        let loads = vec![load(10, 1.0, 10), load(30, 9.0, 90), load(20, 5.0, 50)];
+        assert_eq!(
+            ThreadSort::TidAsc
+                .sorted(&loads)
+                .iter()
+                .map(|t| t.tid)
+                .collect::<Vec<_>>(),
+            vec![10, 20, 30]
+        );
+        assert_eq!(
+            ThreadSort::LoadDesc
+                .sorted(&loads)
+                .iter()
+                .map(|t| t.tid)
+                .collect::<Vec<_>>(),
+            vec![30, 20, 10]
+        );
+    }
+
+    #[test]
+    fn sort_mode_cycles() {
+        let mut sort = ThreadSort::default();
+        for _ in 0..3 {
+            sort = sort.next();
+        }
+        assert_eq!(sort, ThreadSort::default());
+    }
+
+    #[test]
+    fn min_load_hides_idle_threads() {
+        let data = ComputedFrame {
+            thread_loads: vec![load(10, 20.0, 200), load(20, 0.5, 5), load(30, 0.1, 1)],
+            ..ComputedFrame::default()
+        };
+        let view = ThreadView {
+            sort: ThreadSort::LoadDesc,
+            min_load: 1.0,
+            ..ThreadView::default()
+        };
+        let text: Vec<String> = render_thread_loads(&data, &metadata(), &Theme::default(), 8, view)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(text.len(), 3);
+        assert!(text[1].starts_with("1 ["));
+        assert!(text[1].contains("20.00% (tid 10,"));
+        assert_eq!(text[2], " +2 more hidden (below 1.0%)");
+
+        let all = render_thread_loads(
+            &data,
+            &metadata(),
+            &Theme::default(),
+            8,
+            ThreadView::default(),
+        );
+        assert_eq!(all.len(), 4);
+    }
+
+    #[test]
+    fn pinned_thread_is_listed_first_despite_filter() {
+        let data = ComputedFrame {
+            thread_loads: vec![load(10, 20.0, 200), load(20, 0.5, 5), load(30, 0.1, 1)],
+            ..ComputedFrame::default()
+        };
+        let mut view = ThreadView {
+            sort: ThreadSort::LoadDesc,
+            min_load: 1.0,
+            pinned: Some(30),
+            ..ThreadView::default()
+        };
+        let tids: Vec<u32> = listed_threads(&data, view).iter().map(|t| t.tid).collect();
+        assert_eq!(tids, [30, 10]);
+        let text: Vec<String> = render_thread_loads(&data, &metadata(), &Theme::default(), 8, view)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert!(text[1].starts_with("1 [") && text[1].ends_with(" pinned"));
+        assert_eq!(text[3], " +1 more hidden (below 1.0%)");
+
+        view.hide_follow_keys = true;
+        let text = render_thread_loads(&data, &metadata(), &Theme::default(), 8, view);
+        assert!(text[1].to_string().starts_with("  ["));
+
+        view.pinned = Some(99);
+        let text = render_thread_loads(&data, &metadata(), &Theme::default(), 8, view);
+        assert_eq!(
+            text[1].to_string(),
+            "  tid 99 (pinned): not among the reported threads"
+        );
+    }
+
+    #[test]
+    fn aggregate_rates_divide_by_period() {
+        let metadata = metadata();
+        // 1ms period: a 1-in-1e6 error in either direction is obvious.
+        let mut data = ComputedFrame {
+            sample_period_ns: 1_000_000,
+            total_sigbus_count: 5,
+            total_cache_miss_count: 7,
+            total_jit_count: 2,
+            total_jit_time: 4_000,
+            ..ComputedFrame::default()
+        };
+        data.rates = RatesPerSecond::for_frame(&data);
+
+        let counts = CumulativeCountStats {
+            sigbus: 12_345,
+            ..CumulativeCountStats::default()
+        };
+        let session = SessionTotals {
+            peak_load: 92.3,
+            counts: Some(&counts),
+        };
+        let text: Vec<String> = render_aggregate_stats(
+            &data,
+            &metadata,
+            &session,
+            TimeUnit::Millis,
+            &Theme::default(),
+        )
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+        let line = |prefix: &str| {
+            text.iter()
+                .find(|l| l.trim_start().starts_with(prefix))
+                .unwrap()
+                .clone()
+        };
+        assert!(line("SIGBUS Cnt").contains("(5000.00 per second)"));
+        assert!(line("SIGBUS Cnt").ends_with("[since start: 12,345]"));
+        assert!(line("CacheMiss Cnt").contains("(7000.00 per second)"));
+        assert!(line("JIT Cnt").contains("(2000.00 per second) (avg 2.000 us/block)"));
+        assert!(line("FEX JIT Load").ends_with("(peak 92.30%)"));
+
+        // Block time needs the cycle counter frequency.
+        let unknown = SessionMetadata {
+            cycle_counter_frequency: 0,
+            ..metadata
+        };
+        let lines = render_aggregate_stats(
+            &data,
+            &unknown,
+            &session,
+            TimeUnit::Millis,
+            &Theme::default(),
+        );
+        let jit_count = lines
+            .iter()
+            .map(ToString::to_string)
+            .find(|l| l.trim_start().starts_with("JIT Cnt"))
+            .unwrap();
+        assert!(jit_count.contains("(2000.00 per second) (avg - us/block)"));
+    }
+
+    #[test]
+    fn time_unit_applies_to_threads_and_totals() {
+        let mut metadata = metadata();
+        let data = ComputedFrame {
+            elapsed_ns: 1_000_000,
+            total_jit_time: 250_000,
+            thread_loads: vec![load(10, 25.0, 250_000)],
+            ..ComputedFrame::default()
+        };
+        let session = SessionTotals {
+            peak_load: 0.0,
+            counts: None,
+        };
+        let text = |metadata: &SessionMetadata, unit: TimeUnit| {
+            let view = ThreadView {
+                unit,
+                ..ThreadView::default()
+            };
+            let thread =
+                render_thread_loads(&data, metadata, &Theme::default(), 8, view)[1].to_string();
+            let totals = render_aggregate_stats(&data, metadata, &session, unit, &Theme::default());
+            (thread, totals[1].to_string())
+        };
+
+        let (thread, jit) = text(&metadata, TimeUnit::Millis);
+        assert!(thread.ends_with("(tid 10, 0 ms/S, 250000 cycles)"));
+        assert_eq!(jit, "       JIT Time: 0.250000 ms/second (0.03 percent)");
+        let (thread, jit) = text(&metadata, TimeUnit::Cycles);
+        assert!(thread.ends_with("(tid 10, 250000 cycles)"));
+        assert_eq!(jit, "       JIT Time: 250000 cycles");
+        let (thread, jit) = text(&metadata, TimeUnit::Percent);
+        assert!(thread.ends_with("(tid 10, 25.00% of sample)"));
+        assert_eq!(jit, "       JIT Time: 25.00% of the sample period");
+
+        metadata.cycle_counter_frequency = 0;
+        let (thread, jit) = text(&metadata, TimeUnit::Millis);
+        assert!(thread.ends_with("(tid 10, 250000 cycles)"));
+        assert!(jit.ends_with("250000 cycles (cycle counter frequency unknown)"));
+        assert_eq!(
+            text(&metadata, TimeUnit::Cycles).1,
+            "       JIT Time: 250000 cycles"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+// SPDX-License-Identifier: MIT
+use std::collections::VecDeque;
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Paragraph, Widget};
+
+use crate::tui::panels::mem_stats::format_bytes;
+use crate::tui::theme::{BLOCK_CHARS, Theme};
+
+/// One memory sample kept for the history panel.
+#[derive(Clone, Copy, Default)]
+pub struct MemPoint {
+    pub timestamp_ns: u64,
+    pub total_anon: u64,
+    pub jit_code: u64,
+}
+
+/// Newest samples the growth rate is fitted over.
+const GROWTH_WINDOW: usize = 60;
+/// Fewest samples a growth rate is fitted from, so a couple of noisy
+/// samples after attaching do not raise a warning.
+const GROWTH_MIN_SAMPLES: usize = 5;
+
+/// A memory series growing faster than the `--mem-growth-warn` threshold.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemGrowth {
+    pub series: &'static str,
+    pub mib_per_min: f64,
+}
+
+/// Least-squares slope of `value` over the newest samples, in MiB per
+/// minute.
+#[allow(clippy::cast_precision_loss)]
+fn growth_rate(history: &VecDeque<MemPoint>, value: impl Fn(&MemPoint) -> u64) -> Option<f64> {
+    let window = history.len().min(GROWTH_WINDOW);
+    if window < GROWTH_MIN_SAMPLES {
+        return None;
+    }
+    let points = history.range(history.len() - window..);
+    // Relative to the first sample, which keeps precision in the fit; replay
+    // playing backwards makes later samples negative.
+    let start = history[history.len() - window].timestamp_ns as f64;
+    let samples: Vec<(f64, f64)> = points
+        .map(|p| {
+            let minutes = (p.timestamp_ns as f64 - start) / 60e9;
+            (minutes, value(p) as f64 / (1024.0 * 1024.0))
+        })
+        .collect();
+    let n = samples.len() as f64;
+    let mean_x = samples.iter().map(|s| s.0).sum::<f64>() / n;
+    let mean_y = samples.iter().map(|s| s.1).sum::<f64>() / n;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (x, y) in &samples {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance += (x - mean_x) * (x - mean_x);
+    }
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+/// The series in `history` growing faster than `threshold` MiB per minute.
+#[must_use]
+pub fn growth_warnings(history: &VecDeque<MemPoint>, threshold: f64) -> Vec<MemGrowth> {
+    [
+        ("JIT code", growth_rate(history, |p| p.jit_code)),
+        ("Total anon", growth_rate(history, |p| p.total_anon)),
+    ]
+    .into_iter()
+    .filter_map(|(series, rate)| {
+        rate.filter(|&rate| rate > threshold)
+            .map(|mib_per_min| MemGrowth {
+                series,
+                mib_per_min,
+            })
+    })
+    .collect()
+}
+
+/// Bar chart of one series, newest value at the right edge, scaled so the
+/// largest visible value fills the full height.
+pub struct SparklineWidget<'a> {
+    pub values: &'a [u64],
+    pub style: Style,
+    /// Value drawn at full height instead of the largest one; bigger values
+    /// are clipped to it.
+    pub max: Option<u64>,
+}
+
+impl Widget for SparklineWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let max = self
+            .max
+            .unwrap_or_else(|| self.values.iter().copied().max().unwrap_or(0));
+        if area.height == 0 || area.width == 0 || max == 0 {
+            return;
+        }
+
+        let steps_per_row = BLOCK_CHARS.len() - 1;
+        let total_steps = steps_per_row * area.height as usize;
+        let columns = (area.width as usize).min(self.values.len());
+
+        for (j, &value) in self.values.iter().rev().take(columns).enumerate() {
+            #[allow(clippy::cast_possible_truncation)]
+            let x = area.x + area.width - 1 - j as u16;
+            #[allow(clippy::cast_possible_truncation)]
+            let steps =
+                (u128::from(value.min(max)) * total_steps as u128 / u128::from(max)) as usize;
+
+            for row in 0..area.height as usize {
+                let filled = steps.saturating_sub(row * steps_per_row).min(steps_per_row);
+                if filled == 0 {
+                    break;
+                }
+                #[allow(clippy::cast_possible_truncation)]
+                let y = area.y + area.height - 1 - row as u16;
+                buf[(x, y)]
+                    .set_char(BLOCK_CHARS[filled])
+                    .set_style(self.style);
+            }
+        }
+    }
+}
+
+pub fn render(frame: &mut ratatui::Frame, area: Rect, history: &VecDeque<MemPoint>, theme: &Theme) {
+    if area.height < 2 || area.width < 10 {
+        return;
+    }
+
+    if history.iter().all(|p| p.total_anon == 0) {
+        frame.render_widget(Paragraph::new("Waiting for memory data..."), area);
+        return;
+    }
+
+    let series: [(&str, Vec<u64>, Style); 2] = [
+        (
+            "Total anon",
+            history.iter().map(|p| p.total_anon).collect(),
+            theme.load_normal,
+        ),
+        (
+            "JIT code",
+            history.iter().map(|p| p.jit_code).collect(),
+            theme.histo_jit_load,
+        ),
+    ];
+
+    // Each series gets a label row plus an equal share of the remaining rows.
+    let chart_height = (area.height / 2).saturating_sub(1).max(1);
+    let mut y = area.y;
+    for (name, values, style) in &series {
+        if y >= area.y + area.height {
+            break;
+        }
+        let current = values.last().copied().unwrap_or(0);
+        let peak = values.iter().copied().max().unwrap_or(0);
+        let label = Line::from(vec![
+            Span::styled(format!("{name}: "), theme.title),
+            Span::raw(format!(
+                "{} (peak {})",
+                format_bytes(current),
+                format_bytes(peak)
+            )),
+        ]);
+        frame.render_widget(Paragraph::new(label), Rect::new(area.x, y, area.width, 1));
+        y += 1;
+
+        let height = chart_height.min(area.y + area.height - y);
+        let chart = Rect::new(area.x, y, area.width, height);
+        frame.render_widget(
+            SparklineWidget {
+                values,
+                style: *style,
+                max: None,
+            },
+            chart,
+        );
+        y += height;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_to_buffer(values: &[u64], width: u16, height: u16) -> Buffer {
+        let area = Rect::new(0, 0, width, height);
+        let mut buf = Buffer::empty(area);
+        SparklineWidget {
+            values,
+            style: Style::default(),
+            max: None,
+        }
+        .render(area, &mut buf);
+        buf
+    }
+
+    #[test]
+    fn sparkline_scales_to_max_value() {
+        let buf = render_to_buffer(&[0, 50, 100], 3, 2);
+        let full = BLOCK_CHARS[BLOCK_CHARS.len() - 1].to_string();
+        // Peak fills both rows, half height fills exactly the bottom row.
+        assert_eq!(buf[(2, 0)].symbol(), full);
+        assert_eq!(buf[(2, 1)].symbol(), full);
+        assert_eq!(buf[(1, 0)].symbol(), " ");
+        assert_eq!(buf[(1, 1)].symbol(), full);
+        assert_eq!(buf[(0, 1)].symbol(), " ");
+    }
+
+    #[test]
+    fn growth_warning_fits_recent_slope() {
+        const MIB: u64 = 1024 * 1024;
+        // One sample a second for two minutes; the JIT grows 1 MiB per
+        // sample (60 MiB/min) while the total stays flat apart from it.
+        let history: VecDeque<MemPoint> = (0..120)
+            .map(|i| MemPoint {
+                timestamp_ns: i * 1_000_000_000,
+                total_anon: 500 * MIB + i * MIB,
+                jit_code: i * MIB,
+            })
+            .collect();
+        let warnings = growth_warnings(&history, 30.0);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].series, "JIT code");
+        assert!((warnings[0].mib_per_min - 60.0).abs() < 1e-6);
+        assert!(growth_warnings(&history, 60.5).is_empty());
+
+        let flat: VecDeque<MemPoint> = (0..120)
+            .map(|i| MemPoint {
+                timestamp_ns: i * 1_000_000_000,
+                total_anon: 500 * MIB,
+                jit_code: 10 * MIB,
+            })
+            .collect();
+        assert!(growth_warnings(&flat, 0.5).is_empty());
+        let short: VecDeque<MemPoint> = history.iter().take(3).copied().collect();
+        assert!(growth_warnings(&short, 0.0).is_empty());
+    }
+
+    #[test]
+    fn sparkline_shows_newest_values_when_narrow() {
+        let buf = render_to_buffer(&[100, 0, 0, 100], 2, 1);
+        assert_eq!(buf[(0, 0)].symbol(), " ");
+        assert_ne!(buf[(1, 0)].symbol(), " ");
+    }
+}
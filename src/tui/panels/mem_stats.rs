@@ -1,16 +1,19 @@
 // SPDX-License-Identifier: MIT
 use ratatui::layout::Rect;
-use ratatui::text::Line;
-use ratatui::widgets::Paragraph;
+use ratatui::style::Modifier;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Paragraph, Wrap};
 
+use crate::fex::smaps::MemSnapshot;
 use crate::sampler::accumulator::ComputedFrame;
+use crate::tui::panels::mem_history::MemGrowth;
 use crate::tui::theme::Theme;
 
 const KIB: u64 = 1024;
 const MIB: u64 = 1024 * KIB;
 const GIB: u64 = 1024 * MIB;
 
-fn format_bytes(bytes: u64) -> String {
+pub fn format_bytes(bytes: u64) -> String {
     if bytes >= GIB {
         #[allow(clippy::cast_precision_loss)]
         let val = bytes as f64 / GIB as f64;
@@ -28,11 +31,132 @@ fn format_bytes(bytes: u64) -> String {
     }
 }
 
-pub fn render(frame: &mut ratatui::Frame, area: Rect, data: &ComputedFrame, _theme: &Theme) {
+/// How the memory panel presents the current sample.
+#[derive(Clone, Copy, Default)]
+pub struct MemView<'a> {
+    /// Report the proportional and swapped totals instead of the resident
+    /// one.
+    pub show_pss: bool,
+    /// Highest resident total seen, shown next to the current one.
+    pub peak: u64,
+    /// Draw the regions as one stacked bar instead of a list of sizes.
+    pub bar: bool,
+    /// Series growing faster than `--mem-growth-warn`, shown as a warning
+    /// above the breakdown.
+    pub growth: &'a [MemGrowth],
+}
+
+fn growth_line(growth: &[MemGrowth], theme: &Theme) -> Line<'static> {
+    let rates: Vec<String> = growth
+        .iter()
+        .map(|g| format!("{} +{:.1} MiB/min", g.series, g.mib_per_min))
+        .collect();
+    Line::styled(
+        format!("Memory growing: {}", rates.join(", ")),
+        theme.load_high,
+    )
+}
+
+/// How many of the largest regions get their name written into the bar.
+const BAR_LABELS: usize = 3;
+
+/// Converts region sizes into cell widths of a bar `width` cells wide that
+/// stands for `total` bytes. Rounding cumulative boundaries rather than each
+/// region keeps the widths summing to the share the regions cover.
+fn segment_widths(sizes: &[u64], total: u64, width: usize) -> Vec<usize> {
+    if total == 0 {
+        return vec![0; sizes.len()];
+    }
+    let total = u128::from(total);
+    let boundary = |bytes: u128| {
+        let cells = (bytes.min(total) * width as u128 + total / 2) / total;
+        usize::try_from(cells).unwrap_or(width)
+    };
+    let mut covered = 0u128;
+    let mut previous = 0;
+    sizes
+        .iter()
+        .map(|&size| {
+            covered += u128::from(size);
+            let end = boundary(covered);
+            let cells = end - previous;
+            previous = end;
+            cells
+        })
+        .collect()
+}
+
+/// Stacked bar of the FEX regions sized to the resident total, followed by
+/// a legend with every non-empty region's size.
+fn bar_lines(mem: &MemSnapshot, width: usize, theme: &Theme) -> Vec<Line<'static>> {
+    let palette = [
+        theme.histo_jit_load,
+        theme.histo_smc,
+        theme.histo_sigbus,
+        theme.histo_softfloat,
+        theme.load_medium,
+        theme.load_high,
+        theme.border_selected,
+    ];
+    let regions: Vec<(&str, u64)> = mem
+        .regions()
+        .into_iter()
+        .skip(1)
+        .filter(|&(_, bytes)| bytes > 0)
+        .collect();
+    let sizes: Vec<u64> = regions.iter().map(|&(_, bytes)| bytes).collect();
+    let widths = segment_widths(&sizes, mem.total_anon, width);
+
+    let mut by_size: Vec<usize> = (0..regions.len()).collect();
+    by_size.sort_by_key(|&i| std::cmp::Reverse(regions[i].1));
+    let labelled = &by_size[..by_size.len().min(BAR_LABELS)];
+
+    let mut bar = Vec::new();
+    let mut legend = Vec::new();
+    for (i, (&(name, bytes), &cells)) in regions.iter().zip(&widths).enumerate() {
+        let style = palette[i % palette.len()];
+        let text = if labelled.contains(&i) && cells > name.len() + 1 {
+            format!(" {name:<w$}", w = cells - 1)
+        } else {
+            " ".repeat(cells)
+        };
+        bar.push(Span::styled(text, style.add_modifier(Modifier::REVERSED)));
+        legend.push(Span::styled("\u{25a0} ", style));
+        legend.push(Span::raw(format!("{name} {}  ", format_bytes(bytes))));
+    }
+    let used: usize = widths.iter().sum();
+    bar.push(Span::styled(
+        "\u{2591}".repeat(width.saturating_sub(used)),
+        theme.border_normal,
+    ));
+    vec![Line::from(bar), Line::default(), Line::from(legend)]
+}
+
+/// Draws the per-region memory breakdown, as a list of sizes or, with
+/// `view.bar`, a stacked bar. A sampling `error` replaces the breakdown,
+/// since it would be stale.
+pub fn render(
+    frame: &mut ratatui::Frame,
+    area: Rect,
+    data: &ComputedFrame,
+    error: Option<&str>,
+    view: MemView<'_>,
+    theme: &Theme,
+) {
     if area.height < 2 || area.width < 10 {
         return;
     }
 
+    if let Some(reason) = error {
+        let paragraph = Paragraph::new(Line::styled(
+            format!("memory sampling unavailable: {reason}"),
+            theme.load_high,
+        ))
+        .wrap(Wrap { trim: true });
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
     if data.mem.total_anon == 0 {
         let paragraph = Paragraph::new("Waiting for memory data...");
         frame.render_widget(paragraph, area);
@@ -40,11 +164,30 @@ pub fn render(frame: &mut ratatui::Frame, area: Rect, data: &ComputedFrame, _the
     }
 
     let mem = &data.mem;
-    let lines = vec![
-        Line::from(format!(
-            "Total FEX Anon memory resident: {}",
-            format_bytes(mem.total_anon)
-        )),
+    let total = if view.show_pss {
+        format!(
+            "Total FEX Anon memory Pss:      {} (swapped: {})",
+            format_bytes(mem.total_pss),
+            format_bytes(mem.total_swap)
+        )
+    } else {
+        format!(
+            "Total FEX Anon memory resident: {} (peak {})",
+            format_bytes(mem.total_anon),
+            format_bytes(view.peak)
+        )
+    };
+    let mut lines = Vec::new();
+    if !view.growth.is_empty() {
+        lines.push(growth_line(view.growth, theme));
+    }
+    lines.push(Line::from(total));
+    if view.bar {
+        lines.extend(bar_lines(mem, usize::from(area.width), theme));
+        frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), area);
+        return;
+    }
+    lines.extend([
         Line::from(format!(
             "    JIT resident:             {}",
             format_bytes(mem.jit_code)
@@ -95,7 +238,7 @@ pub fn render(frame: &mut ratatui::Frame, area: Rect, data: &ComputedFrame, _the
             mem.largest_anon.begin,
             mem.largest_anon.end,
         )),
-    ];
+    ]);
 
     let paragraph = Paragraph::new(lines);
     frame.render_widget(paragraph, area);
@@ -115,4 +258,77 @@ mod tests {
         assert_eq!(format_bytes(1024 * 1024 * 1024), "1.0 GiB");
         assert_eq!(format_bytes(2 * 1024 * 1024 * 1024), "2.0 GiB");
     }
+
+    fn render_to_text(data: &ComputedFrame, error: Option<&str>) -> String {
+        render_view_to_text(data, error, MemView::default())
+    }
+
+    fn render_view_to_text(data: &ComputedFrame, error: Option<&str>, view: MemView<'_>) -> String {
+        let backend = ratatui::backend::TestBackend::new(60, 14);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal
+            .draw(|f| render(f, f.area(), data, error, view, &Theme::default()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|row| {
+                row.iter()
+                    .map(ratatui::buffer::Cell::symbol)
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn sampling_error_replaces_waiting_message() {
+        let empty = ComputedFrame::default();
+        assert!(render_to_text(&empty, None).contains("Waiting for memory data"));
+
+        let text = render_to_text(&empty, Some("failed to read smaps"));
+        assert!(text.contains("memory sampling unavailable: failed to read smaps"));
+        assert!(!text.contains("Waiting"));
+    }
+
+    #[test]
+    fn segment_widths_fill_the_covered_share() {
+        assert_eq!(segment_widths(&[50, 25, 25], 100, 8), vec![4, 2, 2]);
+        // A third each still adds up to the full bar.
+        assert_eq!(segment_widths(&[1, 1, 1], 3, 10).iter().sum::<usize>(), 10);
+        // Regions covering half the total fill half the bar.
+        assert_eq!(segment_widths(&[30, 20], 100, 10), vec![3, 2]);
+        assert_eq!(segment_widths(&[5], 0, 10), vec![0]);
+    }
+
+    #[test]
+    fn bar_view_labels_largest_regions() {
+        let mut data = ComputedFrame::default();
+        data.mem.total_anon = 100 * MIB;
+        data.mem.jit_code = 60 * MIB;
+        data.mem.frontend = 30 * MIB;
+        data.mem.misc = 10 * MIB;
+        let view = MemView {
+            bar: true,
+            ..MemView::default()
+        };
+        let text = render_view_to_text(&data, None, view);
+        let bar = text.lines().nth(1).unwrap();
+        assert!(bar.contains(" jit_code"));
+        assert!(bar.contains(" frontend"));
+        assert!(text.contains("misc 10 MiB"));
+        assert!(!text.contains("OpDispatcher resident"));
+
+        let growth = [MemGrowth {
+            series: "JIT code",
+            mib_per_min: 12.34,
+        }];
+        let view = MemView {
+            growth: &growth,
+            ..MemView::default()
+        };
+        let text = render_view_to_text(&data, None, view);
+        assert!(text.starts_with("Memory growing: JIT code +12.3 MiB/min"));
+    }
 }
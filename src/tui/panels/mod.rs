@@ -1,5 +1,9 @@
 // SPDX-License-Identifier: MIT
+pub mod cache_efficiency;
+pub mod cache_locks;
 pub mod header;
 pub mod histogram;
 pub mod jit_stats;
+pub mod mem_history;
 pub mod mem_stats;
+pub mod thread_history;
@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: MIT
+use std::collections::VecDeque;
+
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+
+use crate::tui::panels::mem_history::SparklineWidget;
+use crate::tui::theme::Theme;
+
+/// Drawn on the baseline for samples the thread was missing from.
+const GAP_MARKER: char = '\u{00b7}';
+
+/// The thread the history panel follows and its load per kept sample;
+/// `None` where the thread was not among the frame's `thread_loads`.
+#[derive(Clone, Copy)]
+pub struct FollowedThread<'a> {
+    pub tid: u32,
+    pub loads: &'a VecDeque<Option<f32>>,
+}
+
+/// Draws the followed thread's load over the kept history on a fixed 0 to
+/// 100% scale, marking the gaps where it was not reported.
+pub fn render(
+    frame: &mut ratatui::Frame,
+    area: Rect,
+    followed: Option<FollowedThread<'_>>,
+    theme: &Theme,
+) {
+    if area.height < 2 || area.width < 10 {
+        return;
+    }
+    let Some(FollowedThread { tid, loads }) = followed else {
+        frame.render_widget(
+            Paragraph::new("Press 1-9 (live) or / to follow a thread from the JIT panel"),
+            area,
+        );
+        return;
+    };
+
+    let current = match loads.back().copied().flatten() {
+        Some(load) => format!("{load:.2}%"),
+        None => "not among the reported threads".to_string(),
+    };
+    let peak = loads.iter().flatten().copied().fold(0.0, f32::max);
+    let gaps = loads.iter().filter(|load| load.is_none()).count();
+    let label = Line::from(vec![
+        Span::styled(format!("Thread {tid}: "), theme.title),
+        Span::raw(format!(
+            "{current} (peak {peak:.2}%, missing from {gaps} of {} samples)",
+            loads.len()
+        )),
+    ]);
+    frame.render_widget(
+        Paragraph::new(label),
+        Rect::new(area.x, area.y, area.width, 1),
+    );
+
+    let chart = Rect::new(area.x, area.y + 1, area.width, area.height - 1);
+    // Hundredths of a percent, so light loads still register.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let values: Vec<u64> = loads
+        .iter()
+        .map(|load| (load.unwrap_or(0.0).clamp(0.0, 100.0) * 100.0).round() as u64)
+        .collect();
+    frame.render_widget(
+        SparklineWidget {
+            values: &values,
+            style: theme.load_normal,
+            max: Some(10_000),
+        },
+        chart,
+    );
+
+    let baseline = chart.y + chart.height - 1;
+    let buf = frame.buffer_mut();
+    for (j, load) in loads.iter().rev().take(chart.width.into()).enumerate() {
+        if load.is_none() {
+            #[allow(clippy::cast_possible_truncation)]
+            let x = chart.x + chart.width - 1 - j as u16;
+            buf[(x, baseline)]
+                .set_char(GAP_MARKER)
+                .set_style(theme.border_normal);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_rows(followed: Option<FollowedThread<'_>>) -> Vec<String> {
+        let backend = ratatui::backend::TestBackend::new(60, 4);
+        let mut terminal = ratatui::Terminal::new(backend).unwrap();
+        terminal
+            .draw(|f| render(f, f.area(), followed, &Theme::default()))
+            .unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content()
+            .chunks(buffer.area.width as usize)
+            .map(|row| {
+                row.iter()
+                    .map(ratatui::buffer::Cell::symbol)
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn absent_samples_are_drawn_as_gaps() {
+        let loads: VecDeque<Option<f32>> = [Some(100.0), None, Some(50.0), None].into();
+        let rows = render_rows(Some(FollowedThread {
+            tid: 42,
+            loads: &loads,
+        }));
+        assert!(rows[0].starts_with("Thread 42: not among the reported threads (peak 100.00%"));
+        let baseline: Vec<char> = rows[3].chars().collect();
+        let gap = GAP_MARKER;
+        assert_eq!(baseline[56..], ['\u{2588}', gap, '\u{2588}', gap]);
+        // The full-load sample reaches the top of the chart, half load does not.
+        let top: Vec<char> = rows[1].chars().collect();
+        assert_eq!(top[56..], ['\u{2588}', ' ', ' ', ' ']);
+
+        assert!(render_rows(None)[0].starts_with("Press 1-9"));
+    }
+}
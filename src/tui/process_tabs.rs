@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: MIT
+use ratatui::layout::Rect;
+use ratatui::text::Line;
+use ratatui::widgets::{Paragraph, Tabs};
+
+use crate::tui::theme::Theme;
+
+/// One entry in the process tab bar.
+pub struct ProcessTab {
+    pub pid: i32,
+    pub name: String,
+}
+
+/// Draws a single-line tab bar listing every attached process, highlighting
+/// the selected one.
+pub fn render(
+    frame: &mut ratatui::Frame,
+    area: Rect,
+    tabs: &[ProcessTab],
+    selected: usize,
+    theme: &Theme,
+) {
+    if area.height == 0 || area.width == 0 {
+        return;
+    }
+
+    if tabs.is_empty() {
+        let line = Line::styled(
+            format!(
+                "{:<width$}",
+                " Waiting for FEX processes...",
+                width = area.width as usize
+            ),
+            theme.status_bar,
+        );
+        frame.render_widget(Paragraph::new(line), area);
+        return;
+    }
+
+    let titles = tabs
+        .iter()
+        .map(|t| Line::from(format!("{} {}", t.pid, t.name)));
+    let widget = Tabs::new(titles)
+        .select(selected)
+        .style(theme.border_normal)
+        .highlight_style(theme.border_selected.patch(theme.title));
+    frame.render_widget(widget, area);
+}
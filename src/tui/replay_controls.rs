@@ -1,9 +1,12 @@
 // SPDX-License-Identifier: MIT
+use ratatui::buffer::Buffer;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, Gauge, Paragraph};
 
+use super::panels::header::format_clock;
 use super::theme::Theme;
+use crate::export::seconds_to_frame;
 
 const SPEED_STEPS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
 const DEFAULT_SPEED_INDEX: usize = 2; // 1.0x
@@ -12,9 +15,17 @@ const NANOS_PER_SECOND: u64 = 1_000_000_000;
 pub struct ReplayControls {
     pub speed: f64,
     pub paused: bool,
+    /// Play backward through the recording.
+    pub reversed: bool,
+    /// Wrap around at the end of the recording instead of stopping.
+    pub looping: bool,
     pub current_frame: usize,
     pub total_frames: usize,
+    /// Frame indices of the recording's markers, in ascending order.
+    pub markers: Vec<usize>,
     speed_index: usize,
+    /// Text typed so far into the go-to-time prompt, while it is open.
+    time_input: Option<String>,
 }
 
 impl ReplayControls {
@@ -23,16 +34,36 @@ impl ReplayControls {
         Self {
             speed: SPEED_STEPS[DEFAULT_SPEED_INDEX],
             paused: false,
+            reversed: false,
+            looping: false,
             current_frame: 0,
             total_frames,
+            markers: Vec::new(),
             speed_index: DEFAULT_SPEED_INDEX,
+            time_input: None,
         }
     }
 
+    /// Playback position and recording length in whole seconds, assuming
+    /// frames `sample_period_ns` apart.
+    #[must_use]
+    pub fn playback_secs(&self, sample_period_ns: u64) -> (u64, u64) {
+        let secs = |frames: usize| frames as u64 * sample_period_ns / NANOS_PER_SECOND;
+        (secs(self.current_frame), secs(self.total_frames))
+    }
+
     pub fn toggle_pause(&mut self) {
         self.paused = !self.paused;
     }
 
+    pub fn toggle_direction(&mut self) {
+        self.reversed = !self.reversed;
+    }
+
+    pub fn toggle_loop(&mut self) {
+        self.looping = !self.looping;
+    }
+
     pub fn speed_up(&mut self) {
         if self.speed_index + 1 < SPEED_STEPS.len() {
             self.speed_index += 1;
@@ -67,6 +98,76 @@ impl ReplayControls {
         }
     }
 
+    /// Seeks to `index`, clamped to the last frame.
+    pub fn seek_to(&mut self, index: usize) {
+        self.current_frame = index.min(self.total_frames.saturating_sub(1));
+    }
+
+    /// Seeks `percent` of the way from the first to the last frame.
+    pub fn seek_percent(&mut self, percent: u8) {
+        #[allow(clippy::cast_precision_loss)]
+        let last = self.total_frames.saturating_sub(1) as f64;
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let index = (last * f64::from(percent.min(100)) / 100.0).round() as usize;
+        self.seek_to(index);
+    }
+
+    /// Seeks to the first marker after the current frame, if any.
+    pub fn seek_next_marker(&mut self) {
+        if let Some(&index) = self.markers.iter().find(|&&m| m > self.current_frame) {
+            self.seek_to(index);
+        }
+    }
+
+    /// Seeks to the last marker before the current frame, if any.
+    pub fn seek_prev_marker(&mut self) {
+        if let Some(&index) = self.markers.iter().rfind(|&&m| m < self.current_frame) {
+            self.seek_to(index);
+        }
+    }
+
+    /// Seeks to the frame covering `seconds` into the recording.
+    pub fn seek_to_time(&mut self, seconds: f64, sample_period_ns: u64) {
+        self.seek_to(seconds_to_frame(seconds, sample_period_ns));
+    }
+
+    /// Opens the go-to-time prompt with an empty input.
+    pub fn begin_time_input(&mut self) {
+        self.time_input = Some(String::new());
+    }
+
+    #[must_use]
+    pub fn time_input(&self) -> Option<&str> {
+        self.time_input.as_deref()
+    }
+
+    /// Appends a digit, `:`, or `.` to the open prompt; other characters are
+    /// ignored.
+    pub fn push_time_char(&mut self, c: char) {
+        if let Some(input) = &mut self.time_input
+            && (c.is_ascii_digit() || c == ':' || c == '.')
+        {
+            input.push(c);
+        }
+    }
+
+    pub fn pop_time_char(&mut self) {
+        if let Some(input) = &mut self.time_input {
+            input.pop();
+        }
+    }
+
+    pub fn cancel_time_input(&mut self) {
+        self.time_input = None;
+    }
+
+    /// Closes the prompt and seeks to the entered time if it parses.
+    pub fn submit_time_input(&mut self, sample_period_ns: u64) {
+        if let Some(seconds) = self.time_input.take().as_deref().and_then(parse_time) {
+            self.seek_to_time(seconds, sample_period_ns);
+        }
+    }
+
     pub fn update_position(&mut self, index: usize) {
         self.current_frame = index;
     }
@@ -82,6 +183,22 @@ impl ReplayControls {
     }
 }
 
+/// Parses `ss`, `mm:ss`, or `hh:mm:ss` into seconds. The last field may
+/// have a fractional part.
+fn parse_time(input: &str) -> Option<f64> {
+    let fields: Vec<&str> = input.split(':').collect();
+    if fields.len() > 3 {
+        return None;
+    }
+    let (seconds, larger) = fields.split_last()?;
+    let mut total: f64 = seconds.parse().ok().filter(|s: &f64| *s >= 0.0)?;
+    for (field, unit) in larger.iter().rev().zip([60.0, 3600.0]) {
+        let value: u32 = field.parse().ok()?;
+        total += f64::from(value) * unit;
+    }
+    Some(total)
+}
+
 fn format_time(frame_index: usize, sample_period_ns: u64) -> String {
     #[allow(clippy::cast_precision_loss)]
     let total_ns = frame_index as f64 * sample_period_ns as f64;
@@ -89,9 +206,7 @@ fn format_time(frame_index: usize, sample_period_ns: u64) -> String {
     let nanos_per_sec = NANOS_PER_SECOND as f64;
     #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
     let total_seconds = (total_ns / nanos_per_sec) as u64;
-    let minutes = total_seconds / 60;
-    let seconds = total_seconds % 60;
-    format!("{minutes:02}:{seconds:02}")
+    format_clock(total_seconds)
 }
 
 pub fn render(
@@ -125,12 +240,15 @@ pub fn render(
 
     let status_icon = if controls.paused {
         "\u{23F8}"
+    } else if controls.reversed {
+        "\u{25C0}"
     } else {
         "\u{25B6}"
     };
 
     let time_str = format_time(controls.current_frame, sample_period_ns);
-    let label = format!(" {status_icon} {:.2}x  ", controls.speed);
+    let loop_tag = if controls.looping { " loop" } else { "" };
+    let label = format!(" {status_icon} {:.2}x{loop_tag}  ", controls.speed);
 
     let ratio = controls.progress_fraction().clamp(0.0, 1.0);
 
@@ -142,20 +260,60 @@ pub fn render(
         .gauge_style(theme.border_selected);
 
     frame.render_widget(gauge, rows[0]);
+    render_marker_ticks(frame.buffer_mut(), rows[0], controls, theme);
+
+    if let Some(input) = controls.time_input() {
+        let prompt = Line::from(vec![
+            Span::styled("Go to (mm:ss): ", theme.title),
+            Span::raw(format!("{input}_")),
+            Span::raw("  [Enter] Jump  [Esc] Cancel"),
+        ]);
+        frame.render_widget(Paragraph::new(prompt), rows[1]);
+        return;
+    }
 
     let help = Line::from(vec![
         Span::styled("[Space]", theme.title),
         Span::raw(" Pause  "),
+        Span::styled("[r]", theme.title),
+        Span::raw(" Reverse  "),
+        Span::styled("[L]", theme.title),
+        Span::raw(" Loop  "),
         Span::styled("[\u{2190}/\u{2192}]", theme.title),
         Span::raw(" Step  "),
         Span::styled("[+/-]", theme.title),
         Span::raw(" Speed  "),
         Span::styled("[Home/End]", theme.title),
-        Span::raw(" Jump"),
+        Span::raw(" Jump  "),
+        Span::styled("[g]", theme.title),
+        Span::raw(" Go to time  "),
+        Span::styled("[n/N]", theme.title),
+        Span::raw(" Marker"),
     ]);
     frame.render_widget(Paragraph::new(help), rows[1]);
 }
 
+/// Column of `index` on a gauge `width` cells wide, matching the scale of
+/// `progress_fraction`.
+fn marker_column(index: usize, total_frames: usize, width: u16) -> u16 {
+    if total_frames <= 1 || width == 0 {
+        return 0;
+    }
+    let last = total_frames - 1;
+    let index = index.min(last);
+    #[allow(clippy::cast_possible_truncation)]
+    let column = (index * usize::from(width - 1) / last) as u16;
+    column
+}
+
+/// Overlays a tick on the gauge row for every marker.
+fn render_marker_ticks(buf: &mut Buffer, area: Rect, controls: &ReplayControls, theme: &Theme) {
+    for &marker in &controls.markers {
+        let x = area.x + marker_column(marker, controls.total_frames, area.width);
+        buf[(x, area.y)].set_char('\u{2502}').set_style(theme.title);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -179,6 +337,28 @@ mod tests {
         assert!(!rc.paused);
     }
 
+    #[test]
+    fn toggle_direction() {
+        let mut rc = ReplayControls::new(10);
+        assert!(!rc.reversed);
+        rc.toggle_direction();
+        assert!(rc.reversed);
+        rc.toggle_direction();
+        assert!(!rc.reversed);
+    }
+
+    #[test]
+    fn progress_resets_on_wrap() {
+        let mut rc = ReplayControls::new(10);
+        rc.toggle_loop();
+        rc.update_position(10);
+        assert!(rc.progress_fraction() >= 1.0);
+        // After wrapping, the source reports the position past frame 0.
+        rc.update_position(1);
+        assert!((rc.progress_fraction() - 1.0 / 9.0).abs() < 1e-9);
+        assert!(!rc.paused);
+    }
+
     #[test]
     fn speed_up_cycles() {
         let mut rc = ReplayControls::new(10);
@@ -216,6 +396,25 @@ mod tests {
         assert_eq!(rc.current_frame, 4);
     }
 
+    #[test]
+    fn seek_percent_rounds_to_a_frame() {
+        let mut rc = ReplayControls::new(11);
+        rc.seek_percent(50);
+        assert_eq!(rc.current_frame, 5);
+        assert!((rc.progress_fraction() - 0.5).abs() < 1e-9);
+        rc.seek_percent(90);
+        assert_eq!(rc.current_frame, 9);
+        rc.seek_percent(0);
+        assert_eq!(rc.current_frame, 0);
+
+        let mut rc = ReplayControls::new(4);
+        rc.seek_percent(50);
+        assert_eq!(rc.current_frame, 2);
+        let mut empty = ReplayControls::new(0);
+        empty.seek_percent(90);
+        assert_eq!(empty.current_frame, 0);
+    }
+
     #[test]
     fn seek_backward_clamps() {
         let mut rc = ReplayControls::new(5);
@@ -266,6 +465,13 @@ mod tests {
         assert_eq!(rc.current_frame, 42);
     }
 
+    #[test]
+    fn playback_secs_scale_by_period() {
+        let mut rc = ReplayControls::new(1200);
+        rc.update_position(300);
+        assert_eq!(rc.playback_secs(500_000_000), (150, 600));
+    }
+
     #[test]
     fn format_time_basic() {
         assert_eq!(format_time(0, 1_000_000_000), "00:00");
@@ -273,6 +479,77 @@ mod tests {
         assert_eq!(format_time(150, 1_000_000_000), "02:30");
     }
 
+    #[test]
+    fn parse_time_formats() {
+        assert_eq!(parse_time("45"), Some(45.0));
+        assert_eq!(parse_time("5:30"), Some(330.0));
+        assert_eq!(parse_time("1:02:03"), Some(3723.0));
+        assert_eq!(parse_time("0:01.5"), Some(1.5));
+        assert_eq!(parse_time(""), None);
+        assert_eq!(parse_time("5:"), None);
+        assert_eq!(parse_time("1:2:3:4"), None);
+    }
+
+    #[test]
+    fn time_prompt_seeks_and_clamps() {
+        let mut rc = ReplayControls::new(1000);
+        rc.begin_time_input();
+        for c in "0:3x0".chars() {
+            rc.push_time_char(c);
+        }
+        assert_eq!(rc.time_input(), Some("0:30"));
+        // 500ms frames: 30s is frame 60.
+        rc.submit_time_input(500_000_000);
+        assert_eq!(rc.current_frame, 60);
+        assert!(rc.time_input().is_none());
+
+        rc.begin_time_input();
+        rc.push_time_char('9');
+        rc.push_time_char('9');
+        rc.pop_time_char();
+        rc.push_time_char('0');
+        rc.push_time_char(':');
+        rc.push_time_char('0');
+        rc.push_time_char('0');
+        rc.submit_time_input(500_000_000);
+        assert_eq!(rc.current_frame, 999);
+
+        rc.begin_time_input();
+        rc.push_time_char('1');
+        rc.cancel_time_input();
+        assert!(rc.time_input().is_none());
+        assert_eq!(rc.current_frame, 999);
+    }
+
+    #[test]
+    fn seeks_between_markers() {
+        let mut rc = ReplayControls::new(100);
+        rc.markers = vec![10, 40, 80];
+        rc.seek_next_marker();
+        assert_eq!(rc.current_frame, 10);
+        rc.seek_next_marker();
+        assert_eq!(rc.current_frame, 40);
+        rc.current_frame = 90;
+        rc.seek_next_marker();
+        assert_eq!(rc.current_frame, 90);
+        rc.seek_prev_marker();
+        assert_eq!(rc.current_frame, 80);
+        rc.current_frame = 40;
+        rc.seek_prev_marker();
+        assert_eq!(rc.current_frame, 10);
+        rc.seek_prev_marker();
+        assert_eq!(rc.current_frame, 10);
+    }
+
+    #[test]
+    fn marker_columns_span_the_gauge() {
+        assert_eq!(marker_column(0, 101, 51), 0);
+        assert_eq!(marker_column(50, 101, 51), 25);
+        assert_eq!(marker_column(100, 101, 51), 50);
+        assert_eq!(marker_column(500, 101, 51), 50);
+        assert_eq!(marker_column(3, 1, 51), 0);
+    }
+
     #[test]
     fn seek_forward_zero_frames() {
         let mut rc = ReplayControls::new(0);
@@ -1,6 +1,12 @@
 // SPDX-License-Identifier: MIT
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
 use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
 
+#[derive(Clone)]
 pub struct Theme {
     pub load_normal: Style,
     pub load_medium: Style,
@@ -38,6 +44,182 @@ impl Default for Theme {
     }
 }
 
+impl Theme {
+    /// Preset for terminals with a light background, where the default white
+    /// borders and titles disappear.
+    #[must_use]
+    pub fn light() -> Self {
+        Self {
+            load_normal: Style::default().fg(Color::Green),
+            load_medium: Style::default().fg(Color::Rgb(0xaf, 0x87, 0x00)),
+            load_high: Style::default().fg(Color::Red),
+            histo_jit_load: Style::default().fg(Color::Magenta),
+            histo_smc: Style::default().fg(Color::Blue),
+            histo_sigbus: Style::default().fg(Color::Rgb(0x00, 0x87, 0x87)),
+            histo_softfloat: Style::default().fg(Color::Green),
+            border_normal: Style::default().fg(Color::DarkGray),
+            border_selected: Style::default().fg(Color::Blue),
+            title: Style::default()
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            status_bar: Style::default().fg(Color::White).bg(Color::Blue),
+            recording_indicator: Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+        }
+    }
+
+    /// Looks up a built-in preset by name.
+    #[must_use]
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" | "default" => Some(Self::default()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    /// Resolves the `--theme` argument: a preset name, or a path to a TOML
+    /// theme file. `None` selects the default theme.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or contains invalid
+    /// colors, modifiers, or fields.
+    pub fn load(spec: Option<&str>) -> Result<Self> {
+        let Some(spec) = spec else {
+            return Ok(Self::default());
+        };
+        if let Some(theme) = Self::preset(spec) {
+            return Ok(theme);
+        }
+
+        let path = Path::new(spec);
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read theme file: {}", path.display()))?;
+        let config: ThemeConfig = toml::from_str(&text)
+            .with_context(|| format!("failed to parse theme file: {}", path.display()))?;
+        config
+            .into_theme()
+            .with_context(|| format!("invalid theme file: {}", path.display()))
+    }
+}
+
+/// On-disk theme description. Every style is optional; missing ones are
+/// taken from the `base` preset (the default theme unless named).
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    pub base: Option<String>,
+    pub load_normal: Option<StyleConfig>,
+    pub load_medium: Option<StyleConfig>,
+    pub load_high: Option<StyleConfig>,
+    pub histo_jit_load: Option<StyleConfig>,
+    pub histo_smc: Option<StyleConfig>,
+    pub histo_sigbus: Option<StyleConfig>,
+    pub histo_softfloat: Option<StyleConfig>,
+    pub border_normal: Option<StyleConfig>,
+    pub border_selected: Option<StyleConfig>,
+    pub title: Option<StyleConfig>,
+    pub status_bar: Option<StyleConfig>,
+    pub recording_indicator: Option<StyleConfig>,
+}
+
+/// A `Style` in serializable form. Colors are ratatui color names
+/// (`"red"`, `"light-blue"`), 256-color indices, or `"#rrggbb"`.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleConfig {
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub modifiers: Vec<String>,
+}
+
+impl ThemeConfig {
+    /// Converts into a `Theme`, filling missing styles from the base preset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the base preset is unknown or a style is invalid.
+    pub fn into_theme(self) -> Result<Theme> {
+        let base = match self.base.as_deref() {
+            None => Theme::default(),
+            Some(name) => {
+                Theme::preset(name).with_context(|| format!("unknown base theme \"{name}\""))?
+            }
+        };
+
+        let pick = |name: &str, config: Option<StyleConfig>, fallback: Style| -> Result<Style> {
+            config.map_or(Ok(fallback), |c| {
+                c.apply(fallback)
+                    .with_context(|| format!("in style \"{name}\""))
+            })
+        };
+
+        Ok(Theme {
+            load_normal: pick("load_normal", self.load_normal, base.load_normal)?,
+            load_medium: pick("load_medium", self.load_medium, base.load_medium)?,
+            load_high: pick("load_high", self.load_high, base.load_high)?,
+            histo_jit_load: pick("histo_jit_load", self.histo_jit_load, base.histo_jit_load)?,
+            histo_smc: pick("histo_smc", self.histo_smc, base.histo_smc)?,
+            histo_sigbus: pick("histo_sigbus", self.histo_sigbus, base.histo_sigbus)?,
+            histo_softfloat: pick(
+                "histo_softfloat",
+                self.histo_softfloat,
+                base.histo_softfloat,
+            )?,
+            border_normal: pick("border_normal", self.border_normal, base.border_normal)?,
+            border_selected: pick(
+                "border_selected",
+                self.border_selected,
+                base.border_selected,
+            )?,
+            title: pick("title", self.title, base.title)?,
+            status_bar: pick("status_bar", self.status_bar, base.status_bar)?,
+            recording_indicator: pick(
+                "recording_indicator",
+                self.recording_indicator,
+                base.recording_indicator,
+            )?,
+        })
+    }
+}
+
+impl StyleConfig {
+    /// Overrides the colors set in this config on top of `base` and adds its
+    /// modifiers.
+    fn apply(&self, base: Style) -> Result<Style> {
+        let mut style = base;
+        if let Some(fg) = &self.fg {
+            style = style.fg(parse_color(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(parse_color(bg)?);
+        }
+        for name in &self.modifiers {
+            style = style.add_modifier(parse_modifier(name)?);
+        }
+        Ok(style)
+    }
+}
+
+fn parse_color(s: &str) -> Result<Color> {
+    Color::from_str(s).map_err(|_| anyhow::anyhow!("unknown color \"{s}\""))
+}
+
+fn parse_modifier(s: &str) -> Result<Modifier> {
+    Ok(match s.to_ascii_lowercase().as_str() {
+        "bold" => Modifier::BOLD,
+        "dim" => Modifier::DIM,
+        "italic" => Modifier::ITALIC,
+        "underlined" | "underline" => Modifier::UNDERLINED,
+        "slow_blink" | "blink" => Modifier::SLOW_BLINK,
+        "rapid_blink" => Modifier::RAPID_BLINK,
+        "reversed" | "reverse" => Modifier::REVERSED,
+        "hidden" => Modifier::HIDDEN,
+        "crossed_out" | "strikethrough" => Modifier::CROSSED_OUT,
+        _ => bail!("unknown modifier \"{s}\""),
+    })
+}
+
 pub const BLOCK_CHARS: [char; 10] = [
     ' ', '\u{2581}', '\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}',
     '\u{2587}', '\u{2588}',
@@ -45,3 +227,69 @@ pub const BLOCK_CHARS: [char; 10] = [
 pub const BLOCK_FULL: char = '\u{2588}';
 pub const SELECTED_MARKER: [char; 2] = ['\u{2610}', '\u{2611}'];
 pub const COLLAPSED_MARKER: [char; 2] = ['\u{25BC}', '\u{25BA}'];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_fall_back_to_base() {
+        let config: ThemeConfig = toml::from_str(
+            r##"
+            base = "light"
+
+            [border_normal]
+            fg = "#102030"
+            modifiers = ["bold"]
+            "##,
+        )
+        .unwrap();
+        let theme = config.into_theme().unwrap();
+
+        assert_eq!(
+            theme.border_normal,
+            Style::default()
+                .fg(Color::Rgb(0x10, 0x20, 0x30))
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(theme.status_bar, Theme::light().status_bar);
+    }
+
+    #[test]
+    fn style_overrides_keep_unset_base_colors() {
+        let config: ThemeConfig = toml::from_str(
+            r#"
+            [status_bar]
+            bg = "dark-gray"
+            "#,
+        )
+        .unwrap();
+        let theme = config.into_theme().unwrap();
+        assert_eq!(
+            theme.status_bar,
+            Style::default().fg(Color::Black).bg(Color::DarkGray)
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let bad_color: ThemeConfig = toml::from_str("[title]\nfg = \"chartreuse-ish\"").unwrap();
+        let err = bad_color.into_theme().err().unwrap();
+        assert!(format!("{err:#}").contains("title"));
+
+        let bad_modifier: ThemeConfig = toml::from_str("[title]\nmodifiers = [\"loud\"]").unwrap();
+        assert!(bad_modifier.into_theme().is_err());
+
+        assert!(toml::from_str::<ThemeConfig>("[no_such_style]\nfg = \"red\"").is_err());
+    }
+
+    #[test]
+    fn presets_resolve_by_name() {
+        assert_eq!(
+            Theme::load(Some("light")).unwrap().title,
+            Theme::light().title
+        );
+        assert_eq!(Theme::load(None).unwrap().title, Theme::default().title);
+        assert!(Theme::load(Some("/nonexistent/theme.toml")).is_err());
+    }
+}
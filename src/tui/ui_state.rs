@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: MIT
+//! Panel layout and display choices saved between runs.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::app::App;
+use super::panels::jit_stats::{ThreadSort, TimeUnit};
+
+/// One panel's saved state, matched to the app's panels by name.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PanelEntry {
+    pub name: String,
+    pub collapsed: bool,
+}
+
+/// What felix restores at startup: the panel order and collapsed panels,
+/// the thread sort order and time unit, and the theme last passed to
+/// `--theme`. Anything missing from the
+/// file keeps its default.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    pub theme: Option<String>,
+    pub sort: Option<ThreadSort>,
+    pub time_unit: Option<TimeUnit>,
+    #[serde(rename = "panel")]
+    pub panels: Vec<PanelEntry>,
+}
+
+impl UiState {
+    /// `$XDG_CONFIG_HOME/felix/state.toml`, falling back to
+    /// `~/.config/felix/state.toml`.
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("felix").join("state.toml"))
+    }
+
+    /// Reads the state saved at `path`. A missing file is the default state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read {}", path.display()));
+            }
+        };
+        toml::from_str(&text).with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    /// Writes the state to `path`, creating its directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let text = toml::to_string(self).context("failed to serialize UI state")?;
+        std::fs::write(path, text).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// Captures the app's current layout, to be saved with `theme`.
+    #[must_use]
+    pub fn capture(app: &App, theme: Option<String>) -> Self {
+        Self {
+            theme,
+            sort: Some(app.thread_sort),
+            time_unit: Some(app.time_unit),
+            panels: app
+                .panels
+                .iter()
+                .map(|p| PanelEntry {
+                    name: p.name.to_string(),
+                    collapsed: p.collapsed,
+                })
+                .collect(),
+        }
+    }
+
+    /// Applies the saved sort order, time unit, panel order and collapsed flags over
+    /// the app's defaults. Saved panels the app no longer has are ignored,
+    /// and panels missing from the file keep their default order after the
+    /// saved ones.
+    pub fn apply(&self, app: &mut App) {
+        if let Some(sort) = self.sort {
+            app.thread_sort = sort;
+        }
+        if let Some(time_unit) = self.time_unit {
+            app.time_unit = time_unit;
+        }
+        for entry in &self.panels {
+            if let Some(panel) = app.panels.iter_mut().find(|p| p.name == entry.name) {
+                panel.collapsed = entry.collapsed;
+            }
+        }
+        app.panels.sort_by_key(|panel| {
+            self.panels
+                .iter()
+                .position(|entry| entry.name == panel.name)
+                .unwrap_or(usize::MAX)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::make_metadata;
+
+    fn make_app() -> App {
+        App::new(make_metadata(), false, 10)
+    }
+
+    #[test]
+    fn saved_state_round_trips_onto_a_new_app() {
+        let mut app = make_app();
+        app.panels[1].collapsed = true;
+        app.panels.swap(0, 2);
+        app.thread_sort = ThreadSort::TidAsc;
+        app.time_unit = TimeUnit::Percent;
+        let state = UiState::capture(&app, Some("light".into()));
+
+        let path = std::env::temp_dir().join(format!(
+            "felix_ui_state_test_{}/state.toml",
+            std::process::id()
+        ));
+        state.save(&path).unwrap();
+        let loaded = UiState::load(&path).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(loaded, state);
+
+        let mut restored = make_app();
+        loaded.apply(&mut restored);
+        assert_eq!(restored.thread_sort, ThreadSort::TidAsc);
+        assert_eq!(restored.time_unit, TimeUnit::Percent);
+        assert!(restored.panels[1].collapsed);
+        assert!(!restored.panels[0].collapsed);
+        let order = |app: &App| app.panels.iter().map(|p| p.name).collect::<Vec<_>>();
+        assert_eq!(order(&restored), order(&app));
+        assert_eq!(restored.panels[0].name, "Memory over time");
+        assert!(UiState::load(&path).unwrap() == UiState::default());
+    }
+
+    #[test]
+    fn partial_state_keeps_defaults() {
+        let state: UiState = toml::from_str(
+            r#"
+            [[panel]]
+            name = "Memory over time"
+            collapsed = true
+
+            [[panel]]
+            name = "A panel from another version"
+            collapsed = true
+            "#,
+        )
+        .unwrap();
+        let mut app = make_app();
+        state.apply(&mut app);
+        assert_eq!(app.thread_sort, ThreadSort::default());
+        let collapsed: Vec<&str> = app
+            .panels
+            .iter()
+            .filter(|p| p.collapsed)
+            .map(|p| p.name)
+            .collect();
+        // The thread history panel starts collapsed.
+        assert_eq!(collapsed, ["Memory over time", "Thread load over time"]);
+        // The one saved panel goes first; the rest keep their order.
+        assert_eq!(app.panels[0].name, "Memory over time");
+        assert_eq!(app.panels[1].name, "FEX JIT Stats");
+    }
+}
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: MIT
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Formats `time` as an RFC 3339 UTC timestamp with millisecond precision,
+/// e.g. `2025-01-31T12:34:56.789Z`. Times before the epoch clamp to it.
+#[must_use]
+pub fn format_rfc3339(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+    let secs = since_epoch.as_secs();
+    let millis = since_epoch.subsec_millis();
+
+    let days = secs / 86_400;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{millis:03}Z",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60,
+    )
+}
+
+/// Formats `time` as a compact UTC timestamp for file names, to the second,
+/// e.g. `20250131T123456Z`.
+#[must_use]
+pub fn format_compact(time: SystemTime) -> String {
+    let mut compact: String = format_rfc3339(time)[..19]
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':'))
+        .collect();
+    compact.push('Z');
+    compact
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) civil date.
+/// Howard Hinnant's `civil_from_days`, restricted to non-negative inputs.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch() {
+        assert_eq!(format_rfc3339(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn known_timestamp() {
+        // 2024-02-29T12:34:56.789Z, a leap day.
+        let t = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        assert_eq!(format_rfc3339(t), "2024-02-29T12:34:56.789Z");
+        assert_eq!(format_compact(t), "20240229T123456Z");
+    }
+
+    #[test]
+    fn end_of_year() {
+        let t = UNIX_EPOCH + Duration::from_secs(1_735_689_599);
+        assert_eq!(format_rfc3339(t), "2024-12-31T23:59:59.000Z");
+    }
+}